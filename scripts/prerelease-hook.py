@@ -31,10 +31,20 @@ def is_prerelease(version):
 CHANGELOG = 'CHANGELOG.md'
 
 
+def unreleased_changes(changelog):
+    match = re.search(r'^## \[Unreleased\]\n(.*?)^## ', changelog,
+                      re.MULTILINE | re.DOTALL)
+    return match.group(1).strip() if match else ''
+
+
 def update_changelog(version):
     with open(CHANGELOG) as source:
         changelog = source.read()
 
+    if not unreleased_changes(changelog):
+        sys.exit('No unreleased changes in {}; refusing to release {}'.format(
+            CHANGELOG, version))
+
     substitutions = [
         (r'## \[Unreleased\]\n',
          "## [Unreleased]\n\n## [{version}] – {today}\n"),
@@ -0,0 +1,261 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The abbreviation extension.
+//!
+//! Documents define abbreviations on lines of their own:
+//!
+//! ```markdown
+//! *[HTML]: HyperText Markup Language
+//! ```
+//!
+//! We mark up all uses of abbreviations with HTML `abbr` tags, which we then
+//! render like any other `abbr` tag in markdown.
+
+use crate::entities::decode_entities;
+use pulldown_cmark::Event;
+use pulldown_cmark::Event::*;
+use pulldown_cmark::Tag::*;
+use std::borrow::Cow;
+
+/// Parse an abbreviation definition on `line`.
+///
+/// Decode entities in the abbreviation and the expansion, like in any other
+/// markdown text.
+fn definition(line: &str) -> Option<(String, String)> {
+    let rest = line.trim_end().strip_prefix("*[")?;
+    let end = rest.find("]:")?;
+    let abbreviation = rest[..end].trim();
+    let expansion = rest[end + 2..].trim();
+    if abbreviation.is_empty() {
+        None
+    } else {
+        Some((
+            decode_entities(abbreviation).into_owned(),
+            decode_entities(expansion).into_owned(),
+        ))
+    }
+}
+
+/// Extract abbreviation definitions from markdown `source`.
+///
+/// Return all abbreviations with their expansion, and `source` without
+/// definitions.  Ignore definitions in fenced code blocks.
+pub fn extract_abbreviations(source: &str) -> (Vec<(String, String)>, String) {
+    let mut abbreviations = Vec::new();
+    let mut output = String::with_capacity(source.len());
+    let mut fence: Option<&str> = None;
+    for line in source.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        let line_fence = ["```", "~~~"]
+            .iter()
+            .find(|marker| trimmed.starts_with(*marker));
+        match (fence, line_fence) {
+            (Some(current), Some(marker)) if current == *marker => fence = None,
+            (None, Some(marker)) => fence = Some(marker),
+            (None, None) => {
+                if let Some(abbreviation) = definition(line) {
+                    abbreviations.push(abbreviation);
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        output.push_str(line);
+    }
+    (abbreviations, output)
+}
+
+/// Escape `text` for an HTML attribute value.
+fn escape_attribute(text: &str) -> String {
+    text.replace('&', "&amp;").replace('"', "&quot;")
+}
+
+/// Whether `text` has no word character at `index`.
+fn is_boundary(text: &str, index: usize) -> bool {
+    !text[index..]
+        .chars()
+        .next()
+        .is_some_and(char::is_alphanumeric)
+}
+
+/// Whether a word ends right before `index` in `text`.
+fn is_boundary_before(text: &str, index: usize) -> bool {
+    !text[..index]
+        .chars()
+        .next_back()
+        .is_some_and(char::is_alphanumeric)
+}
+
+/// Mark up all `abbreviations` in `text`, and add the events to `events`.
+fn mark_up_text<'a>(
+    text: Cow<'a, str>,
+    abbreviations: &[(String, String)],
+    events: &mut Vec<Event<'a>>,
+) {
+    let mut start = 0;
+    let mut index = 0;
+    while index < text.len() {
+        let found = if is_boundary_before(&text, index) {
+            abbreviations.iter().find(|(abbreviation, _)| {
+                text[index..].starts_with(abbreviation.as_str())
+                    && is_boundary(&text, index + abbreviation.len())
+            })
+        } else {
+            None
+        };
+        match found {
+            Some((abbreviation, expansion)) => {
+                if start < index {
+                    events.push(Text(Cow::Owned(text[start..index].to_string())));
+                }
+                events.push(InlineHtml(Cow::Owned(format!(
+                    "<abbr title=\"{}\">",
+                    escape_attribute(expansion)
+                ))));
+                events.push(Text(Cow::Owned(abbreviation.clone())));
+                events.push(InlineHtml(Cow::Borrowed("</abbr>")));
+                index += abbreviation.len();
+                start = index;
+            }
+            None => {
+                index += text[index..].chars().next().map_or(1, char::len_utf8);
+            }
+        }
+    }
+    if start == 0 {
+        events.push(Text(text));
+    } else if start < text.len() {
+        events.push(Text(Cow::Owned(text[start..].to_string())));
+    }
+}
+
+/// Mark up all uses of `abbreviations` in markdown `events`.
+///
+/// Wrap every use of an abbreviation in an HTML `abbr` tag with the
+/// expansion as title.  Leave code and images alone.
+pub fn insert_abbreviations<'a, I>(events: I, abbreviations: &[(String, String)]) -> Vec<Event<'a>>
+where
+    I: IntoIterator<Item = Event<'a>>,
+{
+    // Try longer abbreviations first
+    let mut abbreviations = abbreviations.to_vec();
+    abbreviations.sort_by_key(|(abbreviation, _)| std::cmp::Reverse(abbreviation.len()));
+    let mut result = Vec::new();
+    let mut verbatim = 0;
+    for event in events {
+        match event {
+            Start(Code) | Start(CodeBlock(_)) | Start(Image(_, _)) => verbatim += 1,
+            End(Code) | End(CodeBlock(_)) | End(Image(_, _)) => verbatim -= 1,
+            Text(text) if verbatim == 0 && !abbreviations.is_empty() => {
+                mark_up_text(text, &abbreviations, &mut result);
+                continue;
+            }
+            _ => {}
+        }
+        result.push(event);
+    }
+    result
+}
+
+/// Get the expansion of an `abbr` tag from its `html`.
+///
+/// Return `None` if `html` is no `abbr` opening tag, and the empty string if
+/// the tag has no title.
+pub(crate) fn abbr_title(html: &str) -> Option<String> {
+    let attributes = html.strip_prefix("<abbr")?.strip_suffix('>')?;
+    if !(attributes.is_empty() || attributes.starts_with(char::is_whitespace)) {
+        return None;
+    }
+    let title = attributes
+        .find("title=\"")
+        .map(|start| &attributes[start + 7..])
+        .and_then(|rest| rest.find('"').map(|end| &rest[..end]))
+        .unwrap_or("");
+    Some(decode_entities(title).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use pulldown_cmark::Parser;
+
+    fn html(abbreviation: &'static str, expansion: &str) -> Vec<Event<'static>> {
+        vec![
+            InlineHtml(Cow::Owned(format!("<abbr title=\"{}\">", expansion))),
+            Text(Cow::Borrowed(abbreviation)),
+            InlineHtml(Cow::Borrowed("</abbr>")),
+        ]
+    }
+
+    #[test]
+    fn extract_definitions() {
+        let source = "*[HTML]: HyperText Markup Language\nSome HTML\n\n```\n*[X]: Y\n```\n";
+        assert_eq!(
+            extract_abbreviations(source),
+            (
+                vec![("HTML".to_string(), "HyperText Markup Language".to_string())],
+                "Some HTML\n\n```\n*[X]: Y\n```\n".to_string()
+            )
+        );
+        assert_eq!(
+            extract_abbreviations("*[R&amp;D]: Research &amp; Development &rarr; Profit\n").0,
+            vec![(
+                "R&D".to_string(),
+                "Research & Development \u{2192} Profit".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn mark_up_abbreviations_in_text() {
+        let abbreviations = vec![
+            (
+                "HTML".to_string(),
+                "HyperText \"Markup\" Language".to_string(),
+            ),
+            ("HTML5".to_string(), "HTML version 5".to_string()),
+        ];
+        let events =
+            insert_abbreviations(Parser::new("HTML5, XHTML and HTML `HTML`"), &abbreviations);
+        let mut expected = vec![Start(Paragraph)];
+        expected.extend(html("HTML5", "HTML version 5"));
+        expected.push(Text(Cow::Borrowed(", XHTML and ")));
+        expected.extend(html("HTML", "HyperText &quot;Markup&quot; Language"));
+        expected.push(Text(Cow::Borrowed(" ")));
+        expected.extend(vec![
+            Start(Code),
+            Text(Cow::Borrowed("HTML")),
+            End(Code),
+            End(Paragraph),
+        ]);
+        assert_eq!(events, expected);
+    }
+
+    #[test]
+    fn title_of_abbr_tag() {
+        assert_eq!(
+            abbr_title("<abbr title=\"A &quot;B&quot;\">"),
+            Some("A \"B\"".to_string())
+        );
+        assert_eq!(
+            abbr_title("<abbr title=\"Caf&eacute; &#x2615;\">"),
+            Some("Caf\u{E9} \u{2615}".to_string())
+        );
+        assert_eq!(abbr_title("<abbr>"), Some(String::new()));
+        assert_eq!(abbr_title("<abbrev>"), None);
+        assert_eq!(abbr_title("</abbr>"), None);
+    }
+}
@@ -0,0 +1,628 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The arguments of our command line.
+//!
+//! We declare all arguments in one table, and build the clap app, the
+//! schema of `--help-json` and the defaults from `MDCAT_*` variables from
+//! this table, because clap has no public API to list the arguments of an
+//! app.
+
+use clap::Arg;
+
+/// An argument of the command line.
+#[derive(Debug, Clone, Copy)]
+pub struct ArgDef {
+    /// The name of the argument, to look it up in matches.
+    pub name: &'static str,
+    /// The short flag, without `-`.
+    pub short: Option<&'static str>,
+    /// The long flag, without `--`, or `None` for positional arguments.
+    pub long: Option<&'static str>,
+    /// Other long flags which help does not show.
+    pub aliases: &'static [&'static str],
+    /// Other long flags which help shows.
+    pub visible_aliases: &'static [&'static str],
+    /// Whether the argument takes a value.
+    pub takes_value: bool,
+    /// The name of the value in help.
+    pub value_name: Option<&'static str>,
+    /// The values the argument accepts, or empty for any value.
+    pub possible_values: &'static [&'static str],
+    /// The default value, if it does not depend on the terminal.
+    pub default: Option<&'static str>,
+    /// Whether the argument may be given many times.
+    pub multiple: bool,
+    /// Whether to hide the argument from help.
+    pub hidden: bool,
+    /// Arguments this argument conflicts with.
+    pub conflicts: &'static [&'static str],
+    /// Arguments this argument requires.
+    pub requires: &'static [&'static str],
+    /// Help for the argument.
+    pub help: &'static str,
+}
+
+impl ArgDef {
+    /// A positional argument `name`.
+    pub const fn positional(name: &'static str, help: &'static str) -> ArgDef {
+        ArgDef {
+            name,
+            short: None,
+            long: None,
+            aliases: &[],
+            visible_aliases: &[],
+            takes_value: true,
+            value_name: None,
+            possible_values: &[],
+            default: None,
+            multiple: false,
+            hidden: false,
+            conflicts: &[],
+            requires: &[],
+            help,
+        }
+    }
+
+    /// A flag `name` without value.
+    pub const fn flag(name: &'static str, long: &'static str, help: &'static str) -> ArgDef {
+        ArgDef {
+            long: Some(long),
+            takes_value: false,
+            ..ArgDef::positional(name, help)
+        }
+    }
+
+    /// An option `name` with a value.
+    pub const fn option(name: &'static str, long: &'static str, help: &'static str) -> ArgDef {
+        ArgDef {
+            long: Some(long),
+            ..ArgDef::positional(name, help)
+        }
+    }
+
+    /// Set the short flag.
+    pub const fn short(self, short: &'static str) -> ArgDef {
+        ArgDef {
+            short: Some(short),
+            ..self
+        }
+    }
+
+    /// Add hidden aliases.
+    pub const fn aliases(self, aliases: &'static [&'static str]) -> ArgDef {
+        ArgDef { aliases, ..self }
+    }
+
+    /// Add aliases which help shows.
+    pub const fn visible_aliases(self, visible_aliases: &'static [&'static str]) -> ArgDef {
+        ArgDef {
+            visible_aliases,
+            ..self
+        }
+    }
+
+    /// Name the value in help.
+    pub const fn value_name(self, value_name: &'static str) -> ArgDef {
+        ArgDef {
+            value_name: Some(value_name),
+            ..self
+        }
+    }
+
+    /// Accept only the given values.
+    pub const fn possible_values(self, possible_values: &'static [&'static str]) -> ArgDef {
+        ArgDef {
+            possible_values,
+            ..self
+        }
+    }
+
+    /// Set the default value.
+    pub const fn default(self, default: &'static str) -> ArgDef {
+        ArgDef {
+            default: Some(default),
+            ..self
+        }
+    }
+
+    /// Allow the argument many times.
+    pub const fn multiple(self) -> ArgDef {
+        ArgDef {
+            multiple: true,
+            ..self
+        }
+    }
+
+    /// Hide the argument from help.
+    pub const fn hidden(self) -> ArgDef {
+        ArgDef {
+            hidden: true,
+            ..self
+        }
+    }
+
+    /// Conflict with other arguments.
+    pub const fn conflicts(self, conflicts: &'static [&'static str]) -> ArgDef {
+        ArgDef { conflicts, ..self }
+    }
+
+    /// Require other arguments.
+    pub const fn requires(self, requires: &'static [&'static str]) -> ArgDef {
+        ArgDef { requires, ..self }
+    }
+
+    /// The clap argument for this argument.
+    pub fn to_arg(self) -> Arg<'static, 'static> {
+        let mut arg = Arg::with_name(self.name)
+            .help(self.help)
+            .takes_value(self.takes_value)
+            .multiple(self.multiple)
+            .hidden(self.hidden);
+        if let Some(short) = self.short {
+            arg = arg.short(short);
+        }
+        if let Some(long) = self.long {
+            arg = arg.long(long);
+        }
+        if !self.aliases.is_empty() {
+            arg = arg.aliases(self.aliases);
+        }
+        for alias in self.visible_aliases {
+            arg = arg.visible_alias(*alias);
+        }
+        if let Some(value_name) = self.value_name {
+            arg = arg.value_name(value_name);
+        }
+        if !self.possible_values.is_empty() {
+            arg = arg.possible_values(self.possible_values);
+        }
+        if let Some(default) = self.default {
+            arg = arg.default_value(default);
+        }
+        if !self.conflicts.is_empty() {
+            arg = arg.conflicts_with_all(self.conflicts);
+        }
+        for required in self.requires {
+            arg = arg.requires(required);
+        }
+        arg
+    }
+}
+
+/// What to keep of the terminal capabilities in pipes.
+const KEEP: &[&str] = &["plain", "colours", "colors", "links", "images"];
+
+/// All arguments of mdcat, in the order of help.
+pub const ARGS: &[ArgDef] = &[
+    ArgDef::positional(
+        "filename",
+        "The files to read.  If - read from standard input instead.  Show the name \
+         of each file before its contents if there are many",
+    )
+    .multiple()
+    .default("-"),
+    ArgDef::flag(
+        "recursive",
+        "recursive",
+        "Render all markdown files in directories and their subdirectories",
+    )
+    .short("r"),
+    ArgDef::flag(
+        "no_colour",
+        "no-colour",
+        "Disable all colours and other styles.",
+    )
+    .short("c")
+    .aliases(&["nocolour", "no-color", "nocolor"]),
+    ArgDef::option(
+        "when_piped",
+        "when-piped",
+        "What to keep if standard output is no terminal: Plain text, colours, \
+         colours and links, or everything including images for a terminal further \
+         down the pipe",
+    )
+    .value_name("KEEP")
+    .possible_values(KEEP)
+    .default("images"),
+    ArgDef::option(
+        "columns",
+        "columns",
+        "Maximum number of columns to use for output",
+    )
+    .visible_aliases(&["width"]),
+    ArgDef::option(
+        "lines",
+        "height",
+        "Number of lines of the terminal, to limit the size of images",
+    )
+    .aliases(&["lines"]),
+    ArgDef::flag(
+        "monochrome",
+        "monochrome",
+        "Use only bold, italic, underline and dim text, no colours",
+    )
+    .short("m")
+    .conflicts(&["no_colour", "theme"]),
+    ArgDef::option(
+        "theme",
+        "theme",
+        "The theme for markdown elements, either a built-in theme or a TOML theme file; \
+         defaults to ~/.config/mdcat/theme.toml if it exists",
+    )
+    .value_name("THEME")
+    .default("default"),
+    ArgDef::flag(
+        "use_bat_assets",
+        "use-bat-assets",
+        "Highlight code with the syntaxes and the theme of bat",
+    ),
+    ArgDef::option(
+        "tab_width",
+        "tab-width",
+        "Expand tabs in code to N spaces at most",
+    )
+    .value_name("N")
+    .default("8"),
+    ArgDef::flag(
+        "show_invisibles",
+        "show-invisibles",
+        "Mark trailing whitespace, tabs and zero-width characters in code blocks",
+    ),
+    ArgDef::flag(
+        "explain_fallbacks",
+        "explain-fallbacks",
+        "Mark and list content which this terminal cannot show faithfully",
+    ),
+    ArgDef::option(
+        "image_viewer",
+        "image-viewer",
+        "Open images in COMMAND, eg, feh, if the terminal cannot show images \
+         inline; mdcat adds the path of the image file or its cached download",
+    )
+    .value_name("COMMAND"),
+    ArgDef::flag(
+        "image_file_links",
+        "image-file-links",
+        "Link to image files and cached downloads of remote images instead of \
+         their URLs if the terminal cannot show images inline",
+    )
+    .conflicts(&["image_viewer"]),
+    ArgDef::flag(
+        "deterministic",
+        "deterministic",
+        "Write the same output for the same input every time, eg, for tests",
+    ),
+    ArgDef::flag(
+        "timings",
+        "timings",
+        "Print how long parsing and highlighting took, and how much we read and wrote, \
+         to stderr",
+    ),
+    ArgDef::flag(
+        "self_bench",
+        "self-bench",
+        "Benchmark rendering of built-in documents and print timings as JSON",
+    )
+    .hidden(),
+    ArgDef::flag(
+        "render_cache",
+        "render-cache",
+        "Reuse rendered output of unchanged documents when rendering many files \
+         or watching a file",
+    ),
+    ArgDef::flag(
+        "align_numbers",
+        "align-numbers",
+        "Right-align table columns of numbers which the table does not align",
+    ),
+    ArgDef::flag(
+        "list_link_definitions",
+        "list-link-definitions",
+        "List reference-style link definitions at the end of the document",
+    ),
+    ArgDef::flag(
+        "compact",
+        "compact",
+        "Use fewer blank lines around headings, lists and code blocks",
+    ),
+    ArgDef::flag(
+        "accessible",
+        "a11y",
+        "Announce structure with text instead of colours, for screen readers",
+    )
+    .aliases(&["accessible"]),
+    ArgDef::flag(
+        "local_only",
+        "local",
+        "Do not load remote resources like images",
+    )
+    .short("l")
+    .aliases(&["local-only"]),
+    ArgDef::flag(
+        "allow_remote",
+        "allow-remote",
+        "Load remote resources like images, even with --preview; remote \
+         resources are cached, limited to 10 MiB, and time out after 10 seconds",
+    )
+    .conflicts(&["local_only"]),
+    ArgDef::flag(
+        "toc",
+        "toc",
+        "Show a table of contents at the start if the document has no [TOC] marker",
+    ),
+    ArgDef::flag(
+        "no_render",
+        "no-render",
+        "Show the markdown source with syntax highlighting instead of rendering it",
+    )
+    .visible_aliases(&["source"])
+    .conflicts(&[
+        "dump_events",
+        "bug_report",
+        "split_output",
+        "max_output_lines",
+        "preview",
+    ]),
+    ArgDef::flag(
+        "crossrefs",
+        "crossrefs",
+        "Number figures and tables, and resolve references like @fig:key",
+    ),
+    ArgDef::flag(
+        "smart",
+        "smart",
+        "Use typographic quotes, dashes and ellipses",
+    ),
+    ArgDef::option(
+        "lang",
+        "lang",
+        "Use the quotes and bullets of LANG, eg, de or fr, for --smart",
+    )
+    .value_name("LANG"),
+    ArgDef::flag(
+        "detect_language",
+        "detect-language",
+        "Detect the language of each paragraph for --smart, and fall back to --lang",
+    ),
+    ArgDef::flag(
+        "preview",
+        "preview",
+        "Format for preview panes of fzf or file managers: Take the size of the \
+         pane from --columns and --height or from fzf, show no images and \
+         access no remote resources, and truncate output to the height of the pane",
+    )
+    .conflicts(&["dump_events", "bug_report", "split_output"]),
+    ArgDef::flag(
+        "watch",
+        "watch",
+        "Clear the screen and render again whenever the file changes",
+    )
+    .short("w")
+    .conflicts(&[
+        "bug_report",
+        "detect_only",
+        "no_render",
+        "split_output",
+        "extract_code",
+        "list_commands",
+        "paginate",
+        "preview",
+        "max_output_lines",
+        "man_fallback",
+    ]),
+    ArgDef::flag(
+        "log",
+        "log",
+        "Render markdown logs, eg, from CI, block by block as they come in, with a \
+         timestamp in UTC before each block, and without images",
+    )
+    .conflicts(&[
+        "watch",
+        "bug_report",
+        "detect_only",
+        "no_render",
+        "split_output",
+        "extract_code",
+        "list_commands",
+        "paginate",
+        "max_output_lines",
+        "man_fallback",
+    ]),
+    ArgDef::flag(
+        "paginate",
+        "paginate",
+        "Show output in $MDCAT_PAGER or $PAGER, or in less -R by default, \
+         without images; do nothing if standard output is no terminal",
+    )
+    .short("p")
+    .conflicts(&["preview", "bug_report", "split_output"]),
+    ArgDef::option(
+        "pipe_to",
+        "pipe-to",
+        "Pipe output to the shell COMMAND, eg, a custom pager or tee; detect the \
+         terminal on standard output, which COMMAND inherits",
+    )
+    .value_name("COMMAND")
+    .conflicts(&["paginate", "watch", "log", "bug_report", "split_output"]),
+    ArgDef::option(
+        "pipe_keep",
+        "pipe-keep",
+        "What to keep for the final destination of --pipe-to, if it is not the \
+         terminal on standard output: Plain text, colours, colours and links, or \
+         everything",
+    )
+    .value_name("KEEP")
+    .possible_values(KEEP)
+    .requires(&["pipe_to"]),
+    ArgDef::option(
+        "max_output_lines",
+        "max-output-lines",
+        "Write at most N lines, and exit with code 2 if the output was truncated",
+    )
+    .value_name("N")
+    .conflicts(&["dump_events", "bug_report", "split_output"]),
+    ArgDef::option(
+        "split_output",
+        "split-output",
+        "Write each top-level section to a file in DIR, named after its heading",
+    )
+    .value_name("DIR")
+    .conflicts(&["dump_events", "bug_report"]),
+    ArgDef::option(
+        "extract_code",
+        "extract-code",
+        "Write each code block to a numbered file in DIR, with an extension \
+         after its language, instead of rendering",
+    )
+    .value_name("DIR")
+    .conflicts(&[
+        "dump_events",
+        "bug_report",
+        "split_output",
+        "paginate",
+        "preview",
+        "max_output_lines",
+    ]),
+    ArgDef::option(
+        "code_lang",
+        "code-lang",
+        "With --extract-code, only write code blocks in LANG, eg, rust",
+    )
+    .value_name("LANG")
+    .requires(&["extract_code"]),
+    ArgDef::flag(
+        "verify",
+        "verify",
+        "Check that rendering keeps all text of the document instead of rendering, \
+         and fail with the first word the output lacks",
+    )
+    .conflicts(&[
+        "dump_events",
+        "bug_report",
+        "split_output",
+        "extract_code",
+        "list_commands",
+        "paginate",
+        "watch",
+        "log",
+    ]),
+    ArgDef::flag(
+        "list_commands",
+        "list-commands",
+        "Print the commands of shell sessions, ie, lines starting with $ in \
+         console or bash code blocks, as a script instead of rendering",
+    )
+    .conflicts(&[
+        "dump_events",
+        "bug_report",
+        "split_output",
+        "extract_code",
+        "paginate",
+        "preview",
+        "max_output_lines",
+    ]),
+    ArgDef::flag(
+        "mdx",
+        "mdx",
+        "Strip JSX from MDX documents; the default for *.mdx files",
+    ),
+    ArgDef::option(
+        "filter_command",
+        "filter-cmd",
+        "Filter each document through the shell COMMAND before rendering, eg, a \
+         template engine",
+    )
+    .value_name("COMMAND")
+    .conflicts(&["log"]),
+    ArgDef::flag(
+        "man_fallback",
+        "man-fallback",
+        "Show the man page of the given name if there's no such file",
+    ),
+    ArgDef::flag(
+        "list_languages",
+        "list-languages",
+        "List all languages for syntax highlighting in code blocks",
+    ),
+    ArgDef::flag(
+        "help_json",
+        "help-json",
+        "Print all arguments with their types and defaults, and environment \
+         variables as JSON, eg, for settings of editor plugins",
+    ),
+    ArgDef::flag("version", "version", "Prints version information").short("V"),
+    ArgDef::flag(
+        "verbose",
+        "verbose",
+        "With --version, also print the supported features",
+    )
+    .short("v")
+    .requires(&["version"]),
+    ArgDef::flag(
+        "bug_report",
+        "bug-report",
+        "Write the environment, the input and the output to mdcat-bug-report.tar, \
+         for bug reports",
+    )
+    .conflicts(&["dump_events", "man_fallback"]),
+    ArgDef::flag(
+        "dump_events",
+        "dump-events",
+        "Dump Markdown parser events and exit",
+    )
+    .hidden(),
+    ArgDef::flag(
+        "detect_only",
+        "detect-only",
+        "Only detect the terminal type and exit",
+    )
+    .hidden(),
+    ArgDef::flag(
+        "ansi_only",
+        "ansi-only",
+        "Limit to standard ANSI formatting",
+    )
+    .conflicts(&["no_colour"])
+    .hidden(),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn default_tab_width() {
+        let tab_width = ARGS.iter().find(|arg| arg.name == "tab_width").unwrap();
+        assert_eq!(
+            tab_width.default,
+            Some(mdcat::DEFAULT_TAB_WIDTH.to_string().as_str())
+        );
+    }
+
+    #[test]
+    fn arguments_refer_to_known_arguments() {
+        for arg in ARGS {
+            for other in arg.conflicts.iter().chain(arg.requires) {
+                assert!(
+                    ARGS.iter().any(|known| known.name == *other),
+                    "{} refers to unknown argument {}",
+                    arg.name,
+                    other
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,166 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reuse the highlighting assets of bat.
+//!
+//! bat compiles custom syntaxes and themes from its config directory into
+//! `syntaxes.bin` and `themes.bin` in its cache directory with `bat cache
+//! --build`.  We load these files, and take the theme from `$BAT_THEME` or
+//! the `--theme` option in the config file of bat.
+
+use failure::{Error, ResultExt};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use syntect::dumps::from_dump_file;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::SyntaxSet;
+
+/// Get the directory of bat below the given base directory.
+///
+/// Take `$override_var` if set, or `bat` in `$base_var`, or `bat` in
+/// `fallback` in the home directory.  Look up variables with `var`.
+fn directory<F>(var: F, override_var: &str, base_var: &str, fallback: &str) -> Option<PathBuf>
+where
+    F: Fn(&str) -> Option<OsString>,
+{
+    var(override_var).map(PathBuf::from).or_else(|| {
+        var(base_var)
+            .map(PathBuf::from)
+            .or_else(|| var("HOME").map(|home| Path::new(&home).join(fallback)))
+            .map(|base| base.join("bat"))
+    })
+}
+
+/// The cache directory of bat.
+fn cache_dir() -> Option<PathBuf> {
+    directory(
+        |name| std::env::var_os(name),
+        "BAT_CACHE_PATH",
+        "XDG_CACHE_HOME",
+        ".cache",
+    )
+}
+
+/// The config file of bat.
+fn config_file() -> Option<PathBuf> {
+    std::env::var_os("BAT_CONFIG_PATH")
+        .map(PathBuf::from)
+        .or_else(|| {
+            directory(
+                |name| std::env::var_os(name),
+                "BAT_CONFIG_DIR",
+                "XDG_CONFIG_HOME",
+                ".config",
+            )
+            .map(|directory| directory.join("config"))
+        })
+}
+
+/// Get the theme from the `--theme` option in the bat `config`.
+///
+/// Take the last theme, like bat does.
+fn theme_from_config(config: &str) -> Option<String> {
+    let words = config
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#'))
+        .flat_map(str::split_whitespace)
+        .collect::<Vec<_>>();
+    let mut theme = None;
+    for (index, word) in words.iter().enumerate() {
+        if let Some(name) = word.strip_prefix("--theme=") {
+            theme = Some(name);
+        } else if *word == "--theme" {
+            theme = words.get(index + 1).copied();
+        }
+    }
+    theme.map(|name| name.trim_matches(|c| c == '"' || c == '\'').to_string())
+}
+
+/// The highlighting theme of bat, if the user chose one.
+pub fn theme() -> Option<String> {
+    std::env::var("BAT_THEME").ok().or_else(|| {
+        let config = std::fs::read_to_string(config_file()?).ok()?;
+        theme_from_config(&config)
+    })
+}
+
+/// Load the dump `name` from the cache directory of bat, if it exists.
+fn load_dump<T: serde::de::DeserializeOwned>(name: &str) -> Result<Option<T>, Error> {
+    match cache_dir().map(|directory| directory.join(name)) {
+        Some(path) if path.is_file() => {
+            let dump = from_dump_file(&path)
+                .with_context(|_| format!("Failed to load bat assets from {}", path.display()))?;
+            Ok(Some(dump))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// The syntaxes of bat, if the user built them.
+pub fn syntaxes() -> Result<Option<SyntaxSet>, Error> {
+    load_dump("syntaxes.bin")
+}
+
+/// The highlighting themes of bat by their names.
+///
+/// Empty if the user did not build any themes.
+pub fn themes() -> Result<BTreeMap<String, Theme>, Error> {
+    Ok(load_dump::<ThemeSet>("themes.bin")?.map_or_else(BTreeMap::new, |set| set.themes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn directories_of_bat() {
+        let var = |name: &str| match name {
+            "HOME" => Some(OsString::from("/home/foo")),
+            "XDG_CACHE_HOME" => Some(OsString::from("/tmp/cache")),
+            _ => None,
+        };
+        assert_eq!(
+            directory(var, "BAT_CACHE_PATH", "XDG_CACHE_HOME", ".cache"),
+            Some(PathBuf::from("/tmp/cache/bat"))
+        );
+        assert_eq!(
+            directory(var, "BAT_CONFIG_DIR", "XDG_CONFIG_HOME", ".config"),
+            Some(PathBuf::from("/home/foo/.config/bat"))
+        );
+        let var = |name: &str| match name {
+            "BAT_CACHE_PATH" => Some(OsString::from("/srv/bat")),
+            _ => None,
+        };
+        assert_eq!(
+            directory(var, "BAT_CACHE_PATH", "XDG_CACHE_HOME", ".cache"),
+            Some(PathBuf::from("/srv/bat"))
+        );
+    }
+
+    #[test]
+    fn theme_of_bat_config() {
+        assert_eq!(
+            theme_from_config("# --theme=Nope\n--theme=\"TwoDark\"\n--italic-text=always\n"),
+            Some("TwoDark".to_string())
+        );
+        assert_eq!(
+            theme_from_config("--theme ansi --paging=never\n"),
+            Some("ansi".to_string())
+        );
+        assert_eq!(theme_from_config("--paging=never\n"), None);
+    }
+}
@@ -0,0 +1,200 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A benchmark of mdcat, for `--self-bench`.
+//!
+//! Render built-in documents a few times, and measure how long each phase
+//! takes, so that users can attach numbers of their machine and their
+//! terminal to issues about performance.
+
+use crate::extract;
+use failure::Error;
+use mdcat::{Environment, Settings};
+use pulldown_cmark::Event;
+use serde::Serialize;
+use std::io;
+use std::io::prelude::*;
+use std::time::{Duration, Instant};
+use syntect::easy::HighlightLines;
+
+/// The documents we render.
+const CORPORA: &[(&str, &str)] = &[
+    ("common-mark", include_str!("../sample/common-mark.md")),
+    ("showcase", include_str!("../sample/showcase.md")),
+];
+
+/// How often we render each document.
+const ROUNDS: u32 = 10;
+
+/// The time of each phase of rendering, in milliseconds per round.
+#[derive(Debug, Default, Serialize)]
+struct Phases {
+    /// Parsing markdown into events.
+    parse: f64,
+    /// Highlighting code blocks.
+    highlight: f64,
+    /// Rendering events, without highlighting.
+    layout: f64,
+    /// Writing rendered output to the terminal.
+    write: f64,
+}
+
+/// The results for a document.
+#[derive(Debug, Serialize)]
+struct Corpus {
+    /// The name of the document.
+    name: &'static str,
+    /// The size of the document, in bytes.
+    bytes: usize,
+    /// The size of rendered output, in bytes.
+    output_bytes: usize,
+    /// How long each phase took.
+    phases: Phases,
+}
+
+/// The results of the benchmark.
+#[derive(Debug, Serialize)]
+struct Report {
+    /// The version of mdcat.
+    version: &'static str,
+    /// The terminal we rendered for.
+    terminal: String,
+    /// The number of columns we rendered for.
+    columns: usize,
+    /// How often we rendered each document.
+    rounds: u32,
+    /// How long it took to load syntaxes and themes, in milliseconds.
+    setup: f64,
+    /// The results for each document.
+    corpora: Vec<Corpus>,
+}
+
+/// Milliseconds of `duration` per round.
+fn per_round(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0 / f64::from(ROUNDS)
+}
+
+/// Highlight all code blocks of `events` with the theme of `settings`.
+fn highlight(settings: &Settings, events: &[Event<'_>]) {
+    let theme = match settings
+        .highlighting_themes()
+        .get(&settings.theme().highlighting)
+    {
+        Some(theme) => theme,
+        None => return,
+    };
+    for block in extract::code_blocks(events.iter().cloned()) {
+        let syntax = settings.syntax_set().find_syntax_by_token(&block.language);
+        if let Some(syntax) = syntax {
+            let mut highlighter = HighlightLines::new(syntax, theme);
+            for line in block.code.split_inclusive('\n') {
+                highlighter.highlight(line, settings.syntax_set());
+            }
+        }
+    }
+}
+
+/// Run the benchmark and write rendered output to `output`.
+///
+/// Get settings from `settings`, which we time as setup, and parse documents
+/// with `parse`.  Return the report as pretty JSON.
+pub fn run<S, P, W>(settings: S, parse: P, output: &mut W) -> Result<String, Error>
+where
+    S: FnOnce() -> Result<Settings, Error>,
+    P: for<'a> Fn(&'a str) -> Vec<Event<'a>>,
+    W: Write,
+{
+    let start = Instant::now();
+    let settings = settings()?;
+    let setup = start.elapsed();
+    let environment = Environment::for_local_directory(std::env::current_dir()?);
+    let mut corpora = Vec::new();
+    for (name, source) in CORPORA {
+        let (mut parsing, mut highlighting) = (Duration::default(), Duration::default());
+        let (mut rendering, mut writing) = (Duration::default(), Duration::default());
+        let mut output_bytes = 0;
+        // Warm up, to compile the regular expressions of syntaxes before we
+        // measure
+        let events = parse(source);
+        highlight(&settings, &events);
+        mdcat::push_tty(&settings, &mut io::sink(), &environment, events.into_iter())?;
+        for _ in 0..ROUNDS {
+            let start = Instant::now();
+            let events = parse(source);
+            parsing += start.elapsed();
+
+            let start = Instant::now();
+            highlight(&settings, &events);
+            highlighting += start.elapsed();
+
+            let start = Instant::now();
+            let mut rendered = Vec::new();
+            mdcat::push_tty(&settings, &mut rendered, &environment, events.into_iter())?;
+            rendering += start.elapsed();
+
+            let start = Instant::now();
+            output.write_all(&rendered)?;
+            output.flush()?;
+            writing += start.elapsed();
+            output_bytes = rendered.len();
+        }
+        corpora.push(Corpus {
+            name,
+            bytes: source.len(),
+            output_bytes,
+            phases: Phases {
+                parse: per_round(parsing),
+                highlight: per_round(highlighting),
+                // Rendering highlights code blocks again
+                layout: per_round(rendering.checked_sub(highlighting).unwrap_or_default()),
+                write: per_round(writing),
+            },
+        });
+    }
+    let report = Report {
+        version: env!("CARGO_PKG_VERSION"),
+        terminal: settings.terminal_capabilities().name.clone(),
+        columns: settings.terminal_size().width,
+        rounds: ROUNDS,
+        setup: setup.as_secs_f64() * 1000.0,
+        corpora,
+    };
+    Ok(serde_json::to_string_pretty(&report)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mdcat::{SettingsBuilder, TerminalCapabilities};
+    use pretty_assertions::assert_eq;
+    use pulldown_cmark::Parser;
+
+    #[test]
+    fn report_all_corpora() {
+        let mut output = Vec::new();
+        let settings = || Ok(SettingsBuilder::new(TerminalCapabilities::ansi()).build()?);
+        let report = run(
+            settings,
+            |source| Parser::new(source).collect(),
+            &mut output,
+        )
+        .unwrap();
+        let report: serde_json::Value = serde_json::from_str(&report).unwrap();
+        assert_eq!(report["rounds"], ROUNDS);
+        assert_eq!(report["corpora"][1]["name"], "showcase");
+        let output_bytes = report["corpora"][0]["output_bytes"].as_u64().unwrap()
+            + report["corpora"][1]["output_bytes"].as_u64().unwrap();
+        assert_eq!(output.len() as u64, output_bytes * u64::from(ROUNDS));
+    }
+}
@@ -0,0 +1,154 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A cache for remote resources on disk.
+//!
+//! We keep the contents of remote resources together with their ETag, to ask
+//! servers whether a resource changed instead of downloading it again.  We
+//! use resources without ETag for a while without asking servers at all.
+
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use url::Url;
+
+/// How long we use cached resources without ETag.
+const MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// A resource in the cache.
+#[derive(Debug, PartialEq)]
+pub(crate) struct CachedResource {
+    /// The ETag of the resource, if the server sent one.
+    pub(crate) etag: Option<String>,
+    /// The contents of the resource.
+    pub(crate) contents: Vec<u8>,
+    /// Whether we may use the resource without asking the server.
+    pub(crate) fresh: bool,
+}
+
+/// A cache for remote resources in a directory.
+pub(crate) struct Cache {
+    directory: PathBuf,
+}
+
+impl Cache {
+    /// A cache in `directory`.
+    pub(crate) fn new(directory: PathBuf) -> Cache {
+        Cache { directory }
+    }
+
+    /// The cache of the current user.
+    ///
+    /// Use `mdcat` in `$XDG_CACHE_HOME` or in `~/.cache`, if we know the home
+    /// directory.
+    pub(crate) fn for_user() -> Option<Cache> {
+        let cache_home = std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+        Some(Cache::new(cache_home.join("mdcat")))
+    }
+
+    /// The paths of the contents and the metadata of `url` in this cache.
+    fn paths(&self, url: &Url) -> (PathBuf, PathBuf) {
+        let mut hasher = DefaultHasher::new();
+        url.as_str().hash(&mut hasher);
+        let name = format!("{:016x}", hasher.finish());
+        (
+            self.directory.join(format!("{}.contents", name)),
+            self.directory.join(format!("{}.meta", name)),
+        )
+    }
+
+    /// The path of the contents of `url` in this cache, if we cached `url`.
+    pub(crate) fn contents_path(&self, url: &Url) -> Option<PathBuf> {
+        let (contents, meta) = self.paths(url);
+        Some(contents).filter(|_| meta.is_file())
+    }
+
+    /// Get the resource at `url` from this cache.
+    ///
+    /// Return `None` if we did not cache `url` or cannot read the cache.
+    pub(crate) fn get(&self, url: &Url) -> Option<CachedResource> {
+        let (contents, meta) = self.paths(url);
+        let modified = fs::metadata(&meta).and_then(|m| m.modified()).ok()?;
+        let meta = fs::read_to_string(meta).ok()?;
+        let mut lines = meta.lines();
+        // Check the URL to rule out collisions of hashes
+        if lines.next() != Some(url.as_str()) {
+            return None;
+        }
+        let etag = lines
+            .next()
+            .filter(|etag| !etag.is_empty())
+            .map(ToString::to_string);
+        let age = SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default();
+        Some(CachedResource {
+            fresh: etag.is_none() && age < MAX_AGE,
+            etag,
+            contents: fs::read(contents).ok()?,
+        })
+    }
+
+    /// Put the `contents` of `url` with the given `etag`, if any, into this
+    /// cache.
+    pub(crate) fn put(&self, url: &Url, etag: Option<&str>, contents: &[u8]) -> io::Result<()> {
+        let (contents_path, meta_path) = self.paths(url);
+        fs::create_dir_all(&self.directory)?;
+        fs::write(contents_path, contents)?;
+        // Write metadata last, so that we never take partial contents for
+        // cached
+        fs::write(meta_path, format!("{}\n{}\n", url, etag.unwrap_or("")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn put_and_get_resources() {
+        let directory =
+            std::env::temp_dir().join(format!("mdcat-cache-test-{}", std::process::id()));
+        let cache = Cache::new(directory.clone());
+        let url = Url::parse("https://example.com/logo.png").unwrap();
+        let other = Url::parse("https://example.com/badge.svg").unwrap();
+        assert_eq!(cache.get(&url), None);
+        cache.put(&url, Some("\"abc\""), b"logo").unwrap();
+        assert_eq!(
+            cache.get(&url),
+            Some(CachedResource {
+                etag: Some("\"abc\"".to_string()),
+                contents: b"logo".to_vec(),
+                fresh: false,
+            })
+        );
+        assert_eq!(cache.get(&other), None);
+        cache.put(&url, None, b"new logo").unwrap();
+        assert_eq!(
+            cache.get(&url),
+            Some(CachedResource {
+                etag: None,
+                contents: b"new logo".to_vec(),
+                fresh: true,
+            })
+        );
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
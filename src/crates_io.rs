@@ -0,0 +1,258 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Read the README of crates published on <https://crates.io>.
+
+use failure::{format_err, Error};
+use flate2::read::GzDecoder;
+use reqwest::header::{HeaderMap, HeaderValue, USER_AGENT};
+use std::io::prelude::*;
+use std::path::Path;
+
+/// A reference to a crate on crates.io.
+#[derive(Debug, PartialEq)]
+pub struct CrateReference<'a> {
+    /// The name of the crate.
+    pub name: &'a str,
+    /// The version of the crate, or `None` for the latest stable version.
+    pub version: Option<&'a str>,
+}
+
+/// The maximum length of crate names on crates.io.
+const MAX_NAME_LENGTH: usize = 64;
+
+/// Whether `name` is a valid crate name on crates.io.
+///
+/// We put names into URLs, so we must not let slashes, queries or `..`
+/// through.
+fn is_crate_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LENGTH
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Whether `version` is a plausible version of a crate, ie, a semantic
+/// version with pre-release and build metadata.
+fn is_version(version: &str) -> bool {
+    version.starts_with(|c: char| c.is_ascii_digit())
+        && version
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '+')
+}
+
+/// Parse a reference to a crate from a command line argument.
+///
+/// Understand `crate:NAME` and `crate:NAME@VERSION`, as well as links to a
+/// crate on crates.io or to its documentation on docs.rs.  Return `None` for
+/// invalid crate names or versions.
+pub fn parse_crate_reference(input: &str) -> Option<CrateReference<'_>> {
+    parse_reference(input).filter(|reference| {
+        is_crate_name(reference.name) && reference.version.is_none_or(is_version)
+    })
+}
+
+/// Parse a reference to a crate from `input`, without validation.
+fn parse_reference(input: &str) -> Option<CrateReference<'_>> {
+    if let Some(reference) = input.strip_prefix("crate:") {
+        let mut parts = reference.splitn(2, '@');
+        let name = parts.next().filter(|name| !name.is_empty())?;
+        let version = parts.next().filter(|version| !version.is_empty());
+        Some(CrateReference { name, version })
+    } else {
+        let path = [
+            "https://crates.io/crates/",
+            "https://docs.rs/crate/",
+            "https://docs.rs/",
+        ]
+        .iter()
+        .find_map(|prefix| input.strip_prefix(prefix))?;
+        let mut segments = path.split('/').filter(|s| !s.is_empty());
+        let name = segments.next()?;
+        let version = segments.next().filter(|version| *version != "latest");
+        Some(CrateReference { name, version })
+    }
+}
+
+/// Create a HTTP client for crates.io.
+///
+/// crates.io rejects requests without a proper user agent.
+fn client() -> Result<reqwest::Client, Error> {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        USER_AGENT,
+        HeaderValue::from_static(concat!(
+            "mdcat/",
+            env!("CARGO_PKG_VERSION"),
+            " (https://github.com/lunaryorn/mdcat)"
+        )),
+    );
+    reqwest::Client::builder()
+        .default_headers(headers)
+        .build()
+        .map_err(Into::into)
+}
+
+/// GET the given `url` and fail if the response has no success status.
+fn get(client: &reqwest::Client, url: &str) -> Result<reqwest::Response, Error> {
+    let response = client.get(url).send()?;
+    if response.status().is_success() {
+        Ok(response)
+    } else {
+        Err(format_err!(
+            "HTTP error status {} by GET {}",
+            response.status(),
+            url
+        ))
+    }
+}
+
+/// Get the latest stable version of the crate with the given `name`.
+fn latest_version(client: &reqwest::Client, name: &str) -> Result<String, Error> {
+    let url = format!("https://crates.io/api/v1/crates/{}", name);
+    let metadata: serde_json::Value = get(client, &url)?.json()?;
+    let krate = &metadata["crate"];
+    krate["max_stable_version"]
+        .as_str()
+        .or_else(|| krate["max_version"].as_str())
+        .map(ToString::to_string)
+        .ok_or_else(|| format_err!("No version of crate {} found", name))
+}
+
+/// Whether `path` denotes the README file in a crate archive.
+///
+/// Crate archives contain a single top-level directory for the crate; we look
+/// for any file named `README` in this directory, regardless of case and
+/// extension.
+fn is_readme(path: &Path) -> bool {
+    path.components().count() == 2
+        && path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .is_some_and(|stem| stem.eq_ignore_ascii_case("readme"))
+}
+
+/// Fetch the README of the given crate from crates.io.
+///
+/// Download the published crate archive and extract its README file.
+pub fn fetch_readme(reference: &CrateReference<'_>) -> Result<String, Error> {
+    let client = client()?;
+    let version = match reference.version {
+        Some(version) => version.to_string(),
+        None => latest_version(&client, reference.name)?,
+    };
+    let url = format!(
+        "https://static.crates.io/crates/{0}/{0}-{1}.crate",
+        reference.name, version
+    );
+    let mut archive = tar::Archive::new(GzDecoder::new(get(&client, &url)?));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if is_readme(&entry.path()?) {
+            let mut readme = String::new();
+            entry.read_to_string(&mut readme)?;
+            return Ok(readme);
+        }
+    }
+    Err(format_err!(
+        "Crate {} {} has no README",
+        reference.name,
+        version
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parse_crate_reference_with_crate_prefix() {
+        assert_eq!(
+            parse_crate_reference("crate:serde"),
+            Some(CrateReference {
+                name: "serde",
+                version: None
+            })
+        );
+        assert_eq!(
+            parse_crate_reference("crate:serde@1.0.82"),
+            Some(CrateReference {
+                name: "serde",
+                version: Some("1.0.82")
+            })
+        );
+        assert_eq!(parse_crate_reference("crate:"), None);
+    }
+
+    #[test]
+    fn parse_crate_reference_with_urls() {
+        let checks = [
+            ("https://crates.io/crates/mdcat", "mdcat", None),
+            (
+                "https://crates.io/crates/mdcat/0.12.1",
+                "mdcat",
+                Some("0.12.1"),
+            ),
+            ("https://docs.rs/mdcat", "mdcat", None),
+            ("https://docs.rs/mdcat/latest/mdcat/", "mdcat", None),
+            (
+                "https://docs.rs/crate/mdcat/0.12.1",
+                "mdcat",
+                Some("0.12.1"),
+            ),
+        ];
+        for &(input, name, version) in checks.iter() {
+            assert_eq!(
+                parse_crate_reference(input),
+                Some(CrateReference { name, version }),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn parse_crate_reference_with_other_input() {
+        assert_eq!(parse_crate_reference("README.md"), None);
+        assert_eq!(parse_crate_reference("https://example.com/README.md"), None);
+    }
+
+    #[test]
+    fn reject_invalid_names_and_versions() {
+        for input in &[
+            "crate:../serde",
+            "crate:serde/1.0.82",
+            "crate:serde?download",
+            "crate:serde@../../index",
+            "crate:serde@1.0/x",
+            "https://docs.rs/serde?query",
+        ] {
+            assert_eq!(parse_crate_reference(input), None, "{}", input);
+        }
+        let long = format!("crate:{}", "a".repeat(65));
+        assert_eq!(parse_crate_reference(&long), None);
+        assert!(parse_crate_reference(&long[..70]).is_some());
+        assert!(parse_crate_reference("crate:serde_json-x@1.0.0-rc.1+build").is_some());
+    }
+
+    #[test]
+    fn is_readme_only_at_top_level() {
+        assert!(is_readme(Path::new("serde-1.0.82/README.md")));
+        assert!(is_readme(Path::new("serde-1.0.82/readme")));
+        assert!(!is_readme(Path::new("serde-1.0.82/src/README.md")));
+        assert!(!is_readme(Path::new("serde-1.0.82/Cargo.toml")));
+    }
+}
@@ -0,0 +1,270 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Numbered figures and tables, and cross-references.
+//!
+//! We follow the syntax of [pandoc-crossref][]: Label figures with an
+//! attribute after the image, and tables with an attribute after the table
+//! caption:
+//!
+//! ```markdown
+//! ![A cat](cat.png){#fig:cat}
+//!
+//! Table: Numbers {#tbl:numbers}
+//! ```
+//!
+//! and refer to them with `@fig:cat` and `@tbl:numbers`.
+//!
+//! [pandoc-crossref]: https://github.com/lierdakil/pandoc-crossref
+
+use pulldown_cmark::Event;
+use pulldown_cmark::Event::*;
+use pulldown_cmark::Tag::*;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// The kinds of labels with the names to refer to them.
+const KINDS: &[(&str, &str)] = &[("fig", "Figure"), ("tbl", "Table")];
+
+/// The prefix of table captions.
+const TABLE_CAPTION: &str = "Table: ";
+
+/// Whether `c` can appear in a label.
+fn is_label_char(c: char) -> bool {
+    c.is_alphanumeric() || c == ':' || c == '-' || c == '_'
+}
+
+/// Parse a label attribute like `{#fig:key}` at the end of `text`.
+///
+/// Return the text before the attribute, and the label.
+fn label_attribute(text: &str) -> Option<(&str, &str)> {
+    let trimmed = text.trim_end();
+    let start = trimmed.rfind("{#")?;
+    let label = trimmed[start + 2..].strip_suffix('}')?;
+    if KINDS
+        .iter()
+        .any(|(kind, _)| label.starts_with(kind) && label[kind.len()..].starts_with(':'))
+        && label.chars().all(is_label_char)
+    {
+        Some((&trimmed[..start], label))
+    } else {
+        None
+    }
+}
+
+/// The name of the `number`th item labelled with `label`.
+fn name_of(label: &str, number: usize) -> String {
+    let kind = label.split(':').next().unwrap_or("");
+    let name = KINDS
+        .iter()
+        .find(|(prefix, _)| *prefix == kind)
+        .map_or("", |(_, name)| name);
+    format!("{} {}", name, number)
+}
+
+/// Merge adjacent text events in `events`.
+fn merge_text<'a, I>(events: I) -> Vec<Event<'a>>
+where
+    I: IntoIterator<Item = Event<'a>>,
+{
+    let mut merged: Vec<Event<'a>> = Vec::new();
+    for event in events {
+        match (merged.last_mut(), event) {
+            (Some(Text(previous)), Text(text)) => previous.to_mut().push_str(&text),
+            (_, event) => merged.push(event),
+        }
+    }
+    merged
+}
+
+/// Number labelled figures and tables in `events`.
+///
+/// Remove label attributes, prefix captions with their number, and return
+/// the names of all labels.
+fn number_captions(events: &mut Vec<Event<'_>>) -> HashMap<String, String> {
+    let mut names = HashMap::new();
+    let mut counters: HashMap<&str, usize> = HashMap::new();
+    let mut index = 0;
+    while index < events.len() {
+        let number = |label: &str, counters: &mut HashMap<&str, usize>| {
+            let kind = KINDS
+                .iter()
+                .map(|(kind, _)| *kind)
+                .find(|kind| label.starts_with(kind))
+                .unwrap_or("");
+            let counter = counters.entry(kind).or_insert(0);
+            *counter += 1;
+            name_of(label, *counter)
+        };
+        match (&events[index], events.get(index + 1)) {
+            (End(Image(_, _)), Some(Text(text))) => {
+                if let Some((rest, label)) = label_attribute(text) {
+                    let label = label.to_string();
+                    let rest = rest.to_string();
+                    let name = number(&label, &mut counters);
+                    // Prefix the image title with the name of the figure
+                    let start = events[..index]
+                        .iter()
+                        .rposition(|event| matches!(event, Start(Image(_, _))))
+                        .unwrap_or(index);
+                    events.insert(start + 1, Text(Cow::Owned(format!("{}: ", name))));
+                    index += 1;
+                    if rest.is_empty() {
+                        events.remove(index + 1);
+                    } else {
+                        events[index + 1] = Text(Cow::Owned(rest));
+                    }
+                    names.insert(label, name);
+                }
+            }
+            (Start(Paragraph), Some(Text(text))) if text.starts_with(TABLE_CAPTION) => {
+                if let Some((caption, label)) = label_attribute(text) {
+                    if label.starts_with("tbl:") {
+                        let label = label.to_string();
+                        let name = number(&label, &mut counters);
+                        let caption = format!("{}: {}", name, &caption[TABLE_CAPTION.len()..]);
+                        events[index + 1] = Text(Cow::Owned(caption.trim_end().to_string()));
+                        names.insert(label, name);
+                    }
+                }
+            }
+            _ => {}
+        }
+        index += 1;
+    }
+    names
+}
+
+/// Resolve all references in `text` with the given `names`.
+///
+/// Leave unknown references alone.
+fn resolve_references(text: &str, names: &HashMap<String, String>) -> Option<String> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    let mut resolved_any = false;
+    while let Some(start) = rest.find('@') {
+        result.push_str(&rest[..start]);
+        let candidate = &rest[start + 1..];
+        let end = candidate
+            .find(|c: char| !is_label_char(c))
+            .unwrap_or(candidate.len());
+        // Do not take trailing punctuation into the label
+        let label = candidate[..end].trim_end_matches([':', '-']);
+        match names.get(label) {
+            Some(name) => {
+                result.push_str(name);
+                resolved_any = true;
+            }
+            None => {
+                result.push('@');
+                result.push_str(label);
+            }
+        }
+        rest = &candidate[label.len()..];
+    }
+    result.push_str(rest);
+    if resolved_any {
+        Some(result)
+    } else {
+        None
+    }
+}
+
+/// Number figures and tables in `events` and resolve cross-references.
+///
+/// Prefix the captions of labelled figures and tables with their number,
+/// eg, "Figure 3: ", and replace references like `@fig:key` with the name
+/// of the figure, eg, "Figure 3".  Leave references in code alone.
+pub fn resolve_crossrefs<'a, I>(events: I) -> Vec<Event<'a>>
+where
+    I: IntoIterator<Item = Event<'a>>,
+{
+    let mut events = merge_text(events);
+    let names = number_captions(&mut events);
+    let mut in_code = false;
+    for event in events.iter_mut() {
+        match event {
+            Start(Code) | Start(CodeBlock(_)) => in_code = true,
+            End(Code) | End(CodeBlock(_)) => in_code = false,
+            Text(text) if !in_code => {
+                if let Some(resolved) = resolve_references(text, &names) {
+                    *text = Cow::Owned(resolved);
+                }
+            }
+            _ => {}
+        }
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use pulldown_cmark::Parser;
+
+    fn text(text: &'static str) -> Event<'static> {
+        Text(Cow::Borrowed(text))
+    }
+
+    #[test]
+    fn number_figures_and_resolve_references() {
+        let markdown = "![A cat](cat.png){#fig:cat}
+
+See @fig:cat, @tbl:numbers and @fig:dog, but not `@fig:cat`.
+
+Table: Numbers {#tbl:numbers}
+";
+        assert_eq!(
+            resolve_crossrefs(Parser::new(markdown)),
+            vec![
+                Start(Paragraph),
+                Start(Image(Cow::Borrowed("cat.png"), Cow::Borrowed(""))),
+                text("Figure 1: "),
+                text("A cat"),
+                End(Image(Cow::Borrowed("cat.png"), Cow::Borrowed(""))),
+                End(Paragraph),
+                Start(Paragraph),
+                text("See Figure 1, Table 1 and @fig:dog, but not "),
+                Start(Code),
+                text("@fig:cat"),
+                End(Code),
+                text("."),
+                End(Paragraph),
+                Start(Paragraph),
+                text("Table 1: Numbers"),
+                End(Paragraph),
+            ]
+        );
+    }
+
+    #[test]
+    fn count_kinds_separately() {
+        let markdown =
+            "![A](a.png){#fig:a}\n\n![B](b.png){#fig:b}\n\nTable: C {#tbl:c}\n\n@fig:b @tbl:c\n";
+        let events = resolve_crossrefs(Parser::new(markdown));
+        assert_eq!(events[events.len() - 2], text("Figure 2 Table 1"));
+    }
+
+    #[test]
+    fn parse_label_attributes() {
+        assert_eq!(label_attribute("{#fig:cat}"), Some(("", "fig:cat")));
+        assert_eq!(
+            label_attribute("Table: Foo {#tbl:foo-bar}"),
+            Some(("Table: Foo ", "tbl:foo-bar"))
+        );
+        assert_eq!(label_attribute("{#sec:cat}"), None);
+        assert_eq!(label_attribute("{#fig:cat dog}"), None);
+    }
+}
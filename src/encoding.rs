@@ -0,0 +1,175 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Decode documents in legacy encodings.
+//!
+//! Markdown is UTF-8 nowadays, but older documents come in UTF-16 with a byte
+//! order mark, eg, from Windows editors, or in Latin-1.  We transcode these
+//! to UTF-8 instead of failing or rendering mojibake.
+//!
+//! We also normalize line endings, so that documents from Windows render just
+//! like documents from Unix.
+
+use std::fmt;
+
+/// An encoding of a document other than UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    /// UTF-16, little endian, with a byte order mark.
+    Utf16Le,
+    /// UTF-16, big endian, with a byte order mark.
+    Utf16Be,
+    /// Windows-1252, the superset of Latin-1 which Windows uses.
+    Windows1252,
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Encoding::Utf16Le => write!(f, "UTF-16LE"),
+            Encoding::Utf16Be => write!(f, "UTF-16BE"),
+            Encoding::Windows1252 => write!(f, "Latin-1 (Windows-1252)"),
+        }
+    }
+}
+
+/// Characters of Windows-1252 for bytes 0x80 to 0x9F, which are control
+/// characters in Latin-1.
+///
+/// Windows-1252 leaves five of these bytes undefined; we map them to the
+/// control characters of Latin-1.
+const WINDOWS_1252: [char; 32] = [
+    '\u{20AC}', '\u{81}', '\u{201A}', '\u{192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2C6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8D}', '\u{17D}', '\u{8F}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2DC}', '\u{2122}', '\u{161}', '\u{203A}', '\u{153}', '\u{9D}', '\u{17E}', '\u{178}',
+];
+
+/// Decode UTF-16 `bytes` with the given byte order.
+///
+/// Replace unpaired surrogates and a trailing odd byte with U+FFFD.
+fn decode_utf16(bytes: &[u8], to_unit: fn([u8; 2]) -> u16) -> String {
+    let units = bytes.chunks(2).map(|chunk| match *chunk {
+        [first, second] => to_unit([first, second]),
+        _ => 0xFFFD,
+    });
+    std::char::decode_utf16(units)
+        .map(|c| c.unwrap_or(std::char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+/// Decode Windows-1252 `bytes`.
+fn decode_windows_1252(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&byte| match byte {
+            0x80..=0x9F => WINDOWS_1252[usize::from(byte - 0x80)],
+            _ => char::from(byte),
+        })
+        .collect()
+}
+
+/// Decode the document `bytes`.
+///
+/// Take UTF-16 if `bytes` start with a UTF-16 byte order mark, UTF-8 if
+/// `bytes` are valid UTF-8, and Windows-1252 otherwise, because every byte
+/// sequence is valid Windows-1252.  Strip UTF-16 byte order marks.
+///
+/// Return the document, and its encoding if not UTF-8.
+pub fn decode(bytes: Vec<u8>) -> (String, Option<Encoding>) {
+    match bytes.as_slice() {
+        [0xFF, 0xFE, rest @ ..] => (
+            decode_utf16(rest, u16::from_le_bytes),
+            Some(Encoding::Utf16Le),
+        ),
+        [0xFE, 0xFF, rest @ ..] => (
+            decode_utf16(rest, u16::from_be_bytes),
+            Some(Encoding::Utf16Be),
+        ),
+        _ => match String::from_utf8(bytes) {
+            Ok(text) => (text, None),
+            Err(error) => (
+                decode_windows_1252(error.as_bytes()),
+                Some(Encoding::Windows1252),
+            ),
+        },
+    }
+}
+
+/// Normalize the decoded document `text`.
+///
+/// Strip a leading byte order mark, and replace CRLF and CR line endings with
+/// LF.
+pub fn normalize(text: String) -> String {
+    let text = match text.strip_prefix('\u{FEFF}') {
+        Some(rest) => rest.to_string(),
+        None => text,
+    };
+    if text.contains('\r') {
+        text.replace("\r\n", "\n").replace('\r', "\n")
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn keep_utf8() {
+        let text = "# Grüße\n";
+        assert_eq!(decode(text.as_bytes().to_vec()), (text.to_string(), None));
+    }
+
+    #[test]
+    fn decode_utf16_with_byte_order_mark() {
+        let text = "# Grüße 🦀\n";
+        let mut little = vec![0xFF, 0xFE];
+        let mut big = vec![0xFE, 0xFF];
+        for unit in text.encode_utf16() {
+            little.extend_from_slice(&unit.to_le_bytes());
+            big.extend_from_slice(&unit.to_be_bytes());
+        }
+        assert_eq!(decode(little), (text.to_string(), Some(Encoding::Utf16Le)));
+        assert_eq!(decode(big), (text.to_string(), Some(Encoding::Utf16Be)));
+    }
+
+    #[test]
+    fn decode_latin1() {
+        let bytes = b"# Gr\xFC\xDFe \x93quoted\x94 \x80\n".to_vec();
+        assert_eq!(
+            decode(bytes),
+            (
+                "# Grüße \u{201C}quoted\u{201D} \u{20AC}\n".to_string(),
+                Some(Encoding::Windows1252)
+            )
+        );
+    }
+
+    #[test]
+    fn normalize_line_endings() {
+        let text = "# Lorem\r\n\r\n```\r\nfoo\rbar\n```\r\n".to_string();
+        assert_eq!(normalize(text), "# Lorem\n\n```\nfoo\nbar\n```\n");
+    }
+
+    #[test]
+    fn strip_byte_order_mark() {
+        let (text, _) = decode(b"\xEF\xBB\xBF# Lorem\r\n".to_vec());
+        assert_eq!(normalize(text), "# Lorem\n");
+        let (text, _) = decode(b"\xFF\xFE#\x00\r\x00\n\x00".to_vec());
+        assert_eq!(normalize(text), "#\n");
+    }
+}
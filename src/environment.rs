@@ -0,0 +1,127 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The environment of a markdown document.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// The environment to render a markdown document in.
+///
+/// Holds everything mdcat would otherwise take from the process, so that
+/// library users and tests can control all ambient inputs.
+#[derive(Debug, Clone)]
+pub struct Environment {
+    base_dir: PathBuf,
+    base_url: Option<String>,
+    hostname: String,
+    variables: HashMap<String, String>,
+}
+
+impl Environment {
+    /// The environment of this process, for a document in `base_dir`.
+    ///
+    /// Take environment variables and the hostname from this process.
+    pub fn for_local_directory<P: AsRef<Path>>(base_dir: P) -> Environment {
+        Environment {
+            base_dir: base_dir.as_ref().to_path_buf(),
+            base_url: None,
+            hostname: local_hostname(),
+            variables: std::env::vars().collect(),
+        }
+    }
+
+    /// An environment for a document in `base_dir` which is independent of
+    /// this process.
+    ///
+    /// The environment has no variables and `localhost` as hostname.
+    pub fn isolated<P: AsRef<Path>>(base_dir: P) -> Environment {
+        Environment {
+            base_dir: base_dir.as_ref().to_path_buf(),
+            base_url: None,
+            hostname: "localhost".to_string(),
+            variables: HashMap::new(),
+        }
+    }
+
+    /// Resolve relative references against `base_url` instead of the base
+    /// directory.
+    ///
+    /// Use this for documents from remote sources.
+    pub fn with_base_url<S: Into<String>>(mut self, base_url: S) -> Environment {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// Use the given `hostname` for local `file://` URLs.
+    pub fn with_hostname<S: Into<String>>(mut self, hostname: S) -> Environment {
+        self.hostname = hostname.into();
+        self
+    }
+
+    /// Set the environment variable `name` to `value`.
+    pub fn with_var<K: Into<String>, V: Into<String>>(mut self, name: K, value: V) -> Environment {
+        self.variables.insert(name.into(), value.into());
+        self
+    }
+
+    /// The base directory of the document.
+    pub fn base_dir(&self) -> &Path {
+        &self.base_dir
+    }
+
+    /// The base URL of the document, if any.
+    pub fn base_url(&self) -> Option<&str> {
+        self.base_url.as_deref()
+    }
+
+    /// The hostname of the system the document is on.
+    pub fn hostname(&self) -> &str {
+        &self.hostname
+    }
+
+    /// Get the environment variable `name`.
+    pub fn var(&self, name: &str) -> Option<&str> {
+        self.variables.get(name).map(String::as_str)
+    }
+}
+
+/// Get the hostname of this system.
+#[cfg(all(unix, feature = "osc8_links"))]
+fn local_hostname() -> String {
+    crate::terminal::gethostname()
+}
+
+/// Get the hostname of this system.
+///
+/// We only need the hostname for OSC 8 links, so just assume `localhost`
+/// otherwise.
+#[cfg(not(all(unix, feature = "osc8_links")))]
+fn local_hostname() -> String {
+    "localhost".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn isolated_environment_has_no_variables() {
+        let environment = Environment::isolated("/tmp").with_var("TERM", "xterm");
+        assert_eq!(environment.var("TERM"), Some("xterm"));
+        assert_eq!(environment.var("HOME"), None);
+        assert_eq!(environment.hostname(), "localhost");
+    }
+}
@@ -0,0 +1,164 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Extract code blocks from documents.
+
+use pulldown_cmark::Event;
+use pulldown_cmark::Event::*;
+use pulldown_cmark::Tag::*;
+use syntect::parsing::SyntaxSet;
+
+/// A code block of a document.
+#[derive(Debug, PartialEq)]
+pub struct CodeBlock {
+    /// The language of the block, or an empty string if it has none.
+    pub language: String,
+    /// The code in the block.
+    pub code: String,
+}
+
+/// Get the language of a code block from its info string.
+///
+/// Take the first word of the info string, eg, `rust` from `rust,no_run`.
+fn language(info: &str) -> &str {
+    info.split(|c: char| c == ',' || c.is_whitespace())
+        .next()
+        .unwrap_or("")
+}
+
+/// Collect all code blocks in `events`.
+pub fn code_blocks<'a, I>(events: I) -> Vec<CodeBlock>
+where
+    I: IntoIterator<Item = Event<'a>>,
+{
+    let mut blocks = Vec::new();
+    let mut current: Option<CodeBlock> = None;
+    for event in events {
+        match event {
+            Start(CodeBlock(info)) => {
+                current = Some(CodeBlock {
+                    language: language(&info).to_string(),
+                    code: String::new(),
+                })
+            }
+            End(CodeBlock(_)) => blocks.extend(current.take()),
+            Text(ref text) => {
+                if let Some(ref mut block) = current {
+                    block.code.push_str(text);
+                }
+            }
+            _ => {}
+        }
+    }
+    blocks
+}
+
+/// Get the name of the file for the code block `block` with the given `number`.
+///
+/// Take the file extension from the syntax of the language of `block` in
+/// `syntax_set`, and use `txt` if we do not know the language.
+pub fn file_name(number: usize, block: &CodeBlock, syntax_set: &SyntaxSet) -> String {
+    let extension = syntax_set
+        .find_syntax_by_token(&block.language)
+        .and_then(|syntax| syntax.file_extensions.first())
+        .map_or("txt", String::as_str);
+    format!("{:03}.{}", number, extension)
+}
+
+/// Languages of code blocks which show shell sessions.
+const SHELL_LANGUAGES: &[&str] = &["console", "shell-session", "bash", "sh", "shell", "zsh"];
+
+/// Extract shell commands from the shell sessions among `blocks`.
+///
+/// Take every line starting with `$` in code blocks of shell languages as a
+/// command, together with its continuation lines if it ends with a backslash.
+pub fn commands(blocks: &[CodeBlock]) -> Vec<String> {
+    let mut commands: Vec<String> = Vec::new();
+    let sessions = blocks.iter().filter(|block| {
+        SHELL_LANGUAGES
+            .iter()
+            .any(|language| language.eq_ignore_ascii_case(&block.language))
+    });
+    for block in sessions {
+        let mut continued = false;
+        for line in block.code.lines() {
+            if continued {
+                if let Some(command) = commands.last_mut() {
+                    command.push('\n');
+                    command.push_str(line);
+                }
+            } else if let Some(command) = line.strip_prefix('$') {
+                commands.push(command.trim_start().to_string());
+            } else {
+                continue;
+            }
+            continued = line.ends_with('\\');
+        }
+    }
+    commands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use pulldown_cmark::Parser;
+
+    #[test]
+    fn collect_code_blocks() {
+        let markdown =
+            "Foo `bar`\n\n```rust,no_run\nfn main() {}\n```\n\n    indented\n\n```\nplain\n```\n";
+        assert_eq!(
+            code_blocks(Parser::new(markdown)),
+            vec![
+                CodeBlock {
+                    language: "rust".to_string(),
+                    code: "fn main() {}\n".to_string(),
+                },
+                CodeBlock {
+                    language: String::new(),
+                    code: "indented\n".to_string(),
+                },
+                CodeBlock {
+                    language: String::new(),
+                    code: "plain\n".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn commands_of_shell_sessions() {
+        let markdown =
+            "```console\n$ cargo build\n   Compiling mdcat\n$ ls \\\n  -l\ntotal 0\n```\n\n\
+                        ```rust\n$ not a command\n```\n\n```bash\n$echo hello\n# comment\n```\n";
+        assert_eq!(
+            commands(&code_blocks(Parser::new(markdown))),
+            vec!["cargo build", "ls \\\n  -l", "echo hello"]
+        );
+    }
+
+    #[test]
+    fn file_names_by_language() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let block = |language: &str| CodeBlock {
+            language: language.to_string(),
+            code: String::new(),
+        };
+        assert_eq!(file_name(1, &block("rust"), &syntax_set), "001.rs");
+        assert_eq!(file_name(12, &block("python"), &syntax_set), "012.py");
+        assert_eq!(file_name(3, &block(""), &syntax_set), "003.txt");
+        assert_eq!(file_name(4, &block("klingon"), &syntax_set), "004.txt");
+    }
+}
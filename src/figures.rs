@@ -0,0 +1,122 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Find figures, ie, images in paragraphs of their own.
+//!
+//! Like pandoc we take attributes in braces right after the image, eg,
+//! `![Logo](logo.png){.right}` or `![Logo](logo.png){align=left}`, for the
+//! alignment of a figure.
+
+use crate::theme::ImageAlignment;
+use pulldown_cmark::Event;
+use pulldown_cmark::Event::*;
+use pulldown_cmark::Tag::*;
+use std::collections::{HashMap, HashSet};
+
+/// The figures of a document.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Figures {
+    /// The alignment of figures by the index of the start event of their
+    /// image.
+    ///
+    /// `None` if the figure has no alignment of its own.
+    pub(crate) alignments: HashMap<usize, Option<ImageAlignment>>,
+    /// The indexes of the text events with attributes of figures.
+    pub(crate) attributes: HashSet<usize>,
+}
+
+/// Get the alignment from pandoc `attributes` in braces, if any.
+fn attribute_alignment(attributes: &str) -> Option<ImageAlignment> {
+    attributes
+        .trim_start_matches('{')
+        .trim_end_matches('}')
+        .split_whitespace()
+        .filter_map(|attribute| {
+            attribute
+                .strip_prefix('.')
+                .or_else(|| attribute.strip_prefix("align="))
+        })
+        .find_map(|name| ImageAlignment::from_name(name.trim_matches('"')))
+}
+
+/// Whether `text` holds pandoc attributes.
+fn is_attributes(text: &str) -> bool {
+    let text = text.trim();
+    text.starts_with('{') && text.ends_with('}')
+}
+
+/// Find all figures in `events`.
+pub(crate) fn figures(events: &[Event]) -> Figures {
+    let mut figures = Figures::default();
+    for (index, window) in events.windows(2).enumerate() {
+        if let [Start(Paragraph), Start(Image(_, _))] = window {
+            let image = index + 1;
+            let end = match events[image..]
+                .iter()
+                .position(|event| matches!(event, End(Image(_, _))))
+            {
+                Some(offset) => image + offset,
+                None => continue,
+            };
+            match &events[end + 1..] {
+                [End(Paragraph), ..] => {
+                    figures.alignments.insert(image, None);
+                }
+                [Text(text), End(Paragraph), ..] if is_attributes(text) => {
+                    figures.alignments.insert(image, attribute_alignment(text));
+                    figures.attributes.insert(end + 1);
+                }
+                _ => {}
+            }
+        }
+    }
+    figures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use pulldown_cmark::Parser;
+
+    fn figures_of(markdown: &str) -> Figures {
+        figures(&Parser::new(markdown).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn find_images_in_paragraphs_of_their_own() {
+        let figures = figures_of("![Logo](logo.png)\n\nSee ![Logo](logo.png)\n");
+        assert_eq!(figures.alignments, vec![(1, None)].into_iter().collect());
+        assert!(figures.attributes.is_empty());
+    }
+
+    #[test]
+    fn alignment_from_attributes() {
+        let figures = figures_of(
+            "![Logo](logo.png){.right width=50%}\n\n![Logo](logo.png){align=\"left\"}\n\n\
+             ![Logo](logo.png){#logo}\n",
+        );
+        assert_eq!(
+            figures.alignments,
+            vec![
+                (1, Some(ImageAlignment::Right)),
+                (7, Some(ImageAlignment::Left)),
+                (13, None)
+            ]
+            .into_iter()
+            .collect()
+        );
+        assert_eq!(figures.attributes, vec![4, 10, 16].into_iter().collect());
+    }
+}
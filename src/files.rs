@@ -0,0 +1,125 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Find the files to render.
+
+use failure::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Extensions of markdown files.
+const MARKDOWN_EXTENSIONS: &[&str] = &["md", "markdown", "mdx"];
+
+/// Whether `path` has the extension of a markdown file.
+fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            MARKDOWN_EXTENSIONS
+                .iter()
+                .any(|markdown| markdown.eq_ignore_ascii_case(extension))
+        })
+}
+
+/// Whether `path` is hidden, like `.git`.
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.'))
+}
+
+/// Add all markdown files in `directory` and its subdirectories to `files`.
+///
+/// Skip hidden files and directories, and add files in order of their paths.
+fn collect_markdown_files(directory: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries = fs::read_dir(directory)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+    for path in entries {
+        if is_hidden(&path) {
+            continue;
+        }
+        if path.is_dir() {
+            collect_markdown_files(&path, files)?;
+        } else if is_markdown(&path) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Get the files to render for the given `filenames`.
+///
+/// Take all markdown files in directories among `filenames` if `recursive`,
+/// and fail for directories otherwise.
+pub fn input_files(filenames: &[String], recursive: bool) -> Result<Vec<String>, Error> {
+    let mut files = Vec::new();
+    for filename in filenames {
+        let path = Path::new(filename);
+        if !path.is_dir() {
+            files.push(filename.clone());
+        } else if recursive {
+            let mut paths = Vec::new();
+            collect_markdown_files(path, &mut paths)?;
+            files.extend(
+                paths
+                    .into_iter()
+                    .map(|path| path.to_string_lossy().into_owned()),
+            );
+        } else {
+            return Err(failure::format_err!(
+                "{} is a directory; use --recursive to render all markdown files in it",
+                filename
+            ));
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn markdown_files_in_directories() {
+        let directory = std::env::temp_dir().join(format!("mdcat-files-{}", std::process::id()));
+        for file in &[
+            "b.md",
+            "a/c.markdown",
+            "a/b/d.MD",
+            "a/notes.txt",
+            ".git/e.md",
+        ] {
+            let path = directory.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "# Foo\n").unwrap();
+        }
+        let name = |file: &str| directory.join(file).to_string_lossy().into_owned();
+        let filenames = vec![name(""), "README.md".to_string()];
+        assert_eq!(
+            input_files(&filenames, true).unwrap(),
+            vec![
+                name("a/b/d.MD"),
+                name("a/c.markdown"),
+                name("b.md"),
+                "README.md".to_string()
+            ]
+        );
+        assert!(input_files(&filenames, false).is_err());
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,439 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Fill text into the width of the terminal.
+//!
+//! Wrap styled output at spaces, soft hyphens and between wide characters,
+//! and indent continuation lines, as we write it.  Like `crate::width`, we
+//! measure and break text by clusters, and never within a cluster.
+
+use crate::width::{cluster_len, cluster_width};
+use std::io;
+use std::io::Write;
+
+/// A soft hyphen, which shows only if we break a line at it.
+const SOFT_HYPHEN: char = '\u{AD}';
+
+/// A pending escape sequence.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Escape {
+    /// We saw ESC and wait for the kind of sequence.
+    Start,
+    /// A CSI sequence, ending with a final byte.
+    Csi,
+    /// A sequence ending with BEL or ST, eg, OSC.
+    String,
+    /// A string sequence after ESC, which ends the sequence with `\`.
+    StringEnd,
+}
+
+/// What separates the current word from the previous one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Separator {
+    /// Spaces, which we drop at line breaks.
+    Spaces(usize),
+    /// A soft hyphen, which we show at line breaks only.
+    Hyphen,
+}
+
+/// A writer which fills text into the width of the terminal.
+///
+/// Buffer words and write them to the underlying writer at the next break
+/// opportunity, and break the line before a word if it does not fit into the
+/// line.  Continue styles on the next line, and indent it by the current
+/// hanging indent.
+///
+/// Leave lines alone while wrapping is disabled, eg, in code.
+pub(crate) struct Filler<W: Write> {
+    /// The underlying writer.
+    inner: W,
+    /// The number of columns to fill text into.
+    width: usize,
+    /// The indentation of continuation lines.
+    indent: usize,
+    /// Whether to wrap text.
+    wrap: bool,
+    /// The column of the cursor after everything written so far.
+    column: usize,
+    /// Whether the current line has any visible text except indentation.
+    content: bool,
+    /// The separator before the current word.
+    separator: Separator,
+    /// The current word, with escape sequences.
+    word: String,
+    /// The number of columns of the current word.
+    word_width: usize,
+    /// The current cluster, which may continue with the next character.
+    cluster: String,
+    /// The current escape sequence, if any.
+    escape: Option<Escape>,
+    /// The SGR sequences active at the cursor.
+    active: Vec<String>,
+    /// The SGR sequences in the current word.
+    word_styles: Vec<String>,
+    /// The OSC 8 link active at the cursor, to continue it on the next line.
+    link: Option<String>,
+    /// The OSC 8 links in the current word, with `None` for the end of a link.
+    word_links: Vec<Option<String>>,
+    /// Incomplete UTF-8 bytes from the last write.
+    incomplete: Vec<u8>,
+}
+
+impl<W: Write> Filler<W> {
+    /// Fill text into `width` columns of `inner`.
+    pub(crate) fn new(inner: W, width: usize) -> Filler<W> {
+        Filler {
+            inner,
+            width,
+            indent: 0,
+            wrap: true,
+            column: 0,
+            content: false,
+            separator: Separator::Spaces(0),
+            word: String::new(),
+            word_width: 0,
+            cluster: String::new(),
+            escape: None,
+            active: Vec::new(),
+            word_styles: Vec::new(),
+            link: None,
+            word_links: Vec::new(),
+            incomplete: Vec::new(),
+        }
+    }
+
+    /// Indent continuation lines by `indent` columns.
+    pub(crate) fn set_indent(&mut self, indent: usize) {
+        self.indent = indent;
+    }
+
+    /// Enable or disable wrapping.
+    ///
+    /// Without wrapping we neither break lines nor take spaces as break
+    /// opportunities, so that a word continues up to the next space after we
+    /// enable wrapping again.
+    pub(crate) fn set_wrap(&mut self, wrap: bool) {
+        self.wrap = wrap;
+    }
+
+    /// Write the current word, with the current indentation.
+    ///
+    /// Call before changing the indentation for text which follows, so that
+    /// the current word still wraps with the indentation it belongs to.
+    pub(crate) fn end_word(&mut self) -> io::Result<()> {
+        self.write_word()
+    }
+
+    /// Write the last word, and get the underlying writer back.
+    pub(crate) fn finish(mut self) -> io::Result<W> {
+        self.write_word()?;
+        Ok(self.inner)
+    }
+
+    /// Write the current word, in the current line or on a new line.
+    fn write_word(&mut self) -> io::Result<()> {
+        self.end_cluster()?;
+        let separator = std::mem::replace(&mut self.separator, Separator::Spaces(0));
+        if self.word.is_empty() {
+            // Keep spaces for the next word, unless we are at the end
+            self.separator = separator;
+            return Ok(());
+        }
+        let separator_width = match separator {
+            Separator::Spaces(spaces) => spaces,
+            Separator::Hyphen => 0,
+        };
+        let overflows = self.width < self.column + separator_width + self.word_width;
+        if self.wrap && self.content && 0 < self.word_width && overflows {
+            if separator == Separator::Hyphen {
+                self.inner.write_all(b"-")?;
+            }
+            // End the link before the indentation of the next line, and
+            // start it again after; terminals join both pieces by its ID.
+            if self.link.is_some() {
+                self.inner.write_all(b"\x1b]8;;\x07")?;
+            }
+            if !self.active.is_empty() {
+                self.inner.write_all(b"\x1b[0m")?;
+            }
+            write!(
+                self.inner,
+                "\n{}{}{}",
+                " ".repeat(self.indent),
+                self.link.as_deref().unwrap_or_default(),
+                self.active.concat()
+            )?;
+            self.column = self.indent;
+            self.content = false;
+        } else if let Separator::Spaces(spaces) = separator {
+            write!(self.inner, "{}", " ".repeat(spaces))?;
+            self.column += spaces;
+        }
+        self.inner.write_all(self.word.as_bytes())?;
+        self.column += self.word_width;
+        self.content = self.content || 0 < self.word_width;
+        for style in std::mem::take(&mut self.word_styles) {
+            if style == "\x1b[0m" || style == "\x1b[m" {
+                self.active.clear();
+            } else {
+                self.active.push(style);
+            }
+        }
+        for link in std::mem::take(&mut self.word_links) {
+            self.link = link;
+        }
+        self.word.clear();
+        self.word_width = 0;
+        Ok(())
+    }
+
+    /// Add `c` to the current escape sequence of the given `kind`.
+    fn push_escape(&mut self, kind: Escape, c: char) {
+        self.word.push(c);
+        self.escape = match (kind, c) {
+            (Escape::Start, '[') => Some(Escape::Csi),
+            (Escape::Start, ']') | (Escape::Start, 'P') | (Escape::Start, '_') => {
+                Some(Escape::String)
+            }
+            (Escape::Start, _) => None,
+            (Escape::Csi, 'm') => {
+                // Remember styles to continue them on the next line
+                let start = self.word.rfind('\x1b').unwrap_or(0);
+                self.word_styles.push(self.word[start..].to_string());
+                None
+            }
+            (Escape::Csi, '@'..='~') => None,
+            (Escape::Csi, _) => Some(Escape::Csi),
+            (Escape::String, '\x07') => {
+                self.push_link();
+                None
+            }
+            (Escape::String, '\x1b') => Some(Escape::StringEnd),
+            (Escape::String, _) => Some(Escape::String),
+            (Escape::StringEnd, '\\') => {
+                self.push_link();
+                None
+            }
+            (Escape::StringEnd, _) => Some(Escape::String),
+        };
+    }
+
+    /// Remember the string sequence at the end of the current word if it is
+    /// an OSC 8 link, to continue the link on the next line.
+    fn push_link(&mut self) {
+        let start = self.word.rfind("\x1b]").unwrap_or(0);
+        let sequence = &self.word[start..];
+        if let Some(link) = sequence.strip_prefix("\x1b]8;") {
+            let link = link
+                .strip_suffix('\x07')
+                .or_else(|| link.strip_suffix("\x1b\\"))
+                .unwrap_or(link);
+            let destination = link.split_once(';').map_or("", |(_, url)| url);
+            self.word_links.push(if destination.is_empty() {
+                None
+            } else {
+                Some(sequence.to_string())
+            });
+        }
+    }
+
+    /// Add the current cluster to the current word.
+    ///
+    /// We may break lines before and after wide clusters, eg, CJK characters
+    /// or emoji, which need no spaces between words.
+    fn end_cluster(&mut self) -> io::Result<()> {
+        if self.cluster.is_empty() {
+            return Ok(());
+        }
+        let cluster = std::mem::take(&mut self.cluster);
+        let width = cluster_width(&cluster);
+        let wide = self.wrap && 1 < width;
+        if wide {
+            self.write_word()?;
+        }
+        self.word.push_str(&cluster);
+        self.word_width += width;
+        if wide {
+            self.write_word()?;
+        }
+        Ok(())
+    }
+
+    /// Fill a single character.
+    fn push(&mut self, c: char) -> io::Result<()> {
+        if let Some(kind) = self.escape {
+            self.push_escape(kind, c);
+            return Ok(());
+        }
+        match c {
+            '\x1b' => {
+                self.end_cluster()?;
+                self.word.push(c);
+                self.escape = Some(Escape::Start);
+            }
+            '\n' => {
+                self.write_word()?;
+                // Drop trailing spaces
+                self.separator = Separator::Spaces(0);
+                self.inner.write_all(b"\n")?;
+                self.column = 0;
+                self.content = false;
+            }
+            ' ' if self.wrap => {
+                self.write_word()?;
+                self.separator = match self.separator {
+                    Separator::Spaces(spaces) => Separator::Spaces(spaces + 1),
+                    Separator::Hyphen => Separator::Spaces(1),
+                };
+            }
+            SOFT_HYPHEN if self.wrap => {
+                self.write_word()?;
+                self.separator = Separator::Hyphen;
+            }
+            SOFT_HYPHEN => {}
+            c => {
+                self.cluster.push(c);
+                if cluster_len(&self.cluster) < self.cluster.len() {
+                    // c starts a new cluster
+                    self.cluster.pop();
+                    self.end_cluster()?;
+                    self.cluster.push(c);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for Filler<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.incomplete.extend_from_slice(buf);
+        let bytes = std::mem::take(&mut self.incomplete);
+        let valid = match std::str::from_utf8(&bytes) {
+            Ok(text) => text.len(),
+            // Keep an incomplete character at the end for the next write
+            Err(error) if error.error_len().is_none() => error.valid_up_to(),
+            Err(_) => {
+                // Not text at all; write it as it is.
+                self.write_word()?;
+                self.inner.write_all(&bytes)?;
+                return Ok(buf.len());
+            }
+        };
+        let text = std::str::from_utf8(&bytes[..valid]).unwrap_or_default();
+        for c in text.chars() {
+            self.push(c)?;
+        }
+        self.incomplete = bytes[valid..].to_vec();
+        Ok(buf.len())
+    }
+
+    /// Flush the underlying writer.
+    ///
+    /// Keep the current word, because we do not know yet where it ends.
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn fill(width: usize, indent: usize, text: &str) -> String {
+        let mut filler = Filler::new(Vec::new(), width);
+        filler.set_indent(indent);
+        filler.write_all(text.as_bytes()).unwrap();
+        String::from_utf8(filler.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn wrap_at_spaces_with_hanging_indent() {
+        assert_eq!(
+            fill(16, 2, "• Lorem ipsum dolor sit amet\n"),
+            "• Lorem ipsum\n  dolor sit amet\n"
+        );
+    }
+
+    #[test]
+    fn continue_styles_on_the_next_line() {
+        assert_eq!(
+            fill(10, 0, "foo \x1b[1mbar baz\x1b[0m qux"),
+            "foo \x1b[1mbar\x1b[0m\n\x1b[1mbaz\x1b[0m qux"
+        );
+    }
+
+    #[test]
+    fn continue_links_on_the_next_line() {
+        assert_eq!(
+            fill(
+                10,
+                2,
+                "foo \x1b]8;id=1;https://example.com\x07bar baz\x1b]8;;\x07 qux"
+            ),
+            "foo \x1b]8;id=1;https://example.com\x07bar\x1b]8;;\x07\n  \
+             \x1b]8;id=1;https://example.com\x07baz\x1b]8;;\x07 qux"
+        );
+    }
+
+    #[test]
+    fn break_between_wide_characters() {
+        assert_eq!(fill(6, 0, "日本語です"), "日本語\nです");
+    }
+
+    #[test]
+    fn break_between_clusters_only() {
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        // The family takes two columns, and just fits at the wrap column
+        assert_eq!(
+            fill(4, 0, &format!("ab{}", family)),
+            format!("ab{}", family)
+        );
+        assert_eq!(
+            fill(3, 0, &format!("ab{}{}", family, family)),
+            format!("ab\n{}\n{}", family, family)
+        );
+        assert_eq!(
+            fill(3, 0, "\u{1F1E9}\u{1F1EA}\u{1F1EB}\u{1F1F7}"),
+            "\u{1F1E9}\u{1F1EA}\n\u{1F1EB}\u{1F1F7}"
+        );
+        assert_eq!(
+            fill(3, 0, "\u{1F44B}\u{1F3FD}\u{1F44B}\u{1F3FF}"),
+            "\u{1F44B}\u{1F3FD}\n\u{1F44B}\u{1F3FF}"
+        );
+        assert_eq!(
+            fill(3, 0, "e\u{301}e\u{301}e\u{301}"),
+            "e\u{301}e\u{301}e\u{301}"
+        );
+    }
+
+    #[test]
+    fn show_soft_hyphens_only_at_line_breaks() {
+        assert_eq!(fill(8, 0, "un\u{AD}break\u{AD}able"), "unbreak-\nable");
+        assert_eq!(fill(20, 0, "un\u{AD}break\u{AD}able"), "unbreakable");
+    }
+
+    #[test]
+    fn leave_long_words_and_unwrapped_text_alone() {
+        assert_eq!(
+            fill(4, 0, "foo https://example.com"),
+            "foo\nhttps://example.com"
+        );
+        let mut filler = Filler::new(Vec::new(), 4);
+        filler.set_wrap(false);
+        filler.write_all(b"fn main() {}\n").unwrap();
+        assert_eq!(filler.finish().unwrap(), b"fn main() {}\n");
+    }
+}
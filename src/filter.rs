@@ -0,0 +1,129 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Filter documents through a command before rendering, for `--filter-cmd`,
+//! and pipe output to a command, for `--pipe-to`.
+//!
+//! Users plug in their own preprocessors this way, eg, template engines or
+//! tools which redact secrets, and their own pagers or screenshot tools.
+
+use failure::{format_err, Error, ResultExt};
+use mdcat::shell_command;
+use std::io::prelude::*;
+use std::process::{Child, Stdio};
+
+/// Filter `input` through the shell `command`.
+///
+/// Write `input` to the standard input of `command` and return its standard
+/// output.  Fail if `command` fails.
+pub fn filter(command: &str, input: String) -> Result<String, Error> {
+    let mut child = shell_command(command, &[])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|_| format!("Failed to start filter command {}", command))?;
+    let mut stdin = child
+        .stdin
+        .take()
+        .expect("Standard input of filter missing");
+    // Write in a thread, lest the command blocks on a full output pipe while
+    // we block on a full input pipe
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child.wait_with_output()?;
+    // The command may exit without reading all input; that's fine as long
+    // as it succeeds
+    let _ = writer.join();
+    if output.status.success() {
+        Ok(String::from_utf8(output.stdout)
+            .with_context(|_| format!("Filter command {} wrote invalid UTF-8", command))?)
+    } else {
+        Err(format_err!(
+            "Filter command {} failed with {}",
+            command,
+            output.status
+        ))
+    }
+}
+
+/// Start the shell `command`, reading from a pipe.
+///
+/// The command inherits standard output.
+pub fn spawn(command: &str) -> Result<Child, Error> {
+    let child = shell_command(command, &[])
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|_| format!("Failed to start pipe command {}", command))?;
+    Ok(child)
+}
+
+/// Wait for the pipe `command` which we started as `child`.
+///
+/// Fail if `command` fails.
+pub fn wait(command: &str, mut child: Child) -> Result<(), Error> {
+    let status = child.wait()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format_err!(
+            "Pipe command {} failed with {}",
+            command,
+            status
+        ))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn filter_through_command() {
+        let output = filter("sed 's/secret/[redacted]/'", "# The secret\n".to_string());
+        assert_eq!(output.unwrap(), "# The [redacted]\n");
+    }
+
+    #[test]
+    fn filter_large_documents() {
+        let input = "Lorem ipsum\n".repeat(100_000);
+        assert_eq!(filter("cat", input.clone()).unwrap(), input);
+    }
+
+    #[test]
+    fn fail_if_the_command_fails() {
+        let error = filter("exit 3", "# Lorem".to_string()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Filter command exit 3 failed with exit status: 3"
+        );
+    }
+
+    #[test]
+    fn pipe_to_command() {
+        let mut child = spawn("cat > /dev/null").unwrap();
+        child.stdin.take().unwrap().write_all(b"# Lorem").unwrap();
+        assert!(wait("cat > /dev/null", child).is_ok());
+    }
+
+    #[test]
+    fn fail_if_the_pipe_command_fails() {
+        let mut child = spawn("exit 3").unwrap();
+        drop(child.stdin.take());
+        let error = wait("exit 3", child).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Pipe command exit 3 failed with exit status: 3"
+        );
+    }
+}
@@ -0,0 +1,91 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Collect footnotes at the end of a document.
+
+use pulldown_cmark::Event;
+use pulldown_cmark::Event::*;
+use pulldown_cmark::Tag::*;
+
+/// Move all footnote definitions in `events` to the end of the document.
+///
+/// Order definitions by the first reference to each footnote, and put
+/// definitions without references last, in the order of the document, so
+/// that the numbers of footnotes increase along the document.
+pub fn collect_footnotes(events: Vec<Event<'_>>) -> Vec<Event<'_>> {
+    let mut body = Vec::with_capacity(events.len());
+    let mut definitions = Vec::new();
+    let mut current: Option<Vec<Event<'_>>> = None;
+    let mut references: Vec<String> = Vec::new();
+    for event in events {
+        match event {
+            Start(FootnoteDefinition(_)) => current = Some(vec![event]),
+            End(FootnoteDefinition(_)) => {
+                if let Some(mut definition) = current.take() {
+                    definition.push(event);
+                    definitions.push(definition);
+                }
+            }
+            event => {
+                if let FootnoteReference(ref name) = event {
+                    if !references
+                        .iter()
+                        .any(|reference| reference == name.as_ref())
+                    {
+                        references.push(name.to_string());
+                    }
+                }
+                match current {
+                    Some(ref mut definition) => definition.push(event),
+                    None => body.push(event),
+                }
+            }
+        }
+    }
+    definitions.sort_by_key(|definition| match definition.first() {
+        Some(Start(FootnoteDefinition(name))) => references
+            .iter()
+            .position(|reference| reference == name.as_ref())
+            .unwrap_or(references.len()),
+        _ => references.len(),
+    });
+    body.extend(definitions.into_iter().flatten());
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use pulldown_cmark::{Options, Parser};
+
+    fn footnotes(markdown: &str) -> Vec<Event<'_>> {
+        collect_footnotes(Parser::new_ext(markdown, Options::ENABLE_FOOTNOTES).collect())
+    }
+
+    #[test]
+    fn move_definitions_to_the_end_in_order_of_references() {
+        let events =
+            footnotes("Foo[^b] and[^a].\n\n[^a]: A.\n\n[^unused]: Unused.\n\n[^b]: B.\n\nBar\n");
+        let names = events
+            .iter()
+            .filter_map(|event| match event {
+                Start(FootnoteDefinition(name)) => Some(name.as_ref()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["b", "a", "unused"]);
+        assert_eq!(&events[..10], &footnotes("Foo[^b] and[^a].\n\nBar\n")[..10]);
+    }
+}
@@ -0,0 +1,173 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Rendering options from YAML front matter.
+//!
+//! Documents can pin options for mdcat in their front matter:
+//!
+//! ```yaml
+//! ---
+//! title: Foo
+//! mdcat: {theme: colorblind, columns: 60}
+//! ---
+//! ```
+//!
+//! or as block mapping:
+//!
+//! ```yaml
+//! ---
+//! mdcat:
+//!   theme: colorblind
+//!   columns: 60
+//! ---
+//! ```
+//!
+//! We understand just enough YAML for these two forms.
+
+/// Split YAML front matter from `source`.
+///
+/// Return the front matter, if any, and the rest of `source`.
+pub fn split_front_matter(source: &str) -> (Option<&str>, &str) {
+    let body = match source
+        .strip_prefix("---\n")
+        .or_else(|| source.strip_prefix("---\r\n"))
+    {
+        Some(body) => body,
+        None => return (None, source),
+    };
+    let mut offset = 0;
+    for line in body.split_inclusive('\n') {
+        let marker = line.trim_end();
+        if marker == "---" || marker == "..." {
+            return (Some(&body[..offset]), &body[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+    // No end marker, so this was no front matter at all
+    (None, source)
+}
+
+/// Remove quotes around a YAML scalar `value`.
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    ['"', '\'']
+        .iter()
+        .find_map(|&quote| {
+            value
+                .strip_prefix(quote)
+                .and_then(|value| value.strip_suffix(quote))
+        })
+        .unwrap_or(value)
+}
+
+/// Parse a `key: value` pair.
+fn key_value(pair: &str) -> Option<(String, String)> {
+    let mut parts = pair.splitn(2, ':');
+    let key = unquote(parts.next()?);
+    let value = unquote(parts.next()?);
+    if key.is_empty() {
+        None
+    } else {
+        Some((key.to_string(), value.to_string()))
+    }
+}
+
+/// Get the options for mdcat from `front_matter`.
+///
+/// Return all key-value pairs of the `mdcat` mapping in `front_matter`, in
+/// order of appearance.
+pub fn mdcat_options(front_matter: &str) -> Vec<(String, String)> {
+    let mut lines = front_matter.lines();
+    while let Some(line) = lines.next() {
+        let value = match line.strip_prefix("mdcat:") {
+            Some(value) => value.trim(),
+            None => continue,
+        };
+        if let Some(flow) = value
+            .strip_prefix('{')
+            .and_then(|value| value.strip_suffix('}'))
+        {
+            return flow.split(',').filter_map(key_value).collect();
+        } else if value.is_empty() {
+            return lines
+                .take_while(|line| line.starts_with(' ') || line.starts_with('\t'))
+                .filter_map(key_value)
+                .collect();
+        }
+    }
+    Vec::new()
+}
+
+/// Get the language of the document from the top-level `lang` key of
+/// `front_matter`, as in Pandoc.
+pub fn language(front_matter: &str) -> Option<String> {
+    front_matter
+        .lines()
+        .filter_map(|line| line.strip_prefix("lang:"))
+        .map(|value| unquote(value).to_string())
+        .find(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn options(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn split_front_matter_from_document() {
+        assert_eq!(
+            split_front_matter("---\ntitle: Foo\n---\n# Foo\n"),
+            (Some("title: Foo\n"), "# Foo\n")
+        );
+        assert_eq!(split_front_matter("# Foo\n"), (None, "# Foo\n"));
+        assert_eq!(split_front_matter("---\n\nFoo\n"), (None, "---\n\nFoo\n"));
+    }
+
+    #[test]
+    fn mdcat_options_in_flow_mapping() {
+        assert_eq!(
+            mdcat_options("title: Foo\nmdcat: {wrap: false, theme: \"colorblind\"}\n"),
+            options(&[("wrap", "false"), ("theme", "colorblind")])
+        );
+    }
+
+    #[test]
+    fn mdcat_options_in_block_mapping() {
+        assert_eq!(
+            mdcat_options("mdcat:\n  theme: colorblind\n  columns: 60\ntitle: Foo\n"),
+            options(&[("theme", "colorblind"), ("columns", "60")])
+        );
+    }
+
+    #[test]
+    fn language_of_document() {
+        assert_eq!(
+            language("title: Foo\nlang: \"de-CH\"\n"),
+            Some("de-CH".to_string())
+        );
+        assert_eq!(language("mdcat:\n  lang: fr\n"), None);
+    }
+
+    #[test]
+    fn mdcat_options_without_mdcat_key() {
+        assert_eq!(mdcat_options("title: Foo\n"), options(&[]));
+    }
+}
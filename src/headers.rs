@@ -0,0 +1,353 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Extra HTTP headers for remote resources, per host.
+//!
+//! Users configure headers for host patterns in `mdcat/headers.toml`, eg, an
+//! API key for the images of an internal wiki:
+//!
+//! ```toml
+//! ["wiki.example.com"]
+//! X-Api-Key = "${WIKI_API_KEY}"
+//!
+//! ["*.example.com"]
+//! Authorization = "Bearer $EXAMPLE_TOKEN"
+//! ```
+//!
+//! We take secrets from the environment of the render, so that the file need
+//! not contain them.  We only send headers over HTTPS, and never along
+//! redirects to other hosts.
+
+use crate::Environment;
+use failure::{Error, ResultExt};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Once;
+use url::Url;
+
+/// Extra headers for hosts.
+#[derive(Debug, PartialEq, Default)]
+pub(crate) struct HostHeaders {
+    /// Headers by host pattern.
+    hosts: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+/// Whether `host` matches `pattern`.
+///
+/// A pattern is either a host name, or `*.` followed by a domain, which
+/// matches all hosts in this domain but not the domain itself.
+fn matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host
+            .strip_suffix(domain)
+            .is_some_and(|subdomain| subdomain.len() > 1 && subdomain.ends_with('.')),
+        None => pattern.eq_ignore_ascii_case(host),
+    }
+}
+
+/// Expand `$NAME` and `${NAME}` in `value` with `lookup`.
+///
+/// Return `None` if `lookup` does not know a variable.
+fn expand<F>(value: &str, lookup: F) -> Option<String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut expanded = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let variable = &rest[start + 1..];
+        let (name, after) = match variable.strip_prefix('{') {
+            Some(braced) => {
+                let end = braced.find('}')?;
+                (&braced[..end], &braced[end + 1..])
+            }
+            None => {
+                let end = variable
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(variable.len());
+                (&variable[..end], &variable[end..])
+            }
+        };
+        if name.is_empty() {
+            expanded.push('$');
+        } else {
+            expanded.push_str(&lookup(name)?);
+        }
+        rest = after;
+    }
+    expanded.push_str(rest);
+    Some(expanded)
+}
+
+impl HostHeaders {
+    /// Read headers for hosts from `toml`.
+    pub(crate) fn from_toml(toml: &str) -> Result<HostHeaders, toml::de::Error> {
+        toml::from_str(toml).map(|hosts| HostHeaders { hosts })
+    }
+
+    /// The headers of the user of `environment`.
+    ///
+    /// Read `mdcat/headers.toml` in `$XDG_CONFIG_HOME` or in `~/.config`, and
+    /// return `None` if it does not exist.
+    pub(crate) fn for_user(environment: &Environment) -> Result<Option<HostHeaders>, Error> {
+        let config_home = environment
+            .var("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| {
+                let home = environment.var("HOME")?;
+                Some(PathBuf::from(home).join(".config"))
+            });
+        let path = match config_home {
+            Some(config_home) => config_home.join("mdcat").join("headers.toml"),
+            None => return Ok(None),
+        };
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|_| format!("Failed to read headers from {}", path.display()))?;
+        let headers = HostHeaders::from_toml(&contents)
+            .with_context(|_| format!("Invalid headers in {}", path.display()))?;
+        Ok(Some(headers))
+    }
+
+    /// Expand variables in all headers with `lookup`.
+    ///
+    /// Leave out headers which refer to unknown variables.
+    pub(crate) fn expand<F>(&self, lookup: F) -> HostHeaders
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let hosts = self
+            .hosts
+            .iter()
+            .map(|(pattern, headers)| {
+                let headers = headers
+                    .iter()
+                    .filter_map(|(name, value)| Some((name.clone(), expand(value, &lookup)?)))
+                    .collect();
+                (pattern.clone(), headers)
+            })
+            .collect();
+        HostHeaders { hosts }
+    }
+
+    /// Get the headers for `url`.
+    ///
+    /// Take headers of all patterns which match the host of `url`.  Return no
+    /// headers for URLs other than `https:`, to not send secrets in plain
+    /// text.
+    pub(crate) fn for_url(&self, url: &Url) -> Vec<(String, String)> {
+        let host = match url.host_str() {
+            Some(host) if url.scheme() == "https" => host,
+            _ => return Vec::new(),
+        };
+        self.hosts
+            .iter()
+            .filter(|(pattern, _)| matches(pattern, host))
+            .flat_map(|(_, headers)| headers.clone())
+            .collect()
+    }
+}
+
+/// Whether to follow a redirect `from` a URL we sent headers to `to` another.
+///
+/// Only follow redirects to the same host and port over HTTPS, because the
+/// headers would go along.
+pub(crate) fn follows_with_headers(from: &Url, to: &Url) -> bool {
+    to.scheme() == "https"
+        && from.host_str() == to.host_str()
+        && from.port_or_known_default() == to.port_or_known_default()
+}
+
+/// Warn only once about invalid headers, even if we render many times.
+static WARN_INVALID: Once = Once::new();
+
+thread_local! {
+    /// The expanded headers of the user for the render on this thread, if any.
+    static CURRENT: RefCell<Option<Rc<HostHeaders>>> = const { RefCell::new(None) };
+}
+
+/// The headers of the user, loaded for a render.
+pub(crate) struct RenderHeaders {
+    /// The headers loaded before, to restore when the render ends.
+    previous: Option<Rc<HostHeaders>>,
+}
+
+impl RenderHeaders {
+    /// Load the headers of the user for a render in `environment` on this
+    /// thread.
+    ///
+    /// Until the returned value drops, remote resources take their headers
+    /// from what we loaded here, instead of reading the file again for every
+    /// image.  Nested renders keep the headers of the outer render.
+    ///
+    /// If we cannot read the headers we warn once and send no extra headers,
+    /// because most remote resources do not need them.
+    pub(crate) fn load(environment: &Environment) -> RenderHeaders {
+        let previous = CURRENT.with(|current| current.borrow().clone());
+        if previous.is_none() {
+            let headers = HostHeaders::for_user(environment).unwrap_or_else(|error| {
+                WARN_INVALID.call_once(|| {
+                    let causes = error.iter_chain().map(ToString::to_string);
+                    eprintln!(
+                        "Sending no extra headers: {}",
+                        causes.collect::<Vec<_>>().join(": ")
+                    )
+                });
+                None
+            });
+            let headers = headers
+                .unwrap_or_default()
+                .expand(|name| environment.var(name).map(ToString::to_string));
+            CURRENT.with(|current| current.replace(Some(Rc::new(headers))));
+        }
+        RenderHeaders { previous }
+    }
+}
+
+impl Drop for RenderHeaders {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        CURRENT.with(|current| current.replace(previous));
+    }
+}
+
+/// The headers of the user for the current render.
+///
+/// Outside of renders we have no environment to expand variables in, so we
+/// send no extra headers.
+pub(crate) fn current() -> Rc<HostHeaders> {
+    CURRENT
+        .with(|current| current.borrow().clone())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn match_host_patterns() {
+        assert!(matches("wiki.example.com", "wiki.example.com"));
+        assert!(matches("*.example.com", "wiki.example.com"));
+        assert!(!matches("*.example.com", "example.com"));
+        assert!(!matches("*.example.com", "wikiexample.com"));
+        assert!(!matches("wiki.example.com", "example.com"));
+    }
+
+    #[test]
+    fn headers_for_url_with_secrets_from_environment() {
+        let headers = HostHeaders::from_toml(
+            "[\"wiki.example.com\"]\nX-Api-Key = \"${WIKI_KEY}\"\nX-Missing = \"$NOPE\"\n\n\
+             [\"*.example.com\"]\nAuthorization = \"Bearer $TOKEN\"\nX-Price = \"5$\"\n",
+        )
+        .unwrap();
+        let lookup = |name: &str| match name {
+            "WIKI_KEY" => Some("secret".to_string()),
+            "TOKEN" => Some("abc".to_string()),
+            _ => None,
+        };
+        let headers = headers.expand(lookup);
+        let wiki = Url::parse("https://wiki.example.com/logo.png").unwrap();
+        assert_eq!(
+            headers.for_url(&wiki),
+            vec![
+                ("Authorization".to_string(), "Bearer abc".to_string()),
+                ("X-Price".to_string(), "5$".to_string()),
+                ("X-Api-Key".to_string(), "secret".to_string()),
+            ]
+        );
+        let other = Url::parse("https://example.org/logo.png").unwrap();
+        assert_eq!(headers.for_url(&other), vec![]);
+    }
+
+    #[test]
+    fn no_headers_over_plain_http() {
+        let headers =
+            HostHeaders::from_toml("[\"wiki.example.com\"]\nX-Api-Key = \"secret\"\n").unwrap();
+        let https = Url::parse("https://wiki.example.com/logo.png").unwrap();
+        let http = Url::parse("http://wiki.example.com/logo.png").unwrap();
+        assert_eq!(
+            headers.for_url(&https),
+            vec![("X-Api-Key".to_string(), "secret".to_string())]
+        );
+        assert_eq!(headers.for_url(&http), vec![]);
+    }
+
+    #[test]
+    fn follow_redirects_with_headers_only_on_the_same_host() {
+        let from = Url::parse("https://wiki.example.com/logo.png").unwrap();
+        let same = Url::parse("https://wiki.example.com:443/images/logo.png").unwrap();
+        let other = Url::parse("https://cdn.example.org/logo.png").unwrap();
+        let port = Url::parse("https://wiki.example.com:8443/logo.png").unwrap();
+        let http = Url::parse("http://wiki.example.com/logo.png").unwrap();
+        assert!(follows_with_headers(&from, &same));
+        assert!(!follows_with_headers(&from, &other));
+        assert!(!follows_with_headers(&from, &port));
+        assert!(!follows_with_headers(&from, &http));
+    }
+
+    #[test]
+    fn load_headers_once_per_render() {
+        let environment = Environment::isolated("/");
+        let _render = RenderHeaders::load(&environment);
+        let headers = current();
+        {
+            let _nested = RenderHeaders::load(&environment);
+            assert!(Rc::ptr_eq(&headers, &current()));
+        }
+        assert!(Rc::ptr_eq(&headers, &current()));
+        drop(_render);
+        assert!(CURRENT.with(|current| current.borrow().is_none()));
+    }
+
+    #[test]
+    fn expand_variables_from_the_environment_of_the_render() {
+        let config_home =
+            std::env::temp_dir().join(format!("mdcat-headers-{}", std::process::id()));
+        std::fs::create_dir_all(config_home.join("mdcat")).unwrap();
+        let path = config_home.join("mdcat").join("headers.toml");
+        let environment = Environment::isolated("/")
+            .with_var("XDG_CONFIG_HOME", config_home.to_str().unwrap())
+            .with_var("WIKI_KEY", "secret");
+        let wiki = Url::parse("https://wiki.example.com/logo.png").unwrap();
+
+        std::fs::write(&path, "[\"wiki.example.com\"]\nX-Api-Key = \"$WIKI_KEY\"\n").unwrap();
+        {
+            let _render = RenderHeaders::load(&environment);
+            assert_eq!(
+                current().for_url(&wiki),
+                vec![("X-Api-Key".to_string(), "secret".to_string())]
+            );
+        }
+
+        // Fall back to no headers if the file is invalid
+        std::fs::write(&path, "X-Api-Key = \"$WIKI_KEY\"\n").unwrap();
+        {
+            let _render = RenderHeaders::load(&environment);
+            assert_eq!(current().for_url(&wiki), vec![]);
+        }
+        std::fs::remove_dir_all(&config_home).unwrap();
+    }
+
+    #[test]
+    fn reject_invalid_headers() {
+        assert!(HostHeaders::from_toml("X-Api-Key = \"secret\"\n").is_err());
+    }
+}
@@ -0,0 +1,611 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tables in raw HTML.
+//!
+//! Documents on GitHub often use HTML tables for what markdown tables cannot
+//! do, eg, cells which span several columns or rows.  We parse a reasonable
+//! subset of HTML tables into markdown table events, to draw them like any
+//! other table instead of showing their HTML source.
+//!
+//! Cells which span several columns or rows take the first of their places
+//! in the table, and leave the other places empty.  We keep bold, italic,
+//! code and links in cells, and drop all other tags.  We leave HTML blocks
+//! which have more than a single table, or nested tables, alone.
+
+use crate::entities::decode_entities;
+use pulldown_cmark::Event::*;
+use pulldown_cmark::Tag::*;
+use pulldown_cmark::{Alignment, Event, Tag};
+use std::borrow::Cow;
+
+/// The maximum number of columns or rows a single cell may span.
+///
+/// Protects against tables with absurd spans.
+const MAX_SPAN: usize = 100;
+
+/// A token of HTML.
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    /// Text between tags.
+    Text(&'a str),
+    /// An opening tag, with its lowercase name and its raw attributes.
+    Open(String, &'a str),
+    /// A closing tag, with its lowercase name.
+    Close(String),
+}
+
+/// The end of the tag which starts at the beginning of `html`.
+///
+/// Skip over `>` in quoted attribute values.
+fn tag_end(html: &str) -> Option<usize> {
+    let mut quote = None;
+    for (index, c) in html.char_indices() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(open), c) if open == c => quote = None,
+            (None, '>') => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Split `html` into tokens.
+///
+/// Skip comments, and take a `<` which starts no tag as text.
+fn tokens(html: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = html;
+    while !rest.is_empty() {
+        let start = rest.find('<').unwrap_or(rest.len());
+        if 0 < start {
+            tokens.push(Token::Text(&rest[..start]));
+            rest = &rest[start..];
+            continue;
+        }
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let (closing, tag) = match rest[1..].strip_prefix('/') {
+            Some(tag) => (true, tag),
+            None => (false, &rest[1..]),
+        };
+        let name_length = tag
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(tag.len());
+        let end = tag_end(rest);
+        match end {
+            Some(end) if 0 < name_length => {
+                let name = tag[..name_length].to_ascii_lowercase();
+                let attributes =
+                    tag[name_length..end - (rest.len() - tag.len())].trim_end_matches('/');
+                tokens.push(if closing {
+                    Token::Close(name)
+                } else {
+                    Token::Open(name, attributes)
+                });
+                rest = &rest[end + 1..];
+            }
+            _ => {
+                tokens.push(Token::Text(&rest[..1]));
+                rest = &rest[1..];
+            }
+        }
+    }
+    tokens
+}
+
+/// Get the value of the attribute `name` from raw tag `attributes`.
+///
+/// Decode entities in the value.
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    let mut rest = attributes.trim_start();
+    while !rest.is_empty() {
+        let key_length = rest
+            .find(|c: char| c.is_whitespace() || c == '=')
+            .unwrap_or(rest.len());
+        let key = &rest[..key_length];
+        rest = rest[key_length..].trim_start();
+        let value = match rest.strip_prefix('=') {
+            Some(value) => {
+                let value = value.trim_start();
+                let (value, remaining) = match value.chars().next() {
+                    Some(quote @ '"') | Some(quote @ '\'') => {
+                        let end = value[1..].find(quote).map_or(value.len(), |end| end + 1);
+                        (&value[1..end], value.get(end + 1..).unwrap_or(""))
+                    }
+                    _ => {
+                        let end = value.find(char::is_whitespace).unwrap_or(value.len());
+                        (&value[..end], &value[end..])
+                    }
+                };
+                rest = remaining.trim_start();
+                value
+            }
+            None => "",
+        };
+        if key.eq_ignore_ascii_case(name) {
+            return Some(decode_entities(value).into_owned());
+        }
+    }
+    None
+}
+
+/// Append `text` to `events`, with whitespace collapsed and entities decoded.
+fn push_text(events: &mut Vec<Event<'static>>, text: &str) {
+    let text = decode_entities(text);
+    let mut collapsed = String::with_capacity(text.len());
+    for (index, word) in text.split(char::is_whitespace).enumerate() {
+        if 0 < index && !collapsed.ends_with(' ') {
+            collapsed.push(' ');
+        }
+        collapsed.push_str(word);
+    }
+    if collapsed.is_empty() {
+        return;
+    }
+    if let Some(Text(last)) = events.last_mut() {
+        if last.ends_with(' ') && collapsed.starts_with(' ') {
+            collapsed.remove(0);
+        }
+        last.to_mut().push_str(&collapsed);
+    } else {
+        events.push(Text(Cow::Owned(collapsed)));
+    }
+}
+
+/// Trim whitespace at the start and the end of the text in `events`.
+fn trim(events: &mut Vec<Event<'static>>) {
+    if let Some(Text(first)) = events.first_mut() {
+        *first = Cow::Owned(first.trim_start().to_string());
+    }
+    if let Some(Text(last)) = events.last_mut() {
+        *last = Cow::Owned(last.trim_end().to_string());
+    }
+    events.retain(|event| match event {
+        Text(text) => !text.is_empty(),
+        _ => true,
+    });
+}
+
+/// A cell of an HTML table.
+#[derive(Debug, Default)]
+struct Cell {
+    /// The contents of the cell.
+    events: Vec<Event<'static>>,
+    /// The number of columns the cell spans.
+    colspan: usize,
+    /// The number of rows the cell spans.
+    rowspan: usize,
+    /// The attributes of the cell for the table layout, eg, `align=right`.
+    attributes: Vec<String>,
+    /// Whether the cell is a heading cell.
+    heading: bool,
+}
+
+/// A row of an HTML table.
+#[derive(Debug, Default)]
+struct Row {
+    /// The cells of the row.
+    cells: Vec<Cell>,
+    /// Whether the row is in the `thead` of the table.
+    in_head: bool,
+}
+
+/// An HTML table while we parse it.
+#[derive(Debug, Default)]
+struct HtmlTable {
+    /// The caption and any text outside of cells.
+    caption: Vec<Event<'static>>,
+    /// The rows of the table.
+    rows: Vec<Row>,
+    /// The current cell, if any.
+    cell: Option<Cell>,
+    /// The inline tags open in the current cell or caption.
+    open: Vec<(String, Tag<'static>)>,
+    /// Whether we are in the `thead` of the table.
+    in_head: bool,
+}
+
+impl HtmlTable {
+    /// The events of the current cell, or of the caption outside of cells.
+    fn events(&mut self) -> &mut Vec<Event<'static>> {
+        match self.cell {
+            Some(ref mut cell) => &mut cell.events,
+            None => &mut self.caption,
+        }
+    }
+
+    /// Close all open inline tags.
+    fn close_inline(&mut self) {
+        while let Some((_, tag)) = self.open.pop() {
+            self.events().push(End(tag));
+        }
+    }
+
+    /// Start an inline `tag` for the HTML element `name`.
+    fn start_inline(&mut self, name: String, tag: Tag<'static>) {
+        self.events().push(Start(tag.clone()));
+        self.open.push((name, tag));
+    }
+
+    /// End the inline HTML element `name`, and all elements inside it.
+    fn end_inline(&mut self, name: &str) {
+        if let Some(position) = self.open.iter().rposition(|(open, _)| open == name) {
+            for (_, tag) in self.open.split_off(position).into_iter().rev() {
+                self.events().push(End(tag));
+            }
+        }
+    }
+
+    /// Start a new row.
+    fn start_row(&mut self) {
+        self.end_cell();
+        self.rows.push(Row {
+            cells: Vec::new(),
+            in_head: self.in_head,
+        });
+    }
+
+    /// Start a new cell with the given raw `attributes`.
+    fn start_cell(&mut self, heading: bool, attributes: &str) {
+        self.end_cell();
+        if self.rows.is_empty() {
+            self.start_row();
+        }
+        let span = |name: &str| {
+            attribute(attributes, name)
+                .and_then(|span| span.trim().parse::<usize>().ok())
+                .map_or(1, |span| span.clamp(1, MAX_SPAN))
+        };
+        let mut layout = Vec::new();
+        if let Some(align) = attribute(attributes, "align") {
+            let align = align.trim().to_ascii_lowercase();
+            if ["left", "center", "right"].contains(&align.as_str()) {
+                layout.push(format!("align={}", align));
+            }
+        }
+        if let Some(width) = attribute(attributes, "width") {
+            let width = width.trim();
+            if width.ends_with('%') && width[..width.len() - 1].parse::<usize>().is_ok() {
+                layout.push(format!("width={}", width));
+            }
+        }
+        self.cell = Some(Cell {
+            events: Vec::new(),
+            colspan: span("colspan"),
+            rowspan: span("rowspan"),
+            attributes: layout,
+            heading,
+        });
+    }
+
+    /// End the current cell, if any.
+    fn end_cell(&mut self) {
+        self.close_inline();
+        if let Some(mut cell) = self.cell.take() {
+            trim(&mut cell.events);
+            if let Some(row) = self.rows.last_mut() {
+                row.cells.push(cell);
+            }
+        }
+    }
+
+    /// Place the cells of all rows in a grid.
+    ///
+    /// Put empty cells at the places which cells of other columns or rows
+    /// span.  Never let a cell span beyond the number of cells in the widest
+    /// row, lest a bad colspan adds lots of empty columns.  Return the rows
+    /// of the grid, with whether they are heading rows.
+    fn grid(self) -> Vec<(bool, Vec<Cell>)> {
+        let columns = self
+            .rows
+            .iter()
+            .map(|row| row.cells.len())
+            .max()
+            .unwrap_or(0);
+        let mut grid = Vec::with_capacity(self.rows.len());
+        // The number of rows below the current row which cells of the rows
+        // above still span, by column
+        let mut spanned: Vec<usize> = Vec::new();
+        let mut in_head = true;
+        for row in self.rows {
+            in_head = in_head
+                && (row.in_head || (!row.cells.is_empty() && row.cells.iter().all(|c| c.heading)));
+            let mut cells = Vec::new();
+            for mut cell in row.cells {
+                while spanned.get(cells.len()).is_some_and(|&rows| 0 < rows) {
+                    cells.push(Cell::default());
+                }
+                let column = cells.len();
+                cell.colspan = cell.colspan.min(columns.saturating_sub(column).max(1));
+                if spanned.len() < column + cell.colspan {
+                    spanned.resize(column + cell.colspan, 0);
+                }
+                for rows in &mut spanned[column..column + cell.colspan] {
+                    *rows = cell.rowspan;
+                }
+                let colspan = cell.colspan;
+                cells.push(cell);
+                cells.extend((1..colspan).map(|_| Cell::default()));
+            }
+            for rows in &mut spanned {
+                *rows = rows.saturating_sub(1);
+            }
+            grid.push((in_head, cells));
+        }
+        grid
+    }
+
+    /// Convert this table into markdown events.
+    fn into_events(mut self) -> Vec<Event<'static>> {
+        self.end_cell();
+        self.close_inline();
+        let mut events = Vec::new();
+        let mut caption = std::mem::take(&mut self.caption);
+        trim(&mut caption);
+        if !caption.is_empty() {
+            events.push(Start(Paragraph));
+            events.extend(caption);
+            events.push(End(Paragraph));
+        }
+        let grid = self.grid();
+        let columns = grid.iter().map(|(_, cells)| cells.len()).max().unwrap_or(0);
+        let alignments = vec![Alignment::None; columns];
+        events.push(Start(Table(alignments.clone())));
+        for (in_head, mut cells) in grid {
+            let row = if in_head { TableHead } else { TableRow };
+            events.push(Start(row.clone()));
+            cells.resize_with(columns, Cell::default);
+            for cell in cells {
+                events.push(Start(TableCell));
+                events.extend(cell.events);
+                if !cell.attributes.is_empty() {
+                    let attributes = format!(" {{{}}}", cell.attributes.join(" "));
+                    events.push(Text(Cow::Owned(attributes)));
+                }
+                events.push(End(TableCell));
+            }
+            events.push(End(row));
+        }
+        events.push(End(Table(alignments)));
+        events
+    }
+}
+
+/// Parse an HTML block with a single `table` into markdown events.
+///
+/// Return `None` if `html` is not just a single table, or has nested tables.
+fn parse_table(html: &str) -> Option<Vec<Event<'static>>> {
+    let tokens = tokens(html.trim());
+    match (tokens.first(), tokens.last()) {
+        (Some(Token::Open(first, _)), Some(Token::Close(last)))
+            if first == "table" && last == "table" => {}
+        _ => return None,
+    }
+    let mut table = HtmlTable::default();
+    for token in &tokens[1..tokens.len() - 1] {
+        match token {
+            Token::Text(text) => {
+                // Ignore whitespace between rows and cells
+                if table.cell.is_some() || !text.trim().is_empty() {
+                    push_text(table.events(), text);
+                }
+            }
+            Token::Open(name, attributes) => match name.as_str() {
+                "table" => return None,
+                "caption" => table.end_cell(),
+                "thead" => table.in_head = true,
+                "tbody" | "tfoot" => table.in_head = false,
+                "tr" => table.start_row(),
+                "td" | "th" => table.start_cell(name == "th", attributes),
+                "b" | "strong" => table.start_inline(name.clone(), Strong),
+                "i" | "em" => table.start_inline(name.clone(), Emphasis),
+                "code" => table.start_inline(name.clone(), Code),
+                "a" => match attribute(attributes, "href") {
+                    Some(href) => {
+                        let title = attribute(attributes, "title").unwrap_or_default();
+                        table.start_inline(name.clone(), Link(href.into(), title.into()));
+                    }
+                    None => table.start_inline(name.clone(), Emphasis),
+                },
+                "img" => {
+                    if let Some(alt) = attribute(attributes, "alt") {
+                        push_text(table.events(), &alt);
+                    }
+                }
+                "br" | "p" | "div" | "li" => push_text(table.events(), " "),
+                _ => {}
+            },
+            Token::Close(name) => match name.as_str() {
+                "caption" => {
+                    table.close_inline();
+                    push_text(table.events(), " ");
+                }
+                "thead" => table.in_head = false,
+                "tr" | "td" | "th" => table.end_cell(),
+                "b" | "strong" | "i" | "em" | "code" | "a" => table.end_inline(name),
+                "p" | "div" | "li" => push_text(table.events(), " "),
+                _ => {}
+            },
+        }
+    }
+    Some(table.into_events())
+}
+
+/// Convert HTML blocks with tables in `events` into markdown tables.
+///
+/// Leave HTML blocks which are no single table alone.
+pub fn convert_html_tables<'a, I>(events: I) -> Vec<Event<'a>>
+where
+    I: IntoIterator<Item = Event<'a>>,
+{
+    let mut result = Vec::new();
+    for event in events {
+        match event {
+            Html(ref html) => match parse_table(html) {
+                Some(table) => result.extend(table),
+                None => result.push(event),
+            },
+            event => result.push(event),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn text(text: &str) -> Event<'static> {
+        Text(Cow::Owned(text.to_string()))
+    }
+
+    fn cell(events: Vec<Event<'static>>) -> Vec<Event<'static>> {
+        let mut cell = vec![Start(TableCell)];
+        cell.extend(events);
+        cell.push(End(TableCell));
+        cell
+    }
+
+    fn row(tag: Tag<'static>, cells: Vec<Vec<Event<'static>>>) -> Vec<Event<'static>> {
+        let mut row = vec![Start(tag.clone())];
+        row.extend(cells.into_iter().flatten());
+        row.push(End(tag));
+        row
+    }
+
+    fn table(columns: usize, rows: Vec<Vec<Event<'static>>>) -> Vec<Event<'static>> {
+        let alignments = vec![Alignment::None; columns];
+        let mut table = vec![Start(Table(alignments.clone()))];
+        table.extend(rows.into_iter().flatten());
+        table.push(End(Table(alignments)));
+        table
+    }
+
+    #[test]
+    fn tokenize_html() {
+        assert_eq!(
+            tokens("<td align=\"a>b\">x < y<!-- note --><br/></TD>"),
+            vec![
+                Token::Open("td".to_string(), " align=\"a>b\""),
+                Token::Text("x "),
+                Token::Text("<"),
+                Token::Text(" y"),
+                Token::Open("br".to_string(), ""),
+                Token::Close("td".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn get_attributes() {
+        let attributes = " class=wide colspan='2' title=\"A &amp; B\" hidden";
+        assert_eq!(attribute(attributes, "colspan"), Some("2".to_string()));
+        assert_eq!(attribute(attributes, "class"), Some("wide".to_string()));
+        assert_eq!(attribute(attributes, "title"), Some("A & B".to_string()));
+        assert_eq!(attribute(attributes, "hidden"), Some(String::new()));
+        assert_eq!(attribute(attributes, "rowspan"), None);
+    }
+
+    #[test]
+    fn convert_simple_table() {
+        let html = "<table>\n  <tr><th>Name</th><th align=\"right\">Size</th></tr>\n\
+                    <tr><td><b>foo</b> &amp;\n bar</td><td>42</td></tr>\n</table>\n";
+        assert_eq!(
+            convert_html_tables(vec![Html(html.into())]),
+            table(
+                2,
+                vec![
+                    row(
+                        TableHead,
+                        vec![
+                            cell(vec![text("Name")]),
+                            cell(vec![text("Size"), text(" {align=right}")]),
+                        ]
+                    ),
+                    row(
+                        TableRow,
+                        vec![
+                            cell(vec![
+                                Start(Strong),
+                                text("foo"),
+                                End(Strong),
+                                text(" & bar")
+                            ]),
+                            cell(vec![text("42")]),
+                        ]
+                    ),
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn convert_merged_cells() {
+        let html = "<table>\n<thead><tr><th colspan=\"2\">A</th><th>B</th></tr></thead>\n\
+                    <tr><td rowspan=2>1<td>2<td>3\n<tr><td>4<td>5</table>";
+        assert_eq!(
+            convert_html_tables(vec![Html(html.into())]),
+            table(
+                3,
+                vec![
+                    row(
+                        TableHead,
+                        vec![cell(vec![text("A")]), cell(vec![]), cell(vec![text("B")])]
+                    ),
+                    row(
+                        TableRow,
+                        vec![
+                            cell(vec![text("1")]),
+                            cell(vec![text("2")]),
+                            cell(vec![text("3")])
+                        ]
+                    ),
+                    row(
+                        TableRow,
+                        vec![cell(vec![]), cell(vec![text("4")]), cell(vec![text("5")])]
+                    ),
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn clamp_spans_to_the_widest_row() {
+        let html = "<table><tr><td colspan=\"50\">A</td></tr><tr><td>1<td>2</table>";
+        assert_eq!(
+            convert_html_tables(vec![Html(html.into())]),
+            table(
+                2,
+                vec![
+                    row(TableRow, vec![cell(vec![text("A")]), cell(vec![])]),
+                    row(TableRow, vec![cell(vec![text("1")]), cell(vec![text("2")])]),
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn keep_other_html() {
+        let events = vec![
+            Html("<div>\n<table><tr><td>1</td></tr></table>\n</div>\n".into()),
+            Html("<table><tr><td><table></table></td></tr></table>\n".into()),
+        ];
+        assert_eq!(convert_html_tables(events.clone()), events);
+    }
+}
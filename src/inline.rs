@@ -0,0 +1,114 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Render small markdown fragments to a single line.
+
+use crate::theme::{overlay, Theme};
+use ansi_term::Style;
+use pulldown_cmark::Event::*;
+use pulldown_cmark::Parser;
+use pulldown_cmark::Tag::*;
+use std::fmt::Write;
+
+/// Render a small `markdown` fragment to a string with ANSI styles.
+///
+/// Render inline markup only, ie, emphasis, strong text, code and links, with
+/// the default theme, and join all blocks into a single line.  Show the
+/// text of images instead of images, and the text of links without their
+/// destination.
+///
+/// Unlike `push_tty` this function loads no syntax definitions or themes for
+/// highlighting, and accesses no resources, so it's cheap enough for shell
+/// prompts and similar tools which render many tiny fragments.
+pub fn render_inline(markdown: &str) -> String {
+    let theme = Theme::default();
+    let mut output = String::with_capacity(markdown.len() * 2);
+    let mut styles: Vec<Style> = Vec::new();
+    let mut emphasis_level = 0;
+    let mut at_start = true;
+    for event in Parser::new(markdown) {
+        let current = styles.last().copied().unwrap_or_default();
+        match event {
+            Text(text) | InlineHtml(text) => {
+                at_start = false;
+                // Writing to a string never fails
+                write!(output, "{}", current.paint(text.as_ref())).unwrap();
+            }
+            SoftBreak | HardBreak => output.push(' '),
+            Start(Emphasis) => {
+                emphasis_level += 1;
+                styles.push(Style {
+                    is_italic: emphasis_level % 2 == 1,
+                    ..current
+                });
+            }
+            Start(Strong) => styles.push(current.bold()),
+            Start(Code) => styles.push(overlay(current, theme.code)),
+            Start(Link(_, _)) => styles.push(overlay(current, theme.link)),
+            Start(Header(_)) => styles.push(overlay(current, theme.heading)),
+            End(Emphasis) => {
+                emphasis_level -= 1;
+                styles.pop();
+            }
+            End(Strong) | End(Code) | End(Link(_, _)) => {
+                styles.pop();
+            }
+            End(Header(_)) => {
+                styles.pop();
+                output.push(' ');
+            }
+            End(Paragraph) | End(Item) | End(CodeBlock(_)) if !at_start => output.push(' '),
+            _ => {}
+        }
+    }
+    let length = output.trim_end().len();
+    output.truncate(length);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ansi_term::Colour;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn render_inline_markup() {
+        assert_eq!(
+            render_inline("Some *emphasis*, **strong** `code`"),
+            format!(
+                "{}{}{}{}{}{}",
+                Style::new().paint("Some "),
+                Style::new().italic().paint("emphasis"),
+                Style::new().paint(", "),
+                Style::new().bold().paint("strong"),
+                Style::new().paint(" "),
+                Style::new().fg(Colour::Yellow).paint("code"),
+            )
+        );
+    }
+
+    #[test]
+    fn render_blocks_on_a_single_line() {
+        assert_eq!(
+            render_inline("# Foo\n\nbar\nbaz [link](http://example.com)\n"),
+            format!(
+                "{}{}{}",
+                Style::new().fg(Colour::Blue).bold().paint("Foo"),
+                Style::new().paint(" bar baz "),
+                Style::new().fg(Colour::Blue).paint("link"),
+            )
+        );
+    }
+}
@@ -0,0 +1,217 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Detect the language of paragraphs.
+//!
+//! A deliberately small classifier: We recognize some languages by their
+//! script, and others by counting their most common words.
+
+use pulldown_cmark::Event::*;
+use pulldown_cmark::Tag::*;
+use pulldown_cmark::{Event, Tag};
+use std::collections::HashMap;
+
+/// The most common words of languages in Latin script.
+const COMMON_WORDS: &[(&str, &[&str])] = &[
+    (
+        "en",
+        &[
+            "the", "and", "of", "to", "is", "in", "that", "it", "with", "for", "this", "are",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "der", "die", "das", "und", "ist", "nicht", "ein", "eine", "mit", "auf", "ich", "sie",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "le", "la", "les", "et", "est", "une", "des", "du", "pas", "que", "qui", "dans",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "el", "los", "las", "y", "es", "una", "del", "que", "por", "con", "para", "muy",
+        ],
+    ),
+    (
+        "it",
+        &[
+            "il", "di", "che", "è", "gli", "della", "non", "una", "per", "sono", "con", "anche",
+        ],
+    ),
+    (
+        "pt",
+        &[
+            "o", "os", "as", "e", "é", "um", "uma", "não", "que", "do", "da", "em",
+        ],
+    ),
+    (
+        "nl",
+        &[
+            "de", "het", "een", "en", "is", "niet", "van", "dat", "op", "ik", "zijn", "met",
+        ],
+    ),
+    (
+        "sv",
+        &[
+            "och", "att", "det", "är", "som", "en", "på", "inte", "med", "för", "jag", "av",
+        ],
+    ),
+    (
+        "pl",
+        &[
+            "i", "w", "nie", "się", "na", "jest", "to", "że", "do", "z", "jak", "ale",
+        ],
+    ),
+];
+
+/// The minimum number of common words we need to detect a language.
+const MINIMUM_SCORE: usize = 2;
+
+/// Detect the language of `text`.
+///
+/// Return the language tag, eg, `de`, or `None` if we are not sure.
+pub fn detect_language(text: &str) -> Option<&'static str> {
+    let letters = text.chars().filter(|c| c.is_alphabetic());
+    let (mut cyrillic, mut ukrainian, mut greek, mut kana, mut total) = (0, 0, 0, 0, 0);
+    for c in letters {
+        total += 1;
+        match c {
+            'і' | 'ї' | 'є' | 'ґ' | 'І' | 'Ї' | 'Є' | 'Ґ' => {
+                cyrillic += 1;
+                ukrainian += 1;
+            }
+            '\u{400}'..='\u{4FF}' => cyrillic += 1,
+            '\u{370}'..='\u{3FF}' => greek += 1,
+            '\u{3040}'..='\u{30FF}' => kana += 1,
+            _ => {}
+        }
+    }
+    if total == 0 {
+        None
+    } else if 0 < kana {
+        Some("ja")
+    } else if total < cyrillic * 2 {
+        Some(if 0 < ukrainian { "uk" } else { "ru" })
+    } else if total < greek * 2 {
+        Some("el")
+    } else {
+        detect_by_common_words(text)
+    }
+}
+
+/// Detect the language of `text` by its most common words.
+fn detect_by_common_words(text: &str) -> Option<&'static str> {
+    let words = text
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>();
+    let mut scores = COMMON_WORDS
+        .iter()
+        .map(|(language, common)| {
+            let score = words
+                .iter()
+                .filter(|word| common.contains(&word.as_str()))
+                .count();
+            (score, *language)
+        })
+        .collect::<Vec<_>>();
+    scores.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+    match scores.as_slice() {
+        [(best, language), (second, _), ..] if MINIMUM_SCORE <= *best && second < best => {
+            Some(language)
+        }
+        _ => None,
+    }
+}
+
+/// Whether `tag` starts a block whose language we detect.
+fn is_text_block(tag: &Tag) -> bool {
+    matches!(tag, Paragraph | Header(_))
+}
+
+/// Detect the language of all paragraphs and headings in `events`.
+///
+/// Map the index of the start event of each paragraph or heading to its
+/// language, if we could detect it.
+pub(crate) fn block_languages(events: &[Event]) -> HashMap<usize, Option<&'static str>> {
+    let mut languages = HashMap::new();
+    let mut current: Option<(usize, String)> = None;
+    for (index, event) in events.iter().enumerate() {
+        match event {
+            Start(ref tag) if is_text_block(tag) => current = Some((index, String::new())),
+            End(ref tag) if is_text_block(tag) => {
+                if let Some((start, text)) = current.take() {
+                    languages.insert(start, detect_language(&text));
+                }
+            }
+            Text(ref text) => {
+                if let Some((_, ref mut buffer)) = current {
+                    buffer.push_str(text);
+                }
+            }
+            SoftBreak | HardBreak => {
+                if let Some((_, ref mut buffer)) = current {
+                    buffer.push(' ');
+                }
+            }
+            _ => {}
+        }
+    }
+    languages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use pulldown_cmark::Parser;
+
+    #[test]
+    fn detect_languages_by_words() {
+        assert_eq!(detect_language("This is the end of the story."), Some("en"));
+        assert_eq!(
+            detect_language("Das ist nicht der Anfang und nicht das Ende."),
+            Some("de")
+        );
+        assert_eq!(
+            detect_language("C'est la fin et le début des vacances."),
+            Some("fr")
+        );
+        assert_eq!(detect_language("Hallo"), None);
+    }
+
+    #[test]
+    fn detect_languages_by_script() {
+        assert_eq!(detect_language("Привет, мир"), Some("ru"));
+        assert_eq!(detect_language("Привіт, світ"), Some("uk"));
+        assert_eq!(detect_language("こんにちは世界"), Some("ja"));
+    }
+
+    #[test]
+    fn languages_of_blocks() {
+        let events =
+            Parser::new("# Das ist der Titel\n\nThis is *the* end.\n\n```\nund der\n```\n")
+                .collect::<Vec<_>>();
+        let languages = block_languages(&events);
+        assert_eq!(languages.len(), 2);
+        assert_eq!(languages[&0], Some("de"));
+        assert_eq!(languages[&3], Some("en"));
+    }
+}
@@ -15,30 +15,111 @@
 #![deny(warnings, missing_docs, clippy::all)]
 
 //! Write markdown to TTYs.
+//!
+//! Render markdown events from [pulldown-cmark] to any writer, eg, to embed
+//! rendered markdown in other terminal applications:
+//!
+//! ```
+//! use mdcat::{push_tty, Environment, SettingsBuilder, TerminalCapabilities, TerminalSize};
+//! use pulldown_cmark::Parser;
+//! use std::io::Write;
+//!
+//! let settings = SettingsBuilder::new(TerminalCapabilities::ansi())
+//!     .terminal_size(TerminalSize::new(60, 20))
+//!     .output_is_tty(false)
+//!     .build()
+//!     .unwrap();
+//! let mut buffer = Vec::new();
+//! {
+//!     // Any writer works, even a trait object
+//!     let mut writer: &mut dyn Write = &mut buffer;
+//!     let environment = Environment::isolated("/");
+//!     push_tty(&settings, &mut writer, &environment, Parser::new("*Hello*")).unwrap();
+//! }
+//! assert_eq!(String::from_utf8(buffer).unwrap(), "\u{1b}[3mHello\u{1b}[0m\n");
+//! ```
+//!
+//! Take the capabilities of the terminal from `TerminalCapabilities`, the
+//! styles of markdown elements from `Theme`, and what resources to load from
+//! `ResourceAccess`.
+//!
+//! [pulldown-cmark]: https://docs.rs/pulldown-cmark
 
-#[cfg(feature = "resources")]
-use url;
-
-use ansi_term::{Colour, Style};
+use ansi_term::Style;
 use failure::Error;
 use pulldown_cmark::Event::*;
 use pulldown_cmark::Tag::*;
-use pulldown_cmark::{Event, Tag};
+use pulldown_cmark::{Alignment, Event, Tag};
 use std::borrow::Cow;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::io;
 use std::io::Write;
-use std::path::Path;
 use syntect::easy::HighlightLines;
-use syntect::highlighting::{Theme, ThemeSet};
+use syntect::highlighting::Theme as SyntectTheme;
 use syntect::parsing::SyntaxSet;
 
+mod abbreviations;
+#[cfg(feature = "remote_resources")]
+mod cache;
+mod crossrefs;
+mod entities;
+mod environment;
+mod figures;
+mod fill;
+mod footnotes;
+#[cfg(feature = "remote_resources")]
+mod headers;
+mod html_tables;
+mod inline;
+mod language;
+mod link_definitions;
+mod messages;
+mod metrics;
 mod resources;
+mod settings;
+mod shell;
+mod spans;
+mod table;
 mod terminal;
+mod theme;
+mod toc;
+mod typography;
+mod verify;
+#[cfg(all(feature = "resources", feature = "external_programs"))]
+mod viewer;
+mod width;
 
 // Expose some select things for use in main
+pub use crate::abbreviations::{extract_abbreviations, insert_abbreviations};
+pub use crate::crossrefs::resolve_crossrefs;
+pub use crate::entities::decode_entities;
+pub use crate::environment::Environment;
+pub use crate::footnotes::collect_footnotes;
+pub use crate::html_tables::convert_html_tables;
+pub use crate::inline::render_inline;
+pub use crate::language::detect_language;
+pub use crate::link_definitions::{collect_link_definitions, LinkDefinition};
+pub use crate::messages::{Message, Messages};
+pub use crate::metrics::{metrics, Metrics};
 pub use crate::resources::ResourceAccess;
+pub use crate::settings::{
+    ImageFallback, Settings, SettingsBuilder, SettingsError, DEFAULT_TAB_WIDTH, MINIMUM_COLUMNS,
+    NARROW_COLUMNS,
+};
+pub use crate::shell::shell_command;
+pub use crate::spans::{render_spans, StyledLine};
 pub use crate::terminal::*;
+pub use crate::theme::{
+    ColumnWidth, ImageAlignment, Margin, Spacing, Theme, ThemeError, WidthHint, BUILTIN_THEMES,
+    BUILTIN_THEME_ALIASES,
+};
+pub use crate::toc::insert_toc;
+pub use crate::typography::Typography;
+pub use crate::verify::{verify, LostText};
+pub use crate::width::{display_width, truncate_to_width, wrap_styled};
+
+use crate::fill::Filler;
+use crate::theme::overlay;
 
 /// Dump markdown events to a writer.
 pub fn dump_events<'a, W, I>(writer: &mut W, events: I) -> Result<(), Error>
@@ -55,38 +136,90 @@ where
 /// Write markdown to a TTY.
 ///
 /// Iterate over Markdown AST `events`, format each event for TTY output and
-/// write the result to a `writer`.
+/// write the result to a `writer`, according to the given `settings`.
+///
+/// Resolve references in the markdown document against the given
+/// `environment`.
 ///
-/// `push_tty` tries to limit output to the given number of TTY `columns` but
-/// does not guarantee that output stays within the column limit.
+/// `push_tty` tries to limit output to the width of the terminal in
+/// `settings` but does not guarantee that output stays within the column
+/// limit.
 pub fn push_tty<'a, 'e, W, I>(
+    settings: &'a Settings,
     writer: &'a mut W,
-    capabilities: TerminalCapabilities,
-    size: TerminalSize,
-    mut events: I,
-    base_dir: &'a Path,
-    resource_access: ResourceAccess,
-    syntax_set: SyntaxSet,
+    environment: &'a Environment,
+    events: I,
 ) -> Result<(), Error>
 where
     I: Iterator<Item = Event<'e>>,
     W: Write,
 {
-    let theme = &ThemeSet::load_defaults().themes["Solarized (dark)"];
-    events
-        .try_fold(
-            Context::new(
-                writer,
-                capabilities,
-                size,
-                base_dir,
-                resource_access,
-                syntax_set,
-                theme,
-            ),
-            write_event,
-        )?
-        .write_pending_links()?;
+    render(
+        settings,
+        &settings.terminal_capabilities,
+        writer,
+        environment,
+        events,
+    )
+}
+
+/// Write markdown `events` to `writer` for a terminal with the given
+/// `capabilities`.
+///
+/// Take everything else from `settings`.
+fn render<'a, 'e, W, I>(
+    settings: &'a Settings,
+    capabilities: &'a TerminalCapabilities,
+    writer: &'a mut W,
+    environment: &'a Environment,
+    events: I,
+) -> Result<(), Error>
+where
+    I: Iterator<Item = Event<'e>>,
+    W: Write,
+{
+    let themes = &settings.highlighting_themes;
+    let theme = themes
+        .get(&settings.theme.highlighting)
+        .unwrap_or_else(|| &themes["Solarized (dark)"]);
+    #[cfg(feature = "remote_resources")]
+    let _headers = match settings.resource_access {
+        ResourceAccess::RemoteAllowed => Some(headers::RenderHeaders::load(environment)),
+        ResourceAccess::LocalOnly => None,
+    };
+    let context = Context::new(settings, capabilities, writer, environment, theme);
+    let events = events.collect::<Vec<_>>();
+    let languages = if settings.detect_languages && settings.typography.is_some() {
+        language::block_languages(&events)
+    } else {
+        HashMap::new()
+    };
+    let figures = figures::figures(&events);
+    let mut context =
+        events
+            .into_iter()
+            .enumerate()
+            .try_fold(context, |mut context, (index, event)| {
+                if let Some(language) = languages.get(&index) {
+                    // Use the typography of the language of the block, or the
+                    // typography of the document if we are not sure.
+                    context.typography.typography = language
+                        .map(Typography::for_language)
+                        .or(settings.typography);
+                }
+                if figures.attributes.contains(&index) {
+                    // Attributes only tell how to show the figure
+                    return Ok(context);
+                }
+                if let Some(alignment) = figures.alignments.get(&index) {
+                    context.image.figure =
+                        Some(alignment.unwrap_or(context.style.theme.image_alignment));
+                }
+                write_event(context, event)
+            })?;
+    context.write_pending_links()?;
+    context.write_fallbacks()?;
+    context.output.writer.finish()?;
     Ok(())
 }
 
@@ -122,8 +255,8 @@ struct Link<'a> {
 /// Input context.
 #[cfg(feature = "resources")]
 struct ResourceContext<'a> {
-    /// The base directory, to resolve relative paths.
-    base_dir: &'a Path,
+    /// The environment, to resolve relative paths and local URLs.
+    environment: &'a Environment,
     /// What resources we may access when processing markdown.
     resource_access: ResourceAccess,
 }
@@ -132,14 +265,20 @@ struct ResourceContext<'a> {
 impl ResourceContext<'_> {
     /// Resolve a reference in the input.
     ///
-    /// If `reference` parses as URL return the parsed URL.  Otherwise assume
-    /// `reference` is a file path, resolve it against `base_dir` and turn it
-    /// into a file:// URL.  If this also fails return `None`.
+    /// If `reference` parses as URL return the parsed URL.  Otherwise resolve
+    /// `reference` against the base URL of the environment if any, or against
+    /// the `file://` URL of the base directory, so that relative paths,
+    /// `..` and absolute paths work alike for remote and local documents.  If
+    /// this also fails return `None`.
     fn resolve_reference(&self, reference: &str) -> Option<url::Url> {
         use url::Url;
-        Url::parse(reference)
-            .or_else(|_| Url::from_file_path(self.base_dir.join(reference)))
-            .ok()
+        Url::parse(reference).ok().or_else(|| {
+            let base = match self.environment.base_url() {
+                Some(base_url) => Url::parse(base_url).ok(),
+                None => Url::from_directory_path(self.environment.base_dir()).ok(),
+            };
+            base?.join(reference).ok()
+        })
     }
 }
 
@@ -147,10 +286,22 @@ impl ResourceContext<'_> {
 struct OutputContext<'a, W: Write> {
     /// The terminal dimensions to limit output to.
     size: TerminalSize,
-    /// A writer to the terminal.
-    writer: &'a mut W,
+    /// A writer to the terminal, which fills text into the width of the
+    /// terminal.
+    writer: Filler<&'a mut W>,
     /// The capabilities of the terminal.
-    capabilities: TerminalCapabilities,
+    capabilities: &'a TerminalCapabilities,
+    /// Whether to write accessible output for screen readers.
+    accessible: bool,
+    /// The messages to write, eg, in accessible output.
+    messages: Messages,
+}
+
+impl<'a, W: Write> OutputContext<'a, W> {
+    /// Whether we write to a narrow terminal.
+    fn is_narrow(&self) -> bool {
+        self.size.width < NARROW_COLUMNS
+    }
 }
 
 #[derive(Debug)]
@@ -167,14 +318,24 @@ struct StyleContext {
     /// We use this information to switch between italic and upright text for
     /// emphasis.
     emphasis_level: usize,
+    /// The styles for markdown elements.
+    theme: Theme,
 }
 
 #[derive(Debug)]
 struct BlockContext {
     /// The number of spaces to indent with.
     indent_level: usize,
+    /// The indentation each enclosing block quote and list item added.
+    nesting: Vec<usize>,
     /// Whether we are at block-level or inline in a block.
     level: BlockLevel,
+    /// The number of blank lines the last block wants after itself.
+    margin: usize,
+    /// Whether we are at the start of a list item, to recognize tasks.
+    at_item_start: bool,
+    /// Whether the last event was a soft break.
+    after_soft_break: bool,
 }
 
 /// Context to keep track of links.
@@ -192,13 +353,21 @@ struct LinkContext<'a> {
     last_text: Option<Cow<'a, str>>,
     /// Whether we are inside an inline link currently.
     inside_inline_link: bool,
+    /// The prefix of the IDs of inline links.
+    #[cfg(feature = "osc8_links")]
+    id_prefix: String,
+    /// The number of inline links so far.
+    #[cfg(feature = "osc8_links")]
+    inline_links: usize,
 }
 
 struct CodeContext<'a> {
     /// Available syntaxes
-    syntax_set: SyntaxSet,
+    syntax_set: &'a SyntaxSet,
     /// The theme to use for highlighting
-    theme: &'a Theme,
+    theme: &'a SyntectTheme,
+    /// All themes for highlighting, for languages with a theme of their own.
+    themes: &'a BTreeMap<String, SyntectTheme>,
     /// The current highlighter.
     ///
     /// If set assume we are in a code block and highlight all text with this
@@ -207,6 +376,21 @@ struct CodeContext<'a> {
     /// Otherwise we are either outside of a code block or in a code block we
     /// cannot highlight.
     current_highlighter: Option<HighlightLines<'a>>,
+    /// Whether we are in a code block with a diff.
+    ///
+    /// We highlight diffs with the colours of our theme rather than with the
+    /// highlighter.
+    in_diff: bool,
+    /// The column in the current line of code, if we are in code.
+    ///
+    /// We use it to expand tabs in code blocks and inline code.
+    column: Option<usize>,
+    /// The number of columns between tab stops.
+    tab_width: usize,
+    /// Whether we are in a code block.
+    in_block: bool,
+    /// Whether to mark invisible characters in code blocks.
+    show_invisibles: bool,
 }
 
 /// Context for images.
@@ -216,6 +400,144 @@ struct ImageContext {
     ///
     /// Suppresses all text output.
     inline_image: bool,
+    /// Why we could not show the current image, if we failed.
+    error: Option<String>,
+    /// The alignment of the current image, if it is a figure.
+    figure: Option<ImageAlignment>,
+    /// What to do with images which the terminal cannot show inline.
+    #[cfg(feature = "resources")]
+    fallback: ImageFallback,
+    /// The local file of the current image, to link to instead of its URL.
+    #[cfg(feature = "resources")]
+    file: Option<url::Url>,
+    /// Whether we showed the current image in a viewer.
+    viewed: bool,
+    /// The output for all images we wrote so far, by a hash of their
+    /// contents and the size we wrote them for.
+    ///
+    /// Keep the number of columns of each image, if known, to align it.
+    #[cfg(any(feature = "iterm2", feature = "sixel"))]
+    payloads: std::collections::HashMap<(u64, usize, usize), ImagePayload>,
+}
+
+/// The output and the number of columns of an image.
+#[cfg(any(feature = "iterm2", feature = "sixel"))]
+type ImagePayload = (Vec<u8>, Option<usize>);
+
+#[cfg(any(feature = "iterm2", feature = "sixel"))]
+impl ImageContext {
+    /// Write the image with `contents` to `writer` at `size`.
+    ///
+    /// Let `render` encode `contents` only the first time we see the same
+    /// contents at the same size, and write the same output for all further
+    /// occurrences, so that we encode images which occur repeatedly, eg,
+    /// badges, only once, even if they come from different URLs.
+    ///
+    /// `render` returns the number of columns of the image, if known, and we
+    /// write as many spaces as `padding` gives for these columns before the
+    /// image.
+    ///
+    /// Return the error of `render` if we could not write the image.  We do
+    /// not remember failures, and try again for the next occurrence.
+    fn write_once<W, F, P>(
+        &mut self,
+        writer: &mut W,
+        size: TerminalSize,
+        contents: &[u8],
+        padding: P,
+        render: F,
+    ) -> io::Result<Result<(), String>>
+    where
+        W: Write,
+        F: FnOnce(&[u8], &mut Vec<u8>) -> Result<Option<usize>, Error>,
+        P: FnOnce(usize) -> usize,
+    {
+        use std::collections::hash_map::{DefaultHasher, Entry};
+        use std::hash::Hasher;
+
+        let mut hasher = DefaultHasher::new();
+        hasher.write(contents);
+        let key = (hasher.finish(), size.width, size.height);
+        let (payload, columns) = match self.payloads.entry(key) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let mut payload = Vec::new();
+                match render(contents, &mut payload) {
+                    Ok(columns) => entry.insert((payload, columns)),
+                    Err(error) => return Ok(Err(error.to_string())),
+                }
+            }
+        };
+        let padding = columns.map_or(0, padding);
+        write!(writer, "{}", " ".repeat(padding))?;
+        writer.write_all(payload).map(Ok)
+    }
+}
+
+/// Context for abbreviations.
+#[derive(Debug)]
+struct AbbreviationContext {
+    /// The expansions of the abbreviations we are currently in.
+    ///
+    /// A stack to address nested `abbr` tags.
+    current: Vec<String>,
+    /// All expansions we showed already.
+    ///
+    /// We show the expansion of an abbreviation only on first use.
+    expanded: HashSet<String>,
+}
+
+/// Context for fallbacks.
+#[derive(Debug)]
+struct FallbackContext {
+    /// Descriptions of all content we degraded so far.
+    ///
+    /// `None` if we do not explain fallbacks.
+    explanations: Option<Vec<String>>,
+}
+
+/// Context for smart punctuation.
+#[derive(Debug)]
+struct TypographyContext {
+    /// The typography for smart punctuation, if enabled.
+    typography: Option<Typography>,
+    /// The last character we wrote in the current block, if any.
+    previous: Option<char>,
+}
+
+/// Context for footnotes.
+#[derive(Debug)]
+struct FootnoteContext {
+    /// The numbers of footnotes by their name.
+    numbers: HashMap<String, usize>,
+    /// The headings of the sections referring to each footnote, by number.
+    back_references: HashMap<usize, Vec<String>>,
+    /// The text of the current heading while we are inside a heading.
+    heading: Option<String>,
+    /// The text of the last heading, if any.
+    section: Option<String>,
+    /// The width of the marker of the current footnote definition.
+    marker_width: usize,
+}
+
+/// Context for tables.
+#[derive(Debug)]
+struct TableContext<'a> {
+    /// The alignments of the columns.
+    alignments: Vec<Alignment>,
+    /// The styled cells of all rows so far.
+    rows: Vec<Vec<String>>,
+    /// The alignments of the cells of all rows so far, if cells have their
+    /// own.
+    cell_alignments: Vec<Vec<Option<Alignment>>>,
+    /// The width hints of the columns from attributes of their cells.
+    width_hints: Vec<Option<WidthHint>>,
+    /// The number of rows in the heading of the table.
+    head_rows: usize,
+    /// Whether we are in the heading of the table.
+    in_head: bool,
+    /// The events of the current cell.
+    cell: Vec<Event<'a>>,
 }
 
 /// Context for TTY rendering.
@@ -235,6 +557,18 @@ struct Context<'io, 'c, 'l, W: Write> {
     code: CodeContext<'c>,
     /// Context for images.
     image: ImageContext,
+    /// Context for abbreviations.
+    abbreviations: AbbreviationContext,
+    /// Context for fallbacks.
+    fallbacks: FallbackContext,
+    /// Context for smart punctuation.
+    typography: TypographyContext,
+    /// Context for footnotes.
+    footnotes: FootnoteContext,
+    /// Context for the current table, if any.
+    table: Option<TableContext<'l>>,
+    /// Whether to right-align columns of numbers in tables.
+    align_numbers: bool,
     /// The kind of the current list item.
     ///
     /// A stack of kinds to address nested lists.
@@ -243,56 +577,109 @@ struct Context<'io, 'c, 'l, W: Write> {
 
 impl<'io, 'c, 'l, W: Write> Context<'io, 'c, 'l, W> {
     fn new(
+        settings: &'io Settings,
+        capabilities: &'io TerminalCapabilities,
         writer: &'io mut W,
-        capabilities: TerminalCapabilities,
-        size: TerminalSize,
-        base_dir: &'io Path,
-        resource_access: ResourceAccess,
-        syntax_set: SyntaxSet,
-        theme: &'c Theme,
-    ) -> Context<'io, 'c, 'l, W> {
+        environment: &'io Environment,
+        theme: &'c SyntectTheme,
+    ) -> Context<'io, 'c, 'l, W>
+    where
+        'io: 'c,
+    {
         #[cfg(not(feature = "resources"))]
         {
             // Mark variables as used if resources are disabled to keep public
             // interface stable but avoid compiler warnings
-            let _ = base_dir;
-            let _ = resource_access;
+            let _ = environment;
         }
         Context {
             #[cfg(feature = "resources")]
             resources: ResourceContext {
-                base_dir,
-                resource_access,
+                environment,
+                resource_access: settings.resource_access,
             },
             output: OutputContext {
-                size,
-                writer,
+                size: settings.terminal_size,
+                writer: Filler::new(writer, settings.terminal_size.width),
                 capabilities,
+                accessible: settings.accessible,
+                messages: settings.messages,
             },
             style: StyleContext {
                 current: Style::new(),
                 previous: Vec::new(),
                 emphasis_level: 0,
+                theme: settings.theme.clone(),
             },
             block: BlockContext {
                 indent_level: 0,
-                /// Whether we are at block-level or inline in a block.
+                nesting: Vec::new(),
                 level: BlockLevel::Inline,
+                margin: 0,
+                at_item_start: false,
+                after_soft_break: false,
             },
             links: LinkContext {
                 pending_links: VecDeque::new(),
                 next_link_index: 1,
                 last_text: None,
                 inside_inline_link: false,
+                #[cfg(feature = "osc8_links")]
+                id_prefix: if settings.deterministic {
+                    "mdcat".to_string()
+                } else {
+                    format!("mdcat-{}", std::process::id())
+                },
+                #[cfg(feature = "osc8_links")]
+                inline_links: 0,
             },
             code: CodeContext {
-                syntax_set,
+                syntax_set: &settings.syntax_set,
                 theme,
+                themes: &settings.highlighting_themes,
                 current_highlighter: None,
+                in_diff: false,
+                column: None,
+                tab_width: settings.tab_width,
+                in_block: false,
+                show_invisibles: settings.show_invisibles,
             },
             image: ImageContext {
                 inline_image: false,
+                error: None,
+                figure: None,
+                #[cfg(feature = "resources")]
+                fallback: settings.image_fallback.clone(),
+                #[cfg(feature = "resources")]
+                file: None,
+                viewed: false,
+                #[cfg(any(feature = "iterm2", feature = "sixel"))]
+                payloads: std::collections::HashMap::new(),
+            },
+            abbreviations: AbbreviationContext {
+                current: Vec::new(),
+                expanded: HashSet::new(),
+            },
+            fallbacks: FallbackContext {
+                explanations: if settings.explain_fallbacks {
+                    Some(Vec::new())
+                } else {
+                    None
+                },
+            },
+            typography: TypographyContext {
+                typography: settings.typography,
+                previous: None,
+            },
+            footnotes: FootnoteContext {
+                numbers: HashMap::new(),
+                back_references: HashMap::new(),
+                heading: None,
+                section: None,
+                marker_width: 0,
             },
+            table: None,
+            align_numbers: settings.align_numbers,
             list_item_kind: Vec::new(),
         }
     }
@@ -302,11 +689,23 @@ impl<'io, 'c, 'l, W: Write> Context<'io, 'c, 'l, W> {
     /// Set `block_context` accordingly, and separate this block from the
     /// previous.
     fn start_inline_text(&mut self) -> io::Result<()> {
+        self.start_block(1)
+    }
+
+    /// Start a new block with a margin of `before` blank lines.
+    ///
+    /// Separate this block from the previous by the larger of `before` and the
+    /// margin the previous block wants after itself.
+    fn start_block(&mut self, before: usize) -> io::Result<()> {
         if let BlockLevel::Block = self.block.level {
-            self.newline_and_indent()?
+            for _ in 0..before.max(self.block.margin) {
+                self.newline()?;
+            }
+            self.indent()?;
         };
         // We are inline now
         self.block.level = BlockLevel::Inline;
+        self.typography.previous = None;
         Ok(())
     }
 
@@ -320,6 +719,7 @@ impl<'io, 'c, 'l, W: Write> Context<'io, 'c, 'l, W> {
         };
         // We are back at blocks now
         self.block.level = BlockLevel::Block;
+        self.block.margin = 1;
         Ok(())
     }
 
@@ -327,7 +727,7 @@ impl<'io, 'c, 'l, W: Write> Context<'io, 'c, 'l, W> {
     ///
     /// Restart all current styles after the newline.
     fn newline(&mut self) -> io::Result<()> {
-        writeln!(self.output.writer)
+        writeln!(&mut self.output.writer)
     }
 
     /// Write a newline and indent.
@@ -339,14 +739,69 @@ impl<'io, 'c, 'l, W: Write> Context<'io, 'c, 'l, W> {
         self.indent()
     }
 
+    /// Indent the content of a nested block quote or list item by `width`
+    /// more columns.
+    ///
+    /// Flatten blocks nested deeper than `MAX_NESTING` levels, ie, do not
+    /// indent them any further, to keep room for their text.
+    fn nest(&mut self, width: usize) {
+        let width = if self.block.nesting.len() < MAX_NESTING {
+            width
+        } else {
+            0
+        };
+        self.block.nesting.push(width);
+        self.block.indent_level += width;
+    }
+
+    /// Remove the indentation of the innermost block quote or list item.
+    fn unnest(&mut self) {
+        self.block.indent_level -= self.block.nesting.pop().unwrap_or(0);
+    }
+
     /// Indent according to the current indentation level.
     fn indent(&mut self) -> io::Result<()> {
-        write!(
-            self.output.writer,
-            "{}",
-            " ".repeat(self.block.indent_level)
-        )
-        .map_err(Into::into)
+        let indentation = self.indentation();
+        write!(self.output.writer, "{}", " ".repeat(indentation))
+    }
+
+    /// The number of columns to indent by.
+    ///
+    /// In narrow terminals indent by half of the width at most, to leave room
+    /// for text in deeply nested blocks.
+    fn indentation(&self) -> usize {
+        if self.output.is_narrow() {
+            self.block.indent_level.min(self.output.size.width / 2)
+        } else {
+            self.block.indent_level
+        }
+    }
+
+    /// The number of columns to indent block quotes by.
+    fn quote_indent(&self) -> usize {
+        if self.output.is_narrow() {
+            2
+        } else {
+            4
+        }
+    }
+
+    /// The number of columns to indent ordered list items by.
+    fn ordered_item_indent(&self) -> usize {
+        if self.output.is_narrow() {
+            3
+        } else {
+            4
+        }
+    }
+
+    /// The size to show images in.
+    ///
+    /// Images take the width right of the current indentation.
+    #[cfg(any(feature = "terminology", feature = "iterm2", feature = "sixel"))]
+    fn image_size(&self) -> TerminalSize {
+        let width = self.output.size.width.saturating_sub(self.indentation());
+        self.output.size.with_width(width.max(1))
     }
 
     /// Push a new style.
@@ -367,22 +822,283 @@ impl<'io, 'c, 'l, W: Write> Context<'io, 'c, 'l, W> {
     }
 
     /// Write `text` with the given `style`.
+    ///
+    /// Write plain text if the output is accessible, and drop colours if the
+    /// terminal is monochrome.  Underline abbreviations with dots.
     fn write_styled<S: AsRef<str>>(&mut self, style: &Style, text: S) -> io::Result<()> {
+        let in_abbreviation = !self.abbreviations.current.is_empty();
         match self.output.capabilities.style {
-            StyleCapability::None => write!(self.output.writer, "{}", text.as_ref())?,
-            StyleCapability::Ansi(ref ansi) => {
-                ansi.write_styled(self.output.writer, style, text)?
+            StyleCapability::Ansi(ref ansi) | StyleCapability::Monochrome(ref ansi)
+                if in_abbreviation && !self.output.accessible =>
+            {
+                let style = match self.output.capabilities.style {
+                    StyleCapability::Monochrome(_) => Style {
+                        foreground: None,
+                        background: None,
+                        ..*style
+                    },
+                    _ => *style,
+                };
+                ansi.write_dotted_underlined(&mut self.output.writer, &style, text)?
+            }
+            StyleCapability::Ansi(ref ansi) if !self.output.accessible => {
+                ansi.write_styled(&mut self.output.writer, style, text)?
+            }
+            StyleCapability::Monochrome(ref ansi) if !self.output.accessible => {
+                let style = Style {
+                    foreground: None,
+                    background: None,
+                    ..*style
+                };
+                ansi.write_styled(&mut self.output.writer, &style, text)?
             }
+            _ => write!(&mut self.output.writer, "{}", text.as_ref())?,
         }
         Ok(())
     }
 
+    /// Write a textual marker for accessible output, on a line of its own.
+    fn write_accessible_marker<S: AsRef<str>>(&mut self, marker: S) -> io::Result<()> {
+        self.start_inline_text()?;
+        write!(&mut self.output.writer, "{}", marker.as_ref())?;
+        self.end_inline_text_with_margin()
+    }
+
     /// Write `text` with current style.
     fn write_styled_current<S: AsRef<str>>(&mut self, text: S) -> io::Result<()> {
         let style = self.style.current;
         self.write_styled(&style, text)
     }
 
+    /// Get `text` with `style` for the terminal.
+    fn styled<S: AsRef<str>>(&self, style: &Style, text: S) -> String {
+        match self.output.capabilities.style {
+            StyleCapability::Ansi(_) if !self.output.accessible => {
+                style.paint(text.as_ref()).to_string()
+            }
+            StyleCapability::Monochrome(_) if !self.output.accessible => Style {
+                foreground: None,
+                background: None,
+                ..*style
+            }
+            .paint(text.as_ref())
+            .to_string(),
+            _ => text.as_ref().to_string(),
+        }
+    }
+
+    /// Render the inline `events` of a table cell to styled text.
+    ///
+    /// Embolden cells in the heading of a table if `head` is set.  Add links in
+    /// cells to the pending links, like reference links.
+    fn render_cell(&mut self, events: Vec<Event<'l>>, head: bool) -> String {
+        let first_text = events.iter().position(|e| matches!(e, Text(_)));
+        let last_text = events.iter().rposition(|e| matches!(e, Text(_)));
+        let mut styles = vec![Style {
+            is_bold: head || self.style.current.is_bold,
+            ..self.style.current
+        }];
+        let mut in_code = false;
+        let mut previous = None;
+        let mut cell = String::new();
+        for (index, event) in events.into_iter().enumerate() {
+            let style = styles[styles.len() - 1];
+            match event {
+                Text(text) => {
+                    let mut text: &str = &text;
+                    // Drop the padding of the cell
+                    if Some(index) == first_text {
+                        text = text.trim_start();
+                    }
+                    if Some(index) == last_text {
+                        text = text.trim_end();
+                    }
+                    if text.is_empty() {
+                        continue;
+                    }
+                    let text = text.replace(SOFT_HYPHEN, "");
+                    let text = match self.typography.typography {
+                        Some(ref typography) if !in_code => {
+                            typography::smarten(&text, typography, &mut previous)
+                        }
+                        _ => text,
+                    };
+                    cell.push_str(&self.styled(&style, text));
+                }
+                Start(Emphasis) => styles.push(overlay(
+                    Style {
+                        is_italic: !style.is_italic,
+                        ..style
+                    },
+                    self.style.theme.emphasis,
+                )),
+                Start(Strong) => styles.push(overlay(style, self.style.theme.strong)),
+                Start(Code) => {
+                    in_code = true;
+                    styles.push(overlay(style, self.style.theme.code));
+                    if self.output.accessible {
+                        cell.push('`');
+                    }
+                }
+                End(Code) => {
+                    in_code = false;
+                    styles.pop();
+                    if self.output.accessible {
+                        cell.push('`');
+                    }
+                }
+                End(Emphasis) | End(Strong) => {
+                    styles.pop();
+                }
+                End(Link(destination, title)) => {
+                    let index = self.add_link(destination, title);
+                    let style = overlay(style, self.style.theme.link);
+                    cell.push_str(&self.styled(&style, format!("[{}]", index)));
+                }
+                SoftBreak | HardBreak => cell.push(' '),
+                InlineHtml(html) => {
+                    let style = overlay(style, self.style.theme.html);
+                    cell.push_str(&self.styled(&style, html));
+                }
+                _ => {}
+            }
+        }
+        cell
+    }
+
+    /// Write the cells of `table`.
+    ///
+    /// Draw a table with borders which fits into the terminal, or in
+    /// accessible mode write one line per row.
+    fn write_table(&mut self, table: TableContext<'l>) -> io::Result<()> {
+        if self.output.accessible {
+            let messages = self.output.messages;
+            write!(
+                &mut self.output.writer,
+                "{}",
+                messages.get(Message::BeginTable)
+            )?;
+            for (index, row) in table.rows.iter().enumerate() {
+                self.newline_and_indent()?;
+                let kind = if index < table.head_rows {
+                    messages.get(Message::Columns)
+                } else {
+                    messages.get(Message::Row)
+                };
+                write!(&mut self.output.writer, "{}: {}", kind, row.join("; "))?;
+            }
+            self.newline_and_indent()?;
+            write!(
+                &mut self.output.writer,
+                "{}",
+                messages.get(Message::EndTable)
+            )
+        } else {
+            let available = self.output.size.width.saturating_sub(self.indentation());
+            let columns = table.rows.iter().map(Vec::len).max().unwrap_or(0);
+            if (self.output.is_narrow() && !table::fits(&table.rows, available))
+                || !table::has_room(columns, available)
+            {
+                return self.write_table_records(table);
+            }
+            let border_style = overlay(self.style.current, self.style.theme.rule);
+            let alignments = if self.align_numbers {
+                table::align_numbers(&table.rows, table.head_rows, &table.alignments)
+            } else {
+                table.alignments.clone()
+            };
+            let hints = self.width_hints(&table);
+            let lines = table::layout(
+                &table.rows,
+                table.head_rows,
+                &alignments,
+                &table.cell_alignments,
+                &hints,
+                available,
+                |border| self.styled(&border_style, border),
+            );
+            // Tables fit into the terminal already
+            self.output.writer.set_wrap(false);
+            for (index, line) in lines.iter().enumerate() {
+                if 0 < index {
+                    self.newline_and_indent()?;
+                }
+                write!(&mut self.output.writer, "{}", line)?;
+            }
+            self.output.writer.set_wrap(true);
+            Ok(())
+        }
+    }
+
+    /// The width hints for the columns of `table`.
+    ///
+    /// Take hints from attributes of cells, or from the theme by the heading
+    /// of the column.
+    fn width_hints(&self, table: &TableContext<'l>) -> Vec<Option<WidthHint>> {
+        let headings = match table.rows.first() {
+            Some(headings) if 0 < table.head_rows => headings.as_slice(),
+            _ => &[],
+        };
+        let columns = headings.len().max(table.width_hints.len());
+        (0..columns)
+            .map(|column| {
+                table
+                    .width_hints
+                    .get(column)
+                    .cloned()
+                    .flatten()
+                    .or_else(|| {
+                        let heading = table::plain_text(headings.get(column)?);
+                        let heading = heading.trim().to_lowercase();
+                        self.style.theme.column_widths.get(&heading).cloned()
+                    })
+            })
+            .collect()
+    }
+
+    /// Write the rows of `table` as records, for narrow terminals.
+    ///
+    /// Write each cell of a row on a line of its own, after the heading of
+    /// its column, and separate rows with short rules.
+    fn write_table_records(&mut self, table: TableContext<'l>) -> io::Result<()> {
+        // Without body rows show the headings as the only record
+        let (headings, body) = if table.head_rows < table.rows.len() {
+            (
+                table.rows[..table.head_rows]
+                    .first()
+                    .cloned()
+                    .unwrap_or_default(),
+                &table.rows[table.head_rows..],
+            )
+        } else {
+            (Vec::new(), &table.rows[..])
+        };
+        let rule_style = overlay(self.style.current, self.style.theme.rule);
+        let indentation = self.indentation();
+        for (index, row) in body.iter().enumerate() {
+            if 0 < index {
+                self.newline_and_indent()?;
+                self.write_styled(&rule_style, "\u{2500}".repeat(3))?;
+                self.newline_and_indent()?;
+            }
+            for (column, cell) in row.iter().enumerate() {
+                if 0 < column {
+                    self.newline_and_indent()?;
+                }
+                // Indent continuation lines of long cells
+                self.output.writer.set_indent(indentation + 2);
+                match headings.get(column).filter(|heading| !heading.is_empty()) {
+                    Some(heading) => write!(self.output.writer, "{}: {}", heading, cell)?,
+                    None => write!(self.output.writer, "{}", cell)?,
+                }
+            }
+        }
+        // Wrap the last word of the last cell like the rest of the cell
+        self.output.writer.end_word()?;
+        self.output.writer.set_indent(indentation);
+        Ok(())
+    }
+
     /// Enable emphasis.
     ///
     /// Enable italic or upright text according to the current emphasis level.
@@ -394,7 +1110,7 @@ impl<'io, 'c, 'l, W: Write> Context<'io, 'c, 'l, W> {
                 is_italic,
                 ..self.style.current
             };
-            self.set_style(new_style);
+            self.set_style(overlay(new_style, self.style.theme.emphasis));
         }
     }
 
@@ -418,66 +1134,417 @@ impl<'io, 'c, 'l, W: Write> Context<'io, 'c, 'l, W> {
     fn write_pending_links(&mut self) -> Result<(), Error> {
         if !self.links.pending_links.is_empty() {
             self.newline()?;
-            let link_style = self.style.current.fg(Colour::Blue);
+            let link_style = overlay(self.style.current, self.style.theme.link);
+            // Keep every link on a line of its own
+            self.output.writer.set_wrap(false);
             while let Some(link) = self.links.pending_links.pop_front() {
                 let link_text = format!("[{}]: {} {}", link.index, link.destination, link.title);
-                self.write_styled(&link_style, link_text)?;
+                if self.output.is_narrow() {
+                    // Keep a line per link even in narrow terminals
+                    let width = self.output.size.width.saturating_sub(self.indentation());
+                    let link_text = truncate_to_width(link_text.trim_end(), width).into_owned();
+                    self.write_styled(&link_style, link_text)?;
+                } else {
+                    self.write_styled(&link_style, link_text)?;
+                }
                 self.newline()?
             }
+            self.output.writer.set_wrap(true);
         };
         Ok(())
     }
 
+    /// Get the number of the footnote with the given `name`.
+    ///
+    /// Number footnotes in the order we first see them.
+    fn footnote_number(&mut self, name: &str) -> usize {
+        let next = self.footnotes.numbers.len() + 1;
+        *self
+            .footnotes
+            .numbers
+            .entry(name.to_string())
+            .or_insert(next)
+    }
+
+    /// Record that we degraded content as described by `explanation`.
+    ///
+    /// If we explain fallbacks write a marker referring to the explanation in
+    /// the summary; otherwise do nothing.
+    fn add_fallback<S: Into<String>>(&mut self, explanation: S) -> io::Result<()> {
+        let index = match self.fallbacks.explanations {
+            Some(ref mut explanations) => {
+                explanations.push(explanation.into());
+                explanations.len()
+            }
+            None => return Ok(()),
+        };
+        let style = self.style.current.dimmed();
+        self.write_styled(&style, format!("[!{}]", index))
+    }
+
+    /// Get the padding for an image of a given number of columns.
+    ///
+    /// Align figures in the space right of the current indentation, and leave
+    /// images in text alone.
+    #[cfg(any(feature = "terminology", feature = "iterm2", feature = "sixel"))]
+    fn image_padding(&self) -> impl Fn(usize) -> usize {
+        let available = self.output.size.width.saturating_sub(self.indentation());
+        let figure = self.image.figure;
+        move |columns| figure.map_or(0, |alignment| alignment.padding(available, columns))
+    }
+
+    /// Write a placeholder for an image we failed to show, because of `error`.
+    ///
+    /// Remember the error to explain the fallback at the end of the image.
+    #[cfg(any(feature = "iterm2", feature = "sixel", feature = "resources"))]
+    fn write_image_warning(&mut self, error: String) -> io::Result<()> {
+        let style = overlay(self.style.current, self.style.theme.warning);
+        let marker = if self.output.accessible {
+            self.output.messages.get(Message::Warning)
+        } else {
+            "\u{26A0}"
+        };
+        self.write_styled(&style, format!("[{} {}]", marker, error))?;
+        write!(&mut self.output.writer, " ")?;
+        self.image.error = Some(error);
+        Ok(())
+    }
+
+    /// Show the image at `link` according to the image fallback, because the
+    /// terminal cannot show images inline.
+    ///
+    /// Open the local file of the image in the viewer, or remember the file
+    /// to link to.  Write a warning if we fail.
+    #[cfg(feature = "resources")]
+    fn write_image_fallback(&mut self, link: &str) -> io::Result<()> {
+        let access = self.resources.resource_access;
+        let url = match self
+            .resources
+            .resolve_reference(link)
+            .filter(|url| access.permits(url))
+        {
+            Some(url) => url,
+            None => return Ok(()),
+        };
+        let result = match self.image.fallback.clone() {
+            ImageFallback::None => return Ok(()),
+            ImageFallback::FileLink => crate::resources::local_file(&url)
+                .map(|path| self.image.file = url::Url::from_file_path(path).ok()),
+            #[cfg(feature = "external_programs")]
+            ImageFallback::Viewer(command) => crate::resources::local_file(&url)
+                .and_then(|path| crate::viewer::view_image(&command, &path))
+                .map(|()| self.image.viewed = true),
+            #[cfg(not(feature = "external_programs"))]
+            ImageFallback::Viewer(_) => Err(failure::format_err!(
+                "mdcat was built without support for image viewers"
+            )),
+        };
+        match result {
+            Ok(()) => Ok(()),
+            Err(error) => self.write_image_warning(error.to_string()),
+        }
+    }
+
+    /// Write a summary of all fallbacks, if we explain fallbacks.
+    fn write_fallbacks(&mut self) -> Result<(), Error> {
+        let explanations = match self.fallbacks.explanations.take() {
+            Some(explanations) if !explanations.is_empty() => explanations,
+            _ => return Ok(()),
+        };
+        self.newline()?;
+        let style = self.style.current.dimmed();
+        let heading = self.output.messages.get(Message::Fallbacks);
+        self.write_styled(&style, heading)?;
+        self.newline()?;
+        for (index, explanation) in explanations.iter().enumerate() {
+            self.write_styled(&style, format!("[!{}] {}", index + 1, explanation))?;
+            self.newline()?;
+        }
+        Ok(())
+    }
+
     /// Write a simple border.
     fn write_border(&mut self) -> io::Result<()> {
-        let separator = "\u{2500}".repeat(self.output.size.width.min(20));
-        let style = self.style.current.fg(Colour::Green);
-        self.write_styled(&style, separator)?;
+        self.write_border_line()?;
         self.newline()
     }
 
+    /// Write a simple border without ending the line.
+    fn write_border_line(&mut self) -> io::Result<()> {
+        let separator = "\u{2500}".repeat(self.output.size.width.min(20));
+        let style = overlay(self.style.current, self.style.theme.rule);
+        self.write_styled(&style, separator)
+    }
+
     /// Write highlighted `text`.
     ///
     /// If the code context has a highlighter, use it to highlight `text` and
     /// write it.  Otherwise write `text` without highlighting.
     fn write_highlighted(&mut self, text: Cow<'l, str>) -> io::Result<()> {
+        if self.code.in_diff {
+            for line in text.split_inclusive('\n') {
+                let style = if line.starts_with('+') {
+                    overlay(self.style.current, self.style.theme.diff_inserted)
+                } else if line.starts_with('-') {
+                    overlay(self.style.current, self.style.theme.diff_deleted)
+                } else {
+                    self.style.current
+                };
+                self.write_code(&style, line)?;
+            }
+            return Ok(());
+        }
         let mut wrote_highlighted: bool = false;
         if let Some(ref mut highlighter) = self.code.current_highlighter {
             if let StyleCapability::Ansi(ref ansi) = self.output.capabilities.style {
-                let regions = highlighter.highlight(&text, &self.code.syntax_set);
-                highlighting::write_as_ansi(self.output.writer, ansi, &regions)?;
+                let start = std::time::Instant::now();
+                let regions = highlighter.highlight(&text, self.code.syntax_set);
+                metrics::add_highlighting(start.elapsed());
+                if self.code.show_invisibles {
+                    for (style, text) in regions {
+                        self.write_code(&highlighting::to_ansi_style(style), text)?;
+                    }
+                } else {
+                    highlighting::write_as_ansi(&mut self.output.writer, ansi, &regions)?;
+                }
                 wrote_highlighted = true;
             }
         }
         if !wrote_highlighted {
-            self.write_styled_current(&text)?;
+            let style = self.style.current;
+            self.write_code(&style, &text)?;
             self.links.last_text = Some(text);
         }
         Ok(())
     }
 
+    /// Write `text` with the given `style`, and dim markers of invisible
+    /// characters in code blocks.
+    fn write_code(&mut self, style: &Style, text: &str) -> io::Result<()> {
+        if !(self.code.in_block && self.code.show_invisibles) {
+            return self.write_styled(style, text);
+        }
+        let marker_style = Style {
+            is_dimmed: true,
+            ..*style
+        };
+        let mut rest = text;
+        while let Some(start) = rest.find(is_invisible_marker) {
+            let end = rest[start..]
+                .find(|c| !is_invisible_marker(c))
+                .map_or(rest.len(), |end| start + end);
+            self.write_styled(style, &rest[..start])?;
+            self.write_styled(&marker_style, &rest[start..end])?;
+            rest = &rest[end..];
+        }
+        self.write_styled(style, rest)
+    }
+
     /// Set a mark on the current position of the terminal if supported,
     /// otherwise do nothing.
     fn set_mark_if_supported(&mut self) -> io::Result<()> {
         match self.output.capabilities.marks {
             #[cfg(feature = "iterm2")]
-            MarkCapability::ITerm2(ref marks) => marks.set_mark(self.output.writer),
+            MarkCapability::ITerm2(ref marks) => marks.set_mark(&mut self.output.writer),
             MarkCapability::None => Ok(()),
         }
     }
 }
 
+/// The number of nested block quotes and list items we indent.
+///
+/// We show deeper blocks at the indentation of this level.
+const MAX_NESTING: usize = 12;
+
+/// A soft hyphen, ie, a possible hyphenation point.
+const SOFT_HYPHEN: char = '\u{AD}';
+
+/// The marker for tabs.
+const TAB_MARKER: char = '\u{2192}';
+
+/// The marker for trailing spaces.
+const TRAILING_SPACE_MARKER: char = '\u{B7}';
+
+/// The marker for zero-width characters.
+const ZERO_WIDTH_MARKER: char = '\u{25CC}';
+
+/// Whether `c` is an invisible character without width.
+fn is_zero_width(c: char) -> bool {
+    matches!(
+        c,
+        '\u{200B}' | '\u{200C}' | '\u{200D}' | '\u{2060}' | '\u{FEFF}'
+    )
+}
+
+/// Whether `c` is a marker for an invisible character.
+fn is_invisible_marker(c: char) -> bool {
+    c == TAB_MARKER || c == TRAILING_SPACE_MARKER || c == ZERO_WIDTH_MARKER
+}
+
+/// Mark trailing spaces and zero-width characters in `text`.
+///
+/// Only mark trailing spaces of complete lines, because the parser may split
+/// lines into many texts.  Leave tabs alone; `expand_tabs` marks them.
+fn mark_invisibles(text: &str) -> String {
+    let mut marked = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        let body = line.strip_suffix('\n').unwrap_or(line);
+        let content = if body.len() < line.len() {
+            body.trim_end_matches([' ', '\t'])
+        } else {
+            body
+        };
+        for c in content.chars() {
+            marked.push(if is_zero_width(c) {
+                ZERO_WIDTH_MARKER
+            } else {
+                c
+            });
+        }
+        for c in body[content.len()..].chars() {
+            marked.push(if c == ' ' { TRAILING_SPACE_MARKER } else { c });
+        }
+        if body.len() < line.len() {
+            marked.push('\n');
+        }
+    }
+    marked
+}
+
+/// Expand tabs in `text` to spaces, with tab stops every `tab_width` columns.
+///
+/// `column` is the column `text` starts at; update it to the column after
+/// `text`.  If `mark` is true start every expanded tab with `TAB_MARKER`.
+fn expand_tabs<'a>(
+    text: Cow<'a, str>,
+    tab_width: usize,
+    mark: bool,
+    column: &mut usize,
+) -> Cow<'a, str> {
+    if !text.contains('\t') {
+        *column = match text.rfind('\n') {
+            Some(index) => text[index + 1..].chars().count(),
+            None => *column + text.chars().count(),
+        };
+        return text;
+    }
+    let mut expanded = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\t' => {
+                let spaces = tab_width - *column % tab_width.max(1);
+                if mark && 0 < spaces {
+                    expanded.push(TAB_MARKER);
+                    expanded.push_str(&" ".repeat(spaces - 1));
+                } else {
+                    expanded.push_str(&" ".repeat(spaces));
+                }
+                *column += spaces;
+            }
+            '\n' => {
+                expanded.push(c);
+                *column = 0;
+            }
+            c => {
+                expanded.push(c);
+                *column += 1;
+            }
+        }
+    }
+    Cow::Owned(expanded)
+}
+
 /// Write a single `event` in the given context.
 fn write_event<'io, 'c, 'l, W: Write>(
     mut ctx: Context<'io, 'c, 'l, W>,
     event: Event<'l>,
 ) -> Result<Context<'io, 'c, 'l, W>, Error> {
+    if ctx.table.is_some() {
+        return write_table_event(ctx, event);
+    }
     match event {
-        SoftBreak | HardBreak => {
+        SoftBreak => {
+            // Reflow paragraphs into the width of the terminal
+            write!(&mut ctx.output.writer, " ")?;
+            ctx.block.after_soft_break = true;
+            ctx.typography.previous = Some('\n');
+            Ok(ctx)
+        }
+        HardBreak => {
             ctx.newline_and_indent()?;
+            ctx.typography.previous = Some('\n');
             Ok(ctx)
         }
         Text(text) => {
+            if let Some(ref mut heading) = ctx.footnotes.heading {
+                heading.push_str(&text);
+            }
+            let in_code = ctx.code.column.is_some();
+            let indentation = ctx.indentation();
+            ctx.output.writer.set_indent(indentation);
+            ctx.output.writer.set_wrap(!in_code);
+            let at_item_start = std::mem::replace(&mut ctx.block.at_item_start, false);
+            let after_soft_break = std::mem::replace(&mut ctx.block.after_soft_break, false);
+            let text = match text.get(..4) {
+                Some(marker @ "[ ] ") | Some(marker @ "[x] ") | Some(marker @ "[X] ")
+                    if at_item_start && !in_code =>
+                {
+                    // A task of a task list
+                    let done = marker != "[ ] ";
+                    if ctx.output.accessible {
+                        let state = if done { Message::Done } else { Message::ToDo };
+                        let state = ctx.output.messages.get(state);
+                        write!(&mut ctx.output.writer, "{}", state)?;
+                    } else {
+                        ctx.write_styled_current(if done { "[\u{2714}] " } else { "[ ] " })?;
+                    }
+                    Cow::Owned(text[4..].to_string())
+                }
+                _ => text,
+            };
+            let text = match text.strip_prefix(": ") {
+                Some(definition) if after_soft_break && !in_code => {
+                    // A definition below the term of a definition list; put
+                    // it on a line of its own, and indent it.
+                    ctx.newline_and_indent()?;
+                    if ctx.output.accessible {
+                        let definition = ctx.output.messages.get(Message::Definition);
+                        write!(&mut ctx.output.writer, "{}", definition)?;
+                    } else {
+                        write!(&mut ctx.output.writer, "    ")?;
+                    }
+                    Cow::Owned(definition.to_string())
+                }
+                _ => text,
+            };
+            // Soft hyphens only show where we break lines, which we never do
+            // in code, so drop them there.
+            let text = if in_code && text.contains(SOFT_HYPHEN) {
+                Cow::Owned(text.replace(SOFT_HYPHEN, ""))
+            } else {
+                text
+            };
+            let text = match ctx.typography.typography {
+                Some(ref typography) if ctx.code.column.is_none() => Cow::Owned(
+                    typography::smarten(&text, typography, &mut ctx.typography.previous),
+                ),
+                _ => {
+                    if let Some(last) = text.chars().last() {
+                        ctx.typography.previous = Some(last);
+                    }
+                    text
+                }
+            };
+            let mark = ctx.code.in_block && ctx.code.show_invisibles;
+            let text = if mark {
+                Cow::Owned(mark_invisibles(&text))
+            } else {
+                text
+            };
+            let text = match ctx.code.column {
+                Some(ref mut column) => expand_tabs(text, ctx.code.tab_width, mark, column),
+                None => text,
+            };
             // When we wrote an inline image suppress the text output, ie, the
             // image title.  We do not need it if we can show the image on the
             // terminal.
@@ -490,63 +1557,205 @@ fn write_event<'io, 'c, 'l, W: Write>(
         End(tag) => end_tag(ctx, tag),
         Html(content) => {
             ctx.newline()?;
-            let html_style = ctx.style.current.fg(Colour::Green);
-            for line in content.lines() {
+            let html_style = overlay(ctx.style.current, ctx.style.theme.html);
+            for (index, line) in content.lines().enumerate() {
                 ctx.write_styled(&html_style, line)?;
+                if index == 0 {
+                    ctx.add_fallback("HTML shown as source")?;
+                }
                 ctx.newline()?;
             }
             Ok(ctx)
         }
         InlineHtml(tag) => {
-            let style = ctx.style.current.fg(Colour::Green);
+            if let Some(title) = abbreviations::abbr_title(&tag) {
+                ctx.abbreviations.current.push(title);
+                return Ok(ctx);
+            }
+            if tag.as_ref() == "</abbr>" {
+                if let Some(title) = ctx.abbreviations.current.pop() {
+                    // Show the expansion on first use
+                    if !title.is_empty() && ctx.abbreviations.expanded.insert(title.clone()) {
+                        ctx.write_styled_current(format!(" ({})", title))?;
+                    }
+                    return Ok(ctx);
+                }
+            }
+            let style = overlay(ctx.style.current, ctx.style.theme.html);
             ctx.write_styled(&style, tag)?;
+            ctx.add_fallback("HTML shown as source")?;
+            Ok(ctx)
+        }
+        FootnoteReference(name) => {
+            let number = ctx.footnote_number(&name);
+            if let Some(ref section) = ctx.footnotes.section {
+                let sections = ctx.footnotes.back_references.entry(number).or_default();
+                if !sections.contains(section) {
+                    sections.push(section.clone());
+                }
+            }
+            let style = overlay(ctx.style.current, ctx.style.theme.link);
+            ctx.write_styled(&style, format!("[^{}]", number))?;
             Ok(ctx)
         }
-        FootnoteReference(_) => panic!("mdcat does not support footnotes"),
     }
 }
 
+/// Write an `event` inside a table in the given context.
+///
+/// Collect the events of each cell and render the cell at its end; write the
+/// whole table at the end of the table.
+fn write_table_event<'io, 'c, 'l, W: Write>(
+    mut ctx: Context<'io, 'c, 'l, W>,
+    event: Event<'l>,
+) -> Result<Context<'io, 'c, 'l, W>, Error> {
+    let table = match ctx.table {
+        Some(ref mut table) => table,
+        None => return write_event(ctx, event),
+    };
+    match event {
+        Start(TableHead) => {
+            table.in_head = true;
+            table.rows.push(Vec::new());
+            table.cell_alignments.push(Vec::new());
+        }
+        End(TableHead) => {
+            table.in_head = false;
+            table.head_rows = table.rows.len();
+        }
+        Start(TableRow) => {
+            table.rows.push(Vec::new());
+            table.cell_alignments.push(Vec::new());
+        }
+        Start(TableCell) => table.cell.clear(),
+        End(TableCell) => {
+            let mut events = std::mem::take(&mut table.cell);
+            let attributes = table::take_cell_attributes(&mut events).unwrap_or_default();
+            if let Some(alignments) = table.cell_alignments.last_mut() {
+                alignments.push(attributes.alignment);
+            }
+            if let Some(hint) = attributes.width {
+                let column = table.rows.last().map_or(0, Vec::len);
+                if table.width_hints.len() <= column {
+                    table.width_hints.resize(column + 1, None);
+                }
+                table.width_hints[column] = Some(hint);
+            }
+            let head = table.in_head;
+            let cell = ctx.render_cell(events, head);
+            if let Some(row) = ctx.table.as_mut().and_then(|table| table.rows.last_mut()) {
+                row.push(cell);
+            }
+        }
+        End(TableRow) => {}
+        End(tag @ Table(_)) => return end_tag(ctx, tag),
+        event => table.cell.push(event),
+    }
+    Ok(ctx)
+}
+
 /// Write the start of a `tag` in the given context.
 fn start_tag<'io, 'c, 'l, W: Write>(
     mut ctx: Context<'io, 'c, 'l, W>,
     tag: Tag<'l>,
 ) -> Result<Context<'io, 'c, 'l, W>, Error> {
     match tag {
-        Paragraph => ctx.start_inline_text()?,
+        Paragraph => ctx.start_block(ctx.style.theme.spacing.paragraph.before)?,
+        Rule if ctx.output.accessible => {
+            ctx.start_inline_text()?;
+            let separator = ctx.output.messages.get(Message::Separator);
+            write!(&mut ctx.output.writer, "{}", separator)?
+        }
         Rule => {
             ctx.start_inline_text()?;
-            let rule = "\u{2550}".repeat(ctx.output.size.width as usize);
-            let style = ctx.style.current.fg(Colour::Green);
+            let rule = "\u{2550}".repeat(ctx.output.size.width);
+            let style = overlay(ctx.style.current, ctx.style.theme.rule);
             ctx.write_styled(&style, rule)?
         }
         Header(level) => {
             // Before we start a new header, write all pending links to keep
             // them close to the text where they appeared in
             ctx.write_pending_links()?;
-            ctx.start_inline_text()?;
+            ctx.start_block(ctx.style.theme.spacing.heading.before)?;
+            ctx.footnotes.heading = Some(String::new());
             ctx.set_mark_if_supported()?;
-            ctx.set_style(Style::new().fg(Colour::Blue).bold());
-            ctx.write_styled_current("\u{2504}".repeat(level as usize))?
+            ctx.set_style(ctx.style.theme.heading);
+            if ctx.output.accessible {
+                let heading = ctx.output.messages.format(Message::HeadingLevel, &[&level]);
+                write!(&mut ctx.output.writer, "{}", heading)?
+            } else {
+                ctx.write_styled_current("\u{2504}".repeat(level as usize))?
+            }
         }
         BlockQuote => {
-            ctx.block.indent_level += 4;
+            if ctx.output.accessible {
+                ctx.write_accessible_marker(ctx.output.messages.get(Message::BeginQuote))?;
+            }
+            ctx.nest(ctx.quote_indent());
             ctx.start_inline_text()?;
             // Make emphasis style and add green colour.
             ctx.enable_emphasis();
-            ctx.style.current = ctx.style.current.fg(Colour::Green);
+            ctx.style.current = overlay(ctx.style.current, ctx.style.theme.block_quote);
         }
         CodeBlock(name) => {
-            ctx.start_inline_text()?;
-            ctx.write_border()?;
+            ctx.start_block(ctx.style.theme.spacing.code_block.before)?;
+            ctx.code.column = Some(0);
+            ctx.code.in_block = true;
+            // The highlighting the theme sets for the language, if any
+            let language_highlighting = ctx
+                .style
+                .theme
+                .language_highlighting
+                .get(&name.to_lowercase())
+                .cloned();
+            ctx.code.in_diff =
+                (name == "diff" || name == "patch") && language_highlighting.is_none();
+            let disabled = language_highlighting == Some(None);
             // Try to get a highlighter for the current code.
-            ctx.code.current_highlighter = if name.is_empty() {
+            let monochrome = matches!(
+                ctx.output.capabilities.style,
+                StyleCapability::Monochrome(_)
+            );
+            ctx.code.current_highlighter = if name.is_empty()
+                || ctx.output.accessible
+                || monochrome
+                || ctx.code.in_diff
+                || disabled
+            {
                 None
             } else {
+                let themes = ctx.code.themes;
+                let theme = language_highlighting
+                    .flatten()
+                    .and_then(|name| themes.get(&name))
+                    .unwrap_or(ctx.code.theme);
                 ctx.code
                     .syntax_set
                     .find_syntax_by_token(&name)
-                    .map(|syntax| HighlightLines::new(syntax, ctx.code.theme))
+                    .map(|syntax| HighlightLines::new(syntax, theme))
             };
+            let highlighted = ctx.code.current_highlighter.is_some()
+                && matches!(ctx.output.capabilities.style, StyleCapability::Ansi(_));
+            if ctx.output.accessible {
+                let begin = if name.is_empty() {
+                    ctx.output.messages.get(Message::BeginCodeBlock).to_string()
+                } else {
+                    ctx.output
+                        .messages
+                        .format(Message::BeginCodeBlockIn, &[&name])
+                };
+                write!(&mut ctx.output.writer, "{}", begin)?;
+            } else {
+                ctx.write_border_line()?;
+            }
+            if !(name.is_empty() || ctx.code.in_diff || disabled || highlighted) {
+                let fallback = ctx
+                    .output
+                    .messages
+                    .format(Message::NoHighlighting, &[&name]);
+                ctx.add_fallback(fallback)?;
+            }
+            ctx.newline()?;
             if ctx.code.current_highlighter.is_none() {
                 // If we found no highlighter (code block had no language or
                 // a language synctex doesn't support) we set a style to
@@ -555,7 +1764,7 @@ fn start_tag<'io, 'c, 'l, W: Write>(
                 // If we have a highlighter we set no style at all because
                 // we pass the entire block contents through the highlighter
                 // and directly write the result as ANSI.
-                let style = ctx.style.current.fg(Colour::Yellow);
+                let style = overlay(ctx.style.current, ctx.style.theme.code);
                 ctx.set_style(style);
             }
         }
@@ -564,35 +1773,83 @@ fn start_tag<'io, 'c, 'l, W: Write>(
                 Some(start) => ListItemKind::Ordered(start),
                 None => ListItemKind::Unordered,
             });
-            ctx.newline()?;
+            match ctx.block.level {
+                BlockLevel::Block => {
+                    let before = ctx.style.theme.spacing.list.before;
+                    for _ in 0..before.max(ctx.block.margin) {
+                        ctx.newline()?;
+                    }
+                }
+                // End the line of the parent list item
+                BlockLevel::Inline => ctx.newline()?,
+            }
         }
         Item => {
             ctx.indent()?;
             ctx.block.level = BlockLevel::Inline;
+            ctx.block.at_item_start = true;
+            ctx.typography.previous = None;
             match ctx.list_item_kind.pop() {
                 Some(ListItemKind::Unordered) => {
-                    write!(ctx.output.writer, "\u{2022} ")?;
-                    ctx.block.indent_level += 2;
+                    let bullet = ctx.typography.typography.map_or('\u{2022}', |t| t.bullet);
+                    write!(&mut ctx.output.writer, "{} ", bullet)?;
+                    ctx.nest(2);
                     ctx.list_item_kind.push(ListItemKind::Unordered);
                 }
                 Some(ListItemKind::Ordered(number)) => {
-                    write!(ctx.output.writer, "{:>2}. ", number)?;
-                    ctx.block.indent_level += 4;
+                    if ctx.output.is_narrow() {
+                        write!(&mut ctx.output.writer, "{}. ", number)?;
+                    } else {
+                        write!(&mut ctx.output.writer, "{:>2}. ", number)?;
+                    }
+                    ctx.nest(ctx.ordered_item_indent());
                     ctx.list_item_kind.push(ListItemKind::Ordered(number + 1));
                 }
                 None => panic!("List item without list item kind"),
             }
         }
-        FootnoteDefinition(_) => panic!("mdcat does not support footnotes"),
-        Table(_) | TableHead | TableRow | TableCell => panic!("mdcat does not support tables"),
+        FootnoteDefinition(name) => {
+            // Write links before footnotes, to keep them close to their text
+            ctx.write_pending_links()?;
+            ctx.start_block(ctx.style.theme.spacing.paragraph.before)?;
+            let marker = format!("[^{}] ", ctx.footnote_number(&name));
+            let style = overlay(ctx.style.current, ctx.style.theme.link);
+            ctx.write_styled(&style, &marker)?;
+            ctx.footnotes.marker_width = marker.len();
+            ctx.block.indent_level += marker.len();
+        }
+        Table(alignments) => {
+            ctx.start_block(ctx.style.theme.spacing.paragraph.before)?;
+            ctx.table = Some(TableContext {
+                alignments,
+                rows: Vec::new(),
+                cell_alignments: Vec::new(),
+                width_hints: Vec::new(),
+                head_rows: 0,
+                in_head: false,
+                cell: Vec::new(),
+            });
+        }
+        TableHead | TableRow | TableCell => panic!("Table part outside of table"),
         Emphasis => ctx.enable_emphasis(),
         Strong => {
-            let style = ctx.style.current.bold();
+            let style = overlay(ctx.style.current, ctx.style.theme.strong);
             ctx.set_style(style)
         }
         Code => {
-            let style = ctx.style.current.fg(Colour::Yellow);
-            ctx.set_style(style)
+            let style = overlay(ctx.style.current, ctx.style.theme.code);
+            ctx.set_style(style);
+            ctx.code.column = Some(0);
+            if ctx.output.accessible {
+                write!(&mut ctx.output.writer, "`")?;
+            }
+        }
+        Image(link, _title) if ctx.output.accessible => {
+            // Screen readers can't read images, so just announce the image;
+            // we write the title and the link as usual.
+            let _ = link;
+            let image = ctx.output.messages.get(Message::Image);
+            write!(&mut ctx.output.writer, "{}", image)?;
         }
         Link(destination, _) => {
             // Do nothing if the terminal doesn’t support inline links of if
@@ -602,7 +1859,10 @@ fn start_tag<'io, 'c, 'l, W: Write>(
                 #[cfg(feature = "osc8_links")]
                 LinkCapability::OSC8(ref osc8) => {
                     if let Some(url) = ctx.resources.resolve_reference(&destination) {
-                        osc8.set_link_url(ctx.output.writer, url)?;
+                        let hostname = ctx.resources.environment.hostname();
+                        ctx.links.inline_links += 1;
+                        let id = format!("{}-{}", ctx.links.id_prefix, ctx.links.inline_links);
+                        osc8.set_link_url(&mut ctx.output.writer, url, hostname, &id)?;
                         ctx.links.inside_inline_link = true;
                     }
                 }
@@ -621,11 +1881,11 @@ fn start_tag<'io, 'c, 'l, W: Write>(
                     .resolve_reference(&link)
                     .filter(|url| access.permits(url))
                 {
-                    terminology.write_inline_image(
-                        &mut ctx.output.writer,
-                        ctx.output.size,
-                        &url,
-                    )?;
+                    let size = ctx.image_size();
+                    let padding = ctx.image_padding();
+                    terminology.write_inline_image(&mut ctx.output.writer, size, &url, padding)?;
+                    // Continue below the image
+                    ctx.indent()?;
                     ctx.image.inline_image = true;
                 }
             }
@@ -637,12 +1897,70 @@ fn start_tag<'io, 'c, 'l, W: Write>(
                     .resolve_reference(&link)
                     .filter(|url| access.permits(url))
                 {
-                    if let Ok(contents) = iterm2.read_and_render(&url) {
-                        iterm2.write_inline_image(ctx.output.writer, url.as_str(), &contents)?;
-                        ctx.image.inline_image = true;
+                    let size = ctx.image_size();
+                    let padding = ctx.image_padding();
+                    let written = match crate::resources::read_url(&url) {
+                        Ok(contents) => ctx.image.write_once(
+                            &mut ctx.output.writer,
+                            size,
+                            &contents,
+                            padding,
+                            |contents, writer| {
+                                let contents = iterm2.render(contents)?;
+                                iterm2
+                                    .write_inline_image(writer, size, url.as_str(), &contents)
+                                    .map_err(Into::into)
+                            },
+                        )?,
+                        Err(error) => Err(error.to_string()),
+                    };
+                    match written {
+                        Ok(()) => {
+                            // Continue below the image
+                            ctx.indent()?;
+                            ctx.image.inline_image = true;
+                        }
+                        Err(error) => ctx.write_image_warning(error)?,
                     }
                 }
             }
+            #[cfg(feature = "sixel")]
+            ImageCapability::Sixel(ref sixel) => {
+                let access = ctx.resources.resource_access;
+                if let Some(url) = ctx
+                    .resources
+                    .resolve_reference(&link)
+                    .filter(|url| access.permits(url))
+                {
+                    let size = ctx.image_size();
+                    let padding = ctx.image_padding();
+                    let written = match crate::resources::read_url(&url) {
+                        Ok(contents) => ctx.image.write_once(
+                            &mut ctx.output.writer,
+                            size,
+                            &contents,
+                            padding,
+                            |contents, writer| {
+                                let image = sixel.scale(size, contents)?;
+                                sixel.write_inline_image(writer, &image)?;
+                                Ok(Some(sixel.columns(size, &image)))
+                            },
+                        )?,
+                        Err(error) => Err(error.to_string()),
+                    };
+                    match written {
+                        Ok(()) => {
+                            // Continue below the image
+                            ctx.indent()?;
+                            ctx.image.inline_image = true;
+                        }
+                        Err(error) => ctx.write_image_warning(error)?,
+                    }
+                }
+            }
+            #[cfg(feature = "resources")]
+            ImageCapability::None => ctx.write_image_fallback(&link)?,
+            #[cfg(not(feature = "resources"))]
             ImageCapability::None => {
                 // Just to mark "link" as used
                 let _ = link;
@@ -658,18 +1976,26 @@ fn end_tag<'io, 'c, 'l, W: Write>(
     tag: Tag<'l>,
 ) -> Result<Context<'io, 'c, 'l, W>, Error> {
     match tag {
-        Paragraph => ctx.end_inline_text_with_margin()?,
+        Paragraph => {
+            ctx.end_inline_text_with_margin()?;
+            ctx.block.margin = ctx.style.theme.spacing.paragraph.after;
+        }
         Rule => ctx.end_inline_text_with_margin()?,
         Header(_) => {
+            ctx.footnotes.section = ctx.footnotes.heading.take();
             ctx.drop_style();
-            ctx.end_inline_text_with_margin()?
+            ctx.end_inline_text_with_margin()?;
+            ctx.block.margin = ctx.style.theme.spacing.heading.after;
         }
         BlockQuote => {
-            ctx.block.indent_level -= 4;
+            ctx.unnest();
             // Drop emphasis and current style
             ctx.style.emphasis_level -= 1;
             ctx.drop_style();
-            ctx.end_inline_text_with_margin()?
+            ctx.end_inline_text_with_margin()?;
+            if ctx.output.accessible {
+                ctx.write_accessible_marker(ctx.output.messages.get(Message::EndQuote))?;
+            }
         }
         CodeBlock(_) => {
             match ctx.code.current_highlighter {
@@ -681,38 +2007,70 @@ fn end_tag<'io, 'c, 'l, W: Write>(
                     ctx.code.current_highlighter = None;
                 }
             }
-            ctx.write_border()?;
+            ctx.code.in_diff = false;
+            ctx.code.column = None;
+            ctx.code.in_block = false;
+            if ctx.output.accessible {
+                let end = ctx.output.messages.get(Message::EndCodeBlock);
+                writeln!(&mut ctx.output.writer, "{}", end)?;
+            } else {
+                ctx.write_border()?;
+            }
             // Move back to block context, but do not add a dedicated margin
             // because the bottom border we printed above already acts as
             // margin.
             ctx.block.level = BlockLevel::Block;
+            ctx.block.margin = ctx.style.theme.spacing.code_block.after;
         }
         List(_) => {
             // End the current list
             ctx.list_item_kind.pop();
             ctx.end_inline_text_with_margin()?;
+            ctx.block.margin = ctx.style.theme.spacing.list.after;
         }
         Item => {
-            // Reset indent level according to list item kind
-            match ctx.list_item_kind.last() {
-                Some(&ListItemKind::Ordered(_)) => ctx.block.indent_level -= 4,
-                Some(&ListItemKind::Unordered) => ctx.block.indent_level -= 2,
-                None => (),
-            }
+            ctx.unnest();
             ctx.end_inline_text_with_margin()?
         }
-        FootnoteDefinition(_) | Table(_) | TableHead | TableRow | TableCell => {}
+        Table(_) => {
+            if let Some(table) = ctx.table.take() {
+                ctx.write_table(table)?;
+            }
+            ctx.end_inline_text_with_margin()?;
+            ctx.block.margin = ctx.style.theme.spacing.paragraph.after;
+        }
+        FootnoteDefinition(name) => {
+            let number = ctx.footnote_number(&name);
+            if let Some(sections) = ctx.footnotes.back_references.remove(&number) {
+                // Refer back to the sections which refer to the footnote
+                ctx.block.margin = 0;
+                ctx.start_block(0)?;
+                let style = ctx.style.current.dimmed();
+                ctx.write_styled(&style, format!("\u{21A9} {}", sections.join(", ")))?;
+            }
+            ctx.end_inline_text_with_margin()?;
+            ctx.block.indent_level -= ctx.footnotes.marker_width;
+            ctx.block.margin = ctx.style.theme.spacing.paragraph.after;
+        }
+        TableHead | TableRow | TableCell => {}
         Emphasis => {
             ctx.drop_style();
             ctx.style.emphasis_level -= 1;
         }
-        Strong | Code => ctx.drop_style(),
+        Strong => ctx.drop_style(),
+        Code => {
+            if ctx.output.accessible {
+                write!(&mut ctx.output.writer, "`")?;
+            }
+            ctx.code.column = None;
+            ctx.drop_style()
+        }
         Link(destination, title) => {
             if ctx.links.inside_inline_link {
                 match ctx.output.capabilities.links {
                     #[cfg(feature = "osc8_links")]
                     LinkCapability::OSC8(ref osc8) => {
-                        osc8.clear_link(ctx.output.writer)?;
+                        osc8.clear_link(&mut ctx.output.writer)?;
                     }
                     LinkCapability::None => {}
                 }
@@ -731,20 +2089,41 @@ fn end_tag<'io, 'c, 'l, W: Write>(
                     _ => {
                         // Reference link
                         let index = ctx.add_link(destination, title);
-                        let style = ctx.style.current.fg(Colour::Blue);
+                        let style = overlay(ctx.style.current, ctx.style.theme.link);
                         ctx.write_styled(&style, format!("[{}]", index))?
                     }
                 }
             }
         }
         Image(link, _) => {
-            if !ctx.image.inline_image {
-                // If we could not write an inline image, write the image link
-                // after the image title.
-                let style = ctx.style.current.fg(Colour::Blue);
-                ctx.write_styled(&style, format!(" ({})", link))?
+            // If we could not write an inline image, write the image link
+            // after the image title.  Inside an inline link, the title already
+            // links to the target of the link; the image link would only
+            // distract then, eg, for badges.
+            #[cfg(feature = "resources")]
+            let file = ctx.image.file.take();
+            #[cfg(not(feature = "resources"))]
+            let file: Option<String> = None;
+            if !ctx.image.inline_image && !ctx.links.inside_inline_link {
+                let style = overlay(ctx.style.current, ctx.style.theme.link);
+                match file {
+                    Some(file) => ctx.write_styled(&style, format!(" ({})", file))?,
+                    None => ctx.write_styled(&style, format!(" ({})", link))?,
+                }
+            }
+            let messages = ctx.output.messages;
+            match ctx.image.error.take() {
+                Some(error) => {
+                    ctx.add_fallback(messages.format(Message::ImageFailed, &[&error, &link]))?
+                }
+                None if !ctx.image.inline_image && !ctx.image.viewed => {
+                    ctx.add_fallback(messages.format(Message::ImageNotShown, &[&link]))?
+                }
+                None => {}
             }
             ctx.image.inline_image = false;
+            ctx.image.viewed = false;
+            ctx.image.figure = None;
         }
     };
     Ok(ctx)
@@ -754,45 +2133,683 @@ fn end_tag<'io, 'c, 'l, W: Write>(
 mod tests {
     use super::*;
     use pretty_assertions::assert_eq;
-    use pulldown_cmark::Parser;
-
-    fn render_string(
-        input: &str,
-        base_dir: &Path,
-        resource_access: ResourceAccess,
-        syntax_set: SyntaxSet,
-        capabilities: TerminalCapabilities,
-        size: TerminalSize,
-    ) -> Result<Vec<u8>, Error> {
-        let source = Parser::new(input);
+    use pulldown_cmark::{Options, Parser};
+    use unicode_width::UnicodeWidthStr;
+
+    fn render_string(input: &str, settings: &Settings) -> Result<String, Error> {
+        let source = Parser::new_ext(input, Options::ENABLE_TABLES | Options::ENABLE_FOOTNOTES);
         let mut sink = Vec::new();
-        push_tty(
-            &mut sink,
-            capabilities,
-            size,
-            source,
-            base_dir,
-            resource_access,
-            syntax_set,
-        )?;
-        Ok(sink)
+        push_tty(settings, &mut sink, &Environment::isolated("/"), source)?;
+        Ok(String::from_utf8(sink)?)
+    }
+
+    fn settings(capabilities: TerminalCapabilities) -> Settings {
+        Settings {
+            terminal_capabilities: capabilities,
+            terminal_size: TerminalSize::default(),
+            resource_access: ResourceAccess::LocalOnly,
+            syntax_set: SyntaxSet::default(),
+            highlighting_themes: highlighting::themes(),
+            accessible: false,
+            theme: Theme::default(),
+            tab_width: DEFAULT_TAB_WIDTH,
+            show_invisibles: false,
+            explain_fallbacks: false,
+            typography: None,
+            detect_languages: false,
+            deterministic: true,
+            messages: Messages::default(),
+            image_fallback: ImageFallback::None,
+            align_numbers: false,
+        }
+    }
+
+    #[test]
+    #[cfg(any(feature = "iterm2", feature = "sixel"))]
+    fn write_repeated_images_once() {
+        let mut context = ImageContext {
+            inline_image: false,
+            error: None,
+            figure: None,
+            fallback: ImageFallback::None,
+            file: None,
+            viewed: false,
+            payloads: std::collections::HashMap::new(),
+        };
+        let size = TerminalSize::default();
+        let mut output = Vec::new();
+        let mut renders = 0;
+        for size in &[size, size, size.with_width(20)] {
+            let written = context
+                .write_once(
+                    &mut output,
+                    *size,
+                    b"badge",
+                    |columns| 8 - columns,
+                    |contents, writer| {
+                        renders += 1;
+                        writer.write_all(contents)?;
+                        Ok(Some(5))
+                    },
+                )
+                .unwrap();
+            assert_eq!(written, Ok(()));
+        }
+        // Encode again for a different size only
+        assert_eq!(renders, 2);
+        assert_eq!(output, b"   badge   badge   badge");
+        let mut failures = 0;
+        for _ in 0..2 {
+            let written = context
+                .write_once(
+                    &mut output,
+                    size,
+                    b"broken",
+                    |_| 0,
+                    |_, _| {
+                        failures += 1;
+                        Err(failure::err_msg("broken image"))
+                    },
+                )
+                .unwrap();
+            assert_eq!(written, Err("broken image".to_string()));
+        }
+        // Try again after failures
+        assert_eq!(failures, 2);
+    }
+
+    #[test]
+    #[cfg(feature = "sixel")]
+    fn align_figures() {
+        let path = std::fs::canonicalize("sample/rust-logo-128x128.png").unwrap();
+        let settings = settings(TerminalCapabilities {
+            image: ImageCapability::Sixel(terminal::sixel::SixelImages),
+            ..TerminalCapabilities::none()
+        });
+        let render = |markdown: String| render_string(&markdown, &settings).unwrap();
+        // The logo takes 13 columns in a terminal of 80 columns
+        let centered = render(format!("![Logo]({})\n", path.display()));
+        assert!(centered.starts_with(&format!("{}\x1bP", " ".repeat(33))));
+        let right = render(format!("![Logo]({}){{.right}}\n", path.display()));
+        assert!(right.starts_with(&format!("{}\x1bP", " ".repeat(67))));
+        assert!(!right.contains(".right"));
+        let inline = render(format!("See ![Logo]({})\n", path.display()));
+        assert!(inline.starts_with("See \x1bP"), "{:?}", &inline[..10]);
+    }
+
+    #[test]
+    #[cfg(feature = "sixel")]
+    fn warn_about_images_we_cannot_decode() {
+        let path = std::env::temp_dir().join(format!("mdcat-broken-{}.png", std::process::id()));
+        std::fs::write(&path, b"not an image").unwrap();
+        let settings = Settings {
+            explain_fallbacks: true,
+            ..settings(TerminalCapabilities {
+                image: ImageCapability::Sixel(terminal::sixel::SixelImages),
+                ..TerminalCapabilities::none()
+            })
+        };
+        let result = render_string(&format!("![Logo]({})\n", path.display()), &settings).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(
+            result.starts_with("[\u{26A0} The Decoder does not support the image format"),
+            "{:?}",
+            result
+        );
+        assert!(
+            result.contains("[!1] Image not shown, The Decoder does not support"),
+            "{:?}",
+            result
+        );
     }
 
     #[test]
     #[allow(non_snake_case)]
     fn GH_49_format_no_colour_simple() {
-        let result = String::from_utf8(
-            render_string(
-                "_lorem_ **ipsum** dolor **sit** _amet_",
-                Path::new("/"),
-                ResourceAccess::LocalOnly,
-                SyntaxSet::default(),
-                TerminalCapabilities::none(),
-                TerminalSize::default(),
-            )
-            .unwrap(),
+        let result = render_string(
+            "_lorem_ **ipsum** dolor **sit** _amet_",
+            &settings(TerminalCapabilities::none()),
         )
         .unwrap();
         assert_eq!(result, "lorem ipsum dolor sit amet\n");
     }
+
+    #[test]
+    fn accessible_output_announces_structure() {
+        let settings = Settings {
+            accessible: true,
+            ..settings(TerminalCapabilities::ansi())
+        };
+        let result = render_string(
+            "## Lorem `ipsum`\n\n> dolor\n\n```rust\nfn main() {}\n```\n\n---\n",
+            &settings,
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            "Heading level 2: Lorem `ipsum`
+
+Begin quote
+
+    dolor
+
+End quote
+
+Begin code block, rust
+fn main() {}
+End code block
+
+Separator
+"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "resources")]
+    fn link_to_image_files() {
+        let logo = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("sample/unicorn.png");
+        let url = url::Url::from_file_path(&logo).unwrap();
+        let markdown = format!("![Logo]({})\n\n![Missing](missing.png)\n", logo.display());
+        let settings = Settings {
+            image_fallback: ImageFallback::FileLink,
+            ..settings(TerminalCapabilities::none())
+        };
+        let result = render_string(&markdown, &settings).unwrap();
+        assert_eq!(
+            result,
+            format!(
+                "Logo ({})\n\n[\u{26A0} No such file or directory (os error 2)] Missing \
+                 (missing.png)\n",
+                url
+            )
+        );
+    }
+
+    #[test]
+    fn accessible_output_in_other_languages() {
+        let settings = Settings {
+            accessible: true,
+            messages: Messages::for_language("de"),
+            ..settings(TerminalCapabilities::ansi())
+        };
+        let result = render_string("## Lorem\n\n> dolor\n\n---\n", &settings).unwrap();
+        assert_eq!(
+            result,
+            "Überschrift Ebene 2: Lorem
+
+Zitatanfang
+
+    dolor
+
+Zitatende
+
+Trennlinie
+"
+        );
+    }
+
+    #[test]
+    fn diff_code_blocks_use_theme_colours() {
+        let result = render_string(
+            "```diff\n foo\n-bar\n+baz\n```\n",
+            &settings(TerminalCapabilities::ansi()),
+        )
+        .unwrap();
+        let theme = Theme::default();
+        assert!(result.contains(&format!("{}", theme.diff_deleted.paint("-bar\n"))));
+        assert!(result.contains(&format!("{}", theme.diff_inserted.paint("+baz\n"))));
+    }
+
+    #[test]
+    fn monochrome_output_has_no_colours() {
+        let settings = Settings {
+            terminal_capabilities: TerminalCapabilities::ansi().monochrome(),
+            theme: Theme::monochrome(),
+            ..settings(TerminalCapabilities::none())
+        };
+        let result = render_string(
+            "# Lorem\n\n*ipsum* `dolor` [sit](http://example.com)\n",
+            &settings,
+        )
+        .unwrap();
+        assert!(result.contains(&format!(
+            "{}",
+            Style::new().bold().underline().paint("Lorem")
+        )));
+        assert!(result.contains(&format!("{}", Style::new().italic().paint("ipsum"))));
+        assert!(result.contains(&format!("{}", Style::new().dimmed().paint("dolor"))));
+        let has_colours = (30..48).any(|code| {
+            result.contains(&format!("[{}", code)) || result.contains(&format!(";{}", code))
+        });
+        assert!(!has_colours, "{:?}", result);
+    }
+
+    #[test]
+    #[cfg(feature = "vte50")]
+    fn images_in_links_link_to_target() {
+        let environment = Environment::isolated("/").with_var("VTE_VERSION", "5002");
+        let settings = settings(TerminalCapabilities::detect_in(&environment));
+        let result = render_string(
+            "[![badge](https://example.com/badge.svg)](https://example.com/ci)\n",
+            &Settings {
+                terminal_capabilities: TerminalCapabilities {
+                    style: StyleCapability::None,
+                    ..settings.terminal_capabilities
+                },
+                ..settings
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            "\x1b]8;id=mdcat-1;https://example.com/ci\x07badge\x1b]8;;\x07\n"
+        );
+    }
+
+    #[test]
+    fn expand_tabs_in_code() {
+        let result = render_string(
+            "```\nfoo\tbar\n\tbaz\n```\n",
+            &Settings {
+                tab_width: 4,
+                ..settings(TerminalCapabilities::none())
+            },
+        )
+        .unwrap();
+        assert!(result.contains("\nfoo bar\n    baz\n"), "{:?}", result);
+    }
+
+    #[test]
+    fn show_invisibles_in_code_blocks() {
+        let markdown = "Trailing  \u{200B}\n\n```\na\tb \nc\u{200B}d\t\n```\n";
+        let result = render_string(
+            markdown,
+            &Settings {
+                tab_width: 4,
+                show_invisibles: true,
+                ..settings(TerminalCapabilities::none())
+            },
+        )
+        .unwrap();
+        assert!(result.starts_with("Trailing  \u{200B}\n"), "{:?}", result);
+        assert!(
+            result.contains("\na\u{2192}  b\u{B7}\nc\u{25CC}d\u{2192}\n"),
+            "{:?}",
+            result
+        );
+
+        let result = render_string(
+            markdown,
+            &Settings {
+                show_invisibles: true,
+                ..settings(TerminalCapabilities::ansi())
+            },
+        )
+        .unwrap();
+        let marker = Style::new().fg(ansi_term::Colour::Yellow).dimmed();
+        assert!(
+            result.contains(&marker.paint("\u{B7}").to_string()),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn smart_punctuation_outside_of_code() {
+        let result = render_string(
+            "\"Guten *Tag*\" -- `\"code\"`\n\n* 'Liste'\n",
+            &Settings {
+                typography: Some(Typography::for_language("de")),
+                ..settings(TerminalCapabilities::none())
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            "\u{201E}Guten Tag\u{201C} \u{2013} \"code\"\n\n\u{2022} \u{201A}Liste\u{2018}\n"
+        );
+    }
+
+    #[test]
+    fn tables() {
+        let markdown = "| a | b |\n|:-:|--:|\n| *foo* bar baz | [1](http://example.com) |\n";
+        let result = render_string(
+            markdown,
+            &Settings {
+                terminal_size: TerminalSize::new(20, 24),
+                ..settings(TerminalCapabilities::none())
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            "┌───────────┬──────┐\n\
+             │     a     │    b │\n\
+             ├───────────┼──────┤\n\
+             │  foo bar  │ 1[1] │\n\
+             │    baz    │      │\n\
+             └───────────┴──────┘\n\
+             \n[1]: http://example\u{2026}\n"
+        );
+
+        let result = render_string(
+            markdown,
+            &Settings {
+                accessible: true,
+                ..settings(TerminalCapabilities::none())
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            "Begin table\nColumns: a; b\nRow: foo bar baz; 1[1]\nEnd table\n\n[1]: http://example.com \n"
+        );
+    }
+
+    #[test]
+    fn table_column_widths_from_attributes_and_theme() {
+        let markdown = "| URL | Description |\n|---|---|\n\
+                        | https://example.com/a/long/url | Lorem ipsum dolor sit amet |\n";
+        let mut theme = Theme::default();
+        theme
+            .column_widths
+            .insert("description".to_string(), WidthHint::parse("60%").unwrap());
+        let settings = Settings {
+            terminal_size: TerminalSize::new(50, 24),
+            theme,
+            ..settings(TerminalCapabilities::none())
+        };
+        let result = render_string(markdown, &settings).unwrap();
+        assert_eq!(
+            result,
+            "┌────────────────────┬───────────────────────────┐\n\
+             │ URL                │ Description               │\n\
+             ├────────────────────┼───────────────────────────┤\n\
+             │ https://example.co │ Lorem ipsum dolor sit     │\n\
+             │ m/a/long/url       │ amet                      │\n\
+             └────────────────────┴───────────────────────────┘\n"
+        );
+        let markdown = markdown.replace("| URL |", "| URL {max-width=12} |");
+        let result = render_string(&markdown, &settings).unwrap();
+        assert_eq!(
+            result,
+            "┌──────────────┬───────────────────────────┐\n\
+             │ URL          │ Description               │\n\
+             ├──────────────┼───────────────────────────┤\n\
+             │ https://exam │ Lorem ipsum dolor sit     │\n\
+             │ ple.com/a/lo │ amet                      │\n\
+             │ ng/url       │                           │\n\
+             └──────────────┴───────────────────────────┘\n"
+        );
+    }
+
+    #[test]
+    fn smart_punctuation_in_language_of_paragraph() {
+        let result = render_string(
+            "\"Das ist nicht der Fall\"\n\n\"Hello\"\n",
+            &Settings {
+                typography: Some(Typography::for_language("en")),
+                detect_languages: true,
+                ..settings(TerminalCapabilities::none())
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            "\u{201E}Das ist nicht der Fall\u{201C}\n\n\u{201C}Hello\u{201D}\n"
+        );
+    }
+
+    #[test]
+    fn task_lists_and_definition_lists() {
+        let result = render_string(
+            "- [ ] Open\n- [x] Done\n\nTerm\n: First definition\n: Second definition\n",
+            &settings(TerminalCapabilities::none()),
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            "\n\u{2022} [ ] Open\n\u{2022} [\u{2714}] Done\n\n\
+             Term\n    First definition\n    Second definition\n"
+        );
+    }
+
+    #[test]
+    fn footnotes_at_the_end_with_back_references() {
+        let markdown =
+            "# Intro\n\nFoo[^b].\n\n[^a]: Unused.\n\n[^b]: Bar.\n\n# Usage\n\nBaz[^b].\n";
+        let events =
+            collect_footnotes(Parser::new_ext(markdown, Options::ENABLE_FOOTNOTES).collect());
+        let mut sink = Vec::new();
+        push_tty(
+            &settings(TerminalCapabilities::none()),
+            &mut sink,
+            &Environment::isolated("/"),
+            events.into_iter(),
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(sink).unwrap(),
+            "\u{2504}Intro\n\nFoo[^1].\n\n\u{2504}Usage\n\nBaz[^1].\n\n\
+             [^1] Bar.\n     \u{21A9} Intro, Usage\n\n[^2] Unused.\n"
+        );
+    }
+
+    #[test]
+    fn highlighting_per_language() {
+        let mut theme = Theme::default();
+        theme.language_highlighting.insert("rust".to_string(), None);
+        theme
+            .language_highlighting
+            .insert("python".to_string(), Some("ansi".to_string()));
+        let settings = Settings {
+            syntax_set: SyntaxSet::load_defaults_newlines(),
+            theme,
+            explain_fallbacks: true,
+            ..settings(TerminalCapabilities::ansi())
+        };
+        let result = render_string(
+            "```rust\nfn main() {}\n```\n\n```python\nname = 'mdcat'\n```\n",
+            &settings,
+        )
+        .unwrap();
+        // No highlighting for Rust, and no fallback either
+        assert!(result.contains("\x1b[33mfn main() {}\n"), "{:?}", result);
+        assert!(!result.contains("[!"), "{:?}", result);
+        // Python in the palette of the terminal
+        assert!(result.contains("\x1b[32mmdcat\x1b[0m"), "{:?}", result);
+    }
+
+    #[test]
+    fn narrow_terminals() {
+        let settings = Settings {
+            terminal_size: TerminalSize::default().with_width(24),
+            ..settings(TerminalCapabilities::none())
+        };
+        let markdown = "| Name | Description | Language |\n|---|---|---|\n| mdcat | cat for markdown | Rust |\n\n\
+                        > Quote\n\n1. Item\n\n[mdcat](https://github.com/lunaryorn/mdcat/tree/master/src)\n";
+        let result = render_string(markdown, &settings).unwrap();
+        assert!(
+            result.starts_with("Name: mdcat\nDescription: cat for\n  markdown\nLanguage: Rust\n"),
+            "{:?}",
+            result
+        );
+        assert!(result.contains("\n  Quote\n"), "{:?}", result);
+        assert!(result.contains("\n1. Item\n"), "{:?}", result);
+        assert!(
+            result.ends_with("\n[1]: https://github.com\u{2026}\n"),
+            "{:?}",
+            result
+        );
+        // Continue the last cell with the same indentation, even if it ends
+        // in inline code
+        let markdown = "| Language | Description |\n|---|---|\n| Rust | `cat for markdown` |\n";
+        let result = render_string(markdown, &settings).unwrap();
+        assert_eq!(result, "Language: Rust\nDescription: cat for\n  markdown\n");
+    }
+
+    #[test]
+    fn flatten_deeply_nested_blocks() {
+        let quotes = format!("{} Lorem ipsum\n", ">".repeat(1000));
+        let lists = (0..300)
+            .map(|level| format!("{}* Lorem ipsum\n", "  ".repeat(level)))
+            .collect::<String>();
+        let emphasis = format!("{}Lorem{}\n", "*a _a ".repeat(500), " a_ a*".repeat(500));
+        let mixed = format!("{}Lorem ipsum\n", "> * 1. ".repeat(200));
+        for markdown in &[quotes, lists, emphasis, mixed] {
+            let markdown = markdown.clone();
+            // Render with a small stack, to catch deep recursion
+            let result = std::thread::Builder::new()
+                .stack_size(256 * 1024)
+                .spawn(move || {
+                    render_string(&markdown, &settings(TerminalCapabilities::none())).unwrap()
+                })
+                .unwrap()
+                .join()
+                .unwrap();
+            assert!(result.contains("Lorem"), "{:?}", result);
+            assert!(
+                result.lines().all(|line| line.width() <= 80),
+                "{:?}",
+                result
+            );
+        }
+    }
+
+    #[test]
+    fn explain_fallbacks() {
+        let markdown = "![Logo](logo.png)\n\n```nosuchlanguage\ncode\n```\n\n<div>\n</div>\n";
+        let result = render_string(
+            markdown,
+            &Settings {
+                explain_fallbacks: true,
+                ..settings(TerminalCapabilities::none())
+            },
+        )
+        .unwrap();
+        assert!(result.contains("Logo (logo.png)[!1]"), "{:?}", result);
+        assert!(result.contains("\u{2500}[!2]\n"), "{:?}", result);
+        assert!(result.contains("<div>[!3]\n</div>\n"), "{:?}", result);
+        assert!(
+            result.ends_with(
+                "\nFallbacks:\n[!1] Image not shown inline: logo.png\n\
+                 [!2] No syntax highlighting for nosuchlanguage\n\
+                 [!3] HTML shown as source\n"
+            ),
+            "{:?}",
+            result
+        );
+
+        let result = render_string(markdown, &settings(TerminalCapabilities::none())).unwrap();
+        assert!(!result.contains("[!"), "{:?}", result);
+    }
+
+    #[test]
+    fn drop_soft_hyphens_and_keep_no_break_spaces() {
+        let result = render_string(
+            "Auto\u{AD}mo\u{AD}bile 10\u{A0}km 5\u{202F}%\n",
+            &settings(TerminalCapabilities::none()),
+        )
+        .unwrap();
+        assert_eq!(result, "Automobile 10\u{A0}km 5\u{202F}%\n");
+    }
+
+    #[test]
+    fn reflow_paragraphs_with_hanging_indents() {
+        let settings = Settings {
+            terminal_size: TerminalSize::new(24, 24),
+            ..settings(TerminalCapabilities::none())
+        };
+        let result = render_string(
+            "Lorem ipsum dolor sit\namet, dolores con\u{AD}se\u{AD}te\u{AD}tur.\n\n\
+             * Lorem ipsum `dolor sit amet` elitr.\n\n\
+             > Lorem ipsum dolor sit amet.\n\n\
+             ```\nfn main() { println!(\"Lorem\"); }\n```\n",
+            &settings,
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            "Lorem ipsum dolor sit\namet, dolores consete-\ntur.\n\n\
+             • Lorem ipsum\n  dolor sit amet elitr.\n\n\
+             \x20 Lorem ipsum dolor sit\n  amet.\n\n\
+             ────────────────────\n\
+             fn main() { println!(\"Lorem\"); }\n\
+             ────────────────────\n"
+        );
+    }
+
+    #[test]
+    fn expand_abbreviations_on_first_use() {
+        let (abbreviations, source) =
+            extract_abbreviations("*[HTML]: HyperText Markup Language\n\nHTML and HTML\n");
+        let mut sink = Vec::new();
+        push_tty(
+            &settings(TerminalCapabilities::none()),
+            &mut sink,
+            &Environment::isolated("/"),
+            insert_abbreviations(Parser::new(&source), &abbreviations).into_iter(),
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(sink).unwrap(),
+            "HTML (HyperText Markup Language) and HTML\n"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "resources")]
+    fn resolve_reference_against_environment() {
+        let environment = Environment::isolated("/foo");
+        let resources = ResourceContext {
+            environment: &environment,
+            resource_access: ResourceAccess::LocalOnly,
+        };
+        assert_eq!(
+            resources.resolve_reference("bar.png").unwrap().as_str(),
+            "file:///foo/bar.png"
+        );
+        let environment = environment.with_base_url("https://example.com/docs/");
+        let resources = ResourceContext {
+            environment: &environment,
+            resource_access: ResourceAccess::LocalOnly,
+        };
+        assert_eq!(
+            resources.resolve_reference("bar.png").unwrap().as_str(),
+            "https://example.com/docs/bar.png"
+        );
+        assert_eq!(
+            resources
+                .resolve_reference("http://example.org")
+                .unwrap()
+                .as_str(),
+            "http://example.org/"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "resources")]
+    fn resolve_relative_references_alike_for_local_and_remote_bases() {
+        let local = Environment::isolated("/foo/docs");
+        let remote = local.clone().with_base_url("https://example.com/docs/");
+        let resolve = |environment: &Environment, reference: &str| {
+            let resources = ResourceContext {
+                environment,
+                resource_access: ResourceAccess::LocalOnly,
+            };
+            resources.resolve_reference(reference).unwrap().to_string()
+        };
+        assert_eq!(
+            resolve(&local, "img/a b.png"),
+            "file:///foo/docs/img/a%20b.png"
+        );
+        assert_eq!(
+            resolve(&remote, "img/a b.png"),
+            "https://example.com/docs/img/a%20b.png"
+        );
+        assert_eq!(resolve(&local, "../a.png"), "file:///foo/a.png");
+        assert_eq!(resolve(&remote, "../a.png"), "https://example.com/a.png");
+        assert_eq!(resolve(&local, "/a.png"), "file:///a.png");
+        assert_eq!(resolve(&remote, "/a.png"), "https://example.com/a.png");
+    }
 }
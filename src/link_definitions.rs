@@ -0,0 +1,515 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reference-style link definitions.
+//!
+//! Definitions like `[id]: https://example.com "Title"` should never show up
+//! in output, but the markdown parser takes definitions for text if they
+//! continue a paragraph, a table or a list item, or if they follow a
+//! definition which spans several lines.  We move all definitions outside of
+//! code blocks and raw HTML blocks to the end of the document, each in a block
+//! of its own, where the parser reliably resolves them and hides them from
+//! output.
+
+use crate::entities::decode_entities;
+use std::collections::HashSet;
+
+/// A reference-style link definition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkDefinition {
+    /// The label of the definition, ie, the reference.
+    pub label: String,
+    /// The link destination.
+    pub destination: String,
+    /// The link title, or an empty string if the definition has none.
+    pub title: String,
+}
+
+/// Split the link destination at the start of `text` from the rest.
+///
+/// Return the raw destination, with angle brackets if any, and the rest.
+fn destination(text: &str) -> Option<(&str, &str)> {
+    let end = if text.starts_with('<') {
+        text.find(['>', '\n'])
+            .filter(|&end| &text[end..end + 1] == ">")?
+            + 1
+    } else {
+        text.find(char::is_whitespace).unwrap_or(text.len())
+    };
+    if end == 0 {
+        None
+    } else {
+        Some((&text[..end], &text[end..]))
+    }
+}
+
+/// Whether `text` is a complete link title, in quotes or parentheses.
+fn is_title(text: &str) -> bool {
+    let close = match text.chars().next() {
+        Some('"') => '"',
+        Some('\'') => '\'',
+        Some('(') => ')',
+        _ => return false,
+    };
+    let mut escaped = false;
+    for (index, c) in text.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            c if c == close => return index + 1 == text.len(),
+            '(' if close == ')' => return false,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Remove backslash escapes from `text` and decode entities.
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match chars.peek() {
+            Some(next) if c == '\\' && next.is_ascii_punctuation() => {}
+            _ => unescaped.push(c),
+        }
+    }
+    decode_entities(&unescaped).into_owned()
+}
+
+/// Parse the start of a definition from `line`.
+///
+/// Return the label, and the rest of the line after the colon.
+fn label(line: &str) -> Option<(&str, &str)> {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    if 3 < indent {
+        return None;
+    }
+    let rest = line[indent..].strip_prefix('[')?;
+    let mut escaped = false;
+    for (index, c) in rest.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '[' => return None,
+            ']' => {
+                let label = &rest[..index];
+                let after = rest[index + 1..].strip_prefix(':')?;
+                return if label.trim().is_empty() || label.starts_with('^') || 999 < label.len() {
+                    None
+                } else {
+                    Some((label, after))
+                };
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parse a definition on `line`, continued on the `next` lines if any.
+///
+/// The destination and the title of a definition may each continue on the
+/// next line.
+///
+/// Return the definition, the definition in normal form, and the number of
+/// next lines it takes.
+fn definition(line: &str, next: &[&str]) -> Option<(LinkDefinition, String, usize)> {
+    let (label, rest) = label(line)?;
+    let mut next = next.iter().map(|line| line.trim());
+    let mut continued = 0;
+    let rest = match rest.trim() {
+        "" => {
+            continued += 1;
+            next.next()?
+        }
+        rest => rest,
+    };
+    let (raw_destination, rest) = destination(rest)?;
+    let raw_title = match rest.trim() {
+        "" => match next.next() {
+            Some(next) if is_title(next) => {
+                continued += 1;
+                next
+            }
+            _ => "",
+        },
+        rest if is_title(rest) => rest,
+        _ => return None,
+    };
+    let destination = raw_destination
+        .strip_prefix('<')
+        .and_then(|destination| destination.strip_suffix('>'))
+        .unwrap_or(raw_destination);
+    let title = raw_title
+        .get(1..raw_title.len().saturating_sub(1))
+        .unwrap_or("");
+    let normal = format!("[{}]: {} {}", label, raw_destination, raw_title);
+    let definition = LinkDefinition {
+        label: label.to_string(),
+        destination: unescape(destination),
+        title: unescape(title),
+    };
+    Some((definition, normal.trim_end().to_string(), continued))
+}
+
+/// Tags which start a raw HTML block which ends at a blank line.
+const HTML_BLOCK_TAGS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "base",
+    "basefont",
+    "blockquote",
+    "body",
+    "caption",
+    "center",
+    "col",
+    "colgroup",
+    "dd",
+    "details",
+    "dialog",
+    "dir",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "frame",
+    "frameset",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "header",
+    "hr",
+    "html",
+    "iframe",
+    "legend",
+    "li",
+    "link",
+    "main",
+    "menu",
+    "menuitem",
+    "nav",
+    "noframes",
+    "ol",
+    "optgroup",
+    "option",
+    "p",
+    "param",
+    "section",
+    "source",
+    "summary",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "title",
+    "tr",
+    "track",
+    "ul",
+];
+
+/// How a raw HTML block ends.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum HtmlBlockEnd {
+    /// After the first line which contains any of these texts, in lowercase.
+    Containing(&'static [&'static str]),
+    /// At the first blank line.
+    BlankLine,
+}
+
+impl HtmlBlockEnd {
+    /// Whether the HTML block ends with `line`.
+    fn ends_at(self, line: &str) -> bool {
+        match self {
+            HtmlBlockEnd::Containing(ends) => {
+                let line = line.to_ascii_lowercase();
+                ends.iter().any(|end| line.contains(end))
+            }
+            HtmlBlockEnd::BlankLine => line.trim().is_empty(),
+        }
+    }
+}
+
+/// The columns of indentation of `line`, with tab stops every four columns.
+fn indentation(line: &str) -> usize {
+    let mut columns = 0;
+    for c in line.chars() {
+        match c {
+            ' ' => columns += 1,
+            '\t' => columns += 4 - columns % 4,
+            _ => break,
+        }
+    }
+    columns
+}
+
+/// Whether `line` starts a raw HTML block, and how the block ends.
+///
+/// Follow the start conditions of CommonMark.  A complete tag on a line of
+/// its own only starts a block if the line does not continue a paragraph.
+fn html_block_start(line: &str, in_paragraph: bool) -> Option<HtmlBlockEnd> {
+    if 3 < indentation(line) {
+        return None;
+    }
+    let text = line.trim().to_ascii_lowercase();
+    let rest = text.strip_prefix('<')?;
+    let ends_tag = |after: &str| after.is_empty() || after.starts_with([' ', '\t', '>']);
+    let raw = ["script", "pre", "style", "textarea"];
+    if raw
+        .iter()
+        .any(|tag| rest.strip_prefix(tag).is_some_and(ends_tag))
+    {
+        return Some(HtmlBlockEnd::Containing(&[
+            "</script>",
+            "</pre>",
+            "</style>",
+            "</textarea>",
+        ]));
+    }
+    if rest.starts_with("!--") {
+        return Some(HtmlBlockEnd::Containing(&["-->"]));
+    }
+    if rest.starts_with('?') {
+        return Some(HtmlBlockEnd::Containing(&["?>"]));
+    }
+    if rest.starts_with("![cdata[") {
+        return Some(HtmlBlockEnd::Containing(&["]]>"]));
+    }
+    if rest
+        .strip_prefix('!')
+        .is_some_and(|after| after.starts_with(|c: char| c.is_ascii_alphabetic()))
+    {
+        return Some(HtmlBlockEnd::Containing(&[">"]));
+    }
+    let tag = rest.strip_prefix('/').unwrap_or(rest);
+    let length = tag
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-'))
+        .unwrap_or(tag.len());
+    let (name, after) = tag.split_at(length);
+    let is_block_tag =
+        HTML_BLOCK_TAGS.contains(&name) && (ends_tag(after) || after.starts_with("/>"));
+    let is_complete_tag = !in_paragraph
+        && name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && !raw.contains(&name)
+        && text.ends_with('>');
+    if is_block_tag || is_complete_tag {
+        Some(HtmlBlockEnd::BlankLine)
+    } else {
+        None
+    }
+}
+
+/// Normalize a link `label` to match references, ie, case-insensitive and
+/// with whitespace collapsed.
+fn normalize_label(label: &str) -> String {
+    label
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Collect reference-style link definitions in markdown `source` at its end.
+///
+/// Take definitions on lines of their own outside of code blocks and raw HTML
+/// blocks, even where CommonMark takes them for text, eg, right after a line
+/// of a paragraph, and put them at the end of `source`, with a blank line
+/// between definitions.  Drop definitions of a label defined before, because
+/// the first definition wins.
+///
+/// Return the definitions and the new source.
+pub fn collect_link_definitions(source: &str) -> (Vec<LinkDefinition>, String) {
+    let mut definitions = Vec::new();
+    let mut normal_forms = Vec::new();
+    let mut labels = HashSet::new();
+    let mut output = String::with_capacity(source.len());
+    let mut fence: Option<&str> = None;
+    let mut html: Option<HtmlBlockEnd> = None;
+    let mut indented_code = false;
+    let mut in_paragraph = false;
+    let lines = source.split_inclusive('\n').collect::<Vec<_>>();
+    let mut index = 0;
+    while let Some(line) = lines.get(index) {
+        index += 1;
+        let trimmed = line.trim_start();
+        let blank = trimmed.is_empty();
+        let line_fence = ["```", "~~~"]
+            .iter()
+            .find(|marker| trimmed.starts_with(*marker));
+        if let Some(end) = html {
+            if end.ends_at(line) {
+                html = None;
+            }
+        } else if let Some(current) = fence {
+            if line_fence == Some(&current) {
+                fence = None;
+            }
+        } else if indented_code && (blank || 3 < indentation(line)) {
+            // The indented code block continues
+        } else if let Some(marker) = line_fence {
+            indented_code = false;
+            fence = Some(marker);
+        } else if !in_paragraph && !blank && 3 < indentation(line) {
+            indented_code = true;
+        } else if let Some(end) = html_block_start(line, in_paragraph) {
+            indented_code = false;
+            html = Some(end).filter(|end| !end.ends_at(line));
+        } else {
+            indented_code = false;
+            let next = &lines[index..lines.len().min(index + 2)];
+            if let Some((definition, normal, continued)) = definition(line, next) {
+                index += continued;
+                in_paragraph = false;
+                if labels.insert(normalize_label(&definition.label)) {
+                    definitions.push(definition);
+                    normal_forms.push(normal);
+                }
+                continue;
+            }
+        }
+        in_paragraph = !blank
+            && fence.is_none()
+            && html.is_none()
+            && !indented_code
+            && !trimmed.starts_with('#');
+        output.push_str(line);
+    }
+    for normal in normal_forms {
+        if !output.is_empty() && !output.ends_with('\n') {
+            output.push('\n');
+        }
+        output.push('\n');
+        output.push_str(&normal);
+        output.push('\n');
+    }
+    (definitions, output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn link(label: &str, destination: &str, title: &str) -> LinkDefinition {
+        LinkDefinition {
+            label: label.to_string(),
+            destination: destination.to_string(),
+            title: title.to_string(),
+        }
+    }
+
+    #[test]
+    fn parse_definitions() {
+        assert_eq!(
+            definition("[Foo]: https://example.com \"A \\\"B\\\"\"\n", &[]),
+            Some((
+                link("Foo", "https://example.com", "A \"B\""),
+                "[Foo]: https://example.com \"A \\\"B\\\"\"".to_string(),
+                0
+            ))
+        );
+        assert_eq!(
+            definition("   [a b]: <https://example.com/a b>", &["  (Title)", "x"]),
+            Some((
+                link("a b", "https://example.com/a b", "Title"),
+                "[a b]: <https://example.com/a b> (Title)".to_string(),
+                1
+            ))
+        );
+        assert_eq!(
+            definition("[c]:", &["  /c", "  'C'"]),
+            Some((link("c", "/c", "C"), "[c]: /c 'C'".to_string(), 2))
+        );
+        assert_eq!(
+            definition("[d]: /d", &["Text"]),
+            Some((link("d", "/d", ""), "[d]: /d".to_string(), 0))
+        );
+        assert_eq!(definition("[f]: /f \"Title\" trailing", &[]), None);
+        assert_eq!(definition("[^1]: A footnote", &[]), None);
+        assert_eq!(definition("    [g]: /g", &[]), None);
+        assert_eq!(definition("[h]:", &["", "/h"]), None);
+    }
+
+    #[test]
+    fn collect_definitions_at_end() {
+        let source = "# Head\n[a]:\n  /a\n  'A'\n[b]: /b\n[A]: /duplicate\n\n\
+                      Use [a] and [b].\n[c]: /c\n\n```\n[d]: /d\n```\n";
+        let (definitions, output) = collect_link_definitions(source);
+        assert_eq!(
+            definitions,
+            vec![
+                link("a", "/a", "A"),
+                link("b", "/b", ""),
+                link("c", "/c", "")
+            ]
+        );
+        assert_eq!(
+            output,
+            "# Head\n\nUse [a] and [b].\n\n```\n[d]: /d\n```\n\n[a]: /a 'A'\n\n[b]: /b\n\n[c]: /c\n"
+        );
+    }
+
+    #[test]
+    fn keep_definitions_in_html_blocks_and_indented_code() {
+        let source = "<pre>\n[d]: keep-html\n\n[e]: keep-pre\n</pre>\n\
+                      <div class=\"note\">\n[f]: keep-div\n</div>\n\n\
+                      <!--\n[g]: keep-comment -->\n\n\
+                      Text\n\n    [h]: keep-code\n    ```\n[i]: /i\n";
+        let (definitions, output) = collect_link_definitions(source);
+        assert_eq!(definitions, vec![link("i", "/i", "")]);
+        assert_eq!(
+            output,
+            "<pre>\n[d]: keep-html\n\n[e]: keep-pre\n</pre>\n\
+             <div class=\"note\">\n[f]: keep-div\n</div>\n\n\
+             <!--\n[g]: keep-comment -->\n\n\
+             Text\n\n    [h]: keep-code\n    ```\n\n[i]: /i\n"
+        );
+    }
+
+    #[test]
+    fn html_blocks() {
+        assert_eq!(
+            html_block_start("<PRE class=\"x\">", false),
+            Some(HtmlBlockEnd::Containing(&[
+                "</script>",
+                "</pre>",
+                "</style>",
+                "</textarea>"
+            ]))
+        );
+        assert_eq!(
+            html_block_start("</div>", true),
+            Some(HtmlBlockEnd::BlankLine)
+        );
+        assert_eq!(
+            html_block_start("<span>", false),
+            Some(HtmlBlockEnd::BlankLine)
+        );
+        assert_eq!(html_block_start("<span>", true), None);
+        assert_eq!(html_block_start("<prefix>", true), None);
+        assert_eq!(html_block_start("    <div>", false), None);
+        assert_eq!(html_block_start("[a]: /a", false), None);
+    }
+}
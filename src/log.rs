@@ -0,0 +1,115 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Markdown logs.
+//!
+//! CI jobs or chat bots stream markdown as log, block by block.  We read
+//! such logs block by block, and render each block as soon as it is complete,
+//! with the time we saw it.
+
+use std::io::{BufRead, Result};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The width of timestamps, including the space after them.
+pub const TIMESTAMP_WIDTH: usize = 9;
+
+/// Read markdown blocks from a log.
+pub struct Blocks<R: BufRead> {
+    /// The log to read from.
+    reader: R,
+}
+
+/// Whether `line` starts or ends a fenced code block.
+fn is_fence(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("```") || line.starts_with("~~~")
+}
+
+impl<R: BufRead> Blocks<R> {
+    /// Read blocks from `reader`.
+    pub fn new(reader: R) -> Blocks<R> {
+        Blocks { reader }
+    }
+
+    /// Read the next block, with the time of its first line.
+    ///
+    /// A block ends at the next blank line outside of fenced code blocks, or
+    /// at the end of the log.  Return `None` at the end of the log.
+    pub fn next_block(&mut self) -> Result<Option<(SystemTime, String)>> {
+        let mut block = String::new();
+        let mut time = None;
+        let mut in_fence = false;
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.reader.read_line(&mut line)? == 0 {
+                break;
+            }
+            if line.trim().is_empty() && !in_fence {
+                if block.is_empty() {
+                    continue;
+                }
+                break;
+            }
+            if is_fence(&line) {
+                in_fence = !in_fence;
+            }
+            time.get_or_insert_with(SystemTime::now);
+            block.push_str(&line);
+        }
+        Ok(time.map(|time| (time, block)))
+    }
+}
+
+/// Format the UTC time of day of `time`, eg, `12:34:56`.
+pub fn timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let seconds_of_day = seconds % 86400;
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::time::Duration;
+
+    #[test]
+    fn read_blocks_at_blank_lines() {
+        let log = "# Deploy\nStarted\n\n\n```\nfoo\n\nbar\n```\n\n* Done";
+        let mut blocks = Blocks::new(log.as_bytes());
+        let mut contents = Vec::new();
+        while let Some((_, block)) = blocks.next_block().unwrap() {
+            contents.push(block);
+        }
+        assert_eq!(
+            contents,
+            vec!["# Deploy\nStarted\n", "```\nfoo\n\nbar\n```\n", "* Done"]
+        );
+    }
+
+    #[test]
+    fn timestamps_in_utc() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        assert_eq!(timestamp(time), "12:26:40");
+        assert_eq!(timestamp(time).len() + 1, TIMESTAMP_WIDTH);
+    }
+}
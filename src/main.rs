@@ -16,68 +16,1162 @@
 
 //! Show CommonMark documents on TTYs.
 
-use mdcat;
-
 use clap::value_t;
-use pulldown_cmark::Parser;
-use std::error::Error;
+use failure::Error;
+use pulldown_cmark::{Event, Options, Parser};
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::prelude::*;
-use std::io::{stdin, stdout};
-use std::path::PathBuf;
+use std::io::{stdin, stdout, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::ThemeSet;
 use syntect::parsing::SyntaxSet;
+use unicode_width::UnicodeWidthStr;
+
+use mdcat::{
+    Environment, ImageCapability, ImageFallback, LinkCapability, MarkCapability, Message, Messages,
+    PixelSize, ResourceAccess, Settings, SettingsBuilder, Spacing, StyleCapability,
+    TerminalCapabilities, TerminalSize, Theme, Typography,
+};
+
+mod args;
+mod bat;
+mod bench;
+#[cfg(feature = "bug_report")]
+mod bug_report;
+#[cfg(feature = "crates_io")]
+mod crates_io;
+mod encoding;
+mod extract;
+mod files;
+#[cfg(feature = "external_programs")]
+mod filter;
+mod frontmatter;
+mod log;
+mod man;
+mod mdx;
+mod pager;
+mod render_cache;
+mod schema;
+mod signature;
+mod split;
+mod timings;
+mod truncate;
+mod variables;
+mod watch;
+
+use crate::render_cache::{Key, RenderCache};
+use crate::timings::Counted;
+use crate::truncate::{LineLimit, Truncated};
+
+/// The maximum number of columns for accessible output.
+///
+/// Braille displays show only a few dozen characters at a time, so keep lines
+/// short in accessible output.
+const ACCESSIBLE_COLUMNS: usize = 40;
+
+/// The exit code if we truncated output with `--max-output-lines`.
+const EXIT_TRUNCATED: i32 = 2;
+
+/// The file name of bug reports.
+#[cfg(feature = "bug_report")]
+const BUG_REPORT_FILE: &str = "mdcat-bug-report.tar";
+
+/// Compiled-in cargo features and whether they are enabled.
+const FEATURES: &[(&str, bool)] = &[
+    ("vte50", cfg!(feature = "vte50")),
+    ("iterm2", cfg!(feature = "iterm2")),
+    ("terminology", cfg!(feature = "terminology")),
+    ("sixel", cfg!(feature = "sixel")),
+    ("osc8_links", cfg!(feature = "osc8_links")),
+    ("resources", cfg!(feature = "resources")),
+    ("remote_resources", cfg!(feature = "remote_resources")),
+    ("crates_io", cfg!(feature = "crates_io")),
+    ("bug_report", cfg!(feature = "bug_report")),
+    ("external_programs", cfg!(feature = "external_programs")),
+    ("minimal_static", cfg!(feature = "minimal_static")),
+];
+
+/// Print the version of mdcat.
+///
+/// If `verbose` also print what this binary supports, for bug reports.
+fn print_version(verbose: bool) {
+    println!("mdcat {}", env!("CARGO_PKG_VERSION"));
+    if verbose {
+        let features = FEATURES
+            .iter()
+            .map(|&(name, enabled)| format!("{}{}", if enabled { '+' } else { '-' }, name))
+            .collect::<Vec<_>>();
+        println!("Features: {}", features.join(" "));
+        let mut images = Vec::new();
+        if cfg!(feature = "iterm2") {
+            // We convert SVG to PNG for iTerm2
+            images.push("iTerm2 (all formats, SVG via rsvg-convert)");
+        }
+        if cfg!(feature = "terminology") {
+            images.push("Terminology (all formats)");
+        }
+        if cfg!(feature = "sixel") {
+            images.push("Sixel (PNG, JPEG, GIF)");
+        }
+        if images.is_empty() {
+            images.push("none");
+        }
+        println!("Inline images: {}", images.join(", "));
+        let http = if cfg!(any(feature = "remote_resources", feature = "crates_io")) {
+            "reqwest"
+        } else {
+            "none"
+        };
+        println!("HTTP backend: {}", http);
+        println!(
+            "Syntaxes: {}",
+            SyntaxSet::load_defaults_newlines().syntaxes().len()
+        );
+        println!("Themes: {}", mdcat::BUILTIN_THEMES.join(", "));
+    }
+}
 
-use mdcat::{ResourceAccess, TerminalCapabilities, TerminalSize};
+/// Print all languages we can highlight in code blocks.
+///
+/// Print the name of each language and the tokens which select the language
+/// in the info string of a code block.
+fn print_languages() {
+    let syntax_set = SyntaxSet::load_defaults_newlines();
+    let mut syntaxes = syntax_set.syntaxes().iter().collect::<Vec<_>>();
+    syntaxes.sort_by_key(|syntax| syntax.name.to_lowercase());
+    for syntax in syntaxes {
+        println!("{}: {}", syntax.name, syntax.file_extensions.join(", "));
+    }
+}
 
 /// Read input for `filename`.
 ///
-/// If `filename` is `-` read from standard input, otherwise try to open and
-/// read the given file.
-fn read_input<T: AsRef<str>>(filename: T) -> std::io::Result<(PathBuf, String)> {
+/// If `filename` is `-` read from standard input.  If `filename` refers to a
+/// crate on crates.io, eg, `crate:serde`, read the README of the crate.
+/// Otherwise try to open and read the given file.
+///
+/// Transcode documents in UTF-16 or Latin-1 to UTF-8, with a warning.  Strip
+/// byte order marks and normalize line endings to LF.
+fn read_input<T: AsRef<str>>(filename: T) -> Result<(PathBuf, String), Error> {
     let cd = std::env::current_dir()?;
-    let mut buffer = String::new();
+    let mut buffer = Vec::new();
+
+    #[cfg(feature = "crates_io")]
+    {
+        if !Path::new(filename.as_ref()).exists() {
+            if let Some(reference) = crates_io::parse_crate_reference(filename.as_ref()) {
+                let readme = crates_io::fetch_readme(&reference)?;
+                return Ok((cd, encoding::normalize(readme)));
+            }
+        }
+    }
 
-    if filename.as_ref() == "-" {
-        stdin().read_to_string(&mut buffer)?;
-        Ok((cd, buffer))
+    let base_dir = if filename.as_ref() == "-" {
+        stdin().read_to_end(&mut buffer)?;
+        cd
     } else {
         let mut source = File::open(filename.as_ref())?;
-        source.read_to_string(&mut buffer)?;
-        let base_dir = cd
-            .join(filename.as_ref())
+        source.read_to_end(&mut buffer)?;
+        cd.join(filename.as_ref())
             .parent()
             .map(|p| p.to_path_buf())
-            .unwrap_or(cd);
-        Ok((base_dir, buffer))
+            .unwrap_or(cd)
+    };
+    let (text, encoding) = encoding::decode(buffer);
+    if let Some(encoding) = encoding {
+        eprintln!(
+            "Warning: {} is not UTF-8; transcoded from {} to UTF-8",
+            filename.as_ref(),
+            encoding
+        );
+    }
+    Ok((base_dir, encoding::normalize(text)))
+}
+
+/// Preprocess `input` according to `args`.
+///
+/// Filter `input` through the filter command of `args`, if any.  Strip the
+/// armor of signed documents and remember their signature in `args`.  Remove
+/// front matter and apply the options for mdcat in the front matter to
+/// `args`.  Strip JSX from MDX documents.  Collect link definitions at the
+/// end, and remember them in `args` if we list them.
+fn preprocess(args: &mut Arguments, input: String) -> Result<String, Error> {
+    #[cfg(feature = "external_programs")]
+    let input = match args.filter_command {
+        Some(ref command) => filter::filter(command, input)?,
+        None => input,
+    };
+    #[cfg(not(feature = "external_programs"))]
+    {
+        if args.filter_command.is_some() {
+            return Err(failure::format_err!(
+                "mdcat was built without support for filter commands"
+            ));
+        }
+    }
+    let (input, signature) = signature::strip_signature(input);
+    args.signature = signature;
+    let (front_matter, body) = frontmatter::split_front_matter(&input);
+    if let Some(front_matter) = front_matter {
+        if let Some(language) = frontmatter::language(front_matter) {
+            args.apply_document_option("lang", &language);
+        }
+        for (key, value) in frontmatter::mdcat_options(front_matter) {
+            args.apply_document_option(&key, &value);
+        }
+    }
+    let body = if args.mdx || mdx::is_mdx(&args.filename) {
+        mdx::strip_jsx(body)
+    } else {
+        body.to_string()
+    };
+    let (link_definitions, body) = mdcat::collect_link_definitions(&body);
+    if args.list_link_definitions {
+        args.link_definitions = link_definitions;
+    }
+    Ok(body)
+}
+
+/// Parse the markdown `input` into events according to `args`.
+///
+/// Convert HTML tables, number figures and tables, collect footnotes at the
+/// end, mark up abbreviations and insert a table of contents.  Add the time
+/// it takes to the timings of this run.
+fn parse<'a>(
+    input: &'a str,
+    abbreviations: &[(String, String)],
+    args: &Arguments,
+) -> Vec<Event<'a>> {
+    timings::time_parse(|| {
+        let parser = Parser::new_ext(input, Options::ENABLE_TABLES | Options::ENABLE_FOOTNOTES);
+        let events = mdcat::convert_html_tables(parser);
+        let events = if args.crossrefs {
+            mdcat::resolve_crossrefs(events)
+        } else {
+            events
+        };
+        let events = mdcat::collect_footnotes(events);
+        let events = mdcat::insert_toc(events, args.toc);
+        mdcat::insert_abbreviations(events, abbreviations)
+    })
+}
+
+/// Create rendering settings from `args`.
+///
+/// With `--use-bat-assets` load the syntaxes and themes of bat, unless we
+/// loaded syntaxes for an earlier render already, and highlight with the
+/// theme of bat.
+fn settings(size: TerminalSize, args: Arguments) -> Result<Settings, Error> {
+    let columns = if args.accessible {
+        args.columns.min(ACCESSIBLE_COLUMNS)
+    } else {
+        args.columns
+    };
+    // Keep the size of character cells if we limit the number of columns or
+    // lines.
+    let pixels = size.cell_pixels().map(|cell| PixelSize {
+        width: cell.width * columns as u32,
+        height: cell.height * args.lines as u32,
+    });
+    let mut theme = if args.compact {
+        Theme {
+            spacing: Spacing::compact(),
+            ..args.theme
+        }
+    } else {
+        args.theme
+    };
+    let mut builder = SettingsBuilder::new(args.terminal_capabilities);
+    let mut syntax_set = args.syntax_set;
+    if args.use_bat_assets {
+        if syntax_set.is_none() {
+            syntax_set = bat::syntaxes()?;
+        }
+        builder = builder.highlighting_themes(bat::themes()?);
+        if let Some(highlighting) = bat::theme() {
+            theme.highlighting = highlighting;
+        }
+    }
+    if let Some(syntax_set) = syntax_set {
+        builder = builder.syntax_set(syntax_set);
+    }
+    if args.smart {
+        let typography = args
+            .lang
+            .as_ref()
+            .map_or_else(Typography::default, |lang| Typography::for_language(lang));
+        builder = builder.smart_punctuation(typography);
+    }
+    builder
+        .terminal_size(TerminalSize {
+            width: columns,
+            height: args.lines,
+            pixels,
+        })
+        .resource_access(args.resource_access)
+        .output_is_tty(args.output_is_tty)
+        .accessible(args.accessible)
+        .tab_width(args.tab_width)
+        .show_invisibles(args.show_invisibles)
+        .explain_fallbacks(args.explain_fallbacks)
+        .image_fallback(args.image_fallback)
+        .align_numbers(args.align_numbers)
+        .detect_languages(args.detect_language)
+        .deterministic(args.deterministic)
+        .messages(Messages::from_env())
+        .theme(theme)
+        .build()
+        .map_err(Into::into)
+}
+
+/// Render markdown `events` according to `args`.
+///
+/// Show the output in a pager or pipe it to a command if the user asked for
+/// it.
+fn render<'a, I>(
+    size: TerminalSize,
+    args: Arguments,
+    environment: &Environment,
+    events: I,
+) -> Result<(), Error>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    match spawn_output(&args)? {
+        Some((mut child, command)) => {
+            let input = child.stdin.take().expect("Output command without input");
+            // Write all output and close the input before waiting for the
+            // command
+            let result = write_rendered(size, args, environment, events, Counted::new(input));
+            wait_for_output(child, command, result)
+        }
+        None => write_rendered(size, args, environment, events, Counted::new(stdout())),
     }
 }
 
-fn process_arguments(size: TerminalSize, args: Arguments) -> Result<(), Box<dyn Error>> {
+/// Start the pager or the pipe command of `args`, if any.
+///
+/// Return the child, and the pipe command if we started one, to check its
+/// exit status.
+fn spawn_output(args: &Arguments) -> Result<Option<(std::process::Child, Option<String>)>, Error> {
+    if args.paginate {
+        return pager::spawn().map(|child| Some((child, None)));
+    }
+    match args.pipe_to {
+        #[cfg(feature = "external_programs")]
+        Some(ref command) => {
+            filter::spawn(command).map(|child| Some((child, Some(command.clone()))))
+        }
+        #[cfg(not(feature = "external_programs"))]
+        Some(_) => Err(failure::format_err!(
+            "mdcat was built without support for pipe commands"
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Wait for the pager or the pipe `command` in `child` after writing output
+/// with `result`.
+///
+/// Ignore broken pipes if the user closes the pager early or the command
+/// does not read all output, but fail if the pipe command fails.
+fn wait_for_output(
+    mut child: std::process::Child,
+    command: Option<String>,
+    result: Result<(), Error>,
+) -> Result<(), Error> {
+    let status = match command {
+        #[cfg(feature = "external_programs")]
+        Some(command) => filter::wait(&command, child),
+        _ => child.wait().map(|_| ()).map_err(Into::into),
+    };
+    match result {
+        Err(ref error) if pager::is_broken_pipe(error) => status,
+        result => result.and(status),
+    }
+}
+
+/// Render markdown `events` according to `args` to `output`.
+fn write_rendered<'a, I, W>(
+    size: TerminalSize,
+    mut args: Arguments,
+    environment: &Environment,
+    events: I,
+    mut output: W,
+) -> Result<(), Error>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: Write,
+{
+    if args.dump_events {
+        mdcat::dump_events(&mut output, events)?;
+        Ok(())
+    } else if let Some(lines) = args.max_output_lines {
+        let signature = args.signature.take();
+        let link_definitions = std::mem::take(&mut args.link_definitions);
+        let settings = settings(size, args)?;
+        let mut output = LineLimit::new(output, lines, size.width);
+        mdcat::push_tty(&settings, &mut output, environment, events)?;
+        write_link_definitions(&settings, &mut output, &link_definitions)?;
+        if let Some(signature) = signature {
+            write_signature_notice(&settings, &mut output, &signature)?;
+        }
+        match output.dropped_lines() {
+            0 => Ok(()),
+            dropped => {
+                write_truncation_notice(&settings, output.get_mut(), dropped)?;
+                Err(Truncated { lines: dropped }.into())
+            }
+        }
+    } else {
+        let signature = args.signature.take();
+        let link_definitions = std::mem::take(&mut args.link_definitions);
+        let settings = settings(size, args)?;
+        mdcat::push_tty(&settings, &mut output, environment, events)?;
+        write_link_definitions(&settings, &mut output, &link_definitions)?;
+        if let Some(signature) = signature {
+            write_signature_notice(&settings, &mut output, &signature)?;
+        }
+        Ok(())
+    }
+}
+
+/// Write the markdown `source` with syntax highlighting instead of rendering
+/// it.
+fn write_source<W: Write>(settings: &Settings, writer: &mut W, source: &str) -> Result<(), Error> {
+    let syntax = settings.syntax_set().find_syntax_by_token("markdown");
+    match (&settings.terminal_capabilities().style, syntax) {
+        (StyleCapability::Ansi(ref ansi), Some(syntax)) if !settings.is_accessible() => {
+            let theme = &ThemeSet::load_defaults().themes["Solarized (dark)"];
+            let mut highlighter = HighlightLines::new(syntax, theme);
+            for line in source.split_inclusive('\n') {
+                let regions = highlighter.highlight(line, settings.syntax_set());
+                mdcat::highlighting::write_as_ansi(writer, ansi, &regions)?;
+            }
+        }
+        _ => writer.write_all(source.as_bytes())?,
+    }
+    Ok(())
+}
+
+/// Tell that we dropped `lines` lines of output.
+fn write_truncation_notice<W: Write>(
+    settings: &Settings,
+    writer: &mut W,
+    lines: usize,
+) -> std::io::Result<()> {
+    // Close links which continue into the dropped lines
+    #[cfg(feature = "osc8_links")]
+    {
+        if let mdcat::LinkCapability::OSC8(ref osc8) = settings.terminal_capabilities().links {
+            osc8.clear_link(writer)?;
+        }
+    }
+    let notice = settings.messages().format(Message::Truncated, &[&lines]);
+    write_notice(settings, writer, notice)
+}
+
+/// List the link `definitions` of the document, in the style of links.
+fn write_link_definitions<W: Write>(
+    settings: &Settings,
+    writer: &mut W,
+    definitions: &[mdcat::LinkDefinition],
+) -> std::io::Result<()> {
+    if definitions.is_empty() {
+        return Ok(());
+    }
+    writeln!(writer)?;
+    for definition in definitions {
+        let line = format!(
+            "[{}]: {} {}",
+            definition.label, definition.destination, definition.title
+        );
+        let line = line.trim_end().to_string();
+        match settings.terminal_capabilities().style {
+            StyleCapability::Ansi(ref ansi) | StyleCapability::Monochrome(ref ansi)
+                if !settings.is_accessible() =>
+            {
+                ansi.write_styled(writer, &settings.theme().link, line)?
+            }
+            _ => write!(writer, "{}", line)?,
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+/// Tell that we collapsed the `signature` at the end of the document.
+fn write_signature_notice<W: Write>(
+    settings: &Settings,
+    writer: &mut W,
+    signature: &signature::Signature,
+) -> std::io::Result<()> {
+    writeln!(writer)?;
+    let notice = settings.messages().format(
+        Message::SignatureCollapsed,
+        &[&signature.kind, &signature.lines],
+    );
+    write_notice(settings, writer, notice)
+}
+
+/// Write a dimmed `notice` about the output on a line of its own.
+fn write_notice<W: Write>(
+    settings: &Settings,
+    writer: &mut W,
+    notice: String,
+) -> std::io::Result<()> {
+    write_dimmed(settings, writer, notice)?;
+    writeln!(writer)
+}
+
+/// Write dimmed `text` which is not part of the document.
+fn write_dimmed<W: Write>(
+    settings: &Settings,
+    writer: &mut W,
+    text: String,
+) -> std::io::Result<()> {
+    match settings.terminal_capabilities().style {
+        StyleCapability::Ansi(ref ansi) | StyleCapability::Monochrome(ref ansi)
+            if !settings.is_accessible() =>
+        {
+            ansi.write_styled(writer, &ansi_term::Style::new().dimmed().italic(), text)
+        }
+        _ => write!(writer, "{}", text),
+    }
+}
+
+/// Write a header with the `name` of a file, before the contents of the file.
+///
+/// Put the name into a rule across the terminal, or announce the file in
+/// accessible output.
+fn write_file_header<W: Write>(
+    settings: &Settings,
+    writer: &mut W,
+    name: &str,
+) -> std::io::Result<()> {
+    let theme = settings.theme();
+    match settings.terminal_capabilities().style {
+        StyleCapability::Ansi(ref ansi) | StyleCapability::Monochrome(ref ansi)
+            if !settings.is_accessible() =>
+        {
+            let (rule, heading) = match settings.terminal_capabilities().style {
+                StyleCapability::Monochrome(_) => (
+                    ansi_term::Style {
+                        foreground: None,
+                        background: None,
+                        ..theme.rule
+                    },
+                    ansi_term::Style {
+                        foreground: None,
+                        background: None,
+                        ..theme.heading
+                    },
+                ),
+                _ => (theme.rule, theme.heading),
+            };
+            let width = settings.terminal_size().width;
+            let rest = width.saturating_sub(name.width() + 4);
+            ansi.write_styled(writer, &rule, "\u{2500}\u{2500} ")?;
+            ansi.write_styled(writer, &heading, name)?;
+            ansi.write_styled(writer, &rule, format!(" {}", "\u{2500}".repeat(rest)))?;
+        }
+        _ if settings.is_accessible() => write!(writer, "File: {}", name)?,
+        _ => write!(writer, "== {} ==", name)?,
+    }
+    writeln!(writer)?;
+    writeln!(writer)
+}
+
+/// Render each top-level section of markdown `events` to a file in `directory`.
+///
+/// Name each file after the slug of the heading of its section.
+fn write_split_output<'a, I>(
+    size: TerminalSize,
+    args: Arguments,
+    directory: &Path,
+    environment: &Environment,
+    events: I,
+) -> Result<(), Error>
+where
+    I: IntoIterator<Item = Event<'a>>,
+{
+    let settings = settings(size, args)?;
+    std::fs::create_dir_all(directory)?;
+    for (name, section) in split::sections(events) {
+        let path = directory.join(format!("{}.ansi", name));
+        let mut output = File::create(&path)?;
+        mdcat::push_tty(&settings, &mut output, environment, section.into_iter())?;
+    }
+    Ok(())
+}
+
+/// Write all code blocks in markdown `events` to numbered files in `directory`.
+///
+/// Only write code blocks in the language `args.code_lang`, if given.
+fn write_code_blocks<'a, I>(
+    size: TerminalSize,
+    args: Arguments,
+    directory: &Path,
+    events: I,
+) -> Result<(), Error>
+where
+    I: IntoIterator<Item = Event<'a>>,
+{
+    let code_lang = args.code_lang.clone();
+    let settings = settings(size, args)?;
+    std::fs::create_dir_all(directory)?;
+    let blocks = extract::code_blocks(events).into_iter().filter(|block| {
+        code_lang
+            .as_ref()
+            .is_none_or(|lang| lang.eq_ignore_ascii_case(&block.language))
+    });
+    for (index, block) in blocks.enumerate() {
+        let name = extract::file_name(index + 1, &block, settings.syntax_set());
+        std::fs::write(directory.join(name), &block.code)?;
+    }
+    Ok(())
+}
+
+/// Render the input of `args` and write a bug report.
+#[cfg(feature = "bug_report")]
+fn write_bug_report(size: TerminalSize, mut args: Arguments) -> Result<(), Error> {
+    let (base_dir, input) = read_input(&args.filename)?;
+    let input = preprocess(&mut args, input)?;
+    let (abbreviations, input) = mdcat::extract_abbreviations(&input);
+    let events = parse(&input, &abbreviations, &args);
+    let environment = Environment::for_local_directory(&base_dir);
+    let settings = settings(size, args)?;
+    let mut output = Vec::new();
+    mdcat::push_tty(&settings, &mut output, &environment, events.into_iter())?;
+    let path = Path::new(BUG_REPORT_FILE);
+    bug_report::write_report(path, &settings, &input, &output)?;
+    Counted::new(stdout()).write_all(&output)?;
+    eprintln!(
+        "Wrote bug report to {}; please check it for private information and attach it to your issue.",
+        path.display()
+    );
+    Ok(())
+}
+
+/// Benchmark rendering for the arguments in `matches`.
+///
+/// Write rendered output to standard error, to measure how fast the terminal
+/// takes output, and the report to standard output.
+fn self_bench(size: TerminalSize, matches: &clap::ArgMatches<'_>) -> Result<(), Error> {
+    let args = Arguments::from_matches(matches)?;
+    let report = bench::run(
+        || settings(size, Arguments::from_matches(matches)?),
+        |source| parse(source, &[], &args),
+        &mut std::io::stderr(),
+    )?;
+    println!("{}", report);
+    Ok(())
+}
+
+/// Render the input of `args` again whenever it changes.
+///
+/// Clear the screen before each render, take fresh arguments from `matches`
+/// to apply changes to the front matter, and load syntaxes only once.  Take
+/// output from the render cache if the user asked for it.
+fn watch_input(
+    size: TerminalSize,
+    args: Arguments,
+    matches: &clap::ArgMatches<'_>,
+) -> Result<(), Error> {
+    if args.filename == "-" {
+        return Err(failure::format_err!("Cannot watch standard input"));
+    }
+    let path = PathBuf::from(&args.filename);
+    let mut first_args = Some(args);
+    let mut syntax_set = None;
+    watch::watch(&path, || {
+        let mut args = match first_args.take() {
+            Some(args) => args,
+            None => Arguments::from_matches(matches)?,
+        };
+        let (base_dir, input) = read_input(&args.filename)?;
+        let mut output = stdout();
+        let cache = render_cache_key(size, &args, &base_dir, &input, &[]);
+        let rendered = match cache.as_ref().and_then(|(cache, key)| cache.get(*key)) {
+            Some(rendered) => rendered,
+            None => {
+                args.syntax_set = syntax_set.take();
+                let input = preprocess(&mut args, input)?;
+                let (abbreviations, input) = mdcat::extract_abbreviations(&input);
+                let environment = Environment::for_local_directory(&base_dir);
+                let events = parse(&input, &abbreviations, &args);
+                let settings = settings(size, args)?;
+                let mut rendered = Vec::new();
+                mdcat::push_tty(&settings, &mut rendered, &environment, events.into_iter())?;
+                if let Some((cache, key)) = cache {
+                    if let Err(error) = cache.put(key, &rendered) {
+                        eprintln!("Failed to cache output: {}", error);
+                    }
+                }
+                syntax_set = Some(settings.into_syntax_set());
+                rendered
+            }
+        };
+        // Clear the screen and move the cursor to the top left corner
+        write!(output, "\x1b[2J\x1b[H")?;
+        output.write_all(&rendered)?;
+        output.flush()?;
+        Ok(())
+    })
+}
+
+/// Render the input of `args` as log, block by block as it comes in.
+///
+/// Prefix each block with a dimmed timestamp, and flush it right away.  Take
+/// fresh arguments from `matches` for each block, and load syntaxes only
+/// once.
+fn render_log(
+    size: TerminalSize,
+    args: Arguments,
+    matches: &clap::ArgMatches<'_>,
+) -> Result<(), Error> {
+    let reader: Box<dyn BufRead> = if args.filename == "-" {
+        Box::new(BufReader::new(stdin()))
+    } else {
+        Box::new(BufReader::new(File::open(&args.filename)?))
+    };
+    let environment = Environment::for_local_directory(std::env::current_dir()?);
+    let mut blocks = log::Blocks::new(reader);
+    let mut first_args = Some(args);
+    let mut syntax_set = None;
+    let mut output = Counted::new(stdout());
+    while let Some((time, block)) = blocks.next_block()? {
+        let mut args = match first_args.take() {
+            Some(args) => args,
+            None => Arguments::from_matches(matches)?,
+        };
+        args.syntax_set = syntax_set.take();
+        let (abbreviations, block) = mdcat::extract_abbreviations(&block);
+        let events = parse(&block, &abbreviations, &args);
+        let settings = settings(size, args)?;
+        let mut rendered = Vec::new();
+        mdcat::push_tty(&settings, &mut rendered, &environment, events.into_iter())?;
+        let rendered = String::from_utf8_lossy(&rendered);
+        let indent = " ".repeat(log::TIMESTAMP_WIDTH);
+        for (index, line) in rendered.trim_matches('\n').lines().enumerate() {
+            if index == 0 {
+                write_dimmed(&settings, &mut output, log::timestamp(time))?;
+                write!(output, " ")?;
+            } else if !line.is_empty() {
+                write!(output, "{}", indent)?;
+            }
+            writeln!(output, "{}", line)?;
+        }
+        output.flush()?;
+        syntax_set = Some(settings.into_syntax_set());
+    }
+    Ok(())
+}
+
+/// Render all files of `args`, each with a header.
+///
+/// Take fresh arguments from `matches` for each file, to apply the front
+/// matter of each file to its own document only.  Show the output in a pager
+/// or pipe it to a command if the user asked for it.
+fn render_files(
+    size: TerminalSize,
+    args: Arguments,
+    matches: &clap::ArgMatches<'_>,
+) -> Result<(), Error> {
+    let single_file_modes = [
+        ("--watch", args.watch),
+        ("--split-output", args.split_output.is_some()),
+        ("--extract-code", args.extract_code.is_some()),
+        ("--list-commands", args.list_commands),
+        ("--verify", args.verify),
+        ("--bug-report", args.bug_report),
+        ("--no-render", args.no_render),
+    ];
+    if let Some((option, _)) = single_file_modes.iter().find(|(_, enabled)| *enabled) {
+        return Err(failure::format_err!("{} needs a single file", option));
+    }
+    let files = files::input_files(&args.filenames, args.recursive)?;
+    match spawn_output(&args)? {
+        Some((mut child, command)) => {
+            let input = child.stdin.take().expect("Output command without input");
+            let result = write_files(size, args, matches, &files, Counted::new(input));
+            wait_for_output(child, command, result)
+        }
+        None => write_files(size, args, matches, &files, Counted::new(stdout())),
+    }
+}
+
+/// The render cache and the key for rendering `input` with `args`, if the
+/// user asked for the cache.
+///
+/// `context` tells apart different output for the same input, eg, with and
+/// without a file header.  Include the canonical `base_dir`, which local
+/// links resolve against.
+///
+/// Never cache output of filter commands, which may change even if the input
+/// stays the same, output of documents with images, which may change while
+/// the document stays the same, and output with OSC 8 links, whose IDs
+/// include the process ID unless output is deterministic.
+fn render_cache_key(
+    size: TerminalSize,
+    args: &Arguments,
+    base_dir: &Path,
+    input: &str,
+    context: &[&str],
+) -> Option<(RenderCache, Key)> {
+    if !args.render_cache || args.filter_command.is_some() || render_cache::has_images(input) {
+        return None;
+    }
+    #[cfg(feature = "osc8_links")]
+    {
+        if let LinkCapability::OSC8(_) = args.terminal_capabilities.links {
+            if !args.deterministic {
+                return None;
+            }
+        }
+    }
+    let base_dir = std::fs::canonicalize(base_dir).unwrap_or_else(|_| base_dir.to_path_buf());
+    let settings = context
+        .iter()
+        .map(ToString::to_string)
+        .chain(vec![
+            base_dir.to_string_lossy().into_owned(),
+            render_cache::describe_capabilities(&args.terminal_capabilities),
+            args.columns.to_string(),
+            args.lines.to_string(),
+            format!("{:?}", size.cell_pixels()),
+            format!("{:?}", args.theme),
+        ])
+        .collect::<Vec<_>>();
+    RenderCache::for_user().map(|cache| (cache, Key::new(input, &settings)))
+}
+
+/// Render the file `filename` at `index` of all files to `output`, with a
+/// header.
+///
+/// Render the file on its own, with its own links and marks, and resolve
+/// references against the directory of the file.  Render with and keep the
+/// given syntax set.
+///
+/// Return the settings we rendered the file with, or `None` if we took the
+/// output from the render cache.
+fn write_file<W: Write>(
+    size: TerminalSize,
+    mut args: Arguments,
+    index: usize,
+    filename: &str,
+    syntax_set: &mut Option<SyntaxSet>,
+    output: &mut W,
+) -> Result<Option<Settings>, Error> {
+    args.filename = filename.to_string();
+    let (base_dir, input) = read_input(filename)?;
+    let cache = render_cache_key(
+        size,
+        &args,
+        &base_dir,
+        &input,
+        &[filename, &(0 < index).to_string()],
+    );
+    if let Some(rendered) = cache.as_ref().and_then(|(cache, key)| cache.get(*key)) {
+        output.write_all(&rendered)?;
+        return Ok(None);
+    }
+    args.syntax_set = syntax_set.take();
+    let input = preprocess(&mut args, input)?;
+    let (abbreviations, input) = mdcat::extract_abbreviations(&input);
+    let environment = Environment::for_local_directory(&base_dir);
+    let events = parse(&input, &abbreviations, &args);
+    let settings = settings(size, args)?;
+    let mut rendered = Vec::new();
+    if 0 < index {
+        writeln!(rendered)?;
+    }
+    write_file_header(&settings, &mut rendered, filename)?;
+    mdcat::push_tty(&settings, &mut rendered, &environment, events.into_iter())?;
+    output.write_all(&rendered)?;
+    if let Some((cache, key)) = cache {
+        if let Err(error) = cache.put(key, &rendered) {
+            eprintln!("Failed to cache output of {}: {}", filename, error);
+        }
+    }
+    Ok(Some(settings))
+}
+
+/// Render `files` to `output`, each with a header.
+///
+/// Render files in parallel, unless we limit the number of output lines and
+/// may stop after the first few files.
+fn write_files<W: Write>(
+    size: TerminalSize,
+    args: Arguments,
+    matches: &clap::ArgMatches<'_>,
+    files: &[String],
+    output: W,
+) -> Result<(), Error> {
+    let workers = std::thread::available_parallelism()
+        .map_or(1, usize::from)
+        .min(files.len());
+    if args.max_output_lines.is_some() || workers < 2 {
+        write_files_in_order(size, args, matches, files, output)
+    } else {
+        write_files_in_parallel(size, matches, files, workers, output)
+    }
+}
+
+/// Render `files` to `output` one after another.
+///
+/// Stop if we exceed the maximum number of output lines.
+fn write_files_in_order<W: Write>(
+    size: TerminalSize,
+    args: Arguments,
+    matches: &clap::ArgMatches<'_>,
+    files: &[String],
+    output: W,
+) -> Result<(), Error> {
+    let mut output = LineLimit::new(
+        output,
+        args.max_output_lines.unwrap_or(usize::MAX),
+        size.width,
+    );
+    let mut first_args = Some(args);
+    let mut syntax_set = None;
+    for (index, filename) in files.iter().enumerate() {
+        let args = match first_args.take() {
+            Some(args) => args,
+            None => Arguments::from_matches(matches)?,
+        };
+        let written = write_file(size, args, index, filename, &mut syntax_set, &mut output)?;
+        let dropped = output.dropped_lines();
+        if 0 < dropped {
+            let settings = match written {
+                Some(settings) => settings,
+                None => settings(size, Arguments::from_matches(matches)?)?,
+            };
+            write_truncation_notice(&settings, output.get_mut(), dropped)?;
+            return Err(Truncated { lines: dropped }.into());
+        }
+        if let Some(settings) = written {
+            syntax_set = Some(settings.into_syntax_set());
+        }
+    }
+    Ok(())
+}
+
+/// Render `files` with `workers` threads, and write them to `output` in order.
+///
+/// Each worker renders one file after another into a buffer, and keeps its
+/// syntax set for the next file.  Stop at the first file which fails, after
+/// writing all files before it.
+fn write_files_in_parallel<W: Write>(
+    size: TerminalSize,
+    matches: &clap::ArgMatches<'_>,
+    files: &[String],
+    workers: usize,
+    mut output: W,
+) -> Result<(), Error> {
+    let next_file = AtomicUsize::new(0);
+    let stopped = AtomicBool::new(false);
+    std::thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        for _ in 0..workers {
+            let sender = sender.clone();
+            let (next_file, stopped) = (&next_file, &stopped);
+            scope.spawn(move || {
+                let mut syntax_set = None;
+                loop {
+                    let index = next_file.fetch_add(1, Ordering::SeqCst);
+                    if files.len() <= index || stopped.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let mut buffer = Vec::new();
+                    let result = Arguments::from_matches(matches)
+                        .map_err(Error::from)
+                        .and_then(|args| {
+                            let filename = &files[index];
+                            write_file(size, args, index, filename, &mut syntax_set, &mut buffer)
+                        });
+                    let result = result.map(|settings| {
+                        if let Some(settings) = settings {
+                            syntax_set = Some(settings.into_syntax_set());
+                        }
+                        buffer
+                    });
+                    if sender.send((index, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+        let mut pending = BTreeMap::new();
+        let mut next_output = 0;
+        let result =
+            receiver
+                .iter()
+                .try_for_each(|(index, result): (usize, Result<Vec<u8>, Error>)| {
+                    pending.insert(index, result);
+                    while let Some(result) = pending.remove(&next_output) {
+                        output.write_all(&result?)?;
+                        next_output += 1;
+                    }
+                    Ok(())
+                });
+        stopped.store(true, Ordering::SeqCst);
+        result
+    })
+}
+
+fn process_arguments(size: TerminalSize, mut args: Arguments) -> Result<(), Error> {
     if args.detect_only {
         println!("Terminal: {}", args.terminal_capabilities.name);
         Ok(())
     } else {
+        #[cfg(feature = "bug_report")]
+        {
+            if args.bug_report {
+                return write_bug_report(size, args);
+            }
+        }
+        #[cfg(not(feature = "bug_report"))]
+        {
+            if args.bug_report {
+                return Err(failure::format_err!(
+                    "mdcat was built without support for bug reports"
+                ));
+            }
+        }
+        if cfg!(feature = "external_programs")
+            && args.man_fallback
+            && args.filename != "-"
+            && !Path::new(&args.filename).exists()
+        {
+            if let Some(source) = man::read_man_page(&args.filename)? {
+                let environment = Environment::for_local_directory(std::env::current_dir()?);
+                return render(
+                    size,
+                    args,
+                    &environment,
+                    man::to_events(&source).into_iter(),
+                );
+            }
+        }
         let (base_dir, input) = read_input(&args.filename)?;
-        let parser = Parser::new(&input);
+        if args.no_render {
+            let settings = settings(size, args)?;
+            write_source(&settings, &mut Counted::new(stdout()), &input)?;
+            return Ok(());
+        }
+        let input = preprocess(&mut args, input)?;
+        let (abbreviations, input) = mdcat::extract_abbreviations(&input);
+        let environment = Environment::for_local_directory(&base_dir);
+        let events = parse(&input, &abbreviations, &args);
+        if args.list_commands {
+            let blocks = extract::code_blocks(events);
+            for command in extract::commands(&blocks) {
+                println!("{}", command);
+            }
+            return Ok(());
+        }
+        if args.verify {
+            return mdcat::verify(&environment, events.into_iter());
+        }
+        if let Some(directory) = args.extract_code.take() {
+            return write_code_blocks(size, args, &directory, events);
+        }
+        match args.split_output.take() {
+            Some(directory) => write_split_output(size, args, &directory, &environment, events),
+            None => render(size, args, &environment, events.into_iter()),
+        }
+    }
+}
 
-        if args.dump_events {
-            mdcat::dump_events(&mut std::io::stdout(), parser)?;
-            Ok(())
-        } else {
-            let syntax_set = SyntaxSet::load_defaults_newlines();
-            mdcat::push_tty(
-                &mut stdout(),
-                args.terminal_capabilities,
-                TerminalSize {
-                    width: args.columns,
-                    ..size
-                },
-                parser,
-                &base_dir,
-                args.resource_access,
-                syntax_set,
-            )?;
-            Ok(())
+/// Get the size of a preview pane from the environment variable `name`.
+fn preview_size(name: &str) -> Option<usize> {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+}
+
+/// Describe the other names of built-in themes, for help.
+fn theme_aliases() -> String {
+    mdcat::BUILTIN_THEME_ALIASES
+        .iter()
+        .map(|(alias, theme)| format!("{} for {}", alias, theme))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Get the theme file of the user, if it exists.
+///
+/// Look for `mdcat/theme.toml` in `$XDG_CONFIG_HOME`, or in `~/.config`.
+fn user_theme_file() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_home.join("mdcat").join("theme.toml")).filter(|path| path.is_file())
+}
+
+/// Read a theme from the TOML file at `path`.
+fn read_theme_file(path: &Path) -> clap::Result<Theme> {
+    std::fs::read_to_string(path)
+        .map_err(Error::from)
+        .and_then(|source| Theme::from_toml(&source))
+        .map_err(|error| {
+            clap::Error::value_validation_auto(format!(
+                "No such theme or invalid theme file {}: {}",
+                path.display(),
+                error
+            ))
+        })
+}
+
+/// What to keep of the capabilities of the terminal if standard output is
+/// no TTY.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum WhenPiped {
+    /// Write plain text.
+    Plain,
+    /// Keep colours and other styles.
+    Colours,
+    /// Keep styles and inline links.
+    Links,
+    /// Keep everything, including images and marks, eg, for a terminal
+    /// emulator further down the pipe.
+    Images,
+}
+
+impl WhenPiped {
+    /// Get the policy of the given `name`, if any.
+    fn from_name(name: &str) -> Option<WhenPiped> {
+        match name {
+            "plain" => Some(WhenPiped::Plain),
+            "colours" | "colors" => Some(WhenPiped::Colours),
+            "links" => Some(WhenPiped::Links),
+            "images" => Some(WhenPiped::Images),
+            _ => None,
+        }
+    }
+
+    /// Drop what this policy does not keep from `capabilities`.
+    fn degrade(self, capabilities: TerminalCapabilities) -> TerminalCapabilities {
+        match self {
+            WhenPiped::Plain => TerminalCapabilities::none(),
+            WhenPiped::Colours => TerminalCapabilities {
+                links: LinkCapability::None,
+                image: ImageCapability::None,
+                marks: MarkCapability::None,
+                ..capabilities
+            },
+            WhenPiped::Links => TerminalCapabilities {
+                image: ImageCapability::None,
+                marks: MarkCapability::None,
+                ..capabilities
+            },
+            WhenPiped::Images => capabilities,
         }
     }
 }
@@ -85,11 +1179,82 @@ fn process_arguments(size: TerminalSize, args: Arguments) -> Result<(), Box<dyn
 /// Represent command line arguments.
 struct Arguments {
     filename: String,
+    /// All files to render.
+    filenames: Vec<String>,
+    /// Whether to render all markdown files in directories.
+    recursive: bool,
     terminal_capabilities: TerminalCapabilities,
+    /// Whether output goes to a TTY, or the user keeps images in pipes.
+    output_is_tty: bool,
     resource_access: ResourceAccess,
     columns: usize,
+    /// Whether the user gave columns explicitly.
+    explicit_columns: bool,
+    lines: usize,
     dump_events: bool,
     detect_only: bool,
+    man_fallback: bool,
+    mdx: bool,
+    /// A shell command to filter input through before parsing.
+    filter_command: Option<String>,
+    toc: bool,
+    crossrefs: bool,
+    /// Whether to use typographic punctuation.
+    smart: bool,
+    /// Whether to detect the language of each paragraph for smart punctuation.
+    detect_language: bool,
+    /// The language of the document, for smart punctuation.
+    lang: Option<String>,
+    /// Whether to show output in a pager.
+    paginate: bool,
+    /// A shell command to pipe output to.
+    pipe_to: Option<String>,
+    /// The maximum number of lines to write, if any.
+    max_output_lines: Option<usize>,
+    /// The directory to write sections to, if any.
+    split_output: Option<PathBuf>,
+    /// The directory to write code blocks to, if any.
+    extract_code: Option<PathBuf>,
+    /// The language of code blocks to write, if not all.
+    code_lang: Option<String>,
+    /// Whether to print the commands of shell sessions instead of rendering.
+    list_commands: bool,
+    /// Whether to check that rendering keeps all text instead of rendering.
+    verify: bool,
+    accessible: bool,
+    compact: bool,
+    tab_width: usize,
+    show_invisibles: bool,
+    /// Whether to mark and list content we could not render faithfully.
+    explain_fallbacks: bool,
+    /// What to do with images which the terminal cannot show inline.
+    image_fallback: ImageFallback,
+    /// Whether to show the markdown source instead of rendering it.
+    no_render: bool,
+    theme: Theme,
+    /// Whether the user chose a theme explicitly.
+    explicit_theme: bool,
+    bug_report: bool,
+    /// Whether to render again whenever the input changes.
+    watch: bool,
+    /// Whether to render the input as log, block by block.
+    log: bool,
+    /// Syntaxes loaded for an earlier render, if any.
+    syntax_set: Option<SyntaxSet>,
+    /// Whether to highlight with the syntaxes and themes of bat.
+    use_bat_assets: bool,
+    /// Whether to write the same output for the same input every time.
+    deterministic: bool,
+    /// Whether to reuse rendered output of unchanged documents.
+    render_cache: bool,
+    /// Whether to right-align columns of numbers in tables.
+    align_numbers: bool,
+    /// Whether to list link definitions at the end of the document.
+    list_link_definitions: bool,
+    /// The signature we collapsed at the end of the document, if any.
+    signature: Option<signature::Signature>,
+    /// The link definitions of the document, if we list them.
+    link_definitions: Vec<mdcat::LinkDefinition>,
 }
 
 impl Arguments {
@@ -103,6 +1268,48 @@ impl Arguments {
         } else {
             TerminalCapabilities::detect()
         };
+        let stdout_is_tty = pager::stdout_is_tty();
+        let when_piped = WhenPiped::from_name(&value_t!(matches, "when_piped", String)?)
+            .expect("Invalid value for --when-piped");
+        let terminal_capabilities = if stdout_is_tty {
+            terminal_capabilities
+        } else {
+            when_piped.degrade(terminal_capabilities)
+        };
+        // With --when-piped images the user vouches for the end of the pipe
+        let output_is_tty = stdout_is_tty || when_piped == WhenPiped::Images;
+        let monochrome = matches.is_present("monochrome");
+        let terminal_capabilities = if monochrome {
+            terminal_capabilities.monochrome()
+        } else {
+            terminal_capabilities
+        };
+        // Keep only what the final destination of the pipe command supports
+        let terminal_capabilities = match matches.value_of("pipe_keep") {
+            Some(policy) => WhenPiped::from_name(policy)
+                .expect("Invalid value for --pipe-keep")
+                .degrade(terminal_capabilities),
+            None => terminal_capabilities,
+        };
+        let pipe_to = matches.value_of("pipe_to").map(ToString::to_string);
+        let preview = matches.is_present("preview");
+        // Only page output for a terminal, and only if we may run programs
+        let paginate =
+            cfg!(feature = "external_programs") && matches.is_present("paginate") && stdout_is_tty;
+        let log = matches.is_present("log");
+        let terminal_capabilities = if preview || paginate || log {
+            // Pagers show neither images nor marks, and logs must not move the
+            // cursor.  Preview panes need images placed at an explicit
+            // position, eg, with kitty's graphics protocol, which we do not
+            // support yet.
+            TerminalCapabilities {
+                image: ImageCapability::None,
+                marks: MarkCapability::None,
+                ..terminal_capabilities
+            }
+        } else {
+            terminal_capabilities
+        };
 
         // On Windows 10 we need to enable ANSI term explicitly.
         #[cfg(windows)]
@@ -111,10 +1318,81 @@ impl Arguments {
         }
 
         let filename = value_t!(matches, "filename", String)?;
+        let filenames = matches
+            .values_of("filename")
+            .map_or_else(Vec::new, |values| values.map(ToString::to_string).collect());
+        let recursive = matches.is_present("recursive");
         let dump_events = matches.is_present("dump_events");
         let detect_only = matches.is_present("detect_only");
-        let columns = value_t!(matches, "columns", usize)?;
-        let resource_access = if matches.is_present("local_only") {
+        let man_fallback = matches.is_present("man_fallback");
+        let mdx = matches.is_present("mdx");
+        let filter_command = matches.value_of("filter_command").map(ToString::to_string);
+        let toc = matches.is_present("toc");
+        let crossrefs = matches.is_present("crossrefs");
+        let smart = matches.is_present("smart");
+        let detect_language = matches.is_present("detect_language");
+        let lang = matches.value_of("lang").map(ToString::to_string);
+        let lines = match preview_size("FZF_PREVIEW_LINES") {
+            Some(lines) if preview && matches.occurrences_of("lines") == 0 => lines,
+            _ => value_t!(matches, "lines", usize)?,
+        };
+        let max_output_lines = if matches.is_present("max_output_lines") {
+            Some(value_t!(matches, "max_output_lines", usize)?)
+        } else if preview {
+            // Never write more lines than the preview pane shows, including
+            // the truncation notice
+            Some(lines.saturating_sub(1).max(1))
+        } else {
+            None
+        };
+        let split_output = matches.value_of_os("split_output").map(PathBuf::from);
+        let extract_code = matches.value_of_os("extract_code").map(PathBuf::from);
+        let code_lang = matches.value_of("code_lang").map(ToString::to_string);
+        let list_commands = matches.is_present("list_commands");
+        let verify = matches.is_present("verify");
+        let accessible = matches.is_present("accessible");
+        let compact = matches.is_present("compact");
+        let tab_width = value_t!(matches, "tab_width", usize)?;
+        let show_invisibles = matches.is_present("show_invisibles");
+        let explain_fallbacks = matches.is_present("explain_fallbacks");
+        let image_fallback = match matches.value_of("image_viewer") {
+            Some(command) => ImageFallback::Viewer(command.to_string()),
+            None if matches.is_present("image_file_links") => ImageFallback::FileLink,
+            None => ImageFallback::None,
+        };
+        let no_render = matches.is_present("no_render");
+        let bug_report = matches.is_present("bug_report");
+        let watch = matches.is_present("watch");
+        let use_bat_assets = matches.is_present("use_bat_assets");
+        let deterministic = matches.is_present("deterministic");
+        let render_cache = matches.is_present("render_cache");
+        let align_numbers = matches.is_present("align_numbers");
+        let list_link_definitions = matches.is_present("list_link_definitions");
+        let explicit_columns = matches.occurrences_of("columns") > 0;
+        let columns = match preview_size("FZF_PREVIEW_COLUMNS") {
+            Some(columns) if preview && !explicit_columns => columns,
+            _ => value_t!(matches, "columns", usize)?,
+        };
+        // Leave room for timestamps in logs
+        let columns = if log {
+            columns.saturating_sub(log::TIMESTAMP_WIDTH)
+        } else {
+            columns
+        };
+        let explicit_theme = monochrome || matches.occurrences_of("theme") > 0;
+        let theme_name = if monochrome {
+            "monochrome".to_string()
+        } else {
+            value_t!(matches, "theme", String)?
+        };
+        let theme = match user_theme_file() {
+            Some(path) if !explicit_theme => read_theme_file(&path)?,
+            _ => Theme::builtin(&theme_name)
+                .map_or_else(|| read_theme_file(Path::new(&theme_name)), Ok)?,
+        };
+        let resource_access = if matches.is_present("allow_remote") {
+            ResourceAccess::RemoteAllowed
+        } else if preview || matches.is_present("local_only") {
             ResourceAccess::LocalOnly
         } else {
             ResourceAccess::RemoteAllowed
@@ -122,19 +1400,96 @@ impl Arguments {
 
         Ok(Arguments {
             filename,
+            filenames,
+            recursive,
             columns,
+            explicit_columns,
+            lines,
             resource_access,
             dump_events,
             detect_only,
+            man_fallback,
+            mdx,
+            filter_command,
+            toc,
+            crossrefs,
+            smart,
+            detect_language,
+            lang,
+            paginate,
+            pipe_to,
+            max_output_lines,
+            split_output,
+            extract_code,
+            code_lang,
+            list_commands,
+            verify,
+            accessible,
+            compact,
+            tab_width,
+            show_invisibles,
+            explain_fallbacks,
+            image_fallback,
+            no_render,
+            theme,
+            explicit_theme,
+            bug_report,
+            watch,
+            log,
+            syntax_set: None,
+            use_bat_assets,
+            deterministic,
+            render_cache,
+            align_numbers,
+            list_link_definitions,
+            signature: None,
+            link_definitions: Vec::new(),
             terminal_capabilities,
+            output_is_tty,
         })
     }
+
+    /// Apply an option for mdcat from the front matter of the document.
+    ///
+    /// Options given on the command line take precedence.  Ignore unknown
+    /// options.
+    fn apply_document_option(&mut self, key: &str, value: &str) {
+        match key {
+            "theme" if !self.explicit_theme => match Theme::builtin(value) {
+                Some(theme) => self.theme = theme,
+                None => eprintln!("Ignoring unknown theme {} in front matter", value),
+            },
+            "columns" | "width" if !self.explicit_columns => match value.parse::<usize>() {
+                // Never exceed the width of the terminal
+                Ok(columns) => self.columns = self.columns.min(columns),
+                Err(_) => eprintln!("Ignoring invalid columns {} in front matter", value),
+            },
+            "compact" => self.compact = self.compact || value == "true",
+            "toc" => self.toc = self.toc || value == "true",
+            "crossrefs" => self.crossrefs = self.crossrefs || value == "true",
+            "smart" => self.smart = self.smart || value == "true",
+            "detect-language" | "detect_language" => {
+                self.detect_language = self.detect_language || value == "true"
+            }
+            "lang" if self.lang.is_none() => self.lang = Some(value.to_string()),
+            "a11y" | "accessible" => self.accessible = self.accessible || value == "true",
+            _ => {}
+        }
+    }
 }
 
 fn main() {
     use clap::*;
+    let start = std::time::Instant::now();
     let size = TerminalSize::detect().unwrap_or_default();
     let columns = size.width.to_string();
+    let lines = size.height.to_string();
+    let theme_help = format!(
+        "The theme for markdown elements, either one of {} ({}) or a TOML theme file; \
+         defaults to ~/.config/mdcat/theme.toml if it exists",
+        mdcat::BUILTIN_THEMES.join(", "),
+        theme_aliases()
+    );
     let app = app_from_crate!()
         // Merge flags and options w/ arguments together, include args in usage
         // string and show options in the order of declaration.  And also:
@@ -143,6 +1498,8 @@ fn main() {
         .setting(AppSettings::DontCollapseArgsInUsage)
         .setting(AppSettings::DeriveDisplayOrder)
         .setting(AppSettings::ColoredHelp)
+        // We handle --version ourselves to support --verbose
+        .setting(AppSettings::DisableVersion)
         .after_help(
             "mdcat uses the standardized CommonMark dialect.  It formats
 markdown documents for viewing in text terminals:
@@ -152,60 +1509,84 @@ markdown documents for viewing in text terminals:
 • In some terminals: Inline images and inline links
 • In iTerm2: Jump marks for headings
 
+Use `mdcat crate:NAME` or `mdcat crate:NAME@VERSION` to read the README of a
+crate on crates.io.
+
 Copyright (C) 2018 Sebastian Wiesner
 Licensed under the Apache License, Version 2.0
 Report issues to <https://github.com/lunaryorn/mdcat>.",
         )
-        .arg(
-            Arg::with_name("filename")
-                .help("The file to read.  If - read from standard input instead")
-                .default_value("-"),
-        )
-        .arg(
-            Arg::with_name("no_colour")
-                .short("c")
-                .long("--no-colour")
-                .aliases(&["nocolour", "no-color", "nocolor"])
-                .help("Disable all colours and other styles."),
-        )
-        .arg(
-            Arg::with_name("columns")
-                .long("columns")
-                .help("Maximum number of columns to use for output")
-                .default_value(&columns),
-        )
-        .arg(
-            Arg::with_name("local_only")
-                .short("l")
-                .long("local")
-                .help("Do not load remote resources like images"),
-        )
-        .arg(
-            Arg::with_name("dump_events")
-                .long("dump-events")
-                .help("Dump Markdown parser events and exit")
-                .hidden(true),
-        )
-        .arg(
-            Arg::with_name("detect_only")
-                .long("detect-only")
-                .help("Only detect the terminal type and exit")
-                .hidden(true),
-        )
-        .arg(
-            Arg::with_name("ansi_only")
-                .long("ansi-only")
-                .help("Limit to standard ANSI formatting")
-                .conflicts_with("no_colour")
-                .hidden(true),
+        .args(
+            &args::ARGS
+                .iter()
+                .map(|arg| match arg.name {
+                    "columns" => arg.to_arg().default_value(&columns),
+                    "lines" => arg.to_arg().default_value(&lines),
+                    "theme" => arg.to_arg().help(&theme_help),
+                    _ => arg.to_arg(),
+                })
+                .collect::<Vec<_>>(),
         );
 
-    let matches = app.get_matches();
+    let matches = app.clone().get_matches();
+    // Parse again with arguments from $MDCAT_* variables before arguments
+    // from the command line
+    let env_arguments =
+        variables::arguments_from_env(args::ARGS, &matches, |name| std::env::var_os(name))
+            .unwrap_or_else(|error| error.exit());
+    let matches = if env_arguments.is_empty() {
+        matches
+    } else {
+        let mut arguments = std::env::args_os();
+        app.clone().get_matches_from(
+            arguments
+                .next()
+                .into_iter()
+                .chain(env_arguments)
+                .chain(arguments),
+        )
+    };
+    if matches.is_present("help_json") {
+        schema::print_schema(app.get_name(), args::ARGS).unwrap_or_else(|error| {
+            eprintln!("{}", Messages::from_env().format(Message::Error, &[&error]));
+            std::process::exit(1);
+        });
+        std::process::exit(0);
+    }
+    if matches.is_present("version") {
+        print_version(matches.is_present("verbose"));
+        std::process::exit(0);
+    }
+    if matches.is_present("list_languages") {
+        print_languages();
+        std::process::exit(0);
+    }
     let arguments = Arguments::from_matches(&matches).unwrap_or_else(|e| e.exit());
-    match process_arguments(size, arguments) {
+    let many_files = 1 < arguments.filenames.len()
+        || arguments.recursive
+        || Path::new(&arguments.filename).is_dir();
+    let result = if matches.is_present("self_bench") {
+        self_bench(size, &matches)
+    } else if many_files {
+        render_files(size, arguments, &matches)
+    } else if arguments.watch {
+        watch_input(size, arguments, &matches)
+    } else if arguments.log {
+        render_log(size, arguments, &matches)
+    } else {
+        process_arguments(size, arguments)
+    };
+    if matches.is_present("timings") {
+        eprintln!("{}", timings::summary(start.elapsed(), &mdcat::metrics()));
+    }
+    match result {
         Ok(_) => std::process::exit(0),
+        Err(ref error) if error.downcast_ref::<Truncated>().is_some() => {
+            std::process::exit(EXIT_TRUNCATED)
+        }
         Err(error) => {
-            eprintln!("Error: {}", error);
+            let messages = Messages::from_env();
+            eprintln!("{}", messages.format(Message::Error, &[&error]));
             std::process::exit(1);
         }
     }
@@ -0,0 +1,530 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Show man pages as markdown.
+//!
+//! Translate the common subset of the `man` roff macros to markdown events.
+//! We don't attempt to implement roff; we just want man pages to look decent.
+
+use failure::{format_err, Error};
+use pulldown_cmark::Event::*;
+use pulldown_cmark::Tag::*;
+use pulldown_cmark::{Event, Tag};
+use std::borrow::Cow;
+use std::process::Command;
+
+/// Run `program` with `args` and return its standard output.
+fn output_of(program: &str, args: &[&str]) -> Result<String, Error> {
+    let output = Command::new(program).args(args).output()?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(format_err!(
+            "{} failed with status {}: {}",
+            program,
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        ))
+    }
+}
+
+/// Read the roff source of the man page with the given `name`.
+///
+/// Ask `man -w` for the location of the man page, and decompress it if
+/// necessary.  Return `None` if there's no man page of the given name.
+///
+/// Reject names which start with `-`, which `man` would take for options.
+pub fn read_man_page(name: &str) -> Result<Option<String>, Error> {
+    if name.is_empty() || name.starts_with('-') {
+        return Ok(None);
+    }
+    let path = match output_of("man", &["-w", "--", name]) {
+        Ok(path) => path.trim().to_string(),
+        Err(_) => return Ok(None),
+    };
+    let decompressor = if path.ends_with(".gz") {
+        Some("gzip")
+    } else if path.ends_with(".bz2") {
+        Some("bzip2")
+    } else if path.ends_with(".xz") {
+        Some("xz")
+    } else {
+        None
+    };
+    let source = match decompressor {
+        Some(program) => output_of(program, &["-dc", &path])?,
+        None => std::fs::read_to_string(&path)?,
+    };
+    Ok(Some(source))
+}
+
+/// Split the arguments of a roff request, respecting double quotes.
+fn split_arguments(arguments: &str) -> Vec<&str> {
+    let mut result = Vec::new();
+    let mut rest = arguments.trim_start();
+    while !rest.is_empty() {
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            result.push(&quoted[..end]);
+            rest = quoted[(end + 1).min(quoted.len())..].trim_start();
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            result.push(&rest[..end]);
+            rest = rest[end..].trim_start();
+        }
+    }
+    result
+}
+
+/// The font of a piece of roff text.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Font {
+    Regular,
+    Bold,
+    Italic,
+}
+
+impl Font {
+    fn tag(self) -> Option<Tag<'static>> {
+        match self {
+            Font::Regular => None,
+            Font::Bold => Some(Strong),
+            Font::Italic => Some(Emphasis),
+        }
+    }
+}
+
+/// Translate the roff escape for a special character `name`.
+fn special_character(name: &str) -> &'static str {
+    match name {
+        "em" => "\u{2014}",
+        "en" => "\u{2013}",
+        "hy" | "mi" => "-",
+        "aq" => "'",
+        "dq" => "\"",
+        "lq" => "\u{201c}",
+        "rq" => "\u{201d}",
+        "oq" => "\u{2018}",
+        "cq" => "\u{2019}",
+        "bu" => "\u{2022}",
+        "co" => "\u{a9}",
+        "rg" => "\u{ae}",
+        "tm" => "\u{2122}",
+        "<-" => "\u{2190}",
+        "->" => "\u{2192}",
+        "ga" => "`",
+        "ti" => "~",
+        "ha" => "^",
+        "rs" => "\\",
+        _ => "",
+    }
+}
+
+/// Split a line of roff text into pieces of text with fonts.
+///
+/// Resolve escapes for special characters and font changes along the way.
+fn parse_text(line: &str, initial_font: Font) -> Vec<(Font, String)> {
+    let mut pieces = Vec::new();
+    let mut font = initial_font;
+    let mut previous_font = Font::Regular;
+    let mut text = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('f') => {
+                let new_font = match chars.next() {
+                    Some('B') => Font::Bold,
+                    Some('I') => Font::Italic,
+                    Some('P') => previous_font,
+                    Some('(') => {
+                        // Two-letter font names like \f(CW, which we treat as
+                        // regular text.
+                        chars.next();
+                        chars.next();
+                        Font::Regular
+                    }
+                    _ => Font::Regular,
+                };
+                if new_font != font {
+                    if !text.is_empty() {
+                        pieces.push((font, std::mem::take(&mut text)));
+                    }
+                    previous_font = font;
+                    font = new_font;
+                }
+            }
+            Some('(') => {
+                let name: String = chars.by_ref().take(2).collect();
+                text.push_str(special_character(&name));
+            }
+            Some('[') => {
+                let name: String = chars.by_ref().take_while(|&c| c != ']').collect();
+                text.push_str(special_character(&name));
+            }
+            Some('*') => {
+                // Skip string registers, which we can't expand.
+                match chars.next() {
+                    Some('(') => {
+                        chars.next();
+                        chars.next();
+                    }
+                    Some('[') => while chars.next().is_some_and(|c| c != ']') {},
+                    _ => {}
+                }
+            }
+            Some('-') => text.push('-'),
+            Some('e') | Some('\\') => text.push('\\'),
+            Some(' ') | Some('~') => text.push(' '),
+            Some('"') => {
+                // A comment till the end of the line
+                break;
+            }
+            Some('&') | Some('c') | Some('|') | Some('^') | Some(')') | Some(',') | Some('/')
+            | None => {}
+            Some(other) => text.push(other),
+        }
+    }
+    if !text.is_empty() {
+        pieces.push((font, text));
+    }
+    pieces
+}
+
+/// Whether the `tag` of a `.IP` request is just a bullet.
+fn is_bullet(tag: &str) -> bool {
+    tag.is_empty() || tag == "\\(bu" || tag == "\\[bu]" || tag == "*" || tag == "-"
+}
+
+/// Translate roff source to markdown events.
+struct Translator {
+    events: Vec<Event<'static>>,
+    in_paragraph: bool,
+    in_code_block: bool,
+    in_list: bool,
+    in_item: bool,
+    /// Whether the next text line is the tag of a `.TP` item.
+    expect_tag: bool,
+    /// Whether the next text line is the title of a section.
+    expect_heading: Option<i32>,
+}
+
+impl Translator {
+    fn new() -> Translator {
+        Translator {
+            events: Vec::new(),
+            in_paragraph: false,
+            in_code_block: false,
+            in_list: false,
+            in_item: false,
+            expect_tag: false,
+            expect_heading: None,
+        }
+    }
+
+    fn close_paragraph(&mut self) {
+        if self.in_paragraph {
+            self.events.push(End(Paragraph));
+            self.in_paragraph = false;
+        }
+    }
+
+    fn close_code_block(&mut self) {
+        if self.in_code_block {
+            self.events.push(End(CodeBlock(Cow::Borrowed(""))));
+            self.in_code_block = false;
+        }
+    }
+
+    fn close_item(&mut self) {
+        self.close_paragraph();
+        if self.in_item {
+            self.events.push(End(Item));
+            self.in_item = false;
+        }
+    }
+
+    fn close_list(&mut self) {
+        self.close_item();
+        if self.in_list {
+            self.events.push(End(List(None)));
+            self.in_list = false;
+        }
+    }
+
+    fn close_all(&mut self) {
+        self.close_code_block();
+        self.close_list();
+    }
+
+    fn start_item(&mut self) {
+        self.close_code_block();
+        self.close_item();
+        if !self.in_list {
+            self.events.push(Start(List(None)));
+            self.in_list = true;
+        }
+        self.events.push(Start(Item));
+        self.in_item = true;
+    }
+
+    fn open_paragraph(&mut self) {
+        if !self.in_paragraph {
+            self.events.push(Start(Paragraph));
+            self.in_paragraph = true;
+        } else if self.events.last() != Some(&HardBreak) {
+            self.events.push(SoftBreak);
+        }
+    }
+
+    fn push_text(&mut self, pieces: Vec<(Font, String)>) {
+        for (font, text) in pieces {
+            match font.tag() {
+                Some(tag) => {
+                    self.events.push(Start(tag.clone()));
+                    self.events.push(Text(Cow::Owned(text)));
+                    self.events.push(End(tag));
+                }
+                None => self.events.push(Text(Cow::Owned(text))),
+            }
+        }
+    }
+
+    fn heading(&mut self, level: i32, title: String) {
+        self.close_all();
+        self.events.push(Start(Header(level)));
+        self.events.push(Text(Cow::Owned(title)));
+        self.events.push(End(Header(level)));
+    }
+
+    /// Write `arguments` with alternating fonts, as in `.BR` and friends.
+    fn alternating_fonts(&mut self, arguments: &[&str], fonts: [Font; 2]) {
+        self.open_paragraph();
+        let pieces = arguments
+            .iter()
+            .enumerate()
+            .flat_map(|(i, argument)| parse_text(argument, fonts[i % 2]))
+            .collect();
+        self.push_text(pieces);
+    }
+
+    fn request(&mut self, name: &str, arguments: &str) {
+        let args = split_arguments(arguments);
+        match name {
+            "TH" => {
+                let title = match (args.first(), args.get(1)) {
+                    (Some(name), Some(section)) => format!("{}({})", name, section),
+                    (Some(name), None) => (*name).to_string(),
+                    _ => return,
+                };
+                self.heading(1, title);
+            }
+            "SH" | "SS" => {
+                let level = if name == "SH" { 2 } else { 3 };
+                if args.is_empty() {
+                    self.close_all();
+                    self.expect_heading = Some(level);
+                } else {
+                    self.heading(level, args.join(" "));
+                }
+            }
+            "PP" | "P" | "LP" => self.close_list(),
+            "sp" => self.close_paragraph(),
+            "br" if self.in_paragraph => self.events.push(HardBreak),
+            "TP" => {
+                self.start_item();
+                self.expect_tag = true;
+            }
+            "IP" => {
+                self.start_item();
+                let tag = args.first().cloned().unwrap_or("");
+                if !is_bullet(tag) {
+                    self.open_paragraph();
+                    self.push_text(parse_text(tag, Font::Regular));
+                    self.events.push(HardBreak);
+                }
+            }
+            "nf" | "EX" => {
+                self.close_paragraph();
+                if !self.in_code_block {
+                    self.events.push(Start(CodeBlock(Cow::Borrowed(""))));
+                    self.in_code_block = true;
+                }
+            }
+            "fi" | "EE" => self.close_code_block(),
+            "B" => self.alternating_fonts(&args, [Font::Bold, Font::Bold]),
+            "I" => self.alternating_fonts(&args, [Font::Italic, Font::Italic]),
+            "BR" => self.alternating_fonts(&args, [Font::Bold, Font::Regular]),
+            "RB" => self.alternating_fonts(&args, [Font::Regular, Font::Bold]),
+            "IR" => self.alternating_fonts(&args, [Font::Italic, Font::Regular]),
+            "RI" => self.alternating_fonts(&args, [Font::Regular, Font::Italic]),
+            "BI" => self.alternating_fonts(&args, [Font::Bold, Font::Italic]),
+            "IB" => self.alternating_fonts(&args, [Font::Italic, Font::Bold]),
+            // Ignore everything else, ie, comments, indentation and other
+            // layout requests.
+            _ => {}
+        }
+    }
+
+    fn text_line(&mut self, line: &str) {
+        if self.in_code_block {
+            let text: String = parse_text(line, Font::Regular)
+                .into_iter()
+                .map(|(_, text)| text)
+                .collect();
+            self.events.push(Text(Cow::Owned(text + "\n")));
+        } else if let Some(level) = self.expect_heading.take() {
+            let title: String = parse_text(line, Font::Regular)
+                .into_iter()
+                .map(|(_, text)| text)
+                .collect();
+            self.heading(level, title);
+        } else if line.trim().is_empty() {
+            self.close_paragraph();
+        } else {
+            self.open_paragraph();
+            self.push_text(parse_text(line, Font::Regular));
+            if self.expect_tag {
+                self.events.push(HardBreak);
+                self.expect_tag = false;
+            }
+        }
+    }
+
+    fn line(&mut self, line: &str) {
+        if line.starts_with('.') || line.starts_with('\'') {
+            let request = line[1..].trim_start();
+            let end = request.find(char::is_whitespace).unwrap_or(request.len());
+            self.request(&request[..end], &request[end..]);
+        } else {
+            self.text_line(line);
+        }
+    }
+
+    fn finish(mut self) -> Vec<Event<'static>> {
+        self.close_all();
+        self.events
+    }
+}
+
+/// Translate the roff `source` of a man page to markdown events.
+pub fn to_events(source: &str) -> Vec<Event<'static>> {
+    let mut translator = Translator::new();
+    for line in source.lines() {
+        translator.line(line);
+    }
+    translator.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn no_man_page_for_options() {
+        assert_eq!(read_man_page("--help").unwrap(), None);
+        assert_eq!(read_man_page("-w").unwrap(), None);
+    }
+
+    fn text(s: &str) -> Event<'static> {
+        Text(Cow::Owned(s.to_string()))
+    }
+
+    #[test]
+    fn split_arguments_with_quotes() {
+        assert_eq!(
+            split_arguments(r#" LS 1 "March 2018" "GNU coreutils""#),
+            vec!["LS", "1", "March 2018", "GNU coreutils"]
+        );
+    }
+
+    #[test]
+    fn parse_text_with_fonts_and_escapes() {
+        assert_eq!(
+            parse_text(
+                r"\fB\-a\fR, \fI\-\-all\fP \(em do not ignore",
+                Font::Regular
+            ),
+            vec![
+                (Font::Bold, "-a".to_string()),
+                (Font::Regular, ", ".to_string()),
+                (Font::Italic, "--all".to_string()),
+                (Font::Regular, " \u{2014} do not ignore".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn to_events_for_simple_page() {
+        let source = r#".\" A comment
+.TH LS 1
+.SH NAME
+ls \- list directory contents
+.SH DESCRIPTION
+List information about the FILEs.
+Sort entries alphabetically.
+.TP
+\fB\-a\fR
+do not ignore entries
+.PP
+Exit status:
+.nf
+0 if OK
+.fi
+"#;
+        assert_eq!(
+            to_events(source),
+            vec![
+                Start(Header(1)),
+                text("LS(1)"),
+                End(Header(1)),
+                Start(Header(2)),
+                text("NAME"),
+                End(Header(2)),
+                Start(Paragraph),
+                text("ls - list directory contents"),
+                End(Paragraph),
+                Start(Header(2)),
+                text("DESCRIPTION"),
+                End(Header(2)),
+                Start(Paragraph),
+                text("List information about the FILEs."),
+                SoftBreak,
+                text("Sort entries alphabetically."),
+                End(Paragraph),
+                Start(List(None)),
+                Start(Item),
+                Start(Paragraph),
+                Start(Strong),
+                text("-a"),
+                End(Strong),
+                HardBreak,
+                text("do not ignore entries"),
+                End(Paragraph),
+                End(Item),
+                End(List(None)),
+                Start(Paragraph),
+                text("Exit status:"),
+                End(Paragraph),
+                Start(CodeBlock(Cow::Borrowed(""))),
+                text("0 if OK\n"),
+                End(CodeBlock(Cow::Borrowed(""))),
+            ]
+        );
+    }
+}
@@ -0,0 +1,140 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tolerate MDX, ie, markdown with JSX.
+//!
+//! We do not parse JSX; instead we strip the JSX parts of MDX documents which
+//! would otherwise show up as broken paragraphs, and keep the markdown.
+
+/// Whether `filename` denotes an MDX document.
+pub fn is_mdx(filename: &str) -> bool {
+    filename.ends_with(".mdx")
+}
+
+/// Whether `line` starts a fenced code block, or ends the code block started
+/// by `fence`.
+fn fence_of(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    ["```", "~~~"]
+        .iter()
+        .find(|fence| line.starts_with(*fence))
+        .map(|fence| &line[..fence.len()])
+}
+
+/// Whether `line` starts an ES module statement.
+fn is_module_statement(line: &str) -> bool {
+    line.starts_with("import ") || line.starts_with("export ")
+}
+
+/// Get the name of the JSX component of the tag on `line`, if any.
+///
+/// JSX components start with an upper case letter, as opposed to HTML tags.
+/// Return the name and whether the tag is a closing tag.
+fn component_tag(line: &str) -> Option<(&str, bool)> {
+    let line = line.trim();
+    let (tag, closing) = match line.strip_prefix("</") {
+        Some(tag) => (tag, true),
+        None => (line.strip_prefix('<')?, false),
+    };
+    if !tag.starts_with(|c: char| c.is_ascii_uppercase()) || !line.ends_with('>') {
+        return None;
+    }
+    let end = tag
+        .find(|c: char| !(c.is_alphanumeric() || c == '.' || c == '_'))
+        .unwrap_or(tag.len());
+    Some((&tag[..end], closing))
+}
+
+/// Strip JSX from the MDX `source`.
+///
+/// Remove `import` and `export` statements, lines with opening or closing
+/// tags of JSX components, and JS expressions on lines of their own, eg,
+/// comments.  Replace self-closing component tags with a placeholder.  Keep
+/// code blocks as they are.
+pub fn strip_jsx(source: &str) -> String {
+    let mut output = String::with_capacity(source.len());
+    let mut fence: Option<&str> = None;
+    let mut in_statement = false;
+    for line in source.lines() {
+        if let Some(current) = fence {
+            if fence_of(line) == Some(current) {
+                fence = None;
+            }
+        } else if in_statement || is_module_statement(line) {
+            // Module statements continue until an empty line or a semicolon
+            in_statement = !(line.trim().is_empty() || line.trim_end().ends_with(';'));
+            continue;
+        } else if let Some(new_fence) = fence_of(line) {
+            fence = Some(new_fence);
+        } else if line.trim().starts_with('{') && line.trim().ends_with('}') {
+            continue;
+        } else if let Some((name, closing)) = component_tag(line) {
+            if !closing && line.trim().ends_with("/>") {
+                output.push_str(&format!("*[{}]*\n", name));
+            }
+            continue;
+        }
+        output.push_str(line);
+        output.push('\n');
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn strip_module_statements() {
+        let source = "import Tabs from '@theme/Tabs';
+import {
+  Foo,
+  Bar,
+} from './components';
+export const meta = {title: 'Foo'};
+
+# Foo
+";
+        assert_eq!(strip_jsx(source), "\n# Foo\n");
+    }
+
+    #[test]
+    fn strip_component_tags_and_keep_children() {
+        let source = "<Tabs>
+<TabItem value=\"a\">
+
+Lorem *ipsum*
+
+</TabItem>
+</Tabs>
+{/* A comment */}
+<Badge text=\"new\" />
+";
+        assert_eq!(strip_jsx(source), "\nLorem *ipsum*\n\n*[Badge]*\n");
+    }
+
+    #[test]
+    fn keep_html_and_code_blocks() {
+        let source = "<details>
+
+```jsx
+import Foo from 'foo';
+<Foo />
+```
+</details>
+";
+        assert_eq!(strip_jsx(source), source);
+    }
+}
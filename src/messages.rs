@@ -0,0 +1,299 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Translations of the messages we write, eg, in accessible output or for
+//! fallbacks.
+//!
+//! `{}` in a message stands for an argument, which we fill in in order.
+
+use std::fmt::Display;
+
+/// A message which we write to the user.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Message {
+    /// The start of a table in accessible output.
+    BeginTable,
+    /// The headings of a table in accessible output.
+    Columns,
+    /// A row of a table in accessible output.
+    Row,
+    /// The end of a table in accessible output.
+    EndTable,
+    /// A warning in accessible output.
+    Warning,
+    /// The heading of the list of fallbacks.
+    Fallbacks,
+    /// A finished task in accessible output.
+    Done,
+    /// An open task in accessible output.
+    ToDo,
+    /// A definition in accessible output.
+    Definition,
+    /// A horizontal rule in accessible output.
+    Separator,
+    /// A heading of the given level in accessible output.
+    HeadingLevel,
+    /// The start of a block quote in accessible output.
+    BeginQuote,
+    /// The end of a block quote in accessible output.
+    EndQuote,
+    /// The start of a code block in accessible output.
+    BeginCodeBlock,
+    /// The start of a code block in the given language in accessible output.
+    BeginCodeBlockIn,
+    /// The end of a code block in accessible output.
+    EndCodeBlock,
+    /// An image in accessible output.
+    Image,
+    /// A fallback for code in the given language, which we cannot highlight.
+    NoHighlighting,
+    /// A fallback for the given image, which the terminal cannot show.
+    ImageNotShown,
+    /// A fallback for an image which we failed to show, with the error and
+    /// the image.
+    ImageFailed,
+    /// A notice about the given number of lines we truncated.
+    Truncated,
+    /// A notice about a signature of the given kind and number of lines.
+    SignatureCollapsed,
+    /// An error.
+    Error,
+}
+
+/// English messages, in the order of `Message`.
+const ENGLISH: &[&str] = &[
+    "Begin table",
+    "Columns",
+    "Row",
+    "End table",
+    "Warning:",
+    "Fallbacks:",
+    "Done: ",
+    "To do: ",
+    "Definition: ",
+    "Separator",
+    "Heading level {}: ",
+    "Begin quote",
+    "End quote",
+    "Begin code block",
+    "Begin code block, {}",
+    "End code block",
+    "Image: ",
+    "No syntax highlighting for {}",
+    "Image not shown inline: {}",
+    "Image not shown, {}: {}",
+    "\u{2026} truncated, {} more lines",
+    "{} signature, {} lines collapsed",
+    "Error: {}",
+];
+
+/// German messages, in the order of `Message`.
+const GERMAN: &[&str] = &[
+    "Tabellenanfang",
+    "Spalten",
+    "Zeile",
+    "Tabellenende",
+    "Warnung:",
+    "Ersatzdarstellungen:",
+    "Erledigt: ",
+    "Offen: ",
+    "Definition: ",
+    "Trennlinie",
+    "Überschrift Ebene {}: ",
+    "Zitatanfang",
+    "Zitatende",
+    "Codeblockanfang",
+    "Codeblockanfang, {}",
+    "Codeblockende",
+    "Bild: ",
+    "Keine Syntaxhervorhebung für {}",
+    "Bild nicht eingebettet angezeigt: {}",
+    "Bild nicht angezeigt, {}: {}",
+    "\u{2026} gekürzt, {} weitere Zeilen",
+    "{}-Signatur, {} Zeilen eingeklappt",
+    "Fehler: {}",
+];
+
+/// French messages, in the order of `Message`.
+const FRENCH: &[&str] = &[
+    "Début du tableau",
+    "Colonnes",
+    "Ligne",
+    "Fin du tableau",
+    "Attention\u{A0}:",
+    "Substitutions\u{A0}:",
+    "Fait\u{A0}: ",
+    "À faire\u{A0}: ",
+    "Définition\u{A0}: ",
+    "Séparateur",
+    "Titre de niveau {}\u{A0}: ",
+    "Début de citation",
+    "Fin de citation",
+    "Début du bloc de code",
+    "Début du bloc de code, {}",
+    "Fin du bloc de code",
+    "Image\u{A0}: ",
+    "Pas de coloration syntaxique pour {}",
+    "Image non affichée dans le texte\u{A0}: {}",
+    "Image non affichée, {}\u{A0}: {}",
+    "\u{2026} tronqué, {} lignes de plus",
+    "Signature {}, {} lignes masquées",
+    "Erreur\u{A0}: {}",
+];
+
+/// Spanish messages, in the order of `Message`.
+const SPANISH: &[&str] = &[
+    "Inicio de tabla",
+    "Columnas",
+    "Fila",
+    "Fin de tabla",
+    "Advertencia:",
+    "Sustituciones:",
+    "Hecho: ",
+    "Pendiente: ",
+    "Definición: ",
+    "Separador",
+    "Encabezado de nivel {}: ",
+    "Inicio de cita",
+    "Fin de cita",
+    "Inicio de bloque de código",
+    "Inicio de bloque de código, {}",
+    "Fin de bloque de código",
+    "Imagen: ",
+    "Sin resaltado de sintaxis para {}",
+    "Imagen no mostrada en línea: {}",
+    "Imagen no mostrada, {}: {}",
+    "\u{2026} truncado, {} líneas más",
+    "Firma {}, {} líneas ocultas",
+    "Error: {}",
+];
+
+/// Languages with translations, by their ISO 639-1 codes.
+const TRANSLATIONS: &[(&str, &[&str])] = &[
+    ("en", ENGLISH),
+    ("de", GERMAN),
+    ("fr", FRENCH),
+    ("es", SPANISH),
+];
+
+/// Messages in a language.
+#[derive(Debug, Clone, Copy)]
+pub struct Messages {
+    /// The translations of all messages, in the order of `Message`.
+    translations: &'static [&'static str],
+}
+
+impl Default for Messages {
+    /// English messages.
+    fn default() -> Messages {
+        Messages {
+            translations: ENGLISH,
+        }
+    }
+}
+
+impl Messages {
+    /// Messages in the given `language`, eg, `de` or `de_DE.UTF-8`.
+    ///
+    /// Fall back to English for languages without translations.
+    pub fn for_language(language: &str) -> Messages {
+        let code = language
+            .split(['_', '-', '.', '@'])
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        TRANSLATIONS
+            .iter()
+            .find(|(name, _)| *name == code)
+            .map_or_else(Messages::default, |&(_, translations)| Messages {
+                translations,
+            })
+    }
+
+    /// Messages in the language of the user.
+    ///
+    /// Take the language from `$MDCAT_LANG`, or from the locale in
+    /// `$LC_ALL`, `$LC_MESSAGES` or `$LANG`.
+    pub fn from_env() -> Messages {
+        ["MDCAT_LANG", "LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .map_or_else(Messages::default, |language| {
+                Messages::for_language(&language)
+            })
+    }
+
+    /// Get the given `message`, with `{}` for its arguments.
+    pub fn get(self, message: Message) -> &'static str {
+        self.translations[message as usize]
+    }
+
+    /// Get the given `message` with `arguments` filled in, in order.
+    pub fn format(self, message: Message, arguments: &[&dyn Display]) -> String {
+        let mut parts = self.get(message).split("{}");
+        let mut text = parts.next().unwrap_or_default().to_string();
+        for (part, argument) in parts.zip(arguments.iter()) {
+            text.push_str(&argument.to_string());
+            text.push_str(part);
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn translate_all_messages() {
+        for (language, translations) in TRANSLATIONS {
+            assert_eq!(translations.len(), ENGLISH.len(), "{}", language);
+            for (translation, english) in translations.iter().zip(ENGLISH) {
+                assert_eq!(
+                    translation.matches("{}").count(),
+                    english.matches("{}").count(),
+                    "{}: {}",
+                    language,
+                    translation
+                );
+            }
+        }
+        assert_eq!(
+            Messages::default().get(Message::Error),
+            ENGLISH[ENGLISH.len() - 1]
+        );
+    }
+
+    #[test]
+    fn messages_for_locales() {
+        let messages = Messages::for_language("de_DE.UTF-8");
+        assert_eq!(messages.get(Message::BeginQuote), "Zitatanfang");
+        let messages = Messages::for_language("C");
+        assert_eq!(messages.get(Message::BeginQuote), "Begin quote");
+    }
+
+    #[test]
+    fn format_messages() {
+        assert_eq!(
+            Messages::default().format(Message::ImageFailed, &[&"timeout", &"logo.png"]),
+            "Image not shown, timeout: logo.png"
+        );
+        assert_eq!(
+            Messages::for_language("es").format(Message::Truncated, &[&3]),
+            "\u{2026} truncado, 3 líneas más"
+        );
+    }
+}
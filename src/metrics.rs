@@ -0,0 +1,64 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Metrics of rendering.
+//!
+//! We count how long we highlight code and how many images we read, over all
+//! renders of the process, for users to report performance problems.
+
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Nanoseconds we spent highlighting code.
+static HIGHLIGHTING: AtomicU64 = AtomicU64::new(0);
+
+/// The number of images we read.
+static IMAGES: AtomicU64 = AtomicU64::new(0);
+
+/// The size of all images we read, in bytes.
+static IMAGE_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// Metrics of all renders of this process so far.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Metrics {
+    /// The time we spent highlighting code.
+    pub highlighting: Duration,
+    /// The number of images we read, from files or from remote URLs.
+    pub images: u64,
+    /// The size of all images we read, in bytes.
+    pub image_bytes: u64,
+}
+
+/// Get the metrics of all renders of this process so far.
+pub fn metrics() -> Metrics {
+    Metrics {
+        highlighting: Duration::from_nanos(HIGHLIGHTING.load(Ordering::Relaxed)),
+        images: IMAGES.load(Ordering::Relaxed),
+        image_bytes: IMAGE_BYTES.load(Ordering::Relaxed),
+    }
+}
+
+/// Add `time` to the time we spent highlighting code.
+pub(crate) fn add_highlighting(time: Duration) {
+    let nanos = u64::try_from(time.as_nanos()).unwrap_or(u64::MAX);
+    HIGHLIGHTING.fetch_add(nanos, Ordering::Relaxed);
+}
+
+/// Count an image of `bytes` bytes which we read.
+#[cfg(feature = "resources")]
+pub(crate) fn add_image(bytes: usize) {
+    IMAGES.fetch_add(1, Ordering::Relaxed);
+    IMAGE_BYTES.fetch_add(bytes as u64, Ordering::Relaxed);
+}
@@ -0,0 +1,102 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Show output in a pager.
+
+use failure::{Error, ResultExt};
+use mdcat::shell_command;
+use std::io;
+use std::process::{Child, Stdio};
+
+/// The pager to use if the user configured none.
+const DEFAULT_PAGER: &str = "less -R";
+
+/// Get the pager command from the values of `$MDCAT_PAGER` and `$PAGER`.
+///
+/// Prefer `$MDCAT_PAGER` over `$PAGER`, and ignore empty values.  We run
+/// the command with the shell, like git does, so it may quote arguments.
+pub fn pager_command(mdcat_pager: Option<String>, pager: Option<String>) -> String {
+    mdcat_pager
+        .into_iter()
+        .chain(pager)
+        .find(|command| !command.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_PAGER.to_string())
+}
+
+/// Whether standard output is a terminal.
+#[cfg(unix)]
+pub fn stdout_is_tty() -> bool {
+    unsafe { libc::isatty(libc::STDOUT_FILENO) == 1 }
+}
+
+/// Whether standard output is a terminal.
+///
+/// We cannot tell on this system, so we assume it is.
+#[cfg(not(unix))]
+pub fn stdout_is_tty() -> bool {
+    true
+}
+
+/// Start the pager configured in the environment, reading from a pipe.
+///
+/// Tell `less` to pass colours through unless the user configured `$LESS`.
+pub fn spawn() -> Result<Child, Error> {
+    let command = pager_command(
+        std::env::var("MDCAT_PAGER").ok(),
+        std::env::var("PAGER").ok(),
+    );
+    let mut pager = shell_command(&command, &[]);
+    pager.stdin(Stdio::piped());
+    if std::env::var_os("LESS").is_none() {
+        pager.env("LESS", "R");
+    }
+    let child = pager
+        .spawn()
+        .with_context(|_| format!("Failed to start pager {}", command))?;
+    Ok(child)
+}
+
+/// Whether `error` says that the pager closed its input early.
+///
+/// This happens if the user quits the pager before reading all output.
+pub fn is_broken_pipe(error: &Error) -> bool {
+    error
+        .iter_chain()
+        .filter_map(|cause| cause.downcast_ref::<io::Error>())
+        .any(|error| error.kind() == io::ErrorKind::BrokenPipe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn pager_command_from_environment() {
+        assert_eq!(pager_command(None, None), "less -R");
+        assert_eq!(pager_command(None, Some("more".to_string())), "more");
+        assert_eq!(
+            pager_command(Some("bat -p".to_string()), Some("more".to_string())),
+            "bat -p"
+        );
+        assert_eq!(
+            pager_command(Some(" ".to_string()), Some("more".to_string())),
+            "more"
+        );
+        assert_eq!(
+            pager_command(Some("less -R --prompt='Page %d'".to_string()), None),
+            "less -R --prompt='Page %d'"
+        );
+    }
+}
@@ -0,0 +1,327 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A cache for rendered output, for `--render-cache`.
+//!
+//! Tools which render the same documents over and over again, eg, previews
+//! in editors or file managers, get rendered output from this cache while
+//! documents and settings stay the same.
+//!
+//! The key of rendered output covers the document, its directory, the command
+//! line, all environment variables of mdcat, and the terminal.  It does not
+//! cover images, so we never cache documents with images.
+//!
+//! We hash keys with SipHash and fixed keys, because the cache outlives the
+//! process and the hashers of std may change with any Rust release.  We keep
+//! at most `MAX_SIZE` bytes of output, and drop the least recently used
+//! output first.
+
+use mdcat::{
+    ImageCapability, LinkCapability, MarkCapability, StyleCapability, TerminalCapabilities,
+};
+use pulldown_cmark::{Event, Parser, Tag};
+use siphasher::sip::SipHasher;
+use std::ffi::OsString;
+use std::fs;
+use std::hash::Hasher;
+use std::io;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// Environment variables which change output, besides `MDCAT_*`.
+const VARIABLES: &[&str] = &["LANG", "LC_ALL", "LC_MESSAGES"];
+
+/// The maximum size of all output in the cache, in bytes.
+const MAX_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Add `bytes` to `hasher`, with their length to keep fields apart.
+fn write_field(hasher: &mut SipHasher, bytes: &[u8]) {
+    hasher.write(&(bytes.len() as u64).to_le_bytes());
+    hasher.write(bytes);
+}
+
+/// Add all `fields` to `hasher`, with their number.
+fn write_fields<I, S>(hasher: &mut SipHasher, fields: I)
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut count = 0_u64;
+    for field in fields {
+        write_field(hasher, field.as_ref().as_bytes());
+        count += 1;
+    }
+    hasher.write(&count.to_le_bytes());
+}
+
+/// Describe the effective `capabilities`, for the key of rendered output.
+///
+/// Take what the terminal supports, not its name, because we degrade the
+/// capabilities of a terminal, eg, in pipes or with `--monochrome`.
+pub fn describe_capabilities(capabilities: &TerminalCapabilities) -> String {
+    let style = match capabilities.style {
+        StyleCapability::None => "none",
+        StyleCapability::Ansi(_) => "ansi",
+        StyleCapability::Monochrome(_) => "monochrome",
+    };
+    let links = match capabilities.links {
+        LinkCapability::None => "none",
+        #[cfg(feature = "osc8_links")]
+        LinkCapability::OSC8(_) => "osc8",
+    };
+    let image = match capabilities.image {
+        ImageCapability::None => "none",
+        #[cfg(feature = "terminology")]
+        ImageCapability::Terminology(_) => "terminology",
+        #[cfg(feature = "iterm2")]
+        ImageCapability::ITerm2(_) => "iterm2",
+        #[cfg(feature = "sixel")]
+        ImageCapability::Sixel(_) => "sixel",
+    };
+    let marks = match capabilities.marks {
+        MarkCapability::None => "none",
+        #[cfg(feature = "iterm2")]
+        MarkCapability::ITerm2(_) => "iterm2",
+    };
+    format!(
+        "style={} links={} image={} marks={}",
+        style, links, image, marks
+    )
+}
+
+/// A key for rendered output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Key(u64);
+
+impl Key {
+    /// The key for rendering `input` with the `settings` of the terminal.
+    ///
+    /// `settings` includes everything about the terminal and the document
+    /// which we do not take from the command line or the environment.
+    pub fn new<S: AsRef<str>>(input: &str, settings: &[S]) -> Key {
+        // SipHasher::new has fixed keys
+        let mut hasher = SipHasher::new();
+        write_field(&mut hasher, env!("CARGO_PKG_VERSION").as_bytes());
+        write_fields(
+            &mut hasher,
+            std::env::args_os().map(|arg| arg.to_string_lossy().into_owned()),
+        );
+        write_fields(
+            &mut hasher,
+            variables(std::env::vars_os())
+                .into_iter()
+                .flat_map(|(name, value)| vec![name, value])
+                .map(|field| field.to_string_lossy().into_owned()),
+        );
+        write_fields(&mut hasher, settings);
+        write_field(&mut hasher, input.as_bytes());
+        Key(hasher.finish())
+    }
+}
+
+/// Whether the markdown `input` shows images.
+///
+/// Output of these documents depends on the images, which the key of the
+/// output does not cover.
+pub fn has_images(input: &str) -> bool {
+    Parser::new(input).any(|event| matches!(event, Event::Start(Tag::Image(..))))
+}
+
+/// All variables which change output, sorted by name.
+fn variables<I>(variables: I) -> Vec<(OsString, OsString)>
+where
+    I: Iterator<Item = (OsString, OsString)>,
+{
+    let mut variables = variables
+        .filter(|(name, _)| {
+            let name = name.to_string_lossy();
+            name.starts_with("MDCAT_") || VARIABLES.contains(&name.as_ref())
+        })
+        .collect::<Vec<_>>();
+    variables.sort();
+    variables
+}
+
+/// A cache for rendered output in a directory.
+pub struct RenderCache {
+    directory: PathBuf,
+}
+
+impl RenderCache {
+    /// A cache in `directory`.
+    pub fn new(directory: PathBuf) -> RenderCache {
+        RenderCache { directory }
+    }
+
+    /// The cache of the current user.
+    ///
+    /// Use `mdcat/rendered` in `$XDG_CACHE_HOME` or in `~/.cache`, if we know
+    /// the home directory.
+    pub fn for_user() -> Option<RenderCache> {
+        let cache_home = std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+        Some(RenderCache::new(cache_home.join("mdcat").join("rendered")))
+    }
+
+    /// The path of output for `key` in this cache.
+    fn path(&self, key: Key) -> PathBuf {
+        self.directory.join(format!("{:016x}.out", key.0))
+    }
+
+    /// Get the output for `key` from this cache.
+    ///
+    /// Mark the output as recently used, to keep it longest.
+    pub fn get(&self, key: Key) -> Option<Vec<u8>> {
+        let path = self.path(key);
+        let output = fs::read(&path).ok()?;
+        // If we cannot mark the output we just drop it earlier
+        let _ = fs::File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(SystemTime::now()));
+        Some(output)
+    }
+
+    /// Put the `output` for `key` into this cache.
+    ///
+    /// Drop the least recently used output if the cache grows beyond
+    /// `MAX_SIZE`.
+    pub fn put(&self, key: Key, output: &[u8]) -> io::Result<()> {
+        fs::create_dir_all(&self.directory)?;
+        let path = self.path(key);
+        // Write to a temporary file first, so that we never take partial
+        // output for cached, even if we render in parallel
+        let temporary = path.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&temporary, output)?;
+        fs::rename(temporary, path)?;
+        self.evict(MAX_SIZE)
+    }
+
+    /// Drop the least recently used output until all output takes at most
+    /// `max_size` bytes.
+    fn evict(&self, max_size: u64) -> io::Result<()> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&self.directory)? {
+            let entry = entry?;
+            let path = entry.path();
+            if path.extension().is_none_or(|extension| extension != "out") {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            entries.push((metadata.modified()?, metadata.len(), path));
+        }
+        let mut size = entries.iter().map(|(_, len, _)| len).sum::<u64>();
+        entries.sort();
+        for (_, len, path) in entries {
+            if size <= max_size {
+                break;
+            }
+            // Another process may have dropped the output already
+            match fs::remove_file(&path) {
+                Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+                _ => size -= len,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn keys_for_inputs_and_settings() {
+        let key = Key::new("# Lorem", &["ansi", "80"]);
+        assert_eq!(key, Key::new("# Lorem", &["ansi", "80"]));
+        assert!(key != Key::new("# Ipsum", &["ansi", "80"]));
+        assert!(key != Key::new("# Lorem", &["ansi", "100"]));
+        assert!(key != Key::new("# Lorem", &["none", "80"]));
+        // Fields stay apart
+        assert!(key != Key::new("# Lorem", &["ansi8", "0"]));
+    }
+
+    #[test]
+    fn keys_for_effective_capabilities() {
+        let ansi = TerminalCapabilities::ansi();
+        let monochrome = TerminalCapabilities::ansi().monochrome();
+        assert_eq!(ansi.name, monochrome.name);
+        assert!(describe_capabilities(&ansi) != describe_capabilities(&monochrome));
+        assert_eq!(
+            describe_capabilities(&TerminalCapabilities::none()),
+            "style=none links=none image=none marks=none"
+        );
+    }
+
+    #[test]
+    fn documents_with_images() {
+        assert!(has_images("# Logo\n\n![Logo](logo.png)\n"));
+        assert!(has_images("[![Build](badge.svg)](https://example.com)\n"));
+        assert!(!has_images("# Logo\n\n[Logo](logo.png) and `![x](y)`\n"));
+    }
+
+    #[test]
+    fn variables_which_change_output() {
+        let all = vec![
+            ("PATH", "/usr/bin"),
+            ("MDCAT_COLUMNS", "60"),
+            ("LANG", "de_DE.UTF-8"),
+            ("MDCAT_ANSI", "1"),
+        ]
+        .into_iter()
+        .map(|(name, value)| (OsString::from(name), OsString::from(value)));
+        let names = variables(all)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["LANG", "MDCAT_ANSI", "MDCAT_COLUMNS"]);
+    }
+
+    #[test]
+    fn put_and_get_output() {
+        let directory =
+            std::env::temp_dir().join(format!("mdcat-render-cache-{}", std::process::id()));
+        let cache = RenderCache::new(directory.clone());
+        let key = Key::new::<&str>("# Lorem", &[]);
+        assert_eq!(cache.get(key), None);
+        cache.put(key, b"\x1b[1mLorem\x1b[0m\n").unwrap();
+        assert_eq!(cache.get(key), Some(b"\x1b[1mLorem\x1b[0m\n".to_vec()));
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn drop_least_recently_used_output() {
+        let directory =
+            std::env::temp_dir().join(format!("mdcat-render-cache-lru-{}", std::process::id()));
+        let cache = RenderCache::new(directory.clone());
+        let keys = [Key(1), Key(2), Key(3)];
+        let hour = std::time::Duration::from_secs(3600);
+        for (age, key) in keys.iter().rev().enumerate() {
+            cache.put(*key, b"Lorem").unwrap();
+            let file = fs::File::options()
+                .write(true)
+                .open(cache.path(*key))
+                .unwrap();
+            file.set_modified(SystemTime::now() - hour * age as u32)
+                .unwrap();
+        }
+        cache.evict(10).unwrap();
+        assert_eq!(cache.get(keys[0]), None);
+        assert_eq!(cache.get(keys[1]), Some(b"Lorem".to_vec()));
+        assert_eq!(cache.get(keys[2]), Some(b"Lorem".to_vec()));
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
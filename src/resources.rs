@@ -41,6 +41,171 @@ impl ResourceAccess {
     }
 }
 
+/// Read the contents of the given `url` if supported.
+///
+/// Fail if we don’t know how to read from `url`, or if we fail to read from
+/// URL.
+///
+/// We currently support `file:` URLs which the underlying operation system can
+/// read (local on UNIX, UNC paths on Windows), and HTTP(S) URLs if enabled at
+/// build system.
+///
+/// Keep HTTP(S) resources with an ETag in the cache of the user, and only ask
+/// whether they changed when reading them again, eg, when rendering again
+/// in watch mode.
+#[cfg(feature = "resources")]
+pub(crate) fn read_url(url: &Url) -> Result<Vec<u8>, failure::Error> {
+    use std::fs::File;
+    use std::io::prelude::*;
+    use std::io::{Error, ErrorKind};
+
+    let contents: Result<Vec<u8>, failure::Error> = match url.scheme() {
+        "file" => match url.to_file_path() {
+            Ok(path) => {
+                let mut buffer = Vec::new();
+                File::open(path)?.read_to_end(&mut buffer)?;
+                Ok(buffer)
+            }
+            Err(_) => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Remote file: URL {} not supported", url),
+            )
+            .into()),
+        },
+        #[cfg(feature = "remote_resources")]
+        "http" | "https" => read_remote(url),
+        _ => Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Protocol of URL {} not supported", url),
+        )
+        .into()),
+    };
+    let contents = contents?;
+    crate::metrics::add_image(contents.len());
+    Ok(contents)
+}
+
+/// The maximum size of remote resources we read, in bytes.
+#[cfg(feature = "remote_resources")]
+const MAX_REMOTE_SIZE: u64 = 10 * 1024 * 1024;
+
+/// How long we wait for remote resources.
+#[cfg(feature = "remote_resources")]
+const REMOTE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// How many redirects we follow for remote resources.
+#[cfg(feature = "remote_resources")]
+const REMOTE_REDIRECTS: usize = 10;
+
+/// Read the contents of the HTTP(S) `url`.
+///
+/// Take resources from the cache of the user while they are fresh, and
+/// otherwise ask the server whether they changed if they have an ETag.  Fail
+/// if the server takes longer than `REMOTE_TIMEOUT` or the resource is larger
+/// than `MAX_REMOTE_SIZE`.
+///
+/// Send the extra headers the user configured for the host of `url`, and
+/// then only follow redirects which keep to this host.
+#[cfg(feature = "remote_resources")]
+fn read_remote(url: &Url) -> Result<Vec<u8>, failure::Error> {
+    use crate::cache::{Cache, CachedResource};
+    use crate::headers::follows_with_headers;
+    use reqwest::header::{ETAG, IF_NONE_MATCH};
+    use reqwest::RedirectPolicy;
+    use std::io::{Error, Read};
+
+    let cache = Cache::for_user();
+    let cached = match cache.as_ref().and_then(|cache| cache.get(url)) {
+        Some(CachedResource {
+            fresh: true,
+            contents,
+            ..
+        }) => return Ok(contents),
+        cached => cached,
+    };
+    let headers = crate::headers::current().for_url(url);
+    let mut client = reqwest::Client::builder().timeout(REMOTE_TIMEOUT);
+    if !headers.is_empty() {
+        client = client.redirect(RedirectPolicy::custom(|attempt| {
+            if REMOTE_REDIRECTS <= attempt.previous().len() {
+                attempt.too_many_redirects()
+            } else if follows_with_headers(&attempt.previous()[0], attempt.url()) {
+                attempt.follow()
+            } else {
+                attempt.stop()
+            }
+        }));
+    }
+    let mut request = client.build()?.get(url.clone());
+    for (name, value) in headers {
+        request = request.header(name.as_str(), value.as_str());
+    }
+    if let Some(etag) = cached.as_ref().and_then(|cached| cached.etag.as_ref()) {
+        request = request.header(IF_NONE_MATCH, etag.as_str());
+    }
+    let mut response = request.send()?;
+    match cached {
+        Some(cached) if response.status() == reqwest::StatusCode::NOT_MODIFIED => {
+            Ok(cached.contents)
+        }
+        _ if response.status().is_success() => {
+            let etag = response
+                .headers()
+                .get(ETAG)
+                .and_then(|etag| etag.to_str().ok())
+                .map(ToString::to_string);
+            let mut buffer = Vec::new();
+            response
+                .by_ref()
+                .take(MAX_REMOTE_SIZE + 1)
+                .read_to_end(&mut buffer)?;
+            if MAX_REMOTE_SIZE < buffer.len() as u64 {
+                let message = format!("{} larger than {} bytes", url, MAX_REMOTE_SIZE);
+                return Err(Error::other(message).into());
+            }
+            if let Some(cache) = cache {
+                // The cache only saves time, so carry on if we can't write it
+                cache.put(url, etag.as_deref(), &buffer).ok();
+            }
+            Ok(buffer)
+        }
+        _ => Err(Error::other(format!(
+            "HTTP error status {} by GET {}",
+            response.status(),
+            url
+        ))
+        .into()),
+    }
+}
+
+/// Get the local file of the resource at `url`.
+///
+/// Read HTTP(S) resources into the cache of the user, and return the path of
+/// the cached download.
+#[cfg(feature = "resources")]
+pub(crate) fn local_file(url: &Url) -> Result<std::path::PathBuf, failure::Error> {
+    use failure::format_err;
+
+    match url.scheme() {
+        "file" => {
+            let path = url
+                .to_file_path()
+                .map_err(|_| format_err!("Remote file: URL {} not supported", url))?;
+            // Fail for missing files, instead of linking to nowhere
+            std::fs::metadata(&path)?;
+            Ok(path)
+        }
+        #[cfg(feature = "remote_resources")]
+        "http" | "https" => {
+            read_remote(url)?;
+            crate::cache::Cache::for_user()
+                .and_then(|cache| cache.contents_path(url))
+                .ok_or_else(|| format_err!("No cached download of {}", url))
+        }
+        _ => Err(format_err!("Protocol of URL {} not supported", url)),
+    }
+}
+
 /// Whether `url` is readable as local file:.
 #[cfg(feature = "resources")]
 fn is_local(url: &Url) -> bool {
@@ -50,6 +215,31 @@ fn is_local(url: &Url) -> bool {
 #[cfg(all(test, feature = "resources"))]
 mod tests {
     pub use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn read_url_with_http_url_fails_when_status_404() {
+        let url = "https://eu.httpbin.org/status/404"
+            .parse::<url::Url>()
+            .unwrap();
+        let result = read_url(&url);
+        assert!(result.is_err(), "Unexpected success: {:?}", result);
+        let error = result.unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "HTTP error status 404 Not Found by GET https://eu.httpbin.org/status/404"
+        )
+    }
+
+    #[test]
+    fn read_url_with_http_url_returns_content_when_status_200() {
+        let url = "https://eu.httpbin.org/bytes/100"
+            .parse::<url::Url>()
+            .unwrap();
+        let result = read_url(&url);
+        assert!(result.is_ok(), "Unexpected error: {:?}", result);
+        assert_eq!(result.unwrap().len(), 100);
+    }
 
     #[test]
     fn resource_access_permits_local_resource() {
@@ -0,0 +1,275 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The schema of our command line, for `--help-json`.
+//!
+//! Wrappers, eg, editor plugins, generate their settings for mdcat from this
+//! schema.  We take arguments from the table in `crate::args`, and add what
+//! the table does not say: Which values are numbers, and which environment
+//! variables we read.
+
+use crate::args::ArgDef;
+use crate::variables;
+use serde::Serialize;
+
+/// Arguments whose values are numbers.
+const NUMBERS: &[&str] = &["columns", "lines", "tab_width", "max_output_lines"];
+
+/// Environment variables which provide defaults for arguments, besides
+/// `MDCAT_*` variables.
+const ARGUMENT_VARIABLES: &[(&str, &[&str])] = &[
+    ("columns", &["FZF_PREVIEW_COLUMNS"]),
+    ("lines", &["FZF_PREVIEW_LINES"]),
+    ("paginate", &["MDCAT_PAGER", "PAGER"]),
+];
+
+/// Defaults of arguments which depend on the terminal, instead of the default
+/// values we give clap at runtime.
+const TERMINAL_DEFAULTS: &[(&str, &str)] =
+    &[("columns", "terminal width"), ("lines", "terminal height")];
+
+/// Environment variables which affect output regardless of arguments.
+const VARIABLES: &[(&str, &str)] = &[
+    (
+        "MDCAT_LANG",
+        "The language of messages; defaults to the locale in $LC_ALL, $LC_MESSAGES or $LANG",
+    ),
+    (
+        "XDG_CONFIG_HOME",
+        "Where to look for mdcat/theme.toml; defaults to ~/.config",
+    ),
+    (
+        "XDG_CACHE_HOME",
+        "Where to cache remote resources; defaults to ~/.cache",
+    ),
+];
+
+/// An argument on the command line.
+#[derive(Debug, Serialize)]
+struct Argument<'a> {
+    /// The name of the argument.
+    name: &'a str,
+    /// The type of the argument: `flag`, `string`, `number` or `choice`.
+    #[serde(rename = "type")]
+    kind: &'static str,
+    /// Whether the argument is positional, ie, has no flag.
+    positional: bool,
+    /// The short flag, without `-`.
+    short: Option<&'a str>,
+    /// The long flag, without `--`.
+    long: Option<&'a str>,
+    /// Other long flags for this argument.
+    aliases: Vec<&'a str>,
+    /// The name of the value in help.
+    value_name: Option<&'a str>,
+    /// The values of a `choice`.
+    possible_values: &'a [&'a str],
+    /// Values to suggest, eg, in completions, for arguments which also take
+    /// other values.
+    suggested_values: Vec<&'static str>,
+    /// The default value.
+    default: Option<String>,
+    /// Whether the argument may be given many times.
+    multiple: bool,
+    /// Environment variables which provide the default value.
+    env: Vec<String>,
+    /// Help for the argument.
+    help: &'a str,
+}
+
+/// An environment variable.
+#[derive(Debug, Serialize)]
+struct Variable {
+    /// The name of the variable.
+    name: &'static str,
+    /// Help for the variable.
+    help: &'static str,
+}
+
+/// The schema of the command line.
+#[derive(Debug, Serialize)]
+struct Schema<'a> {
+    /// The name of the program.
+    name: &'a str,
+    /// The version of the program.
+    version: &'static str,
+    /// All visible arguments, in the order of help.
+    arguments: Vec<Argument<'a>>,
+    /// Environment variables which affect output.
+    environment: Vec<Variable>,
+}
+
+/// The type of the argument of the given `name` which takes a value.
+fn value_type(name: &str, possible_values: &[&str]) -> &'static str {
+    if !possible_values.is_empty() {
+        "choice"
+    } else if NUMBERS.contains(&name) {
+        "number"
+    } else {
+        "string"
+    }
+}
+
+/// The default of `arg`.
+///
+/// Describe defaults which depend on the terminal, instead of the default
+/// values we give clap at runtime.
+fn default_value(arg: &ArgDef) -> Option<String> {
+    match TERMINAL_DEFAULTS
+        .iter()
+        .find(|(argument, _)| *argument == arg.name)
+    {
+        Some((_, default)) => Some(default.to_string()),
+        None => arg.default.map(ToString::to_string),
+    }
+}
+
+/// Values to suggest for `arg`.
+///
+/// `--theme` takes built-in themes and their aliases, but also theme files, so
+/// we cannot make built-in themes possible values in clap.
+fn suggested_values(arg: &ArgDef) -> Vec<&'static str> {
+    match arg.name {
+        "theme" => mdcat::BUILTIN_THEMES
+            .iter()
+            .copied()
+            .chain(mdcat::BUILTIN_THEME_ALIASES.iter().map(|&(alias, _)| alias))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Environment variables for `arg`.
+///
+/// `MDCAT_*` variables take precedence over other variables.
+fn argument_variables(arg: &ArgDef) -> Vec<String> {
+    let others = ARGUMENT_VARIABLES
+        .iter()
+        .filter(|(argument, _)| *argument == arg.name)
+        .flat_map(|(_, variables)| variables.iter().map(ToString::to_string));
+    variables::variable_for(arg)
+        .into_iter()
+        .chain(others)
+        .collect()
+}
+
+/// Get the schema of the program `name` with the arguments `args`.
+fn schema<'a>(name: &'a str, args: &'a [ArgDef]) -> Schema<'a> {
+    let arguments = args
+        .iter()
+        .filter(|arg| !arg.hidden)
+        .map(|arg| Argument {
+            name: arg.name,
+            kind: if arg.takes_value {
+                value_type(arg.name, arg.possible_values)
+            } else {
+                "flag"
+            },
+            positional: arg.long.is_none(),
+            short: arg.short,
+            long: arg.long,
+            aliases: arg
+                .visible_aliases
+                .iter()
+                .chain(arg.aliases)
+                .copied()
+                .collect(),
+            value_name: arg.value_name,
+            possible_values: arg.possible_values,
+            suggested_values: suggested_values(arg),
+            default: default_value(arg),
+            multiple: arg.multiple,
+            env: argument_variables(arg),
+            help: arg.help,
+        })
+        .collect();
+    Schema {
+        name,
+        version: env!("CARGO_PKG_VERSION"),
+        arguments,
+        environment: VARIABLES
+            .iter()
+            .map(|&(name, help)| Variable { name, help })
+            .collect(),
+    }
+}
+
+/// Print the schema of the program `name` with the arguments `args` as JSON.
+pub fn print_schema(name: &str, args: &[ArgDef]) -> serde_json::Result<()> {
+    println!("{}", serde_json::to_string_pretty(&schema(name, args))?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn schema_of_arguments() {
+        let args = [
+            ArgDef::positional("filename", "Files")
+                .multiple()
+                .default("-"),
+            ArgDef::option("columns", "columns", "Columns")
+                .aliases(&["width"])
+                .default("80"),
+            ArgDef::option("when_piped", "when-piped", "Keep")
+                .value_name("KEEP")
+                .possible_values(&["plain", "images"]),
+            ArgDef::flag("secret", "secret", "Secret").hidden(),
+        ];
+        let schema = serde_json::to_value(schema("mdcat", &args)).unwrap();
+        let arguments = schema["arguments"].as_array().unwrap();
+        let names = arguments
+            .iter()
+            .map(|argument| argument["name"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["filename", "columns", "when_piped"]);
+        assert_eq!(
+            arguments[1],
+            json!({
+                "name": "columns",
+                "type": "number",
+                "positional": false,
+                "short": null,
+                "long": "columns",
+                "aliases": ["width"],
+                "value_name": null,
+                "possible_values": [],
+                "suggested_values": [],
+                "default": "terminal width",
+                "multiple": false,
+                "env": ["MDCAT_COLUMNS", "FZF_PREVIEW_COLUMNS"],
+                "help": "Columns",
+            })
+        );
+        assert_eq!(arguments[0]["positional"], json!(true));
+        assert_eq!(arguments[0]["multiple"], json!(true));
+        assert_eq!(arguments[2]["type"], json!("choice"));
+        assert_eq!(arguments[2]["value_name"], json!("KEEP"));
+    }
+
+    #[test]
+    fn suggest_built_in_themes_and_aliases() {
+        let theme = ArgDef::option("theme", "theme", "Theme");
+        let suggestions = suggested_values(&theme);
+        assert!(suggestions.contains(&"monochrome"));
+        assert!(suggestions.contains(&"colorblind"));
+        assert!(suggestions.contains(&"deuteranopia"));
+        assert!(suggestions.contains(&"protanopia"));
+        assert_eq!(value_type(theme.name, theme.possible_values), "string");
+    }
+}
@@ -0,0 +1,482 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Settings for markdown rendering.
+
+use failure::Fail;
+use std::collections::BTreeMap;
+use std::fmt;
+use syntect::highlighting::Theme as SyntectTheme;
+use syntect::parsing::SyntaxSet;
+
+use crate::messages::Messages;
+use crate::resources::ResourceAccess;
+use crate::terminal::highlighting;
+use crate::terminal::{ImageCapability, MarkCapability, TerminalCapabilities, TerminalSize};
+use crate::theme::Theme;
+use crate::typography::Typography;
+
+/// The minimum number of columns we can render to.
+///
+/// Below [`NARROW_COLUMNS`] we lay out documents for narrow terminals, but
+/// with fewer columns than this not even single words fit.
+pub const MINIMUM_COLUMNS: usize = 10;
+
+/// Terminals with fewer columns are narrow.
+///
+/// We lay out documents for narrow terminals, eg, SSH clients on phones or
+/// split panes, with less indentation, and show tables as lists of records.
+pub const NARROW_COLUMNS: usize = 40;
+
+/// The default number of columns between tab stops in code.
+pub const DEFAULT_TAB_WIDTH: usize = 8;
+
+/// What to do with images which the terminal cannot show inline.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImageFallback {
+    /// Show the title and the link of images only.
+    None,
+    /// Open each image in the given viewer command, eg, `feh`, with the path
+    /// of the local file or cached download of the image as last argument.
+    Viewer(String),
+    /// Link to the local file or the cached download of each image instead of
+    /// its URL.
+    FileLink,
+}
+
+/// Settings for markdown rendering.
+///
+/// Use `SettingsBuilder` to create settings.
+pub struct Settings {
+    /// The capabilities of the terminal to write to.
+    pub(crate) terminal_capabilities: TerminalCapabilities,
+    /// The size of the terminal to write to.
+    pub(crate) terminal_size: TerminalSize,
+    /// What resources we may access when rendering.
+    pub(crate) resource_access: ResourceAccess,
+    /// Available syntaxes for highlighting code blocks.
+    pub(crate) syntax_set: SyntaxSet,
+    /// Available themes for highlighting code blocks, by their names.
+    pub(crate) highlighting_themes: BTreeMap<String, SyntectTheme>,
+    /// The styles for markdown elements.
+    pub(crate) theme: Theme,
+    /// Whether to render for screen readers and braille displays.
+    ///
+    /// If set, announce structure with text, eg, "Heading level 2:", instead
+    /// of colours and box drawing characters, and do not show images inline.
+    pub(crate) accessible: bool,
+    /// The number of columns between tab stops in code.
+    pub(crate) tab_width: usize,
+    /// Whether to mark invisible characters in code blocks.
+    pub(crate) show_invisibles: bool,
+    /// Whether to mark content we could not render faithfully.
+    pub(crate) explain_fallbacks: bool,
+    /// The typography for smart punctuation, if enabled.
+    pub(crate) typography: Option<Typography>,
+    /// Whether to detect the language of each paragraph for smart punctuation.
+    pub(crate) detect_languages: bool,
+    /// Whether to write the same output for the same input every time.
+    pub(crate) deterministic: bool,
+    /// The messages to write, eg, in accessible output.
+    pub(crate) messages: Messages,
+    /// What to do with images which the terminal cannot show inline.
+    pub(crate) image_fallback: ImageFallback,
+    /// Whether to right-align columns of numbers in tables.
+    pub(crate) align_numbers: bool,
+}
+
+impl Settings {
+    /// The capabilities of the terminal to write to.
+    pub fn terminal_capabilities(&self) -> &TerminalCapabilities {
+        &self.terminal_capabilities
+    }
+
+    /// The size of the terminal to write to.
+    pub fn terminal_size(&self) -> TerminalSize {
+        self.terminal_size
+    }
+
+    /// What resources we may access when rendering.
+    pub fn resource_access(&self) -> ResourceAccess {
+        self.resource_access
+    }
+
+    /// Available syntaxes for highlighting code blocks.
+    pub fn syntax_set(&self) -> &SyntaxSet {
+        &self.syntax_set
+    }
+
+    /// Take the available syntaxes, eg, to build new settings without loading
+    /// syntaxes again.
+    pub fn into_syntax_set(self) -> SyntaxSet {
+        self.syntax_set
+    }
+
+    /// Available themes for highlighting code blocks, by their names.
+    pub fn highlighting_themes(&self) -> &BTreeMap<String, SyntectTheme> {
+        &self.highlighting_themes
+    }
+
+    /// The styles for markdown elements.
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    /// Whether to render for screen readers and braille displays.
+    pub fn is_accessible(&self) -> bool {
+        self.accessible
+    }
+
+    /// The number of columns between tab stops in code.
+    pub fn tab_width(&self) -> usize {
+        self.tab_width
+    }
+
+    /// Whether to mark invisible characters in code blocks.
+    pub fn show_invisibles(&self) -> bool {
+        self.show_invisibles
+    }
+
+    /// The messages to write, eg, in accessible output.
+    pub fn messages(&self) -> Messages {
+        self.messages
+    }
+
+    /// What to do with images which the terminal cannot show inline.
+    pub fn image_fallback(&self) -> &ImageFallback {
+        &self.image_fallback
+    }
+
+    /// Whether to mark content we could not render faithfully.
+    pub fn explain_fallbacks(&self) -> bool {
+        self.explain_fallbacks
+    }
+
+    /// The typography for smart punctuation, if enabled.
+    pub fn typography(&self) -> Option<&Typography> {
+        self.typography.as_ref()
+    }
+
+    /// Whether to detect the language of each paragraph for smart punctuation.
+    pub fn detect_languages(&self) -> bool {
+        self.detect_languages
+    }
+
+    /// Whether to write the same output for the same input every time.
+    pub fn deterministic(&self) -> bool {
+        self.deterministic
+    }
+
+    /// Whether to right-align columns of numbers in tables.
+    pub fn align_numbers(&self) -> bool {
+        self.align_numbers
+    }
+}
+
+/// An error in settings.
+#[derive(Debug, PartialEq)]
+pub enum SettingsError {
+    /// The terminal is too narrow to render to.
+    TooNarrow {
+        /// The width of the terminal.
+        width: usize,
+        /// The minimum width we need.
+        minimum: usize,
+    },
+    /// The terminal has no lines to render to.
+    NoLines,
+    /// A terminal capability requires output to a TTY.
+    RequiresTty(&'static str),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingsError::TooNarrow { width, minimum } => write!(
+                f,
+                "Terminal width {} too small, need at least {} columns",
+                width, minimum
+            ),
+            SettingsError::NoLines => {
+                write!(f, "Terminal height 0 too small, need at least 1 line")
+            }
+            SettingsError::RequiresTty(capability) => {
+                write!(f, "{} require output to a TTY", capability)
+            }
+        }
+    }
+}
+
+impl Fail for SettingsError {}
+
+/// Build and validate settings.
+pub struct SettingsBuilder {
+    terminal_capabilities: TerminalCapabilities,
+    terminal_size: TerminalSize,
+    resource_access: ResourceAccess,
+    syntax_set: Option<SyntaxSet>,
+    highlighting_themes: BTreeMap<String, SyntectTheme>,
+    theme: Theme,
+    accessible: bool,
+    tab_width: usize,
+    show_invisibles: bool,
+    explain_fallbacks: bool,
+    typography: Option<Typography>,
+    detect_languages: bool,
+    deterministic: bool,
+    messages: Messages,
+    image_fallback: ImageFallback,
+    align_numbers: bool,
+    output_is_tty: bool,
+}
+
+impl SettingsBuilder {
+    /// Start building settings for a terminal with the given `capabilities`.
+    ///
+    /// Assume a TTY of the default size, with local resources only, the
+    /// default theme, default syntaxes and the default tab width.
+    pub fn new(terminal_capabilities: TerminalCapabilities) -> SettingsBuilder {
+        SettingsBuilder {
+            terminal_capabilities,
+            terminal_size: TerminalSize::default(),
+            resource_access: ResourceAccess::LocalOnly,
+            syntax_set: None,
+            highlighting_themes: BTreeMap::new(),
+            theme: Theme::default(),
+            accessible: false,
+            tab_width: DEFAULT_TAB_WIDTH,
+            show_invisibles: false,
+            explain_fallbacks: false,
+            typography: None,
+            detect_languages: false,
+            deterministic: false,
+            messages: Messages::default(),
+            image_fallback: ImageFallback::None,
+            align_numbers: false,
+            output_is_tty: true,
+        }
+    }
+
+    /// Set the size of the terminal.
+    pub fn terminal_size(mut self, terminal_size: TerminalSize) -> SettingsBuilder {
+        self.terminal_size = terminal_size;
+        self
+    }
+
+    /// Set what resources we may access.
+    pub fn resource_access(mut self, resource_access: ResourceAccess) -> SettingsBuilder {
+        self.resource_access = resource_access;
+        self
+    }
+
+    /// Set the syntaxes for highlighting code blocks.
+    pub fn syntax_set(mut self, syntax_set: SyntaxSet) -> SettingsBuilder {
+        self.syntax_set = Some(syntax_set);
+        self
+    }
+
+    /// Add themes for highlighting code blocks, eg, the themes of bat.
+    ///
+    /// Themes replace built-in themes of the same name.
+    pub fn highlighting_themes(
+        mut self,
+        highlighting_themes: BTreeMap<String, SyntectTheme>,
+    ) -> SettingsBuilder {
+        self.highlighting_themes.extend(highlighting_themes);
+        self
+    }
+
+    /// Set the styles for markdown elements.
+    pub fn theme(mut self, theme: Theme) -> SettingsBuilder {
+        self.theme = theme;
+        self
+    }
+
+    /// Render for screen readers and braille displays.
+    pub fn accessible(mut self, accessible: bool) -> SettingsBuilder {
+        self.accessible = accessible;
+        self
+    }
+
+    /// Set the number of columns between tab stops in code.
+    ///
+    /// We expand tabs in code to spaces, because the terminal would align
+    /// tabs to its own tab stops which ignore the indentation of code.
+    pub fn tab_width(mut self, tab_width: usize) -> SettingsBuilder {
+        self.tab_width = tab_width;
+        self
+    }
+
+    /// Mark trailing whitespace, tabs and zero-width characters in code
+    /// blocks with dim symbols.
+    pub fn show_invisibles(mut self, show_invisibles: bool) -> SettingsBuilder {
+        self.show_invisibles = show_invisibles;
+        self
+    }
+
+    /// Mark every place where we degraded content, eg, skipped an image, and
+    /// list these places after the document.
+    pub fn explain_fallbacks(mut self, explain_fallbacks: bool) -> SettingsBuilder {
+        self.explain_fallbacks = explain_fallbacks;
+        self
+    }
+
+    /// Enable smart punctuation according to `typography`.
+    ///
+    /// Replace straight quotes, dashes and dots in text with typographic
+    /// punctuation, and use the bullet of `typography` for list items.
+    pub fn smart_punctuation(mut self, typography: Typography) -> SettingsBuilder {
+        self.typography = Some(typography);
+        self
+    }
+
+    /// Detect the language of each paragraph and heading, and use its
+    /// typography for smart punctuation.
+    ///
+    /// Fall back to the typography given to `smart_punctuation` if we cannot
+    /// detect the language of a paragraph.  Does nothing without smart
+    /// punctuation.
+    pub fn detect_languages(mut self, detect_languages: bool) -> SettingsBuilder {
+        self.detect_languages = detect_languages;
+        self
+    }
+
+    /// Write the same output for the same input every time, eg, for tests.
+    ///
+    /// Without this setting IDs of inline links include the process ID, to
+    /// tell links of different documents apart in the same terminal.
+    pub fn deterministic(mut self, deterministic: bool) -> SettingsBuilder {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Set the messages to write, eg, in accessible output or for fallbacks.
+    ///
+    /// Use English messages by default.
+    pub fn messages(mut self, messages: Messages) -> SettingsBuilder {
+        self.messages = messages;
+        self
+    }
+
+    /// Set what to do with images which the terminal cannot show inline.
+    ///
+    /// Show only the title and the link of images by default.
+    pub fn image_fallback(mut self, image_fallback: ImageFallback) -> SettingsBuilder {
+        self.image_fallback = image_fallback;
+        self
+    }
+
+    /// Right-align columns of tables which have only numbers, unless the
+    /// table aligns these columns explicitly.
+    pub fn align_numbers(mut self, align_numbers: bool) -> SettingsBuilder {
+        self.align_numbers = align_numbers;
+        self
+    }
+
+    /// Set whether we write to a TTY.
+    ///
+    /// Inline images and marks only work on a TTY.
+    pub fn output_is_tty(mut self, output_is_tty: bool) -> SettingsBuilder {
+        self.output_is_tty = output_is_tty;
+        self
+    }
+
+    /// Validate and build the settings.
+    pub fn build(self) -> Result<Settings, SettingsError> {
+        if self.terminal_size.width < MINIMUM_COLUMNS {
+            return Err(SettingsError::TooNarrow {
+                width: self.terminal_size.width,
+                minimum: MINIMUM_COLUMNS,
+            });
+        }
+        if self.terminal_size.height == 0 {
+            return Err(SettingsError::NoLines);
+        }
+        if !self.output_is_tty {
+            if !matches!(self.terminal_capabilities.image, ImageCapability::None) {
+                return Err(SettingsError::RequiresTty("Inline images"));
+            }
+            if !matches!(self.terminal_capabilities.marks, MarkCapability::None) {
+                return Err(SettingsError::RequiresTty("Marks"));
+            }
+        }
+        let mut highlighting_themes = highlighting::themes();
+        highlighting_themes.extend(self.highlighting_themes);
+        Ok(Settings {
+            terminal_capabilities: self.terminal_capabilities,
+            terminal_size: self.terminal_size,
+            resource_access: self.resource_access,
+            syntax_set: self
+                .syntax_set
+                .unwrap_or_else(SyntaxSet::load_defaults_newlines),
+            highlighting_themes,
+            theme: self.theme,
+            accessible: self.accessible,
+            tab_width: self.tab_width,
+            show_invisibles: self.show_invisibles,
+            explain_fallbacks: self.explain_fallbacks,
+            typography: self.typography,
+            detect_languages: self.detect_languages,
+            deterministic: self.deterministic,
+            messages: self.messages,
+            image_fallback: self.image_fallback,
+            align_numbers: self.align_numbers,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn build_rejects_narrow_terminals() {
+        let result = SettingsBuilder::new(TerminalCapabilities::ansi())
+            .terminal_size(TerminalSize::new(8, 24))
+            .syntax_set(SyntaxSet::default())
+            .build();
+        assert_eq!(
+            result.err(),
+            Some(SettingsError::TooNarrow {
+                width: 8,
+                minimum: MINIMUM_COLUMNS
+            })
+        );
+        let result = SettingsBuilder::new(TerminalCapabilities::ansi())
+            .terminal_size(TerminalSize::new(MINIMUM_COLUMNS, 24))
+            .syntax_set(SyntaxSet::default())
+            .build();
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn build_rejects_terminals_without_lines() {
+        let result = SettingsBuilder::new(TerminalCapabilities::ansi())
+            .terminal_size(TerminalSize::new(80, 0))
+            .syntax_set(SyntaxSet::default())
+            .build();
+        assert_eq!(result.err(), Some(SettingsError::NoLines));
+    }
+
+    #[test]
+    fn build_accepts_plain_output_without_tty() {
+        let settings = SettingsBuilder::new(TerminalCapabilities::none())
+            .output_is_tty(false)
+            .syntax_set(SyntaxSet::default())
+            .build()
+            .unwrap();
+        assert_eq!(settings.terminal_size().width, 80);
+    }
+}
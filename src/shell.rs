@@ -0,0 +1,74 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Run commands which users configure, eg, pagers, filters and viewers.
+
+use std::ffi::OsStr;
+use std::process::Command;
+
+/// A shell command to run `command` with `args`.
+///
+/// Run `command` with `sh -c` so users may quote arguments and use shell
+/// syntax, and pass `args` as positional parameters after `command`.
+#[cfg(unix)]
+pub fn shell_command(command: &str, args: &[&OsStr]) -> Command {
+    let mut shell = Command::new("sh");
+    if args.is_empty() {
+        shell.arg("-c").arg(command);
+    } else {
+        // $0 names the shell in messages, and "$@" expands to args
+        shell
+            .arg("-c")
+            .arg(format!("{} \"$@\"", command))
+            .arg("sh")
+            .args(args);
+    }
+    shell
+}
+
+/// A shell command to run `command` with `args`.
+///
+/// Run `command` with `cmd /C` and pass `args` after `command`.
+#[cfg(not(unix))]
+pub fn shell_command(command: &str, args: &[&OsStr]) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command).args(args);
+    shell
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn output_of(command: &str, args: &[&str]) -> String {
+        let args = args.iter().map(OsStr::new).collect::<Vec<_>>();
+        let output = shell_command(command, &args).output().unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    fn run_with_shell_syntax() {
+        assert_eq!(output_of("printf '%s|' 'a b' c", &[]), "a b|c|");
+        assert_eq!(output_of("echo a && echo b", &[]), "a\nb\n");
+    }
+
+    #[test]
+    fn pass_args_after_command() {
+        assert_eq!(
+            output_of("printf '%s|' 'a b'", &["c d", "$HOME"]),
+            "a b|c d|$HOME|"
+        );
+    }
+}
@@ -0,0 +1,151 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Signed documents.
+//!
+//! Release notes are sometimes signed with PGP, as clearsigned message, or
+//! with a signature at the end.  We strip the armor of signed messages and
+//! collapse signatures, instead of rendering a wall of base64.
+
+/// The first line of a clearsigned PGP message.
+const SIGNED_MESSAGE: &str = "-----BEGIN PGP SIGNED MESSAGE-----";
+
+/// The kinds of signatures with the armor lines they start and end with.
+const SIGNATURES: &[(&str, &str, &str)] = &[
+    (
+        "PGP",
+        "-----BEGIN PGP SIGNATURE-----",
+        "-----END PGP SIGNATURE-----",
+    ),
+    (
+        "SSH",
+        "-----BEGIN SSH SIGNATURE-----",
+        "-----END SSH SIGNATURE-----",
+    ),
+];
+
+/// A signature we collapsed.
+#[derive(Debug, PartialEq)]
+pub struct Signature {
+    /// The kind of signature, eg, `PGP`.
+    pub kind: &'static str,
+    /// The number of lines of the signature.
+    pub lines: usize,
+}
+
+/// Strip the armor of a clearsigned message from `source`.
+///
+/// Drop the first line and the armor headers up to the first blank line, and
+/// undo the dash escaping of lines in the message.
+fn strip_signed_message(source: &str) -> Option<String> {
+    let mut lines = source.lines();
+    if lines.next()?.trim_end() != SIGNED_MESSAGE {
+        return None;
+    }
+    let mut message = String::new();
+    for line in lines.by_ref() {
+        if line.trim().is_empty() {
+            break;
+        }
+    }
+    for line in lines {
+        message.push_str(line.strip_prefix("- ").unwrap_or(line));
+        message.push('\n');
+    }
+    Some(message)
+}
+
+/// Split a signature at the end of `source` from `source`.
+///
+/// Return the rest of `source` and the signature, if any.
+fn split_signature(source: &str) -> (&str, Option<Signature>) {
+    let trimmed = source.trim_end();
+    for &(kind, begin, end) in SIGNATURES {
+        if !trimmed.ends_with(end) {
+            continue;
+        }
+        let start = trimmed
+            .match_indices(begin)
+            .map(|(index, _)| index)
+            .filter(|&index| index == 0 || trimmed[..index].ends_with('\n'))
+            .last();
+        if let Some(start) = start {
+            let lines = trimmed[start..].lines().count();
+            return (&source[..start], Some(Signature { kind, lines }));
+        }
+    }
+    (source, None)
+}
+
+/// Strip the armor of signed messages and signatures at the end of `source`.
+///
+/// Return the document without armor and signature, and the signature we
+/// collapsed, if any.
+pub fn strip_signature(source: String) -> (String, Option<Signature>) {
+    let source = strip_signed_message(&source).unwrap_or(source);
+    let (body, signature) = split_signature(&source);
+    match signature {
+        Some(signature) => (body.to_string(), Some(signature)),
+        None => (source, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn strip_clearsigned_messages() {
+        let source = "-----BEGIN PGP SIGNED MESSAGE-----\nHash: SHA256\n\n\
+                      # mdcat 0.13.0\n\n- -----\n\n* Fixes\n\
+                      -----BEGIN PGP SIGNATURE-----\n\niQEzBAEBCAAdFiEE\n=Ab3x\n\
+                      -----END PGP SIGNATURE-----\n";
+        assert_eq!(
+            strip_signature(source.to_string()),
+            (
+                "# mdcat 0.13.0\n\n-----\n\n* Fixes\n".to_string(),
+                Some(Signature {
+                    kind: "PGP",
+                    lines: 5
+                })
+            )
+        );
+    }
+
+    #[test]
+    fn collapse_signatures_at_the_end() {
+        let source = "# Notes\n\n-----BEGIN SSH SIGNATURE-----\nU1NIU0lH\n\
+                      -----END SSH SIGNATURE-----\n\n";
+        assert_eq!(
+            strip_signature(source.to_string()),
+            (
+                "# Notes\n\n".to_string(),
+                Some(Signature {
+                    kind: "SSH",
+                    lines: 3
+                })
+            )
+        );
+    }
+
+    #[test]
+    fn leave_other_documents_alone() {
+        let source = "# Notes\n\n```\n-----BEGIN PGP SIGNATURE-----\n```\n";
+        assert_eq!(
+            strip_signature(source.to_string()),
+            (source.to_string(), None)
+        );
+    }
+}
@@ -0,0 +1,249 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Rendered markdown as lines of styled spans.
+//!
+//! We render markdown for an ANSI terminal and parse the output again, so
+//! that spans have exactly the layout of `push_tty`.  Spans only keep what
+//! `ansi_term::Style` has: Dotted underlines become plain underlines, and we
+//! drop OSC sequences, eg, OSC 8 links, and all other escape sequences.
+
+use crate::environment::Environment;
+use crate::settings::Settings;
+use crate::terminal::*;
+use ansi_term::{Colour, Style};
+use failure::Error;
+use pulldown_cmark::Event;
+
+/// A line of rendered markdown, as spans of text with their style.
+pub type StyledLine = Vec<(String, Style)>;
+
+/// The colour with the standard ANSI `index`.
+fn colour(index: u8) -> Colour {
+    match index {
+        0 => Colour::Black,
+        1 => Colour::Red,
+        2 => Colour::Green,
+        3 => Colour::Yellow,
+        4 => Colour::Blue,
+        5 => Colour::Purple,
+        6 => Colour::Cyan,
+        7 => Colour::White,
+        index => Colour::Fixed(index),
+    }
+}
+
+/// Parse an extended colour from SGR `params`, ie, after `38` or `48`.
+fn extended_colour<'a, I: Iterator<Item = &'a str>>(params: &mut I) -> Option<Colour> {
+    let mut next = || params.next().and_then(|param| param.parse::<u8>().ok());
+    match next()? {
+        5 => next().map(Colour::Fixed),
+        2 => Some(Colour::RGB(next()?, next()?, next()?)),
+        _ => None,
+    }
+}
+
+/// Apply the parameters of an SGR sequence to `style`.
+fn apply_sgr(style: &mut Style, params: &str) {
+    let mut params = params.split(';');
+    while let Some(param) = params.next() {
+        // Take the main parameter of sub-parameters, eg, dotted underlines
+        let code = param.split(':').next().unwrap_or("");
+        match code.parse::<u8>().unwrap_or(0) {
+            0 => *style = Style::new(),
+            1 => style.is_bold = true,
+            2 => style.is_dimmed = true,
+            3 => style.is_italic = true,
+            4 => style.is_underline = true,
+            5 => style.is_blink = true,
+            7 => style.is_reverse = true,
+            8 => style.is_hidden = true,
+            9 => style.is_strikethrough = true,
+            22 => {
+                style.is_bold = false;
+                style.is_dimmed = false;
+            }
+            23 => style.is_italic = false,
+            24 => style.is_underline = false,
+            25 => style.is_blink = false,
+            27 => style.is_reverse = false,
+            28 => style.is_hidden = false,
+            29 => style.is_strikethrough = false,
+            code @ 30..=37 => style.foreground = Some(colour(code - 30)),
+            38 => style.foreground = extended_colour(&mut params),
+            39 => style.foreground = None,
+            code @ 40..=47 => style.background = Some(colour(code - 40)),
+            48 => style.background = extended_colour(&mut params),
+            49 => style.background = None,
+            code @ 90..=97 => style.foreground = Some(Colour::Fixed(code - 90 + 8)),
+            code @ 100..=107 => style.background = Some(Colour::Fixed(code - 100 + 8)),
+            _ => {}
+        }
+    }
+}
+
+/// Append `c` with `style` to `line`.
+fn push_char(line: &mut StyledLine, c: char, style: Style) {
+    match line.last_mut() {
+        Some((text, last)) if *last == style => text.push(c),
+        _ => line.push((c.to_string(), style)),
+    }
+}
+
+/// Split ANSI formatted `output` into lines of styled spans.
+///
+/// Understands the SGR sequences `push_tty` writes for ANSI terminals, and
+/// skips all other CSI sequences and OSC sequences, eg, OSC 8 links.
+fn to_lines(output: &str) -> Vec<StyledLine> {
+    let mut lines = Vec::new();
+    let mut line = StyledLine::new();
+    let mut style = Style::new();
+    let mut chars = output.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' if chars.peek() == Some(&'[') => {
+                chars.next();
+                let mut params = String::new();
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        if c == 'm' {
+                            apply_sgr(&mut style, &params);
+                        }
+                        break;
+                    }
+                    params.push(c);
+                }
+            }
+            '\x1b' if chars.peek() == Some(&']') => {
+                // Skip up to BEL or ST
+                let mut previous = chars.next();
+                for c in chars.by_ref() {
+                    if c == '\x07' || (previous == Some('\x1b') && c == '\\') {
+                        break;
+                    }
+                    previous = Some(c);
+                }
+            }
+            '\n' => lines.push(std::mem::take(&mut line)),
+            c => push_char(&mut line, c, style),
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Render markdown `events` to lines of styled spans.
+///
+/// Lay out `events` like `push_tty` does for an ANSI terminal with the size
+/// and theme of `settings`, but return the output as lines of text spans
+/// with their style, for TUI applications which draw text themselves.  Write
+/// no inline links, images or marks.
+pub fn render_spans<'a, I>(
+    settings: &Settings,
+    environment: &Environment,
+    events: I,
+) -> Result<Vec<StyledLine>, Error>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    let capabilities = TerminalCapabilities {
+        name: "Spans".to_string(),
+        style: StyleCapability::Ansi(AnsiStyle),
+        links: LinkCapability::None,
+        image: ImageCapability::None,
+        marks: MarkCapability::None,
+    };
+    let mut output = Vec::new();
+    crate::render(settings, &capabilities, &mut output, environment, events)?;
+    Ok(to_lines(&String::from_utf8(output)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SettingsBuilder;
+    use pretty_assertions::assert_eq;
+    use pulldown_cmark::Parser;
+    use syntect::parsing::SyntaxSet;
+
+    #[test]
+    fn parse_sgr_sequences() {
+        let mut style = Style::new();
+        apply_sgr(&mut style, "1;38;5;9;4:4");
+        assert_eq!(style, Style::new().bold().underline().fg(Colour::Fixed(9)));
+        apply_sgr(&mut style, "0;34;48;2;1;2;3");
+        assert_eq!(
+            style,
+            Style::new().fg(Colour::Blue).on(Colour::RGB(1, 2, 3))
+        );
+        apply_sgr(&mut style, "1;4:4;24;22");
+        assert_eq!(
+            style,
+            Style::new().fg(Colour::Blue).on(Colour::RGB(1, 2, 3))
+        );
+    }
+
+    #[test]
+    fn dotted_underlines_become_plain_underlines() {
+        assert_eq!(
+            to_lines("\x1b[1m\x1b[4:4mCSS\x1b[24m bold\x1b[0m plain"),
+            vec![vec![
+                ("CSS".to_string(), Style::new().bold().underline()),
+                (" bold".to_string(), Style::new().bold()),
+                (" plain".to_string(), Style::new()),
+            ]]
+        );
+    }
+
+    #[test]
+    fn drop_osc_8_links() {
+        let output =
+            "\x1b]8;id=1;https://example.com\x07\x1b[34mlink\x1b[0m\x1b]8;;\x1b\\ text\x1b[2K";
+        assert_eq!(
+            to_lines(output),
+            vec![vec![
+                ("link".to_string(), Style::new().fg(Colour::Blue)),
+                (" text".to_string(), Style::new()),
+            ]]
+        );
+    }
+
+    #[test]
+    fn render_markdown_to_spans() {
+        let settings = SettingsBuilder::new(TerminalCapabilities::none())
+            .syntax_set(SyntaxSet::new())
+            .build()
+            .unwrap();
+        let lines = render_spans(
+            &settings,
+            &Environment::isolated("/"),
+            Parser::new("# Foo\n\nSome *bar*\n"),
+        )
+        .unwrap();
+        let heading = Style::new().fg(Colour::Blue).bold();
+        assert_eq!(
+            lines,
+            vec![
+                vec![("\u{2504}Foo".to_string(), heading)],
+                vec![],
+                vec![
+                    ("Some ".to_string(), Style::new()),
+                    ("bar".to_string(), Style::new().italic())
+                ],
+            ]
+        );
+    }
+}
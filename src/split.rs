@@ -0,0 +1,134 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Split documents into sections.
+
+use pulldown_cmark::Event;
+use pulldown_cmark::Event::*;
+use pulldown_cmark::Tag::*;
+use std::collections::HashSet;
+
+/// The name of the section before the first top-level heading.
+const PREAMBLE: &str = "index";
+
+/// Make a slug for a file name from `text`.
+///
+/// Lower-case all letters and replace runs of other characters with a
+/// single dash.
+pub fn slug(text: &str) -> String {
+    let mut slug = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let length = slug.trim_end_matches('-').len();
+    slug.truncate(length);
+    slug
+}
+
+/// Make `slug` unique among `taken` slugs, and mark it as taken.
+fn unique_slug(slug: String, taken: &mut HashSet<String>) -> String {
+    let slug = if slug.is_empty() {
+        "section".to_string()
+    } else {
+        slug
+    };
+    let mut candidate = slug.clone();
+    let mut counter = 1;
+    while taken.contains(&candidate) {
+        counter += 1;
+        candidate = format!("{}-{}", slug, counter);
+    }
+    taken.insert(candidate.clone());
+    candidate
+}
+
+/// Split `events` into top-level sections.
+///
+/// Start a new section at every top-level heading, and name each section by
+/// the slug of its heading.  Name the section before the first top-level
+/// heading, if any, `index`.
+pub fn sections<'a, I>(events: I) -> Vec<(String, Vec<Event<'a>>)>
+where
+    I: IntoIterator<Item = Event<'a>>,
+{
+    let mut sections: Vec<(String, Vec<Event<'a>>)> = Vec::new();
+    let mut taken = HashSet::new();
+    let mut heading: Option<String> = None;
+    for event in events {
+        match event {
+            Start(Header(1)) => {
+                heading = Some(String::new());
+                sections.push((String::new(), Vec::new()));
+            }
+            End(Header(1)) => {
+                if let (Some(text), Some(section)) = (heading.take(), sections.last_mut()) {
+                    section.0 = unique_slug(slug(&text), &mut taken);
+                }
+            }
+            Text(ref text) => {
+                if let Some(ref mut heading) = heading {
+                    heading.push_str(text);
+                }
+            }
+            _ => {}
+        }
+        if sections.is_empty() {
+            sections.push((unique_slug(PREAMBLE.to_string(), &mut taken), Vec::new()));
+        }
+        sections.last_mut().unwrap().1.push(event);
+    }
+    sections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use pulldown_cmark::Parser;
+
+    fn section_names(markdown: &str) -> Vec<String> {
+        sections(Parser::new(markdown))
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    #[test]
+    fn slug_of_heading() {
+        assert_eq!(slug("Hello, World!"), "hello-world");
+        assert_eq!(slug("  Über `mdcat` 2.0 "), "über-mdcat-2-0");
+    }
+
+    #[test]
+    fn split_at_top_level_headings() {
+        assert_eq!(
+            section_names("Intro\n\n# Usage\n\n## Options\n\n# `mdcat` FAQ\n\n# Usage\n"),
+            vec!["index", "usage", "mdcat-faq", "usage-2"]
+        );
+    }
+
+    #[test]
+    fn keep_all_events() {
+        let markdown = "# Foo\n\nBar\n\n# Baz\n";
+        let events: Vec<Event<'_>> = sections(Parser::new(markdown))
+            .into_iter()
+            .flat_map(|(_, events)| events)
+            .collect();
+        assert_eq!(events, Parser::new(markdown).collect::<Vec<_>>());
+    }
+}
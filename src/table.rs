@@ -0,0 +1,691 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Layout of tables.
+//!
+//! Measure and wrap styled cell contents, fit columns into the width of the
+//! terminal, and draw cells with box drawing characters.
+//!
+//! Cells take the alignment of their column from the delimiter row, unless
+//! they end with an attribute of their own, eg, `{align=right}`.  Width
+//! attributes in cells set the width of their column, eg, `{width=20}` or
+//! `{max-width=60%}`.
+
+use crate::theme::WidthHint;
+use crate::width::{cluster_len, cluster_width};
+use pulldown_cmark::{Alignment, Event};
+use std::borrow::Cow;
+
+/// A piece of styled text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Piece<'a> {
+    /// An escape sequence, which takes no space on the terminal.
+    Escape(&'a str),
+    /// A cluster of characters which the terminal shows as one.
+    Cluster(&'a str),
+}
+
+impl<'a> Piece<'a> {
+    /// Whether this piece is whitespace.
+    fn is_whitespace(&self) -> bool {
+        match self {
+            Piece::Cluster(cluster) => cluster.starts_with(char::is_whitespace),
+            Piece::Escape(_) => false,
+        }
+    }
+}
+
+/// Split styled `text` into escape sequences and visible clusters.
+///
+/// We understand CSI sequences, eg, SGR styles, and OSC sequences terminated
+/// by BEL or ST.
+fn pieces(text: &str) -> Vec<Piece<'_>> {
+    let mut pieces = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c != '\x1b' {
+            let end = start + cluster_len(&text[start..]);
+            while chars.next_if(|&(index, _)| index < end).is_some() {}
+            pieces.push(Piece::Cluster(&text[start..end]));
+            continue;
+        }
+        let end = match chars.next() {
+            Some((_, '[')) => chars
+                .find(|&(_, c)| ('@'..='~').contains(&c))
+                .map(|(index, c)| index + c.len_utf8()),
+            Some((_, ']')) => {
+                let mut end = None;
+                while let Some((index, c)) = chars.next() {
+                    if c == '\x07' {
+                        end = Some(index + 1);
+                        break;
+                    } else if c == '\x1b' && chars.peek().map(|&(_, c)| c) == Some('\\') {
+                        end = chars.next().map(|(index, _)| index + 1);
+                        break;
+                    }
+                }
+                end
+            }
+            Some((index, c)) => Some(index + c.len_utf8()),
+            None => None,
+        };
+        pieces.push(Piece::Escape(&text[start..end.unwrap_or(text.len())]));
+    }
+    pieces
+}
+
+/// The number of columns styled `text` takes on the terminal.
+///
+/// Ignore escape sequences, and count wide characters, eg, CJK characters, as
+/// two columns.
+pub(crate) fn display_width(text: &str) -> usize {
+    pieces(text)
+        .into_iter()
+        .map(|piece| match piece {
+            Piece::Cluster(cluster) => cluster_width(cluster),
+            Piece::Escape(_) => 0,
+        })
+        .sum()
+}
+
+/// The width of the longest word in styled `text`.
+fn longest_word(text: &str) -> usize {
+    let mut longest = 0;
+    let mut current = 0;
+    for piece in pieces(text) {
+        match piece {
+            piece if piece.is_whitespace() => current = 0,
+            Piece::Cluster(cluster) => {
+                current += cluster_width(cluster);
+                longest = longest.max(current);
+            }
+            Piece::Escape(_) => {}
+        }
+    }
+    longest
+}
+
+/// Whether `escape` resets all styles.
+fn is_reset(escape: &str) -> bool {
+    escape == "\x1b[0m" || escape == "\x1b[m"
+}
+
+/// Lines of wrapped styled text.
+struct Lines<'a> {
+    /// All complete lines.
+    lines: Vec<String>,
+    /// The current line.
+    line: String,
+    /// The width of the current line.
+    width: usize,
+    /// The styles active at the end of the current line.
+    active: Vec<&'a str>,
+}
+
+impl<'a> Lines<'a> {
+    /// Append `piece` to the current line.
+    fn push(&mut self, piece: Piece<'a>) {
+        match piece {
+            Piece::Escape(escape) => {
+                if is_reset(escape) {
+                    self.active.clear();
+                } else if escape.starts_with("\x1b[") && escape.ends_with('m') {
+                    self.active.push(escape);
+                }
+                self.line.push_str(escape);
+            }
+            Piece::Cluster(cluster) => {
+                self.width += cluster_width(cluster);
+                self.line.push_str(cluster);
+            }
+        }
+    }
+
+    /// End the current line, and continue the active styles on the next line.
+    fn break_line(&mut self) {
+        if !self.active.is_empty() {
+            self.line.push_str("\x1b[0m");
+        }
+        let next = self.active.concat();
+        self.lines.push(std::mem::replace(&mut self.line, next));
+        self.width = 0;
+    }
+
+    /// Append `word` of the given `width` to the current line, or to a new
+    /// line if it does not fit into `max_width`.
+    ///
+    /// Break words longer than `max_width` at `max_width`.
+    fn push_word(&mut self, word: Vec<Piece<'a>>, width: usize, max_width: usize) {
+        let separator = if self.width == 0 || width == 0 { 0 } else { 1 };
+        if max_width < self.width + separator + width && 0 < self.width {
+            self.break_line();
+        } else if separator == 1 {
+            self.push(Piece::Cluster(" "));
+        }
+        for piece in word {
+            if let Piece::Cluster(cluster) = piece {
+                if max_width < self.width + cluster_width(cluster) && 0 < self.width {
+                    self.break_line();
+                }
+            }
+            self.push(piece);
+        }
+    }
+}
+
+/// Wrap styled `text` into lines of at most `width` columns.
+///
+/// Break lines at whitespace, and words longer than `width` anywhere.  End
+/// every line with a style reset if styles are active, and continue these
+/// styles on the next line.
+pub(crate) fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Lines {
+        lines: Vec::new(),
+        line: String::new(),
+        width: 0,
+        active: Vec::new(),
+    };
+    let mut word = Vec::new();
+    let mut word_width = 0;
+    for piece in pieces(text) {
+        match piece {
+            piece if piece.is_whitespace() => {
+                if 0 < word_width {
+                    lines.push_word(std::mem::take(&mut word), word_width, width);
+                    word_width = 0;
+                }
+            }
+            Piece::Cluster(cluster) => {
+                word_width += cluster_width(cluster);
+                word.push(piece);
+            }
+            Piece::Escape(_) => word.push(piece),
+        }
+    }
+    if !word.is_empty() {
+        lines.push_word(word, word_width, width);
+    }
+    lines.lines.push(lines.line);
+    lines.lines
+}
+
+/// Compute the widths of columns for `rows` of styled cells.
+///
+/// Give each column the width of its widest cell, within the bounds of its
+/// width hint in `hints`, if the table fits into `available` columns for
+/// cell contents.  Otherwise shrink the widest columns, down to the longest
+/// word or the minimum width of each column if possible, then down to the
+/// minimum width, and down to a single column at worst.
+pub(crate) fn column_widths(
+    rows: &[Vec<String>],
+    columns: usize,
+    available: usize,
+    hints: &[Option<WidthHint>],
+) -> Vec<usize> {
+    let mut widths = vec![1; columns];
+    let mut words = vec![1; columns];
+    for row in rows {
+        for (index, cell) in row.iter().enumerate().take(columns) {
+            widths[index] = widths[index].max(display_width(cell));
+            words[index] = words[index].max(longest_word(cell));
+        }
+    }
+    let mut minimums = vec![1; columns];
+    for (index, hint) in hints.iter().enumerate().take(columns) {
+        let hint = hint.unwrap_or_default();
+        if let Some(max) = hint.max {
+            widths[index] = widths[index].min(max.columns(available));
+        }
+        if let Some(min) = hint.min {
+            minimums[index] = min.columns(available);
+            widths[index] = widths[index].max(minimums[index]);
+        }
+        words[index] = words[index].max(minimums[index]).min(widths[index]);
+    }
+    for minimum in &[words, minimums, vec![1; columns]] {
+        while available < widths.iter().sum::<usize>() {
+            let widest = (0..columns)
+                .filter(|&index| minimum[index] < widths[index])
+                .max_by_key(|&index| widths[index]);
+            match widest {
+                Some(index) => widths[index] -= 1,
+                None => break,
+            }
+        }
+    }
+    widths
+}
+
+/// The border characters of a horizontal rule: left, between cells, right.
+type Rule = (char, char, char);
+
+/// The rule at the top of a table.
+const TOP: Rule = ('\u{250C}', '\u{252C}', '\u{2510}');
+/// The rule below the heading of a table.
+const MIDDLE: Rule = ('\u{251C}', '\u{253C}', '\u{2524}');
+/// The rule at the bottom of a table.
+const BOTTOM: Rule = ('\u{2514}', '\u{2534}', '\u{2518}');
+
+/// Draw a horizontal rule for columns of the given `widths`.
+fn rule(widths: &[usize], (left, between, right): Rule) -> String {
+    let cells = widths
+        .iter()
+        .map(|width| "\u{2500}".repeat(width + 2))
+        .collect::<Vec<_>>();
+    format!("{}{}{}", left, cells.join(&between.to_string()), right)
+}
+
+/// Attributes of a table cell.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub(crate) struct CellAttributes {
+    /// The alignment of the cell.
+    pub(crate) alignment: Option<Alignment>,
+    /// The width of the column of the cell.
+    pub(crate) width: Option<WidthHint>,
+}
+
+impl CellAttributes {
+    /// Parse space separated attributes, eg, `align=right max-width=20`.
+    ///
+    /// Return `None` if there is an unknown or invalid attribute.
+    fn parse(text: &str) -> Option<CellAttributes> {
+        let mut attributes = CellAttributes::default();
+        for attribute in text.split_whitespace() {
+            let (key, value) = attribute.split_once('=')?;
+            if key == "align" {
+                attributes.alignment = Some(match value {
+                    "left" => Alignment::Left,
+                    "center" => Alignment::Center,
+                    "right" => Alignment::Right,
+                    _ => return None,
+                });
+            } else {
+                let hint = attributes.width.unwrap_or_default();
+                attributes.width = Some(hint.with_attribute(key, value)?);
+            }
+        }
+        Some(attributes)
+    }
+}
+
+/// Take the attributes at the end of the cell with the given `events`, eg,
+/// `{align=right width=20%}`.
+///
+/// Remove the attributes from `events`, and return them, or `None` if the
+/// cell has no attributes.
+pub(crate) fn take_cell_attributes(events: &mut Vec<Event<'_>>) -> Option<CellAttributes> {
+    let (rest, attributes) = match events.last() {
+        Some(Event::Text(text)) => {
+            let text = text.trim_end();
+            let start = text.rfind('{')?;
+            let attributes = text[start..]
+                .strip_prefix('{')
+                .and_then(|text| text.strip_suffix('}'))
+                .filter(|text| !text.trim().is_empty())
+                .and_then(CellAttributes::parse)?;
+            (text[..start].to_string(), attributes)
+        }
+        _ => return None,
+    };
+    if rest.trim().is_empty() {
+        events.pop();
+    } else if let Some(last) = events.last_mut() {
+        *last = Event::Text(Cow::Owned(rest));
+    }
+    Some(attributes)
+}
+
+/// Whether plain `text` is a number, eg, `-1,000.5`, `42 %` or `$3`.
+fn is_number(text: &str) -> bool {
+    let text = text.trim();
+    let text = text.trim_start_matches(['+', '-', '\u{2212}', '$', '\u{20AC}', '\u{A3}']);
+    let text = text.trim_end_matches(['%', ' ']);
+    text.starts_with(|c: char| c.is_ascii_digit())
+        && text
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '.' | ',' | '_' | '\'' | ' '))
+}
+
+/// Right-align columns of numbers in `rows` which have no explicit
+/// alignment in `alignments`.
+///
+/// A column has numbers if all its non-empty cells below the `head_rows` are
+/// numbers, and it has at least one such cell.
+pub(crate) fn align_numbers(
+    rows: &[Vec<String>],
+    head_rows: usize,
+    alignments: &[Alignment],
+) -> Vec<Alignment> {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    (0..columns.max(alignments.len()))
+        .map(|column| match alignments.get(column) {
+            Some(Alignment::None) | None => {
+                let mut cells = rows
+                    .iter()
+                    .skip(head_rows)
+                    .filter_map(|row| row.get(column))
+                    .map(|cell| plain_text(cell))
+                    .filter(|text| !text.trim().is_empty())
+                    .peekable();
+                if cells.peek().is_some() && cells.all(|text| is_number(&text)) {
+                    Alignment::Right
+                } else {
+                    Alignment::None
+                }
+            }
+            Some(&alignment) => alignment,
+        })
+        .collect()
+}
+
+/// Styled `text` without escape sequences.
+pub(crate) fn plain_text(text: &str) -> String {
+    pieces(text)
+        .into_iter()
+        .filter_map(|piece| match piece {
+            Piece::Cluster(cluster) => Some(cluster),
+            Piece::Escape(_) => None,
+        })
+        .collect()
+}
+
+/// Pad `line` of the given display `width` to `column` according to
+/// `alignment`.
+fn align(line: &str, width: usize, column: usize, alignment: Alignment) -> String {
+    let space = column.saturating_sub(width);
+    let (before, after) = match alignment {
+        Alignment::Right => (space, 0),
+        Alignment::Center => (space / 2, space - space / 2),
+        Alignment::Left | Alignment::None => (0, space),
+    };
+    format!("{}{}{}", " ".repeat(before), line, " ".repeat(after))
+}
+
+/// Whether a table of styled cells fits into `available` columns without
+/// breaking words in cells.
+pub(crate) fn fits(rows: &[Vec<String>], available: usize) -> bool {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let words = (0..columns)
+        .map(|column| {
+            rows.iter()
+                .filter_map(|row| row.get(column))
+                .map(|cell| longest_word(cell))
+                .max()
+                .unwrap_or(0)
+        })
+        .sum::<usize>();
+    words + 3 * columns < available
+}
+
+/// Whether a table of `columns` columns has room for one character in each
+/// column, besides borders and padding, in `available` columns.
+pub(crate) fn has_room(columns: usize, available: usize) -> bool {
+    3 * columns + 1 + columns <= available
+}
+
+/// Lay out a table of styled cells to fit into `available` columns.
+///
+/// The first `head_rows` of `rows` are the heading of the table, which we
+/// separate from the body by a rule.  Align cells with their own alignment
+/// from `cell_alignments`, if any, or with the alignment of their column from
+/// `alignments`.  Fit columns into their width hints from `hints`, if
+/// possible.  Draw borders with `border`, which styles a string of box
+/// drawing characters.
+///
+/// Return the lines of the table.
+pub(crate) fn layout<F>(
+    rows: &[Vec<String>],
+    head_rows: usize,
+    alignments: &[Alignment],
+    cell_alignments: &[Vec<Option<Alignment>>],
+    hints: &[Option<WidthHint>],
+    available: usize,
+    border: F,
+) -> Vec<String>
+where
+    F: Fn(&str) -> String,
+{
+    let columns = rows
+        .iter()
+        .map(Vec::len)
+        .max()
+        .unwrap_or(0)
+        .max(alignments.len())
+        .max(1);
+    // Every column takes three columns for borders and padding, and the
+    // table takes one more for the closing border.
+    let widths = column_widths(
+        rows,
+        columns,
+        available.saturating_sub(3 * columns + 1),
+        hints,
+    );
+    let vertical = border("\u{2502}");
+    let mut lines = vec![border(&rule(&widths, TOP))];
+    for (index, row) in rows.iter().enumerate() {
+        if 0 < index && index == head_rows {
+            lines.push(border(&rule(&widths, MIDDLE)));
+        }
+        let cells = widths
+            .iter()
+            .enumerate()
+            .map(|(column, &width)| {
+                row.get(column)
+                    .map_or_else(|| vec![String::new()], |cell| wrap(cell, width))
+            })
+            .collect::<Vec<_>>();
+        let height = cells.iter().map(Vec::len).max().unwrap_or(1);
+        for line_index in 0..height {
+            let mut line = vertical.clone();
+            for (column, cell) in cells.iter().enumerate() {
+                let text = cell.get(line_index).map_or("", String::as_str);
+                let alignment = cell_alignments
+                    .get(index)
+                    .and_then(|row| row.get(column).cloned().flatten())
+                    .or_else(|| alignments.get(column).cloned())
+                    .unwrap_or(Alignment::None);
+                line.push(' ');
+                line.push_str(&align(text, display_width(text), widths[column], alignment));
+                line.push(' ');
+                line.push_str(&vertical);
+            }
+            lines.push(line);
+        }
+    }
+    lines.push(border(&rule(&widths, BOTTOM)));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::theme::ColumnWidth;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn display_width_of_styled_text() {
+        assert_eq!(display_width("\x1b[1mfoo\x1b[0m bar"), 7);
+        assert_eq!(
+            display_width("\x1b]8;;http://example.com\x1b\\a\x1b]8;;\x07"),
+            1
+        );
+        assert_eq!(display_width("日本"), 4);
+    }
+
+    #[test]
+    fn wrap_plain_text() {
+        assert_eq!(wrap("foo bar baz", 7), vec!["foo bar", "baz"]);
+        assert_eq!(wrap("foobarbaz", 4), vec!["foob", "arba", "z"]);
+        assert_eq!(wrap("", 4), vec![""]);
+    }
+
+    #[test]
+    fn wrap_styled_text() {
+        assert_eq!(
+            wrap("\x1b[1mfoo bar\x1b[0m baz", 4),
+            vec!["\x1b[1mfoo\x1b[0m", "\x1b[1mbar\x1b[0m", "baz"]
+        );
+    }
+
+    #[test]
+    fn column_widths_within_available_space() {
+        let rows = vec![
+            vec!["a".to_string(), "foo bar".to_string()],
+            vec!["bb".to_string(), "x".to_string()],
+        ];
+        assert_eq!(column_widths(&rows, 2, 20, &[]), vec![2, 7]);
+        assert_eq!(column_widths(&rows, 2, 6, &[]), vec![2, 4]);
+        assert_eq!(column_widths(&rows, 3, 1, &[]), vec![1, 1, 1]);
+    }
+
+    #[test]
+    fn column_widths_with_hints() {
+        let rows = vec![vec![
+            "https://example.com/a/very/long/url".to_string(),
+            "Lorem ipsum dolor sit amet".to_string(),
+        ]];
+        let fixed = WidthHint::parse("width=50%");
+        let at_least = WidthHint::parse("min-width=20");
+        let at_most = WidthHint::parse("max-width=10");
+        // Without hints the URL takes the space, and the description breaks
+        assert_eq!(column_widths(&rows, 2, 40, &[]), vec![35, 5]);
+        assert_eq!(column_widths(&rows, 2, 40, &[None, fixed]), vec![20, 20]);
+        assert_eq!(column_widths(&rows, 2, 40, &[None, at_least]), vec![20, 20]);
+        assert_eq!(column_widths(&rows, 2, 40, &[at_most, None]), vec![10, 26]);
+        assert_eq!(column_widths(&rows, 2, 80, &[None, fixed]), vec![35, 40]);
+        // Shrink hinted columns as well if the table does not fit otherwise
+        assert_eq!(column_widths(&rows, 2, 10, &[None, at_least]), vec![1, 9]);
+    }
+
+    #[test]
+    fn fit_tables_without_breaking_words() {
+        let rows = vec![
+            vec!["Name".to_string(), "Default".to_string()],
+            vec!["columns".to_string(), "80".to_string()],
+        ];
+        // Two columns of seven characters each, and borders and padding
+        assert!(fits(&rows, 21));
+        assert!(!fits(&rows, 20));
+    }
+
+    #[test]
+    fn room_for_a_character_per_column() {
+        assert!(has_room(3, 13));
+        assert!(!has_room(3, 12));
+        assert!(has_room(20, 81));
+        assert!(!has_room(20, 80));
+    }
+
+    #[test]
+    fn layout_table() {
+        let rows = vec![
+            vec!["a".to_string(), "b".to_string()],
+            vec!["foo bar".to_string(), "1".to_string()],
+        ];
+        let lines = layout(
+            &rows,
+            1,
+            &[Alignment::Left, Alignment::Right],
+            &[],
+            &[],
+            13,
+            ToString::to_string,
+        );
+        assert_eq!(
+            lines,
+            vec![
+                "┌───────┬───┐",
+                "│ a     │ b │",
+                "├───────┼───┤",
+                "│ foo   │ 1 │",
+                "│ bar   │   │",
+                "└───────┴───┘",
+            ]
+        );
+    }
+
+    #[test]
+    fn override_alignment_of_cells() {
+        let rows = vec![
+            vec!["a".to_string(), "b".to_string()],
+            vec!["foo".to_string(), "1".to_string()],
+        ];
+        let cell_alignments = vec![
+            vec![Some(Alignment::Right)],
+            vec![None, Some(Alignment::Left)],
+        ];
+        let lines = layout(
+            &rows,
+            1,
+            &[Alignment::Left, Alignment::Right],
+            &cell_alignments,
+            &[],
+            20,
+            ToString::to_string,
+        );
+        assert_eq!(lines[1], "│   a │ b │");
+        assert_eq!(lines[3], "│ foo │ 1 │");
+    }
+
+    #[test]
+    fn take_attributes() {
+        let mut events = vec![Event::Text(Cow::Borrowed(" x {align=center} "))];
+        assert_eq!(
+            take_cell_attributes(&mut events),
+            Some(CellAttributes {
+                alignment: Some(Alignment::Center),
+                width: None,
+            })
+        );
+        assert_eq!(events, vec![Event::Text(Cow::Borrowed(" x "))]);
+        let mut events = vec![Event::Text(Cow::Borrowed("x {max-width=60% align=left}"))];
+        assert_eq!(
+            take_cell_attributes(&mut events),
+            Some(CellAttributes {
+                alignment: Some(Alignment::Left),
+                width: Some(WidthHint {
+                    min: None,
+                    max: Some(ColumnWidth::Percent(60)),
+                }),
+            })
+        );
+        for invalid in &[" {align=middle} ", "{width=wide}", "{}", "{color=red}"] {
+            let mut events = vec![Event::Text(Cow::Borrowed(*invalid))];
+            assert_eq!(take_cell_attributes(&mut events), None);
+            assert_eq!(events.len(), 1);
+        }
+    }
+
+    #[test]
+    fn align_columns_of_numbers() {
+        let rows = vec![
+            vec!["Name".to_string(), "Price".to_string(), "Tax".to_string()],
+            vec![
+                "Foo".to_string(),
+                "\x1b[1m$1,000.50\x1b[0m".to_string(),
+                "7 %".to_string(),
+            ],
+            vec!["Bar".to_string(), "".to_string(), "19".to_string()],
+        ];
+        assert_eq!(
+            align_numbers(
+                &rows,
+                1,
+                &[Alignment::None, Alignment::None, Alignment::Left]
+            ),
+            vec![Alignment::None, Alignment::Right, Alignment::Left]
+        );
+    }
+}
@@ -30,4 +30,48 @@ impl AnsiStyle {
     ) -> Result<()> {
         write!(write, "{}", style.paint(text.as_ref()))
     }
+
+    /// Write styled text with a dotted underline to the given writer.
+    ///
+    /// Terminals without support for dotted underlines show a plain underline
+    /// or no underline at all.  End only the underline after `text`, and
+    /// reset `style` just like `write_styled` does.
+    pub fn write_dotted_underlined<W: Write, V: AsRef<str>>(
+        &self,
+        write: &mut W,
+        style: &Style,
+        text: V,
+    ) -> Result<()> {
+        write!(
+            write,
+            "{}\x1b[4:4m{}\x1b[24m{}",
+            style.prefix(),
+            text.as_ref(),
+            style.suffix()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ansi_term::Colour;
+    use pretty_assertions::assert_eq;
+
+    fn dotted(style: Style, text: &str) -> String {
+        let mut buffer = Vec::new();
+        AnsiStyle
+            .write_dotted_underlined(&mut buffer, &style, text)
+            .unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn dotted_underline_keeps_outer_style() {
+        assert_eq!(dotted(Style::new(), "CSS"), "\x1b[4:4mCSS\x1b[24m");
+        assert_eq!(
+            dotted(Colour::Red.bold(), "CSS"),
+            "\x1b[1;31m\x1b[4:4mCSS\x1b[24m\x1b[0m"
+        );
+    }
 }
@@ -0,0 +1,149 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The EXIF orientation of JPEG images.
+//!
+//! Cameras, eg, of phones, store photos as the sensor saw them, and tell
+//! the orientation of the camera in the EXIF metadata.  We need to rotate
+//! such photos ourselves, because the image crate ignores EXIF metadata.
+
+use image::DynamicImage;
+
+/// The EXIF tag of the orientation.
+const ORIENTATION_TAG: u16 = 0x0112;
+
+/// Read an unsigned 16 bit integer at `offset` of `data`.
+fn read_u16(data: &[u8], offset: usize, little_endian: bool) -> Option<u16> {
+    let bytes = [*data.get(offset)?, *data.get(offset + 1)?];
+    Some(if little_endian {
+        u16::from_le_bytes(bytes)
+    } else {
+        u16::from_be_bytes(bytes)
+    })
+}
+
+/// Read an unsigned 32 bit integer at `offset` of `data`.
+fn read_u32(data: &[u8], offset: usize, little_endian: bool) -> Option<u32> {
+    let high = read_u16(data, offset, little_endian)?;
+    let low = read_u16(data, offset + 2, little_endian)?;
+    Some(if little_endian {
+        u32::from(low) << 16 | u32::from(high)
+    } else {
+        u32::from(high) << 16 | u32::from(low)
+    })
+}
+
+/// Get the orientation from the TIFF structure of EXIF data in `tiff`.
+fn tiff_orientation(tiff: &[u8]) -> Option<u16> {
+    let little_endian = match tiff.get(..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let ifd = read_u32(tiff, 4, little_endian)? as usize;
+    let entries = read_u16(tiff, ifd, little_endian)? as usize;
+    (0..entries)
+        .map(|index| ifd + 2 + index * 12)
+        .find(|&entry| read_u16(tiff, entry, little_endian) == Some(ORIENTATION_TAG))
+        .and_then(|entry| read_u16(tiff, entry + 8, little_endian))
+}
+
+/// Get the EXIF orientation of the JPEG image in `contents`.
+///
+/// Return `None` if `contents` is no JPEG image or has no orientation.
+pub(crate) fn orientation(contents: &[u8]) -> Option<u16> {
+    if !contents.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut offset = 2;
+    loop {
+        let marker = *contents.get(offset + 1)?;
+        // Stop at the image data
+        if contents[offset] != 0xFF || marker == 0xDA {
+            return None;
+        }
+        let length = read_u16(contents, offset + 2, false)? as usize;
+        let segment = contents.get(offset + 4..offset + 2 + length)?;
+        if marker == 0xE1 && segment.starts_with(b"Exif\0\0") {
+            return tiff_orientation(&segment[6..]);
+        }
+        offset += 2 + length;
+    }
+}
+
+/// Turn `image` upright according to its EXIF `orientation`.
+///
+/// Leave images with unknown orientations alone.
+pub(crate) fn apply_orientation(image: DynamicImage, orientation: u16) -> DynamicImage {
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, Rgba, RgbaImage};
+    use pretty_assertions::assert_eq;
+
+    /// A JPEG header with an EXIF segment with the given `orientation`.
+    fn jpeg_with_orientation(orientation: u8, little_endian: bool) -> Vec<u8> {
+        // An IFD with a single SHORT entry for the orientation
+        let mut tiff = if little_endian {
+            b"II\x2a\0\x08\0\0\0\x01\0\x12\x01\x03\0\x01\0\0\0".to_vec()
+        } else {
+            b"MM\0\x2a\0\0\0\x08\0\x01\x01\x12\0\x03\0\0\0\x01".to_vec()
+        };
+        let value = if little_endian {
+            [orientation, 0, 0, 0]
+        } else {
+            [0, orientation, 0, 0]
+        };
+        tiff.extend_from_slice(&value);
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE0, 0, 4, 0, 0, 0xFF, 0xE1, 0];
+        jpeg.push((2 + 6 + tiff.len()) as u8);
+        jpeg.extend_from_slice(b"Exif\0\0");
+        jpeg.extend_from_slice(&tiff);
+        jpeg.extend_from_slice(&[0xFF, 0xDA]);
+        jpeg
+    }
+
+    #[test]
+    fn read_orientation_of_jpeg() {
+        assert_eq!(orientation(&jpeg_with_orientation(6, true)), Some(6));
+        assert_eq!(orientation(&jpeg_with_orientation(8, false)), Some(8));
+        assert_eq!(orientation(&[0xFF, 0xD8, 0xFF, 0xDA]), None);
+        assert_eq!(orientation(b"\x89PNG\r\n"), None);
+    }
+
+    #[test]
+    fn turn_images_upright() {
+        let mut image = RgbaImage::from_pixel(2, 1, Rgba([0, 0, 0, 255]));
+        image.put_pixel(1, 0, Rgba([255, 255, 255, 255]));
+        let image = DynamicImage::ImageRgba8(image);
+        let upright = apply_orientation(image.clone(), 6);
+        assert_eq!(upright.dimensions(), (1, 2));
+        assert_eq!(upright.get_pixel(0, 1), Rgba([255, 255, 255, 255]));
+        let upright = apply_orientation(image.clone(), 2);
+        assert_eq!(upright.get_pixel(0, 0), Rgba([255, 255, 255, 255]));
+        assert_eq!(apply_orientation(image, 1).dimensions(), (2, 1));
+    }
+}
@@ -16,61 +16,228 @@
 
 use super::ansi::AnsiStyle;
 use ansi_term::Colour;
+use std::collections::BTreeMap;
 use std::io::{Result, Write};
-use syntect::highlighting::{FontStyle, Style};
+use std::str::FromStr;
+use syntect::highlighting::{
+    Color, FontStyle, ScopeSelectors, Style, StyleModifier, Theme, ThemeItem, ThemeSet,
+};
 
-/// Write regions as ANSI 8-bit coloured text.
+/// The name of the highlighting theme in the colours of the terminal.
+pub const ANSI_THEME: &str = "ansi";
+
+/// Scopes and their colours in the 16 colour palette of the terminal.
+///
+/// `None` leaves the default colour of the terminal.
+const ANSI_SCOPES: &[(&str, Option<u8>, Option<FontStyle>)] = &[
+    ("comment", Some(8), Some(FontStyle::ITALIC)),
+    ("string, constant.character", Some(2), None),
+    ("constant", Some(5), None),
+    ("keyword, storage", Some(4), None),
+    (
+        "entity.name.type, entity.name.class, support.type, support.class",
+        Some(3),
+        None,
+    ),
+    ("entity.name.function, support.function", Some(6), None),
+    ("entity.name.tag", Some(4), None),
+    ("entity.other.attribute-name", Some(3), None),
+    ("invalid", Some(1), None),
+    ("markup.heading", Some(4), Some(FontStyle::BOLD)),
+    ("markup.bold", None, Some(FontStyle::BOLD)),
+    ("markup.italic", None, Some(FontStyle::ITALIC)),
+    ("markup.underline.link", Some(4), None),
+    ("markup.inserted", Some(2), None),
+    ("markup.deleted", Some(1), None),
+];
+
+/// A colour from the palette of the terminal.
+///
+/// Like bat we store the `index` of the colour in the red channel of a
+/// transparent colour.
+fn palette_colour(index: u8) -> Color {
+    Color {
+        r: index,
+        g: 0,
+        b: 0,
+        a: 0,
+    }
+}
+
+/// The default colour of the terminal.
+///
+/// Like bat we mark the default colour with an alpha value of 1.
+const DEFAULT_COLOUR: Color = Color {
+    r: 0,
+    g: 0,
+    b: 0,
+    a: 1,
+};
+
+/// A highlighting theme in the colours of the terminal.
+///
+/// Instead of fixed colours this theme uses the 16 colour palette of the
+/// terminal, so that highlighting matches the colour scheme of the terminal.
+pub fn ansi_theme() -> Theme {
+    let mut theme = Theme {
+        name: Some(ANSI_THEME.to_string()),
+        ..Theme::default()
+    };
+    theme.settings.foreground = Some(DEFAULT_COLOUR);
+    theme.scopes = ANSI_SCOPES
+        .iter()
+        .map(|&(scopes, colour, font_style)| ThemeItem {
+            scope: ScopeSelectors::from_str(scopes).expect("Invalid built-in scope selectors"),
+            style: StyleModifier {
+                foreground: colour.map(palette_colour),
+                background: None,
+                font_style,
+            },
+        })
+        .collect();
+    theme
+}
+
+/// All highlighting themes by their names.
+///
+/// The default themes of syntect, and our theme in the colours of the terminal.
+pub fn themes() -> BTreeMap<String, Theme> {
+    let mut themes = ThemeSet::load_defaults().themes;
+    themes.insert(ANSI_THEME.to_string(), ansi_theme());
+    themes
+}
+
+/// Convert a syntect `style` to an ANSI style.
 ///
 /// We use this function to simplify syntax highlighting to 8-bit ANSI values
 /// which every theme provides.  Contrary to 24 bit colours this gives us a good
 /// guarantee that highlighting works with any terminal colour theme, whether
 /// light or dark, and saves us all the hassle of mismatching colours.
 ///
-/// We assume Solarized colours here: Solarized cleanly maps to 8-bit ANSI
-/// colours so we can safely map its RGB colour values back to ANSI colours.  We
-/// do so for all accent colours, but leave "base*" colours alone: Base colours
-/// change depending on light or dark Solarized; to address both light and dark
-/// backgrounds we must map all base colours to the default terminal colours.
+/// We take colours of the terminal palette, see `ansi_theme`, as they are.
+/// Besides we assume Solarized colours here: Solarized cleanly maps to 8-bit
+/// ANSI colours so we can safely map its RGB colour values back to ANSI
+/// colours.  We do so for all accent colours, but leave "base*" colours
+/// alone: Base colours change depending on light or dark Solarized; to address
+/// both light and dark backgrounds we must map all base colours to the default
+/// terminal colours.  We write all other colours as 24 bit colours.
 ///
 /// Furthermore we completely ignore any background colour settings, to avoid
 /// conflicts with the terminal colour themes.
+pub fn to_ansi_style(style: Style) -> ansi_term::Style {
+    let fg = style.foreground;
+    let mut ansi_style = ansi_term::Style::new();
+    match fg.a {
+        0 => ansi_style.foreground = Some(to_palette_colour(fg.r)),
+        1 => ansi_style.foreground = None,
+        _ => ansi_style.foreground = solarized_colour(fg),
+    }
+    let font = style.font_style;
+    ansi_style.is_bold = font.contains(FontStyle::BOLD);
+    ansi_style.is_italic = font.contains(FontStyle::ITALIC);
+    ansi_style.is_underline = font.contains(FontStyle::UNDERLINE);
+    ansi_style
+}
+
+/// The ANSI colour for the colour with `index` in the palette.
+fn to_palette_colour(index: u8) -> Colour {
+    match index {
+        0 => Colour::Black,
+        1 => Colour::Red,
+        2 => Colour::Green,
+        3 => Colour::Yellow,
+        4 => Colour::Blue,
+        5 => Colour::Purple,
+        6 => Colour::Cyan,
+        7 => Colour::White,
+        index => Colour::Fixed(index),
+    }
+}
+
+/// The ANSI colour for the Solarized colour `fg`, or the 24 bit colour for
+/// other colours.
+///
+/// `None` for Solarized base colours, to use the default colour instead.
+fn solarized_colour(fg: Color) -> Option<Colour> {
+    match (fg.r, fg.g, fg.b) {
+        // base03, base02, base01, base00, base0, base1, base2, and base3
+        (0x00, 0x2b, 0x36)
+        | (0x07, 0x36, 0x42)
+        | (0x58, 0x6e, 0x75)
+        | (0x65, 0x7b, 0x83)
+        | (0x83, 0x94, 0x96)
+        | (0x93, 0xa1, 0xa1)
+        | (0xee, 0xe8, 0xd5)
+        | (0xfd, 0xf6, 0xe3) => None,
+        (0xb5, 0x89, 0x00) => Some(Colour::Yellow),
+        (0xcb, 0x4b, 0x16) => Some(Colour::Fixed(9)), // Bright red
+        (0xdc, 0x32, 0x2f) => Some(Colour::Red),
+        (0xd3, 0x36, 0x82) => Some(Colour::Purple),
+        (0x6c, 0x71, 0xc4) => Some(Colour::Fixed(13)), // Bright purple
+        (0x26, 0x8b, 0xd2) => Some(Colour::Blue),
+        (0x2a, 0xa1, 0x98) => Some(Colour::Cyan),
+        (0x85, 0x99, 0x00) => Some(Colour::Green),
+        (r, g, b) => Some(Colour::RGB(r, g, b)),
+    }
+}
+
+/// Write regions as ANSI 8-bit coloured text.
+///
+/// See `to_ansi_style` for how we map syntect styles to ANSI styles.
 pub fn write_as_ansi<W: Write>(
     writer: &mut W,
     ansi: &AnsiStyle,
     regions: &[(Style, &str)],
 ) -> Result<()> {
     for &(style, text) in regions {
-        let rgb = {
-            let fg = style.foreground;
-            (fg.r, fg.g, fg.b)
-        };
-        let mut ansi_style = ansi_term::Style::new();
-        match rgb {
-            // base03, base02, base01, base00, base0, base1, base2, and base3
-            (0x00, 0x2b, 0x36)
-            | (0x07, 0x36, 0x42)
-            | (0x58, 0x6e, 0x75)
-            | (0x65, 0x7b, 0x83)
-            | (0x83, 0x94, 0x96)
-            | (0x93, 0xa1, 0xa1)
-            | (0xee, 0xe8, 0xd5)
-            | (0xfd, 0xf6, 0xe3) => ansi_style.foreground = None,
-            (0xb5, 0x89, 0x00) => ansi_style.foreground = Some(Colour::Yellow),
-            (0xcb, 0x4b, 0x16) => ansi_style.foreground = Some(Colour::Fixed(9)), // Bright red
-            (0xdc, 0x32, 0x2f) => ansi_style.foreground = Some(Colour::Red),
-            (0xd3, 0x36, 0x82) => ansi_style.foreground = Some(Colour::Purple),
-            (0x6c, 0x71, 0xc4) => ansi_style.foreground = Some(Colour::Fixed(13)), // Bright purple
-            (0x26, 0x8b, 0xd2) => ansi_style.foreground = Some(Colour::Blue),
-            (0x2a, 0xa1, 0x98) => ansi_style.foreground = Some(Colour::Cyan),
-            (0x85, 0x99, 0x00) => ansi_style.foreground = Some(Colour::Green),
-            (r, g, b) => panic!("Unexpected RGB colour: #{:2>0x}{:2>0x}{:2>0x}", r, g, b),
-        };
-        let font = style.font_style;
-        ansi_style.is_bold = font.contains(FontStyle::BOLD);
-        ansi_style.is_italic = font.contains(FontStyle::ITALIC);
-        ansi_style.is_underline = font.contains(FontStyle::UNDERLINE);
-        ansi.write_styled(writer, &ansi_style, text)?;
+        ansi.write_styled(writer, &to_ansi_style(style), text)?;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use syntect::easy::HighlightLines;
+    use syntect::parsing::SyntaxSet;
+
+    #[test]
+    fn highlight_in_the_colours_of_the_terminal() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let syntax = syntax_set.find_syntax_by_token("rust").unwrap();
+        let theme = ansi_theme();
+        let mut highlighter = HighlightLines::new(syntax, &theme);
+        let styles = highlighter
+            .highlight("let s = \"foo\"; // bar\n", &syntax_set)
+            .into_iter()
+            .map(|(style, text)| (to_ansi_style(style), text))
+            .collect::<Vec<_>>();
+        let style_of = |text: &str| styles.iter().find(|(_, t)| t.contains(text)).unwrap().0;
+        assert_eq!(style_of("let"), ansi_term::Style::new().fg(Colour::Blue));
+        assert_eq!(style_of("foo"), ansi_term::Style::new().fg(Colour::Green));
+        assert_eq!(style_of(";"), ansi_term::Style::new());
+        assert_eq!(
+            style_of(" bar"),
+            ansi_term::Style::new().fg(Colour::Fixed(8)).italic()
+        );
+    }
+
+    #[test]
+    fn other_colours_as_24_bit_colours() {
+        let style = Style {
+            foreground: Color {
+                r: 0x12,
+                g: 0x34,
+                b: 0x56,
+                a: 0xFF,
+            },
+            ..Style::default()
+        };
+        assert_eq!(
+            to_ansi_style(style),
+            ansi_term::Style::new().fg(Colour::RGB(0x12, 0x34, 0x56))
+        );
+    }
+}
@@ -16,7 +16,7 @@
 
 use mime::Mime;
 use std::io::prelude::*;
-use std::io::{Error, ErrorKind};
+use std::io::Error;
 use std::process::*;
 
 pub fn detect_mime_type(buffer: &[u8]) -> Result<Mime, failure::Error> {
@@ -42,14 +42,11 @@ pub fn detect_mime_type(buffer: &[u8]) -> Result<Mime, failure::Error> {
             .parse()
             .map_err(Into::into)
     } else {
-        Err(Error::new(
-            ErrorKind::Other,
-            format!(
-                "file --brief --mime-type failed with status {}: {}",
-                output.status,
-                String::from_utf8_lossy(&output.stderr)
-            ),
-        )
+        Err(Error::other(format!(
+            "file --brief --mime-type failed with status {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        ))
         .into())
     }
 }
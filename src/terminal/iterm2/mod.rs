@@ -20,6 +20,9 @@
 //! See <https://www.iterm2.com> for more information.
 
 use super::osc::write_osc;
+use super::TerminalSize;
+use crate::environment::Environment;
+use crate::resources::read_url;
 use failure::Error;
 use std::ffi::OsStr;
 use std::io::{self, Write};
@@ -30,9 +33,10 @@ mod magic;
 
 pub mod svg;
 
-/// Whether we run inside iTerm2 or not.
-pub fn is_iterm2() -> bool {
-    std::env::var("TERM_PROGRAM")
+/// Whether we run inside iTerm2 in `environment` or not.
+pub fn is_iterm2(environment: &Environment) -> bool {
+    environment
+        .var("TERM_PROGRAM")
         .map(|value| value.contains("iTerm.app"))
         .unwrap_or(false)
 }
@@ -50,74 +54,45 @@ impl ITerm2Marks {
 /// Iterm2 inline iamges.
 pub struct ITerm2Images;
 
-/// Read the contents of the given `url` if supported.
-///
-/// Fail if we don’t know how to read from `url`, or if we fail to read from
-/// URL.
-///
-/// We currently support `file:` URLs which the underlying operation system can
-/// read (local on UNIX, UNC paths on Windows), and HTTP(S) URLs if enabled at
-/// build system.
-fn read_url(url: &Url) -> Result<Vec<u8>, Error> {
-    use std::fs::File;
-    use std::io::prelude::*;
-    use std::io::{Error, ErrorKind};
-
-    match url.scheme() {
-        "file" => match url.to_file_path() {
-            Ok(path) => {
-                let mut buffer = Vec::new();
-                File::open(path)?.read_to_end(&mut buffer)?;
-                Ok(buffer)
-            }
-            Err(_) => Err(Error::new(
-                ErrorKind::InvalidInput,
-                format!("Remote file: URL {} not supported", url),
-            )
-            .into()),
-        },
-        #[cfg(feature = "remote_resources")]
-        "http" | "https" => {
-            let mut response = reqwest::get(url.clone())?;
-            if response.status().is_success() {
-                let mut buffer = Vec::new();
-                response.read_to_end(&mut buffer)?;
-                Ok(buffer)
-            } else {
-                Err(Error::new(
-                    ErrorKind::Other,
-                    format!("HTTP error status {} by GET {}", response.status(), url),
-                )
-                .into())
-            }
-        }
-        _ => Err(Error::new(
-            ErrorKind::InvalidInput,
-            format!("Protocol of URL {} not supported", url),
-        )
-        .into()),
-    }
-}
-
 impl ITerm2Images {
     /// Write an iterm2 inline image command to `writer`.
     ///
     /// `name` is the local file name and `contents` are the contents of the
     /// given file.
+    ///
+    /// If we know the dimensions of the image give its size in character
+    /// cells explicitly, to make iTerm2 continue text at the right line after
+    /// the image.  Otherwise let iTerm2 size the image.
+    ///
+    /// iTerm2 leaves the cursor after the last line of the image, so we add a
+    /// newline to move the cursor to the start of the line below the image.
+    ///
+    /// Return the number of columns of the image, if we know its size.
     pub fn write_inline_image<W: Write, S: AsRef<OsStr>>(
         &self,
         writer: &mut W,
+        max_size: TerminalSize,
         name: S,
         contents: &[u8],
-    ) -> io::Result<()> {
+    ) -> io::Result<Option<usize>> {
+        let cells = immeta::load_from_buf(contents).ok().map(|m| {
+            let d = m.dimensions();
+            max_size.image_cells(d.width, d.height)
+        });
+        let size = cells
+            .map(|(columns, lines)| format!("width={};height={};", columns, lines))
+            .unwrap_or_default();
         write_osc(
             writer,
             &format!(
-                "1337;File=name={};inline=1:{}",
+                "1337;File=name={};{}inline=1:{}",
                 base64::encode(name.as_ref().as_bytes()),
+                size,
                 base64::encode(contents)
             ),
-        )
+        )?;
+        writeln!(writer)?;
+        Ok(cells.map(|(columns, _)| columns))
     }
 
     /// Read `url` and render to an image if necessary.
@@ -125,12 +100,19 @@ impl ITerm2Images {
     /// Render the binary content of the (rendered) image or an IO error if
     /// reading or rendering failed.
     pub fn read_and_render(&self, url: &Url) -> Result<Vec<u8>, Error> {
-        let contents = read_url(&url)?;
-        let mime = magic::detect_mime_type(&contents)?;
+        self.render(&read_url(url)?)
+    }
+
+    /// Render `contents` to an image if necessary.
+    ///
+    /// Return the binary content of the (rendered) image or an error if
+    /// rendering failed.
+    pub fn render(&self, contents: &[u8]) -> Result<Vec<u8>, Error> {
+        let mime = magic::detect_mime_type(contents)?;
         if mime.type_() == mime::IMAGE && mime.subtype().as_str() == "svg" {
-            svg::render_svg(&contents).map_err(Into::into)
+            svg::render_svg(contents).map_err(Into::into)
         } else {
-            Ok(contents)
+            Ok(contents.to_vec())
         }
     }
 }
@@ -138,29 +120,29 @@ impl ITerm2Images {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use pretty_assertions::assert_eq;
-
-    #[test]
-    fn read_url_with_http_url_fails_when_status_404() {
-        let url = "https://eu.httpbin.org/status/404"
-            .parse::<url::Url>()
-            .unwrap();
-        let result = read_url(&url);
-        assert!(result.is_err(), "Unexpected success: {:?}", result);
-        let error = result.unwrap_err().to_string();
-        assert_eq!(
-            error,
-            "HTTP error status 404 Not Found by GET https://eu.httpbin.org/status/404"
-        )
-    }
 
     #[test]
-    fn read_url_with_http_url_returns_content_when_status_200() {
-        let url = "https://eu.httpbin.org/bytes/100"
-            .parse::<url::Url>()
+    fn write_inline_image_with_size_in_cells() {
+        let contents = include_bytes!("../../../sample/rust-logo-128x128.png");
+        let size = TerminalSize {
+            width: 80,
+            height: 24,
+            pixels: Some(crate::terminal::PixelSize {
+                width: 800,
+                height: 480,
+            }),
+        };
+        let mut output = Vec::new();
+        let columns = ITerm2Images
+            .write_inline_image(&mut output, size, "logo.png", contents)
             .unwrap();
-        let result = read_url(&url);
-        assert!(result.is_ok(), "Unexpected error: {:?}", result);
-        assert_eq!(result.unwrap().len(), 100);
+        assert_eq!(columns, Some(13));
+        let output = String::from_utf8(output).unwrap();
+        assert!(
+            output.starts_with("\x1b]1337;File=name=bG9nby5wbmc=;width=13;height=7;inline=1:"),
+            "{:?}",
+            &output[..80]
+        );
+        assert!(output.ends_with("\x07\n"));
     }
 }
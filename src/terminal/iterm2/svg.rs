@@ -15,7 +15,7 @@
 //! SVG "rendering" for mdcat.
 
 use std::io::prelude::*;
-use std::io::{Error, ErrorKind, Result};
+use std::io::{Error, Result};
 use std::process::{Command, Stdio};
 
 /// Render an SVG image to a PNG pixel graphic for display.
@@ -44,13 +44,10 @@ fn render_svg_with_rsvg_convert(svg: &[u8]) -> Result<Vec<u8>> {
     if output.status.success() {
         Ok(output.stdout)
     } else {
-        Err(Error::new(
-            ErrorKind::Other,
-            format!(
-                "rsvg-convert failed with status {}: {}",
-                output.status,
-                String::from_utf8_lossy(&output.stderr)
-            ),
-        ))
+        Err(Error::other(format!(
+            "rsvg-convert failed with status {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        )))
     }
 }
@@ -16,7 +16,11 @@
 
 // Support modules for terminal writing.
 
+use crate::environment::Environment;
+
 mod ansi;
+#[cfg(feature = "sixel")]
+mod exif;
 pub mod highlighting;
 mod size;
 
@@ -24,11 +28,15 @@ mod size;
 mod iterm2;
 #[cfg(any(feature = "osc8_links", feature = "iterm2"))]
 mod osc;
+#[cfg(feature = "sixel")]
+pub(crate) mod sixel;
 #[cfg(feature = "terminology")]
 mod terminology;
 
 pub use self::ansi::AnsiStyle;
-pub use self::size::Size as TerminalSize;
+#[cfg(all(unix, feature = "osc8_links"))]
+pub(crate) use self::osc::gethostname;
+pub use self::size::{PixelSize, Size as TerminalSize};
 
 /// The capability of basic styling.
 pub enum StyleCapability {
@@ -36,6 +44,8 @@ pub enum StyleCapability {
     None,
     /// The terminal supports ANSI styles.
     Ansi(AnsiStyle),
+    /// The terminal supports ANSI font attributes, but no colours.
+    Monochrome(AnsiStyle),
 }
 
 /// How the terminal supports inline links.
@@ -68,6 +78,9 @@ pub enum ImageCapability {
     /// The terminal understands the iterm2 way of inline images.
     #[cfg(feature = "iterm2")]
     ITerm2(self::iterm2::ITerm2Images),
+    /// The terminal understands sixel graphics.
+    #[cfg(feature = "sixel")]
+    Sixel(self::sixel::SixelImages),
 }
 
 /// The capabilities of a terminal.
@@ -107,46 +120,148 @@ impl TerminalCapabilities {
         }
     }
 
+    /// Disable colours but keep font attributes.
+    ///
+    /// Does nothing if the terminal supports no styles at all.
+    pub fn monochrome(self) -> TerminalCapabilities {
+        let style = match self.style {
+            StyleCapability::Ansi(ansi) => StyleCapability::Monochrome(ansi),
+            other => other,
+        };
+        TerminalCapabilities { style, ..self }
+    }
+
     /// Detect the capabilities of the current terminal.
     pub fn detect() -> TerminalCapabilities {
-        // Pattern matching lets use feature-switch branches, depending on
-        // enabled terminal support.  In an if chain we can't do this, so that's
-        // why we have this weird match here.  Note: Don't use true here because
-        // that makes clippy complain.
-        match 1 {
-            #[cfg(feature = "iterm2")]
-            _ if self::iterm2::is_iterm2() => TerminalCapabilities {
-                name: "iTerm2".to_string(),
-                style: StyleCapability::Ansi(AnsiStyle),
-                links: LinkCapability::OSC8(self::osc::OSC8Links::for_localhost()),
-                image: ImageCapability::ITerm2(self::iterm2::ITerm2Images),
-                marks: MarkCapability::ITerm2(self::iterm2::ITerm2Marks),
-            },
-            #[cfg(feature = "terminology")]
-            _ if self::terminology::is_terminology() => TerminalCapabilities {
-                name: "Terminology".to_string(),
-                style: StyleCapability::Ansi(AnsiStyle),
-                links: LinkCapability::OSC8(self::osc::OSC8Links::for_localhost()),
-                image: ImageCapability::Terminology(self::terminology::TerminologyImages),
-                marks: MarkCapability::None,
-            },
-            #[cfg(feature = "vte50")]
-            _ if get_vte_version().filter(|&v| v >= (50, 0)).is_some() => TerminalCapabilities {
-                name: "VTE 50".to_string(),
-                style: StyleCapability::Ansi(AnsiStyle),
-                links: LinkCapability::OSC8(self::osc::OSC8Links::for_localhost()),
-                image: ImageCapability::None,
-                marks: MarkCapability::None,
-            },
-            _ => TerminalCapabilities::ansi(),
+        TerminalCapabilities::detect_in(&Environment::for_local_directory("."))
+    }
+
+    /// Detect the capabilities of the terminal in the given `environment`.
+    ///
+    /// Detect every capability on its own, so that we use whatever a terminal
+    /// supports even if we do not know the terminal as a whole.
+    pub fn detect_in(environment: &Environment) -> TerminalCapabilities {
+        TerminalCapabilities {
+            name: detect_name(environment),
+            style: StyleCapability::Ansi(AnsiStyle),
+            links: detect_links(environment),
+            image: detect_image(environment),
+            marks: detect_marks(environment),
+        }
+    }
+}
+
+// Pattern matching lets use feature-switch branches, depending on enabled
+// terminal support.  In an if chain we can't do this, so that's why we have
+// these weird matches below.  Note: Don't use true here because that makes
+// clippy complain.
+
+/// Detect the name of the terminal in `environment`.
+fn detect_name(environment: &Environment) -> String {
+    #[cfg(not(any(
+        feature = "iterm2",
+        feature = "terminology",
+        feature = "vte50",
+        feature = "sixel"
+    )))]
+    {
+        // Mark environment as used if no terminal support is enabled
+        let _ = environment;
+    }
+    let name = match 1 {
+        #[cfg(feature = "iterm2")]
+        _ if self::iterm2::is_iterm2(environment) => "iTerm2",
+        #[cfg(feature = "terminology")]
+        _ if self::terminology::is_terminology(environment) => "Terminology",
+        #[cfg(feature = "vte50")]
+        _ if is_vte50(environment) => "VTE 50",
+        #[cfg(feature = "sixel")]
+        _ if self::sixel::is_sixel_terminal(environment) => "Sixel",
+        _ => "Ansi",
+    };
+    name.to_string()
+}
+
+/// Detect how the terminal in `environment` supports inline links.
+fn detect_links(environment: &Environment) -> LinkCapability {
+    #[cfg(not(feature = "osc8_links"))]
+    {
+        // Mark environment as used if no link support is enabled
+        let _ = environment;
+    }
+    match 1 {
+        #[cfg(feature = "iterm2")]
+        _ if self::iterm2::is_iterm2(environment) => LinkCapability::OSC8(self::osc::OSC8Links),
+        #[cfg(feature = "terminology")]
+        _ if self::terminology::is_terminology(environment) => {
+            LinkCapability::OSC8(self::osc::OSC8Links)
+        }
+        #[cfg(feature = "vte50")]
+        _ if is_vte50(environment) => LinkCapability::OSC8(self::osc::OSC8Links),
+        #[cfg(feature = "osc8_links")]
+        _ if environment.var("TERM_PROGRAM") == Some("WezTerm")
+            || environment
+                .var("TERM")
+                .is_some_and(|term| term.starts_with("foot")) =>
+        {
+            LinkCapability::OSC8(self::osc::OSC8Links)
+        }
+        _ => LinkCapability::None,
+    }
+}
+
+/// Detect how the terminal in `environment` shows images inline.
+fn detect_image(environment: &Environment) -> ImageCapability {
+    #[cfg(not(any(feature = "iterm2", feature = "terminology", feature = "sixel")))]
+    {
+        // Mark environment as used if no image support is enabled
+        let _ = environment;
+    }
+    match 1 {
+        #[cfg(feature = "iterm2")]
+        _ if self::iterm2::is_iterm2(environment) => {
+            ImageCapability::ITerm2(self::iterm2::ITerm2Images)
+        }
+        #[cfg(feature = "terminology")]
+        _ if self::terminology::is_terminology(environment) => {
+            ImageCapability::Terminology(self::terminology::TerminologyImages)
+        }
+        #[cfg(feature = "sixel")]
+        _ if self::sixel::is_sixel_terminal(environment) => {
+            ImageCapability::Sixel(self::sixel::SixelImages)
         }
+        _ => ImageCapability::None,
     }
 }
 
-/// Get the version of the underlying VTE terminal if any.
+/// Detect how the terminal in `environment` sets marks.
+fn detect_marks(environment: &Environment) -> MarkCapability {
+    #[cfg(not(feature = "iterm2"))]
+    {
+        // Mark environment as used if no mark support is enabled
+        let _ = environment;
+    }
+    match 1 {
+        #[cfg(feature = "iterm2")]
+        _ if self::iterm2::is_iterm2(environment) => {
+            MarkCapability::ITerm2(self::iterm2::ITerm2Marks)
+        }
+        _ => MarkCapability::None,
+    }
+}
+
+/// Whether we run in a terminal based on VTE 50 or newer in `environment`.
+#[cfg(feature = "vte50")]
+fn is_vte50(environment: &Environment) -> bool {
+    get_vte_version(environment)
+        .filter(|&v| v >= (50, 0))
+        .is_some()
+}
+
+/// Get the version of the underlying VTE terminal in `environment` if any.
 #[cfg(feature = "vte50")]
-pub fn get_vte_version() -> Option<(u8, u8)> {
-    std::env::var("VTE_VERSION").ok().and_then(|value| {
+pub fn get_vte_version(environment: &Environment) -> Option<(u8, u8)> {
+    environment.var("VTE_VERSION").and_then(|value| {
         value[..2]
             .parse::<u8>()
             .into_iter()
@@ -35,34 +35,32 @@ pub fn write_osc<W: Write>(writer: &mut W, command: &str) -> Result<()> {
 /// It may panic! if the internal buffer for the hostname is too small, but we
 /// use a reasonably large buffer, so we consider any panics from this function
 /// as bug which you should report.
+///
+/// [gethostname]: http://pubs.opengroup.org/onlinepubs/009695399/functions/gethostname.html
 #[cfg(all(unix, feature = "osc8_links"))]
 pub fn gethostname() -> String {
-    let mut buffer = vec![0 as u8; 256];
+    let mut buffer = vec![0_u8; 256];
     let returncode =
         unsafe { libc::gethostname(buffer.as_mut_ptr() as *mut libc::c_char, buffer.len()) };
     if returncode != 0 {
         panic!("gethostname failed!  Please report an issue to <https://github.com/lunaryorn/mdcat/issues>!");
     }
-    let end = buffer
-        .iter()
-        .position(|&b| b == 0)
-        .unwrap_or_else(|| buffer.len());
+    let end = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
     String::from_utf8_lossy(&buffer[0..end]).to_string()
 }
 
+/// Access to OSC 8 links.
 #[cfg(feature = "osc8_links")]
-pub struct OSC8Links {
-    hostname: String,
-}
+pub struct OSC8Links;
 
 /// Whether the given `url` needs to get an explicit host.
 ///
 /// [OSC 8] links require that `file://` URLs give an explicit hostname, as
-/// received by [gethostname], to disambiguate `file://` printed over SSH
+/// of the local system, to disambiguate `file://` printed over SSH
 /// connections.
 ///
 /// This function checks whether we need to explicit set the host of the given
-/// `url` to the hostname of this system per `gethostname()`.  We do so if `url`
+/// `url` to the hostname of this system.  We do so if `url`
 /// is a `file://` URL and the host is
 ///
 /// * empty,
@@ -70,7 +68,6 @@ pub struct OSC8Links {
 /// * or a IPv4/IPv6 loopback address.
 ///
 /// [OSC 8]: https://git.io/vd4ee
-/// [gethostname]: http://pubs.opengroup.org/onlinepubs/009695399/functions/gethostname.html
 #[cfg(feature = "osc8_links")]
 fn url_needs_explicit_host(url: &Url) -> bool {
     if url.scheme() == "file" {
@@ -88,37 +85,36 @@ fn url_needs_explicit_host(url: &Url) -> bool {
 
 #[cfg(feature = "osc8_links")]
 impl OSC8Links {
-    /// Create OSC 8 links support for this host.
-    ///
-    /// Queries and remembers the hostname of this system as per `gethostname()`
-    /// to resolve local `file://` URLs.
-    pub fn for_localhost() -> OSC8Links {
-        OSC8Links {
-            hostname: gethostname(),
-        }
-    }
-
     /// Set a link to the given `destination` URL for subsequent text.
     ///
+    /// Give the link the given `id`, so that terminals and multiplexers join
+    /// the pieces of a link which spans multiple lines into a single link.
+    ///
     /// Take ownership of `destination` to resolve `file://` URLs for localhost
-    /// and loopback addresses, and print these with the proper hostname of the
+    /// and loopback addresses, and print these with the given `hostname` of the
     /// local system instead to make `file://` URLs work properly over SSH.
     ///
     /// See <https://git.io/vd4ee#file-uris-and-the-hostname>.
-    pub fn set_link_url<W: Write>(&self, writer: &mut W, mut destination: Url) -> Result<()> {
+    pub fn set_link_url<W: Write>(
+        &self,
+        writer: &mut W,
+        mut destination: Url,
+        hostname: &str,
+        id: &str,
+    ) -> Result<()> {
         if url_needs_explicit_host(&destination) {
-            destination.set_host(Some(&self.hostname)).unwrap();
+            destination.set_host(Some(hostname)).unwrap();
         }
-        self.set_link(writer, destination.as_str())
+        self.set_link(writer, &format!("id={}", id), destination.as_str())
     }
 
     /// Clear the current link if any.
     pub fn clear_link<W: Write>(&self, writer: &mut W) -> Result<()> {
-        self.set_link(writer, "")
+        self.set_link(writer, "", "")
     }
 
-    fn set_link<W: Write>(&self, writer: &mut W, destination: &str) -> Result<()> {
-        write_osc(writer, &format!("8;;{}", destination))
+    fn set_link<W: Write>(&self, writer: &mut W, params: &str, destination: &str) -> Result<()> {
+        write_osc(writer, &format!("8;{};{}", params, destination))
     }
 }
 
@@ -153,7 +149,7 @@ mod tests {
             ("file://[::1]/foo/bar", true),
         ];
 
-        for (url, expected) in checks.into_iter() {
+        for (url, expected) in checks.iter() {
             let parsed = super::Url::parse(url).unwrap();
             let needs_host = super::url_needs_explicit_host(&parsed);
             assert_eq!(
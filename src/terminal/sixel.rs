@@ -0,0 +1,244 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Sixel graphics.
+//!
+//! [Sixel][] is the bitmap graphics format of DEC terminals, which xterm,
+//! mlterm, WezTerm and foot understand.
+//!
+//! [Sixel]: https://vt100.net/docs/vt3xx-gp/chapter14.html
+
+use super::exif;
+use super::{PixelSize, TerminalSize};
+use crate::environment::Environment;
+use crate::resources::read_url;
+use failure::Error;
+use image::{FilterType, GenericImageView, RgbaImage};
+use std::io::{self, Write};
+use url::Url;
+
+/// The size of character cells we assume if the terminal does not tell.
+const DEFAULT_CELL: PixelSize = PixelSize {
+    width: 10,
+    height: 20,
+};
+
+/// The number of levels per colour channel in our palette.
+const LEVELS: u8 = 6;
+
+/// Whether we run in a terminal which understands sixels in `environment`.
+///
+/// xterm only shows sixels if built with sixel support and started with `-ti
+/// vt340`, but otherwise ignores them.
+pub fn is_sixel_terminal(environment: &Environment) -> bool {
+    let term = environment.var("TERM").unwrap_or_default();
+    environment.var("TERM_PROGRAM") == Some("WezTerm")
+        || environment.var("MLTERM").is_some()
+        || term.starts_with("foot")
+        || term.starts_with("mlterm")
+        || environment.var("XTERM_VERSION").is_some()
+}
+
+/// Provides access to printing images as sixels.
+pub struct SixelImages;
+
+impl SixelImages {
+    /// Read the image at `url` and scale it down to fit into `max_size`.
+    ///
+    /// Turn photos upright according to their EXIF orientation.
+    pub fn read_and_scale(&self, max_size: TerminalSize, url: &Url) -> Result<RgbaImage, Error> {
+        self.scale(max_size, &read_url(url)?)
+    }
+
+    /// Load the image from `contents` and scale it down to fit into
+    /// `max_size`.
+    ///
+    /// Turn photos upright according to their EXIF orientation.
+    pub fn scale(&self, max_size: TerminalSize, contents: &[u8]) -> Result<RgbaImage, Error> {
+        let image = image::load_from_memory(contents)?;
+        let image = match exif::orientation(contents) {
+            Some(orientation) => exif::apply_orientation(image, orientation),
+            None => image,
+        };
+        let cell = max_size.cell_pixels().unwrap_or(DEFAULT_CELL);
+        let (width, height) = (
+            cell.width * max_size.width as u32,
+            cell.height * max_size.height as u32,
+        );
+        if width < image.width() || height < image.height() {
+            Ok(image.resize(width, height, FilterType::Triangle).to_rgba())
+        } else {
+            Ok(image.to_rgba())
+        }
+    }
+
+    /// The number of columns `image` takes in a terminal of `max_size`.
+    pub fn columns(&self, max_size: TerminalSize, image: &RgbaImage) -> usize {
+        let cell = max_size.cell_pixels().unwrap_or(DEFAULT_CELL);
+        let width = image.width() as usize;
+        let cell_width = (cell.width as usize).max(1);
+        width.div_ceil(cell_width)
+    }
+
+    /// Write `image` as sixels to `writer`.
+    ///
+    /// Leave the cursor at the start of the line below the image.
+    pub fn write_inline_image<W: Write>(
+        &self,
+        writer: &mut W,
+        image: &RgbaImage,
+    ) -> io::Result<()> {
+        writer.write_all(encode(image).as_bytes())?;
+        writeln!(writer)
+    }
+}
+
+/// The index of the colour of `pixel` in our palette, or `None` for a
+/// transparent pixel.
+fn palette_index(pixel: image::Rgba<u8>) -> Option<usize> {
+    let [red, green, blue, alpha] = pixel.data;
+    if alpha < 128 {
+        None
+    } else {
+        let level = |value: u8| usize::from((u16::from(value) * u16::from(LEVELS - 1) + 127) / 255);
+        let levels = usize::from(LEVELS);
+        Some((level(red) * levels + level(green)) * levels + level(blue))
+    }
+}
+
+/// Write `count` repetitions of the sixel `bits` to `buffer`.
+fn push_run(buffer: &mut String, bits: u8, count: usize) {
+    let sixel = char::from(0x3F + bits);
+    match count {
+        0 => {}
+        1..=3 => buffer.extend(std::iter::repeat_n(sixel, count)),
+        _ => {
+            buffer.push_str(&format!("!{}", count));
+            buffer.push(sixel);
+        }
+    }
+}
+
+/// Encode `image` as sixel sequence.
+///
+/// Map the colours of `image` to a palette of 216 colours, and leave
+/// transparent pixels untouched.
+fn encode(image: &RgbaImage) -> String {
+    let (width, height) = image.dimensions();
+    let colours = usize::from(LEVELS).pow(3);
+    // Start sixels with a transparent background and a pixel aspect ratio of 1:1
+    let mut sixels = format!("\x1bP0;1;0q\"1;1;{};{}", width, height);
+    let max = u32::from(LEVELS - 1);
+    for index in 0..colours {
+        let percent = |level: usize| level as u32 * 100 / max;
+        let levels = usize::from(LEVELS);
+        sixels.push_str(&format!(
+            "#{};2;{};{};{}",
+            index,
+            percent(index / (levels * levels)),
+            percent(index / levels % levels),
+            percent(index % levels)
+        ));
+    }
+    let mut band = vec![Vec::new(); colours];
+    for top in (0..height).step_by(6) {
+        // Collect the bits of all colours in this band of six rows
+        for bits in band.iter_mut() {
+            bits.clear();
+        }
+        for x in 0..width {
+            for y in top..(top + 6).min(height) {
+                if let Some(index) = palette_index(*image.get_pixel(x, y)) {
+                    let bits: &mut Vec<u8> = &mut band[index];
+                    bits.resize(width as usize, 0);
+                    bits[x as usize] |= 1 << (y - top);
+                }
+            }
+        }
+        let mut first = true;
+        for (index, bits) in band.iter().enumerate().filter(|(_, b)| !b.is_empty()) {
+            if !first {
+                // Return to the start of the band for the next colour
+                sixels.push('$');
+            }
+            first = false;
+            sixels.push_str(&format!("#{}", index));
+            let mut run = (bits[0], 0);
+            for &b in bits {
+                if b == run.0 {
+                    run.1 += 1;
+                } else {
+                    push_run(&mut sixels, run.0, run.1);
+                    run = (b, 1);
+                }
+            }
+            push_run(&mut sixels, run.0, run.1);
+        }
+        sixels.push('-');
+    }
+    sixels.push_str("\x1b\\");
+    sixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn palette_index_of_pixels() {
+        assert_eq!(palette_index(Rgba([0, 0, 0, 255])), Some(0));
+        assert_eq!(palette_index(Rgba([255, 255, 255, 255])), Some(215));
+        assert_eq!(palette_index(Rgba([255, 0, 0, 255])), Some(180));
+        assert_eq!(palette_index(Rgba([255, 0, 0, 0])), None);
+    }
+
+    #[test]
+    fn encode_small_image() {
+        let mut image = RgbaImage::from_pixel(5, 7, Rgba([255, 0, 0, 255]));
+        image.put_pixel(4, 0, Rgba([0, 0, 0, 0]));
+        let sixels = encode(&image);
+        assert!(sixels.starts_with("\x1bP0;1;0q\"1;1;5;7#0;2;0;0;0"));
+        assert!(sixels.ends_with("#180!4~}-#180!5@-\x1b\\"), "{:?}", sixels);
+    }
+
+    #[test]
+    fn read_and_scale_png() {
+        let path = std::fs::canonicalize("sample/rust-logo-128x128.png").unwrap();
+        let url = Url::from_file_path(path).unwrap();
+        let image = SixelImages
+            .read_and_scale(TerminalSize::new(80, 24), &url)
+            .unwrap();
+        assert_eq!(image.dimensions(), (128, 128));
+        assert_eq!(SixelImages.columns(TerminalSize::new(80, 24), &image), 13);
+        let image = SixelImages
+            .read_and_scale(TerminalSize::new(20, 3), &url)
+            .unwrap();
+        assert_eq!(image.dimensions(), (60, 60));
+    }
+
+    #[test]
+    fn sixel_terminals() {
+        let environment = Environment::isolated("/tmp");
+        assert!(!is_sixel_terminal(&environment));
+        assert!(is_sixel_terminal(
+            &environment.clone().with_var("TERM", "foot-extra")
+        ));
+        assert!(is_sixel_terminal(
+            &environment.clone().with_var("TERM_PROGRAM", "WezTerm")
+        ));
+        assert!(!is_sixel_terminal(&environment.with_var("TERM", "linux")));
+    }
+}
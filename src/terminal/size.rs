@@ -14,6 +14,15 @@
 
 //! Terminal size.
 
+/// The size of a terminal in pixels.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PixelSize {
+    /// The width of the terminal, in pixels.
+    pub width: u32,
+    /// The height of the terminal, in pixels.
+    pub height: u32,
+}
+
 /// The size of a text terminal.
 #[derive(Debug, Copy, Clone)]
 pub struct Size {
@@ -21,24 +30,32 @@ pub struct Size {
     pub width: usize,
     /// The height of the terminal, in lines.
     pub height: usize,
+    /// The size of the terminal in pixels, if the terminal reports it.
+    pub pixels: Option<PixelSize>,
 }
 
 impl Default for Size {
     /// A good default size assumption for a terminal: 80x24.
     fn default() -> Size {
-        Size {
-            width: 80,
-            height: 24,
-        }
+        Size::new(80, 24)
     }
 }
 
 impl Size {
-    fn new(width: usize, height: usize) -> Size {
-        Size { width, height }
+    /// A terminal with `width` columns and `height` lines and unknown size in
+    /// pixels.
+    pub fn new(width: usize, height: usize) -> Size {
+        Size {
+            width,
+            height,
+            pixels: None,
+        }
     }
 
     /// Get terminal size from `$COLUMNS` and `$LINES`.
+    ///
+    /// Ignore empty sizes, like `TIOCGWINSZ` of a terminal which does not
+    /// know its size.
     pub fn from_env() -> Option<Size> {
         let columns = std::env::var("COLUMNS")
             .ok()
@@ -48,18 +65,171 @@ impl Size {
             .and_then(|value| value.parse::<usize>().ok());
 
         match (columns, rows) {
-            (Some(columns), Some(rows)) => Some(Size::new(columns, rows)),
+            (Some(columns), Some(rows)) if 0 < columns && 0 < rows => {
+                Some(Size::new(columns, rows))
+            }
             _ => None,
         }
     }
 
+    /// Get the terminal size from the TTY on standard output.
+    ///
+    /// Query the size with the `TIOCGWINSZ` ioctl, which also gives the size
+    /// in pixels if the terminal reports it.
+    #[cfg(unix)]
+    pub fn from_tty() -> Option<Size> {
+        let mut winsize = libc::winsize {
+            ws_row: 0,
+            ws_col: 0,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut winsize) };
+        if result == 0 && 0 < winsize.ws_col && 0 < winsize.ws_row {
+            let pixels = if 0 < winsize.ws_xpixel && 0 < winsize.ws_ypixel {
+                Some(PixelSize {
+                    width: u32::from(winsize.ws_xpixel),
+                    height: u32::from(winsize.ws_ypixel),
+                })
+            } else {
+                None
+            };
+            Some(Size {
+                width: usize::from(winsize.ws_col),
+                height: usize::from(winsize.ws_row),
+                pixels,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Get the terminal size from the TTY.
+    #[cfg(not(unix))]
+    pub fn from_tty() -> Option<Size> {
+        term_size::dimensions().map(|(w, h)| Size::new(w, h))
+    }
+
     /// Detect the terminal size.
     ///
     /// Get the terminal size from the underlying TTY, and fallback to
     /// `$COLUMNS` and `$LINES`.
     pub fn detect() -> Option<Size> {
-        term_size::dimensions()
-            .map(|(w, h)| Size::new(w, h))
-            .or_else(Size::from_env)
+        Size::from_tty().or_else(Size::from_env)
+    }
+
+    /// This size with only `width` columns, keeping the size of character
+    /// cells.
+    pub fn with_width(self, width: usize) -> Size {
+        let pixels = self.cell_pixels().map(|cell| PixelSize {
+            width: cell.width * width as u32,
+            height: cell.height * self.height as u32,
+        });
+        Size {
+            width,
+            height: self.height,
+            pixels,
+        }
+    }
+
+    /// The size of a single character cell in pixels, if known.
+    pub fn cell_pixels(&self) -> Option<PixelSize> {
+        self.pixels
+            .filter(|_| 0 < self.width && 0 < self.height)
+            .map(|pixels| PixelSize {
+                width: pixels.width / self.width as u32,
+                height: pixels.height / self.height as u32,
+            })
+    }
+
+    /// The number of columns and lines to show an image of the given size in.
+    ///
+    /// `width` and `height` are the size of the image in pixels.  If we know
+    /// the size of character cells show the image in its natural size, and
+    /// scale it down to fit into the terminal.  Otherwise scale the image to
+    /// the full width of the terminal, assuming that cells are twice as high as
+    /// wide.
+    pub fn image_cells(&self, width: u32, height: u32) -> (usize, usize) {
+        let (width, height) = (f64::from(width.max(1)), f64::from(height));
+        match self.cell_pixels().filter(|c| 0 < c.width && 0 < c.height) {
+            Some(cell) => {
+                let (cell_width, cell_height) = (f64::from(cell.width), f64::from(cell.height));
+                let scale = (self.width as f64 * cell_width / width)
+                    .min(self.height as f64 * cell_height / height.max(1.0))
+                    .min(1.0);
+                let columns = (width * scale / cell_width).ceil() as usize;
+                let lines = (height * scale / cell_height).ceil() as usize;
+                (columns.max(1), lines.max(1))
+            }
+            None => {
+                let lines = (height * (self.width / 2) as f64 / width) as usize;
+                (self.width, lines.max(1))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn cell_pixels() {
+        let size = Size {
+            width: 80,
+            height: 24,
+            pixels: Some(PixelSize {
+                width: 800,
+                height: 480,
+            }),
+        };
+        assert_eq!(
+            size.cell_pixels(),
+            Some(PixelSize {
+                width: 10,
+                height: 20
+            })
+        );
+        assert_eq!(Size::default().cell_pixels(), None);
+    }
+
+    #[test]
+    fn image_cells_with_known_cell_size() {
+        let size = Size {
+            width: 80,
+            height: 24,
+            pixels: Some(PixelSize {
+                width: 800,
+                height: 480,
+            }),
+        };
+        // Natural size
+        assert_eq!(size.image_cells(100, 50), (10, 3));
+        // Scaled down to the width of the terminal
+        assert_eq!(size.image_cells(1600, 200), (80, 5));
+        // Scaled down to the height of the terminal
+        assert_eq!(size.image_cells(100, 960), (5, 24));
+    }
+
+    #[test]
+    fn with_width_keeps_cell_size() {
+        let size = Size {
+            width: 80,
+            height: 24,
+            pixels: Some(PixelSize {
+                width: 800,
+                height: 480,
+            }),
+        };
+        let narrow = size.with_width(30);
+        assert_eq!(narrow.width, 30);
+        assert_eq!(narrow.cell_pixels(), size.cell_pixels());
+        assert_eq!(Size::new(80, 24).with_width(30).pixels, None);
+    }
+
+    #[test]
+    fn image_cells_with_unknown_cell_size() {
+        assert_eq!(Size::default().image_cells(400, 200), (80, 20));
     }
 }
@@ -17,12 +17,14 @@
 //! [Terminology]: http://terminolo.gy
 
 use super::TerminalSize;
+use crate::environment::Environment;
 use std::io::{Result, Write};
 use url::Url;
 
-/// Whether we run in terminology or not.
-pub fn is_terminology() -> bool {
-    std::env::var("TERMINOLOGY")
+/// Whether we run in terminology in `environment` or not.
+pub fn is_terminology(environment: &Environment) -> bool {
+    environment
+        .var("TERMINOLOGY")
         .map(|value| value.trim() == "1")
         .unwrap_or(false)
 }
@@ -32,11 +34,16 @@ pub struct TerminologyImages;
 
 impl TerminologyImages {
     /// Write an inline image for Terminology.
-    pub fn write_inline_image<W: Write>(
+    ///
+    /// Indent every line of the image by as many spaces as `padding` gives
+    /// for the columns of the image.  Leave the cursor at the start of the
+    /// line below the image.
+    pub fn write_inline_image<W: Write, P: FnOnce(usize) -> usize>(
         &self,
         writer: &mut W,
         max_size: TerminalSize,
         url: &Url,
+        padding: P,
     ) -> Result<()> {
         // Terminology escape sequence is like: set texture to path, then draw a
         // rectangle of chosen character to be replaced by the given texture.
@@ -50,23 +57,20 @@ impl TerminologyImages {
         // We need to compute image proportion to draw the appropriate
         // rectangle. If we can't compute the image proportion (e.g. it's an
         // external URL), we fallback to a rectangle that is half of the screen.
-        let columns = max_size.width;
-
-        let lines = Some(url)
+        let (columns, lines) = Some(url)
             .filter(|url| url.scheme() == "file")
             .and_then(|url| url.to_file_path().ok())
             .and_then(|path| immeta::load_from_file(path).ok())
             .map(|m| {
                 let d = m.dimensions();
-                let (w, h) = (f64::from(d.width), f64::from(d.height));
-                // We divide by 2 because terminal cursor/font most likely has a
-                // 1:2 proportion
-                (h * (columns / 2) as f64 / w) as usize
+                max_size.image_cells(d.width, d.height)
             })
-            .unwrap_or(max_size.height / 2);
+            .unwrap_or((max_size.width, max_size.height / 2));
 
+        let padding = " ".repeat(padding(columns));
         let mut command = format!("\x1b}}ic#{};{};{}\x00", columns, lines, url.as_str());
         for _ in 0..lines {
+            command.push_str(&padding);
             command.push_str("\x1b}ib\x00");
             for _ in 0..columns {
                 command.push('#');
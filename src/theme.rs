@@ -0,0 +1,849 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Styles for markdown elements.
+//!
+//! Besides the built-in themes, mdcat reads themes from TOML files, which
+//! start from a built-in theme and set the styles of some elements:
+//!
+//! ```toml
+//! base = "dark"
+//! highlighting = "Solarized (light)"
+//!
+//! [heading]
+//! color = "#268bd2"
+//! bold = true
+//!
+//! [link]
+//! color = 33
+//! underline = true
+//! ```
+
+use crate::terminal::highlighting;
+use ansi_term::{Colour, Style};
+use failure::{Error, Fail};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Styles for the elements of a markdown document.
+#[derive(Debug, Clone)]
+pub struct Theme {
+    /// The style of headings.
+    pub heading: Style,
+    /// The style of block quotes.
+    ///
+    /// Applies on top of emphasis, ie, block quotes are italic anyway.
+    pub block_quote: Style,
+    /// The style of inline code and code blocks without highlighting.
+    pub code: Style,
+    /// The style of link references.
+    pub link: Style,
+    /// The style of emphasis, on top of italic or upright text.
+    pub emphasis: Style,
+    /// The style of strong emphasis.
+    pub strong: Style,
+    /// The style of inline and block HTML.
+    pub html: Style,
+    /// The style of rules and borders of code blocks.
+    pub rule: Style,
+    /// The style of inserted lines in diffs.
+    pub diff_inserted: Style,
+    /// The style of deleted lines in diffs.
+    pub diff_deleted: Style,
+    /// The style of warnings in the document, eg, for images we cannot show.
+    pub warning: Style,
+    /// Blank lines around blocks.
+    pub spacing: Spacing,
+    /// The alignment of images in paragraphs of their own.
+    pub image_alignment: ImageAlignment,
+    /// The name of the syntect theme for highlighting code blocks.
+    pub highlighting: String,
+    /// Highlighting for code blocks of particular languages, by language.
+    ///
+    /// The name of the syntect theme for code blocks in the language, or
+    /// `None` to not highlight code blocks in the language at all.
+    pub language_highlighting: BTreeMap<String, Option<String>>,
+    /// Width hints for table columns, by the lowercase heading of the column.
+    ///
+    /// Attributes in table cells take precedence over these hints.
+    pub column_widths: BTreeMap<String, WidthHint>,
+}
+
+/// Blank lines before and after a block.
+///
+/// Between two blocks we write the larger of the margin after the first and
+/// the margin before the second block.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Margin {
+    /// Blank lines before the block.
+    pub before: usize,
+    /// Blank lines after the block.
+    pub after: usize,
+}
+
+impl Margin {
+    /// A margin of `before` and `after` blank lines.
+    pub fn new(before: usize, after: usize) -> Margin {
+        Margin { before, after }
+    }
+}
+
+/// Blank lines around markdown blocks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spacing {
+    /// Blank lines around headings.
+    pub heading: Margin,
+    /// Blank lines around paragraphs.
+    pub paragraph: Margin,
+    /// Blank lines around lists.
+    pub list: Margin,
+    /// Blank lines around code blocks.
+    pub code_block: Margin,
+}
+
+impl Default for Spacing {
+    /// A single blank line between all blocks.
+    fn default() -> Spacing {
+        Spacing {
+            heading: Margin::new(1, 1),
+            paragraph: Margin::new(1, 1),
+            list: Margin::new(1, 1),
+            code_block: Margin::new(1, 1),
+        }
+    }
+}
+
+impl Spacing {
+    /// Compact spacing for small panes.
+    ///
+    /// Keep blank lines before headings and after paragraphs and lists, but
+    /// continue right below headings, and put no extra blank lines around
+    /// code blocks, which have borders anyway.
+    pub fn compact() -> Spacing {
+        Spacing {
+            heading: Margin::new(1, 0),
+            paragraph: Margin::new(0, 1),
+            list: Margin::new(0, 1),
+            code_block: Margin::new(0, 0),
+        }
+    }
+}
+
+/// The alignment of an image in the width of the terminal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageAlignment {
+    /// Align the image at the left edge of the text.
+    Left,
+    /// Center the image.
+    Center,
+    /// Align the image at the right edge of the terminal.
+    Right,
+}
+
+impl ImageAlignment {
+    /// The alignment of the given `name`, ie, `left`, `center` or `right`.
+    pub fn from_name(name: &str) -> Option<ImageAlignment> {
+        match name {
+            "left" => Some(ImageAlignment::Left),
+            "center" | "centre" => Some(ImageAlignment::Center),
+            "right" => Some(ImageAlignment::Right),
+            _ => None,
+        }
+    }
+
+    /// The number of columns to pad an image `columns` wide with.
+    ///
+    /// `available` is the number of columns to align the image in.
+    pub fn padding(self, available: usize, columns: usize) -> usize {
+        let free = available.saturating_sub(columns);
+        match self {
+            ImageAlignment::Left => 0,
+            ImageAlignment::Center => free / 2,
+            ImageAlignment::Right => free,
+        }
+    }
+}
+
+/// A width of a table column.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnWidth {
+    /// A number of terminal columns.
+    Columns(usize),
+    /// A percentage of the width available to the contents of the table.
+    Percent(usize),
+}
+
+impl ColumnWidth {
+    /// Parse a width from `text`, eg, `20` or `60%`.
+    pub fn parse(text: &str) -> Option<ColumnWidth> {
+        match text.strip_suffix('%') {
+            Some(percent) => percent
+                .parse()
+                .ok()
+                .filter(|&percent| percent <= 100)
+                .map(ColumnWidth::Percent),
+            None => text.parse().ok().map(ColumnWidth::Columns),
+        }
+    }
+
+    /// The number of terminal columns of this width, for tables with
+    /// `available` columns for their contents.
+    ///
+    /// Columns are at least one terminal column wide.
+    pub fn columns(self, available: usize) -> usize {
+        match self {
+            ColumnWidth::Columns(columns) => columns,
+            ColumnWidth::Percent(percent) => available * percent / 100,
+        }
+        .max(1)
+    }
+}
+
+/// A hint for the width of a table column.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct WidthHint {
+    /// The column should be at least this wide.
+    ///
+    /// Columns only get narrower if the table does not fit otherwise.
+    pub min: Option<ColumnWidth>,
+    /// The column should be at most this wide, even if that breaks words.
+    pub max: Option<ColumnWidth>,
+}
+
+impl WidthHint {
+    /// Add the width attribute `key` with the given `value` to this hint.
+    ///
+    /// `width` sets the minimum and the maximum width, `min-width` and
+    /// `max-width` only one of both.  Return `None` if `key` is no width
+    /// attribute or `value` no valid width.
+    pub fn with_attribute(self, key: &str, value: &str) -> Option<WidthHint> {
+        let width = ColumnWidth::parse(value)?;
+        match key {
+            "width" => Some(WidthHint {
+                min: Some(width),
+                max: Some(width),
+            }),
+            "min-width" => Some(WidthHint {
+                min: Some(width),
+                ..self
+            }),
+            "max-width" => Some(WidthHint {
+                max: Some(width),
+                ..self
+            }),
+            _ => None,
+        }
+    }
+
+    /// Parse a hint from `text`.
+    ///
+    /// `text` is either a plain width, eg, `60%`, for a column of exactly
+    /// this width, or space separated width attributes, eg,
+    /// `min-width=20 max-width=60%`.
+    pub fn parse(text: &str) -> Option<WidthHint> {
+        if let Some(width) = ColumnWidth::parse(text.trim()) {
+            return Some(WidthHint {
+                min: Some(width),
+                max: Some(width),
+            });
+        }
+        text.split_whitespace()
+            .try_fold(WidthHint::default(), |hint, attribute| {
+                let (key, value) = attribute.split_once('=')?;
+                hint.with_attribute(key, value)
+            })
+    }
+}
+
+/// The names of all built-in themes.
+pub const BUILTIN_THEMES: &[&str] = &[
+    "default",
+    "light",
+    "dark",
+    "high-contrast",
+    "colorblind",
+    "monochrome",
+];
+
+/// Other names of built-in themes, with the name of the theme.
+pub const BUILTIN_THEME_ALIASES: &[(&str, &str)] =
+    &[("deuteranopia", "colorblind"), ("protanopia", "colorblind")];
+
+/// The syntect theme for highlighting code blocks on dark backgrounds.
+const DARK_HIGHLIGHTING: &str = "Solarized (dark)";
+
+/// The syntect theme for highlighting code blocks on light backgrounds.
+const LIGHT_HIGHLIGHTING: &str = "Solarized (light)";
+
+impl Default for Theme {
+    /// The default theme of mdcat.
+    fn default() -> Theme {
+        Theme {
+            heading: Style::new().fg(Colour::Blue).bold(),
+            block_quote: Style::new().fg(Colour::Green),
+            code: Style::new().fg(Colour::Yellow),
+            link: Style::new().fg(Colour::Blue),
+            emphasis: Style::new(),
+            strong: Style::new().bold(),
+            html: Style::new().fg(Colour::Green),
+            rule: Style::new().fg(Colour::Green),
+            diff_inserted: Style::new().fg(Colour::Green),
+            diff_deleted: Style::new().fg(Colour::Red),
+            warning: Style::new().fg(Colour::Yellow).bold(),
+            spacing: Spacing::default(),
+            image_alignment: ImageAlignment::Center,
+            highlighting: DARK_HIGHLIGHTING.to_string(),
+            language_highlighting: BTreeMap::new(),
+            column_widths: BTreeMap::new(),
+        }
+    }
+}
+
+impl Theme {
+    /// A theme for terminals with a light background.
+    ///
+    /// Uses dark colours and highlights code with Solarized light.
+    pub fn light() -> Theme {
+        Theme {
+            heading: Style::new().fg(Colour::Fixed(25)).bold(),
+            block_quote: Style::new().fg(Colour::Fixed(28)),
+            code: Style::new().fg(Colour::Fixed(130)),
+            link: Style::new().fg(Colour::Fixed(25)),
+            emphasis: Style::new(),
+            strong: Style::new().bold(),
+            html: Style::new().fg(Colour::Fixed(28)),
+            rule: Style::new().fg(Colour::Fixed(242)),
+            diff_inserted: Style::new().fg(Colour::Fixed(28)),
+            diff_deleted: Style::new().fg(Colour::Fixed(124)),
+            warning: Style::new().fg(Colour::Fixed(130)).bold(),
+            spacing: Spacing::default(),
+            image_alignment: ImageAlignment::Center,
+            highlighting: LIGHT_HIGHLIGHTING.to_string(),
+            language_highlighting: BTreeMap::new(),
+            column_widths: BTreeMap::new(),
+        }
+    }
+
+    /// A theme for terminals with a dark background.
+    ///
+    /// Uses bright colours and highlights code with Solarized dark.
+    pub fn dark() -> Theme {
+        Theme {
+            heading: Style::new().fg(Colour::Fixed(75)).bold(),
+            block_quote: Style::new().fg(Colour::Fixed(114)),
+            code: Style::new().fg(Colour::Fixed(221)),
+            link: Style::new().fg(Colour::Fixed(75)),
+            emphasis: Style::new(),
+            strong: Style::new().bold(),
+            html: Style::new().fg(Colour::Fixed(114)),
+            rule: Style::new().fg(Colour::Fixed(245)),
+            diff_inserted: Style::new().fg(Colour::Fixed(114)),
+            diff_deleted: Style::new().fg(Colour::Fixed(210)),
+            warning: Style::new().fg(Colour::Fixed(214)).bold(),
+            spacing: Spacing::default(),
+            image_alignment: ImageAlignment::Center,
+            highlighting: DARK_HIGHLIGHTING.to_string(),
+            language_highlighting: BTreeMap::new(),
+            column_widths: BTreeMap::new(),
+        }
+    }
+
+    /// A theme with high contrast.
+    ///
+    /// Mostly relies on font attributes and the default foreground colour of
+    /// the terminal, which has the best contrast to the background, and uses
+    /// only bright and bold colours otherwise.
+    pub fn high_contrast() -> Theme {
+        Theme {
+            heading: Style::new().bold().underline(),
+            block_quote: Style::new(),
+            code: Style::new().reverse(),
+            link: Style::new().bold().underline(),
+            emphasis: Style::new(),
+            strong: Style::new().bold(),
+            html: Style::new().fg(Colour::Fixed(14)).bold(),
+            rule: Style::new().bold(),
+            diff_inserted: Style::new().fg(Colour::Fixed(10)).bold(),
+            diff_deleted: Style::new().fg(Colour::Fixed(9)).bold(),
+            warning: Style::new().fg(Colour::Fixed(11)).bold().reverse(),
+            spacing: Spacing::default(),
+            image_alignment: ImageAlignment::Center,
+            highlighting: DARK_HIGHLIGHTING.to_string(),
+            language_highlighting: BTreeMap::new(),
+            column_widths: BTreeMap::new(),
+        }
+    }
+
+    /// A theme safe for red-green colour blindness.
+    ///
+    /// Avoids red and green in favour of blue and yellow, which people with
+    /// deuteranopia or protanopia can tell apart.
+    pub fn colorblind() -> Theme {
+        Theme {
+            heading: Style::new().fg(Colour::Blue).bold(),
+            block_quote: Style::new().fg(Colour::Cyan),
+            code: Style::new().fg(Colour::Yellow),
+            link: Style::new().fg(Colour::Blue).underline(),
+            emphasis: Style::new(),
+            strong: Style::new().bold(),
+            html: Style::new().fg(Colour::Purple),
+            rule: Style::new().fg(Colour::Cyan),
+            diff_inserted: Style::new().fg(Colour::Blue).bold(),
+            diff_deleted: Style::new().fg(Colour::Yellow).strikethrough(),
+            warning: Style::new().fg(Colour::Yellow).bold().underline(),
+            spacing: Spacing::default(),
+            image_alignment: ImageAlignment::Center,
+            highlighting: DARK_HIGHLIGHTING.to_string(),
+            language_highlighting: BTreeMap::new(),
+            column_widths: BTreeMap::new(),
+        }
+    }
+
+    /// A theme without colours.
+    ///
+    /// Uses only font attributes, for monochrome terminals and printing.
+    pub fn monochrome() -> Theme {
+        Theme {
+            heading: Style::new().bold().underline(),
+            block_quote: Style::new(),
+            code: Style::new().dimmed(),
+            link: Style::new().underline(),
+            emphasis: Style::new(),
+            strong: Style::new().bold(),
+            html: Style::new().dimmed(),
+            rule: Style::new().dimmed(),
+            diff_inserted: Style::new().bold(),
+            diff_deleted: Style::new().strikethrough(),
+            warning: Style::new().bold().reverse(),
+            spacing: Spacing::default(),
+            image_alignment: ImageAlignment::Center,
+            highlighting: DARK_HIGHLIGHTING.to_string(),
+            language_highlighting: BTreeMap::new(),
+            column_widths: BTreeMap::new(),
+        }
+    }
+
+    /// Get the built-in theme with the given `name`.
+    ///
+    /// See `BUILTIN_THEMES` for all names, and `BUILTIN_THEME_ALIASES` for
+    /// other names of these themes.
+    pub fn builtin(name: &str) -> Option<Theme> {
+        let name = BUILTIN_THEME_ALIASES
+            .iter()
+            .find(|(alias, _)| *alias == name)
+            .map_or(name, |(_, theme)| theme);
+        match name {
+            "default" => Some(Theme::default()),
+            "light" => Some(Theme::light()),
+            "dark" => Some(Theme::dark()),
+            "high-contrast" => Some(Theme::high_contrast()),
+            "colorblind" => Some(Theme::colorblind()),
+            "monochrome" => Some(Theme::monochrome()),
+            _ => None,
+        }
+    }
+
+    /// Read a theme from the TOML `source`.
+    ///
+    /// Start from the built-in theme named by `base`, or the default theme,
+    /// and replace the styles of all elements the TOML source sets.  Fail if
+    /// the source is no valid TOML, refers to unknown themes or colours, or
+    /// has unknown keys.
+    pub fn from_toml(source: &str) -> Result<Theme, Error> {
+        let file: ThemeFile = toml::from_str(source)?;
+        let base = file.base.as_ref().map_or("default", String::as_str);
+        let mut theme = Theme::builtin(base).ok_or_else(|| ThemeError::UnknownTheme {
+            name: base.to_string(),
+        })?;
+        let themes = highlighting::themes();
+        if let Some(highlighting) = file.highlighting {
+            if !themes.contains_key(&highlighting) {
+                return Err(ThemeError::UnknownHighlighting { name: highlighting }.into());
+            }
+            theme.highlighting = highlighting;
+        }
+        for (language, highlighting) in file.language_highlighting {
+            let highlighting = match highlighting {
+                // Highlight the language as usual
+                LanguageHighlightingFile::Enabled(true) => continue,
+                LanguageHighlightingFile::Enabled(false) => None,
+                LanguageHighlightingFile::Theme(name) => {
+                    if !themes.contains_key(&name) {
+                        return Err(ThemeError::UnknownHighlighting { name }.into());
+                    }
+                    Some(name)
+                }
+            };
+            theme
+                .language_highlighting
+                .insert(language.to_lowercase(), highlighting);
+        }
+        for (heading, width) in file.column_widths {
+            let hint = WidthHint::parse(&width).ok_or(ThemeError::InvalidColumnWidth { width })?;
+            theme.column_widths.insert(heading.to_lowercase(), hint);
+        }
+        if let Some(alignment) = file.image_alignment {
+            theme.image_alignment = ImageAlignment::from_name(&alignment)
+                .ok_or(ThemeError::InvalidAlignment { alignment })?;
+        }
+        let styles = vec![
+            (file.heading, &mut theme.heading),
+            (file.block_quote, &mut theme.block_quote),
+            (file.code, &mut theme.code),
+            (file.link, &mut theme.link),
+            (file.emphasis, &mut theme.emphasis),
+            (file.strong, &mut theme.strong),
+            (file.html, &mut theme.html),
+            (file.rule, &mut theme.rule),
+            (file.diff_inserted, &mut theme.diff_inserted),
+            (file.diff_deleted, &mut theme.diff_deleted),
+            (file.warning, &mut theme.warning),
+        ];
+        for (file_style, style) in styles {
+            if let Some(file_style) = file_style {
+                *style = file_style.to_style()?;
+            }
+        }
+        Ok(theme)
+    }
+}
+
+/// An error in a theme file.
+#[derive(Debug, PartialEq)]
+pub enum ThemeError {
+    /// The theme file refers to an unknown built-in theme.
+    UnknownTheme {
+        /// The name of the theme.
+        name: String,
+    },
+    /// The theme file refers to an unknown syntect theme.
+    UnknownHighlighting {
+        /// The name of the theme.
+        name: String,
+    },
+    /// The theme file has an invalid colour.
+    InvalidColour {
+        /// The invalid colour.
+        colour: String,
+    },
+    /// The theme file has an invalid image alignment.
+    InvalidAlignment {
+        /// The invalid alignment.
+        alignment: String,
+    },
+    /// The theme file has an invalid width of a table column.
+    InvalidColumnWidth {
+        /// The invalid width.
+        width: String,
+    },
+}
+
+impl fmt::Display for ThemeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThemeError::UnknownTheme { name } => write!(f, "Unknown base theme: {}", name),
+            ThemeError::UnknownHighlighting { name } => {
+                write!(f, "Unknown highlighting theme: {}", name)
+            }
+            ThemeError::InvalidColour { colour } => write!(f, "Invalid colour: {}", colour),
+            ThemeError::InvalidAlignment { alignment } => {
+                write!(f, "Invalid image alignment: {}", alignment)
+            }
+            ThemeError::InvalidColumnWidth { width } => {
+                write!(f, "Invalid column width: {}", width)
+            }
+        }
+    }
+}
+
+impl Fail for ThemeError {}
+
+/// A theme in a TOML file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeFile {
+    base: Option<String>,
+    highlighting: Option<String>,
+    heading: Option<StyleFile>,
+    block_quote: Option<StyleFile>,
+    code: Option<StyleFile>,
+    link: Option<StyleFile>,
+    emphasis: Option<StyleFile>,
+    strong: Option<StyleFile>,
+    html: Option<StyleFile>,
+    rule: Option<StyleFile>,
+    diff_inserted: Option<StyleFile>,
+    diff_deleted: Option<StyleFile>,
+    warning: Option<StyleFile>,
+    image_alignment: Option<String>,
+    #[serde(default)]
+    language_highlighting: BTreeMap<String, LanguageHighlightingFile>,
+    #[serde(default)]
+    column_widths: BTreeMap<String, String>,
+}
+
+/// The highlighting of a language in a TOML file.
+///
+/// Either whether to highlight the language at all, or the name of the
+/// syntect theme for the language.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum LanguageHighlightingFile {
+    Enabled(bool),
+    Theme(String),
+}
+
+/// A colour in a TOML file.
+///
+/// Either a number of the 256 colour palette, or a string with the name of a
+/// basic colour or an RGB colour of the form `#rrggbb`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ColourFile {
+    Fixed(u8),
+    Name(String),
+}
+
+impl ColourFile {
+    /// Convert to a colour.
+    fn to_colour(&self) -> Result<Colour, ThemeError> {
+        let name = match self {
+            ColourFile::Fixed(index) => return Ok(Colour::Fixed(*index)),
+            ColourFile::Name(name) => name,
+        };
+        let invalid = || ThemeError::InvalidColour {
+            colour: name.clone(),
+        };
+        match name.to_lowercase().as_str() {
+            "black" => Ok(Colour::Black),
+            "red" => Ok(Colour::Red),
+            "green" => Ok(Colour::Green),
+            "yellow" => Ok(Colour::Yellow),
+            "blue" => Ok(Colour::Blue),
+            "purple" | "magenta" => Ok(Colour::Purple),
+            "cyan" => Ok(Colour::Cyan),
+            "white" => Ok(Colour::White),
+            rgb if rgb.len() == 7 && rgb.starts_with('#') => {
+                let channel = |index: usize| {
+                    u8::from_str_radix(rgb.get(index..index + 2).ok_or_else(invalid)?, 16)
+                        .map_err(|_| invalid())
+                };
+                Ok(Colour::RGB(channel(1)?, channel(3)?, channel(5)?))
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// The style of an element in a TOML file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct StyleFile {
+    color: Option<ColourFile>,
+    background: Option<ColourFile>,
+    bold: bool,
+    dimmed: bool,
+    italic: bool,
+    underline: bool,
+    reverse: bool,
+    strikethrough: bool,
+}
+
+impl StyleFile {
+    /// Convert to a style.
+    fn to_style(&self) -> Result<Style, ThemeError> {
+        Ok(Style {
+            foreground: self.color.as_ref().map(ColourFile::to_colour).transpose()?,
+            background: self
+                .background
+                .as_ref()
+                .map(ColourFile::to_colour)
+                .transpose()?,
+            is_bold: self.bold,
+            is_dimmed: self.dimmed,
+            is_italic: self.italic,
+            is_underline: self.underline,
+            is_blink: false,
+            is_reverse: self.reverse,
+            is_hidden: false,
+            is_strikethrough: self.strikethrough,
+        })
+    }
+}
+
+/// Apply `style` on top of `base`.
+///
+/// Take the colours of `style` if set, and enable the font attributes of
+/// either style.
+pub(crate) fn overlay(base: Style, style: Style) -> Style {
+    Style {
+        foreground: style.foreground.or(base.foreground),
+        background: style.background.or(base.background),
+        is_bold: base.is_bold || style.is_bold,
+        is_dimmed: base.is_dimmed || style.is_dimmed,
+        is_italic: base.is_italic || style.is_italic,
+        is_underline: base.is_underline || style.is_underline,
+        is_blink: base.is_blink || style.is_blink,
+        is_reverse: base.is_reverse || style.is_reverse,
+        is_hidden: base.is_hidden || style.is_hidden,
+        is_strikethrough: base.is_strikethrough || style.is_strikethrough,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn builtin_themes_exist() {
+        for name in BUILTIN_THEMES {
+            assert!(Theme::builtin(name).is_some(), "{}", name);
+        }
+        for (alias, name) in BUILTIN_THEME_ALIASES {
+            assert!(BUILTIN_THEMES.contains(name), "{}", name);
+            assert_eq!(
+                format!("{:?}", Theme::builtin(alias)),
+                format!("{:?}", Theme::builtin(name)),
+                "{}",
+                alias
+            );
+        }
+        assert!(Theme::builtin("no such theme").is_none());
+    }
+
+    #[test]
+    fn pad_images_for_alignment() {
+        assert_eq!(ImageAlignment::Left.padding(80, 20), 0);
+        assert_eq!(ImageAlignment::Center.padding(80, 21), 29);
+        assert_eq!(ImageAlignment::Right.padding(80, 20), 60);
+        assert_eq!(ImageAlignment::Right.padding(10, 20), 0);
+    }
+
+    #[test]
+    fn theme_from_toml() {
+        let theme = Theme::from_toml(
+            "base = \"light\"\nhighlighting = \"InspiredGitHub\"\nimage_alignment = \"right\"\n\n\
+             [heading]\ncolor = \"#268bd2\"\nbold = true\n\n\
+             [link]\ncolor = 33\nbackground = \"white\"\nunderline = true\n\n\
+             [language_highlighting]\nLog = false\ndiff = \"InspiredGitHub\"\nrust = true\n\n\
+             [column_widths]\nDescription = \"60%\"\nurl = \"max-width=30\"\n",
+        )
+        .unwrap();
+        assert_eq!(theme.highlighting, "InspiredGitHub");
+        assert_eq!(theme.image_alignment, ImageAlignment::Right);
+        assert_eq!(
+            theme.language_highlighting,
+            vec![
+                ("diff".to_string(), Some("InspiredGitHub".to_string())),
+                ("log".to_string(), None),
+            ]
+            .into_iter()
+            .collect()
+        );
+        assert_eq!(
+            theme.heading,
+            Style::new().fg(Colour::RGB(0x26, 0x8b, 0xd2)).bold()
+        );
+        assert_eq!(
+            theme.link,
+            Style::new()
+                .fg(Colour::Fixed(33))
+                .on(Colour::White)
+                .underline()
+        );
+        assert_eq!(theme.code, Theme::light().code);
+        assert_eq!(
+            theme.column_widths,
+            vec![
+                (
+                    "description".to_string(),
+                    WidthHint {
+                        min: Some(ColumnWidth::Percent(60)),
+                        max: Some(ColumnWidth::Percent(60)),
+                    }
+                ),
+                (
+                    "url".to_string(),
+                    WidthHint {
+                        min: None,
+                        max: Some(ColumnWidth::Columns(30)),
+                    }
+                ),
+            ]
+            .into_iter()
+            .collect()
+        );
+    }
+
+    #[test]
+    fn parse_width_hints() {
+        assert_eq!(ColumnWidth::parse("20"), Some(ColumnWidth::Columns(20)));
+        assert_eq!(ColumnWidth::parse("60%"), Some(ColumnWidth::Percent(60)));
+        assert_eq!(ColumnWidth::parse("120%"), None);
+        assert_eq!(ColumnWidth::Percent(60).columns(50), 30);
+        assert_eq!(ColumnWidth::Percent(0).columns(50), 1);
+        assert_eq!(
+            WidthHint::parse("min-width=20 max-width=50%"),
+            Some(WidthHint {
+                min: Some(ColumnWidth::Columns(20)),
+                max: Some(ColumnWidth::Percent(50)),
+            })
+        );
+        assert_eq!(WidthHint::parse("wide"), None);
+        assert_eq!(WidthHint::parse("height=20"), None);
+    }
+
+    #[test]
+    fn invalid_themes_from_toml() {
+        let error = |source: &str| Theme::from_toml(source).unwrap_err().to_string();
+        assert_eq!(error("base = \"neon\""), "Unknown base theme: neon");
+        assert_eq!(
+            error("highlighting = \"Neon\""),
+            "Unknown highlighting theme: Neon"
+        );
+        assert_eq!(
+            error("[code]\ncolor = \"#12345g\""),
+            "Invalid colour: #12345g"
+        );
+        assert_eq!(
+            error("image_alignment = \"justify\""),
+            "Invalid image alignment: justify"
+        );
+        assert_eq!(
+            error("[column_widths]\nURL = \"wide\""),
+            "Invalid column width: wide"
+        );
+        assert_eq!(
+            error("[language_highlighting]\nlog = \"Neon\""),
+            "Unknown highlighting theme: Neon"
+        );
+        assert!(Theme::from_toml("[code]\nblink = true").is_err());
+        assert!(Theme::from_toml("[quote]\nbold = true").is_err());
+    }
+
+    #[test]
+    fn overlay_keeps_base_colours_and_attributes() {
+        let base = Style::new().fg(Colour::Green).italic();
+        assert_eq!(
+            overlay(base, Style::new().bold()),
+            Style::new().fg(Colour::Green).italic().bold()
+        );
+        assert_eq!(
+            overlay(base, Style::new().fg(Colour::Blue)),
+            Style::new().fg(Colour::Blue).italic()
+        );
+    }
+}
@@ -0,0 +1,113 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Timings of a run of mdcat, for `--timings`.
+//!
+//! We measure how long we parse documents and count the bytes we write, and
+//! summarize them together with the metrics of rendering on a single line,
+//! which users can paste into issues about performance.
+
+use mdcat::Metrics;
+use std::convert::TryFrom;
+use std::io;
+use std::io::prelude::*;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Nanoseconds we spent parsing documents.
+static PARSING: AtomicU64 = AtomicU64::new(0);
+
+/// The number of bytes we wrote to the output.
+static OUTPUT_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// Parse a document with `parse`, and add the time it takes to the time we
+/// spent parsing.
+pub fn time_parse<T, F: FnOnce() -> T>(parse: F) -> T {
+    let start = Instant::now();
+    let result = parse();
+    let nanos = u64::try_from(start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+    PARSING.fetch_add(nanos, Ordering::Relaxed);
+    result
+}
+
+/// Output which counts the bytes we write.
+pub struct Counted<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> Counted<W> {
+    /// Count the bytes we write to `inner`.
+    pub fn new(inner: W) -> Counted<W> {
+        Counted { inner }
+    }
+}
+
+impl<W: Write> Write for Counted<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        OUTPUT_BYTES.fetch_add(written as u64, Ordering::Relaxed);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Milliseconds of `duration`, with a fraction.
+fn milliseconds(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Summarize a run of the given `total` duration on a single line, with the
+/// `metrics` of rendering.
+pub fn summary(total: Duration, metrics: &Metrics) -> String {
+    format!(
+        "mdcat: {:.1} ms total, parse {:.1} ms, highlight {:.1} ms, {} images ({} bytes), {} bytes output",
+        milliseconds(total),
+        milliseconds(Duration::from_nanos(PARSING.load(Ordering::Relaxed))),
+        milliseconds(metrics.highlighting),
+        metrics.images,
+        metrics.image_bytes,
+        OUTPUT_BYTES.load(Ordering::Relaxed)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn count_output() {
+        let before = OUTPUT_BYTES.load(Ordering::Relaxed);
+        let mut output = Counted::new(Vec::new());
+        write!(output, "Grüße").unwrap();
+        assert_eq!(output.inner, "Grüße".as_bytes());
+        assert!(7 <= OUTPUT_BYTES.load(Ordering::Relaxed) - before);
+    }
+
+    #[test]
+    fn summarize_run() {
+        let metrics = Metrics {
+            highlighting: Duration::from_micros(2500),
+            images: 2,
+            image_bytes: 1024,
+        };
+        let summary = summary(Duration::from_millis(12), &metrics);
+        assert!(summary.starts_with("mdcat: 12.0 ms total, parse "));
+        assert!(summary.contains(" ms, highlight 2.5 ms, 2 images (1024 bytes), "));
+        assert!(summary.ends_with(" bytes output"));
+    }
+}
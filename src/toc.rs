@@ -0,0 +1,197 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tables of contents.
+
+use pulldown_cmark::Event;
+use pulldown_cmark::Event::*;
+use pulldown_cmark::Tag::*;
+use std::borrow::Cow;
+
+/// Get the text of all headings in `events`, with their level.
+fn headings(events: &[Event<'_>]) -> Vec<(i32, String)> {
+    let mut headings = Vec::new();
+    let mut current: Option<(i32, String)> = None;
+    for event in events {
+        match event {
+            Start(Header(level)) => current = Some((*level, String::new())),
+            End(Header(_)) => headings.extend(current.take()),
+            Text(text) | InlineHtml(text) => {
+                if let Some((_, ref mut heading)) = current {
+                    heading.push_str(text);
+                }
+            }
+            _ => {}
+        }
+    }
+    headings
+}
+
+/// Create events for a table of contents of `headings`.
+///
+/// Render the table of contents as nested unordered list.
+fn toc_events<'a>(headings: &[(i32, String)]) -> Vec<Event<'a>> {
+    let mut events = Vec::new();
+    // The levels of the currently open lists
+    let mut levels: Vec<i32> = Vec::new();
+    for (level, text) in headings {
+        match levels.last() {
+            Some(&top) if top < *level => {
+                // Start a nested list in the current item
+                events.push(Start(List(None)));
+                levels.push(*level);
+            }
+            Some(_) => {
+                while 1 < levels.len() && *level < levels[levels.len() - 1] {
+                    events.push(End(Item));
+                    events.push(End(List(None)));
+                    levels.pop();
+                }
+                // Close the previous item at this level
+                events.push(End(Item));
+            }
+            None => {
+                events.push(Start(List(None)));
+                levels.push(*level);
+            }
+        }
+        events.push(Start(Item));
+        events.push(Text(Cow::Owned(text.clone())));
+    }
+    for _ in levels {
+        events.push(End(Item));
+        events.push(End(List(None)));
+    }
+    events
+}
+
+/// Whether `events` start with a table of contents marker.
+///
+/// Return the number of events of the marker if so.
+///
+/// We understand a paragraph with just `[TOC]` and a `<!-- toc -->` HTML
+/// comment as markers.
+fn toc_marker(events: &[Event<'_>]) -> Option<usize> {
+    match events.first()? {
+        Html(html) if html.trim().eq_ignore_ascii_case("<!-- toc -->") => Some(1),
+        Start(Paragraph) => {
+            let mut text = String::new();
+            for (index, event) in events.iter().enumerate().skip(1) {
+                match event {
+                    Text(part) => text.push_str(part),
+                    End(Paragraph) if text.trim() == "[TOC]" => return Some(index + 1),
+                    _ => return None,
+                }
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+/// Insert a table of contents into markdown `events`.
+///
+/// Replace all table of contents markers in `events`, ie, `[TOC]` on a
+/// paragraph of its own and `<!-- toc -->`, with a table of contents of
+/// all headings.  If `events` contain no marker and `at_start` is true,
+/// insert the table of contents at the start of the document.
+pub fn insert_toc<'a, I>(events: I, at_start: bool) -> Vec<Event<'a>>
+where
+    I: IntoIterator<Item = Event<'a>>,
+{
+    let events: Vec<Event<'a>> = events.into_iter().collect();
+    let headings = headings(&events);
+    let mut result = Vec::with_capacity(events.len());
+    let mut found_marker = false;
+    let mut index = 0;
+    while index < events.len() {
+        match toc_marker(&events[index..]) {
+            Some(length) => {
+                result.extend(toc_events(&headings));
+                found_marker = true;
+                index += length;
+            }
+            None => {
+                result.push(events[index].clone());
+                index += 1;
+            }
+        }
+    }
+    if at_start && !found_marker {
+        result.splice(0..0, toc_events(&headings));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use pulldown_cmark::Parser;
+
+    fn toc_of(markdown: &str, at_start: bool) -> Vec<Event<'_>> {
+        insert_toc(Parser::new(markdown), at_start)
+    }
+
+    #[test]
+    fn nested_toc_events() {
+        let headings = vec![
+            (1, "a".to_string()),
+            (2, "b".to_string()),
+            (1, "c".to_string()),
+        ];
+        assert_eq!(
+            toc_events(&headings),
+            vec![
+                Start(List(None)),
+                Start(Item),
+                Text(Cow::Borrowed("a")),
+                Start(List(None)),
+                Start(Item),
+                Text(Cow::Borrowed("b")),
+                End(Item),
+                End(List(None)),
+                End(Item),
+                Start(Item),
+                Text(Cow::Borrowed("c")),
+                End(Item),
+                End(List(None)),
+            ]
+        );
+    }
+
+    #[test]
+    fn replace_markers() {
+        let expected = toc_of("# Foo\n\n## Bar\n", true);
+        for marker in &["[TOC]", "<!-- toc -->"] {
+            let markdown = format!("Intro\n\n{}\n\n# Foo\n\n## Bar\n", marker);
+            let events = toc_of(&markdown, false);
+            // Intro paragraph, then the table of contents
+            assert_eq!(&events[3..13], &expected[..10], "{}", marker);
+        }
+    }
+
+    #[test]
+    fn no_marker() {
+        let markdown = "# Foo\n\nBar\n";
+        assert_eq!(
+            toc_of(markdown, false),
+            Parser::new(markdown).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            &toc_of(markdown, true)[..5],
+            &toc_events(&[(1, "Foo".to_string())])[..]
+        );
+    }
+}
@@ -0,0 +1,364 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Limit the number of output lines.
+
+use failure::Fail;
+use std::fmt;
+use std::io::{Result, Write};
+use unicode_width::UnicodeWidthChar;
+
+/// Where we are in the output, with regards to escape sequences.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum State {
+    /// Plain text.
+    Text,
+    /// After an ESC.
+    Escape,
+    /// Inside a CSI sequence.
+    Csi,
+    /// Inside an OSC sequence.
+    Osc,
+    /// After an ESC inside an OSC sequence, ie, perhaps at ST.
+    OscEscape,
+}
+
+/// A writer which writes only a limited number of lines.
+///
+/// Count lines the way the terminal shows them: a line ends at a newline, or
+/// where the terminal wraps it at the last column, and an unterminated last
+/// line counts as well.  Silently drop all lines after the limit, but count
+/// them, and reset the style and close any open hyperlink where we cut.
+pub struct LineLimit<W: Write> {
+    inner: W,
+    /// The number of columns of the terminal.
+    columns: usize,
+    /// The number of lines we may still write.
+    remaining: usize,
+    /// The number of lines we dropped.
+    dropped: usize,
+    /// Whether we cut the output already.
+    cut: bool,
+    /// The column of the current line.
+    column: usize,
+    /// Where we are with regards to escape sequences.
+    state: State,
+    /// The pending bytes of the current character or escape sequence.
+    pending: Vec<u8>,
+    /// Whether the last SGR sequence left some style active.
+    styled: bool,
+    /// Whether an OSC 8 hyperlink is open.
+    link: bool,
+}
+
+impl<W: Write> LineLimit<W> {
+    /// Write at most `lines` lines to `inner`, for a terminal with `columns`.
+    pub fn new(inner: W, lines: usize, columns: usize) -> LineLimit<W> {
+        LineLimit {
+            inner,
+            columns: if columns == 0 { usize::MAX } else { columns },
+            remaining: lines,
+            dropped: 0,
+            cut: false,
+            column: 0,
+            state: State::Text,
+            pending: Vec::new(),
+            styled: false,
+            link: false,
+        }
+    }
+
+    /// The number of lines we dropped so far.
+    ///
+    /// Include the unterminated last line if we dropped it.
+    pub fn dropped_lines(&self) -> usize {
+        if self.cut && 0 < self.column {
+            self.dropped + 1
+        } else {
+            self.dropped
+        }
+    }
+
+    /// Get the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// End the current line.
+    fn end_line(&mut self) {
+        if self.cut {
+            self.dropped += 1;
+        } else {
+            self.remaining -= 1;
+        }
+        self.column = 0;
+    }
+
+    /// Whether the line limit leaves no room for the current line.
+    fn exhausted(&self) -> bool {
+        self.cut || self.remaining == 0
+    }
+
+    /// Track the effect of the complete escape sequence in `pending`.
+    fn end_escape(&mut self) {
+        let sequence = std::mem::take(&mut self.pending);
+        if sequence.starts_with(b"\x1b[") && sequence.ends_with(b"m") {
+            let parameters = &sequence[2..sequence.len() - 1];
+            self.styled = !parameters
+                .split(|&b| b == b';')
+                .all(|p| p.is_empty() || p == b"0");
+        } else if sequence.starts_with(b"\x1b]8;") {
+            let target = sequence[4..]
+                .splitn(2, |&b| b == b';')
+                .nth(1)
+                .unwrap_or_default();
+            let target = target
+                .strip_suffix(b"\x07")
+                .or_else(|| target.strip_suffix(b"\x1b\\"))
+                .unwrap_or(target);
+            self.link = !target.is_empty();
+        }
+        self.state = State::Text;
+    }
+
+    /// Account for the printable character at the start of `pending`, if
+    /// complete.
+    ///
+    /// Return `false` if the character does not fit within the limit.
+    fn put_char(&mut self) -> bool {
+        let width = match std::str::from_utf8(&self.pending) {
+            Ok(text) => text.chars().next().and_then(|c| c.width()).unwrap_or(0),
+            Err(error) if error.error_len().is_none() => return true,
+            Err(_) => 1,
+        };
+        self.pending.clear();
+        if 0 < self.column && self.columns < self.column + width {
+            if self.exhausted() {
+                self.cut = true;
+            }
+            self.end_line();
+        }
+        if 0 < width && self.exhausted() {
+            self.cut = true;
+        }
+        self.column += width;
+        !self.cut
+    }
+
+    /// Process `byte` and return whether it fits within the limit.
+    fn put(&mut self, byte: u8) -> bool {
+        match self.state {
+            State::Text => match byte {
+                0x1b => {
+                    self.pending.clear();
+                    self.pending.push(byte);
+                    self.state = State::Escape;
+                }
+                b'\n' => {
+                    if self.exhausted() {
+                        self.cut = true;
+                    }
+                    self.end_line();
+                }
+                b'\r' => self.column = 0,
+                b'\t' => {
+                    let width = 8 - self.column % 8;
+                    if 0 < self.column && self.columns < self.column + width {
+                        self.column = self.columns;
+                    } else {
+                        if self.exhausted() {
+                            self.cut = true;
+                        }
+                        self.column += width;
+                    }
+                }
+                _ if byte < 0x20 || byte == 0x7f => {}
+                _ => {
+                    self.pending.push(byte);
+                    return self.put_char();
+                }
+            },
+            State::Escape => {
+                self.pending.push(byte);
+                match byte {
+                    b'[' => self.state = State::Csi,
+                    b']' => self.state = State::Osc,
+                    _ => self.end_escape(),
+                }
+            }
+            State::Csi => {
+                self.pending.push(byte);
+                if (0x40..=0x7e).contains(&byte) {
+                    self.end_escape();
+                }
+            }
+            State::Osc => {
+                self.pending.push(byte);
+                match byte {
+                    0x07 => self.end_escape(),
+                    0x1b => self.state = State::OscEscape,
+                    _ => {}
+                }
+            }
+            State::OscEscape => {
+                self.pending.push(byte);
+                if byte == b'\\' {
+                    self.end_escape();
+                } else {
+                    self.state = State::Osc;
+                }
+            }
+        }
+        !self.cut
+    }
+}
+
+impl<W: Write> Write for LineLimit<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        if self.cut {
+            for &byte in buf {
+                self.put(byte);
+            }
+            return Ok(buf.len());
+        }
+        // Remember the style at the start of each escape sequence, because we
+        // cut before the sequence, ie, before it takes effect.
+        let mut cut = None;
+        let mut start = 0;
+        let mut styled = self.styled;
+        let mut link = self.link;
+        for (index, &byte) in buf.iter().enumerate() {
+            if self.state == State::Text && self.pending.is_empty() {
+                start = index;
+                styled = self.styled;
+                link = self.link;
+            }
+            if !self.put(byte) {
+                cut = Some((start, index + 1));
+                break;
+            }
+        }
+        match cut {
+            Some((cut, rest)) => {
+                self.inner.write_all(&buf[..cut])?;
+                if link {
+                    self.inner.write_all(b"\x1b]8;;\x07")?;
+                }
+                if styled {
+                    self.inner.write_all(b"\x1b[0m")?;
+                }
+                for &byte in &buf[rest..] {
+                    self.put(byte);
+                }
+            }
+            None => self.inner.write_all(buf)?,
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Output was truncated.
+#[derive(Debug)]
+pub struct Truncated {
+    /// The number of lines we dropped.
+    pub lines: usize,
+}
+
+impl fmt::Display for Truncated {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Output truncated, {} more lines", self.lines)
+    }
+}
+
+impl Fail for Truncated {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn drop_lines_after_limit() {
+        let mut limit = LineLimit::new(Vec::new(), 2, 80);
+        limit.write_all(b"foo\nb").unwrap();
+        limit.write_all(b"ar\nbaz\n").unwrap();
+        limit.write_all(b"spam\n").unwrap();
+        assert_eq!(limit.dropped_lines(), 2);
+        assert_eq!(String::from_utf8_lossy(limit.get_mut()), "foo\nbar\n");
+    }
+
+    #[test]
+    fn keep_output_within_limit() {
+        let mut limit = LineLimit::new(Vec::new(), 2, 80);
+        limit.write_all(b"foo\nbar\n").unwrap();
+        assert_eq!(limit.dropped_lines(), 0);
+        assert_eq!(String::from_utf8_lossy(limit.get_mut()), "foo\nbar\n");
+    }
+
+    #[test]
+    fn count_unterminated_last_line() {
+        let mut limit = LineLimit::new(Vec::new(), 2, 80);
+        limit.write_all(b"foo\nbar\nbaz").unwrap();
+        assert_eq!(limit.dropped_lines(), 1);
+        assert_eq!(String::from_utf8_lossy(limit.get_mut()), "foo\nbar\n");
+    }
+
+    #[test]
+    fn count_wrapped_lines() {
+        let mut limit = LineLimit::new(Vec::new(), 2, 4);
+        limit.write_all("foobarbaz\n漢字漢字\n".as_bytes()).unwrap();
+        assert_eq!(limit.dropped_lines(), 3);
+        assert_eq!(String::from_utf8_lossy(limit.get_mut()), "foobarba");
+    }
+
+    #[test]
+    fn reset_style_when_cutting_inside_styled_span() {
+        let mut limit = LineLimit::new(Vec::new(), 1, 80);
+        limit.write_all(b"\x1b[1mfoo\nbar\x1b[0m\nbaz\n").unwrap();
+        assert_eq!(limit.dropped_lines(), 2);
+        assert_eq!(
+            String::from_utf8_lossy(limit.get_mut()),
+            "\x1b[1mfoo\n\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn close_link_when_cutting_inside_link() {
+        let mut limit = LineLimit::new(Vec::new(), 1, 80);
+        limit
+            .write_all(b"\x1b]8;;https://example.com\x1b\\foo\n")
+            .unwrap();
+        limit.write_all(b"bar\x1b]8;;\x1b\\\n").unwrap();
+        assert_eq!(limit.dropped_lines(), 1);
+        assert_eq!(
+            String::from_utf8_lossy(limit.get_mut()),
+            "\x1b]8;;https://example.com\x1b\\foo\n\x1b]8;;\x07"
+        );
+    }
+
+    #[test]
+    fn keep_style_reset_right_at_limit() {
+        let mut limit = LineLimit::new(Vec::new(), 1, 80);
+        limit.write_all(b"\x1b[1mfoo\x1b[0m\n\x1b[0m").unwrap();
+        assert_eq!(limit.dropped_lines(), 0);
+        assert_eq!(
+            String::from_utf8_lossy(limit.get_mut()),
+            "\x1b[1mfoo\x1b[0m\n\x1b[0m"
+        );
+    }
+}
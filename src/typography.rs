@@ -0,0 +1,193 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Smart punctuation according to the typographic conventions of a language.
+//!
+//! Replace straight quotes with the quotation marks of the language, `--` and
+//! `---` with en and em dashes, and `...` with an ellipsis.
+
+/// Typographic conventions of a language.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Typography {
+    /// Opening and closing quotation marks.
+    pub quotes: (char, char),
+    /// Opening and closing quotation marks inside quotes.
+    pub inner_quotes: (char, char),
+    /// The bullet of unordered list items.
+    pub bullet: char,
+}
+
+/// Quotation marks of English and many other languages.
+const ENGLISH_QUOTES: ((char, char), (char, char)) =
+    (('\u{201C}', '\u{201D}'), ('\u{2018}', '\u{2019}'));
+
+/// Low-high quotation marks, eg, of German.
+const LOW_HIGH_QUOTES: ((char, char), (char, char)) =
+    (('\u{201E}', '\u{201C}'), ('\u{201A}', '\u{2018}'));
+
+/// Guillemets with English quotation marks inside, eg, of French.
+const GUILLEMETS: ((char, char), (char, char)) = (('\u{AB}', '\u{BB}'), ('\u{201C}', '\u{201D}'));
+
+/// Guillemets with low-high quotation marks inside, eg, of Russian.
+const GUILLEMETS_LOW_HIGH: ((char, char), (char, char)) =
+    (('\u{AB}', '\u{BB}'), ('\u{201E}', '\u{201C}'));
+
+/// Closing quotation marks on both sides, eg, of Swedish.
+const RIGHT_QUOTES: ((char, char), (char, char)) =
+    (('\u{201D}', '\u{201D}'), ('\u{2019}', '\u{2019}'));
+
+/// Low-right quotation marks with guillemets inside, eg, of Polish.
+const LOW_RIGHT_QUOTES: ((char, char), (char, char)) =
+    (('\u{201E}', '\u{201D}'), ('\u{AB}', '\u{BB}'));
+
+/// Reversed guillemets, eg, of Danish.
+const REVERSED_GUILLEMETS: ((char, char), (char, char)) =
+    (('\u{BB}', '\u{AB}'), ('\u{203A}', '\u{2039}'));
+
+/// Corner brackets of Japanese.
+const CORNER_BRACKETS: ((char, char), (char, char)) =
+    (('\u{300C}', '\u{300D}'), ('\u{300E}', '\u{300F}'));
+
+/// The common bullet.
+const BULLET: char = '\u{2022}';
+
+/// The en dash which French and Russian typography use for list items.
+const DASH_BULLET: char = '\u{2013}';
+
+impl Default for Typography {
+    /// English typography.
+    fn default() -> Typography {
+        Typography::new(ENGLISH_QUOTES, BULLET)
+    }
+}
+
+impl Typography {
+    fn new(quotes: ((char, char), (char, char)), bullet: char) -> Typography {
+        Typography {
+            quotes: quotes.0,
+            inner_quotes: quotes.1,
+            bullet,
+        }
+    }
+
+    /// Get the typography for the language with the given `tag`, eg, `de` or
+    /// `fr-CH`.
+    ///
+    /// Look at the primary language only, and use English typography for
+    /// languages we do not know.
+    pub fn for_language(tag: &str) -> Typography {
+        let language = tag.split(['-', '_']).next().unwrap_or("").to_lowercase();
+        match language.as_str() {
+            "de" | "cs" | "sk" | "sl" | "is" | "lt" => Typography::new(LOW_HIGH_QUOTES, BULLET),
+            "fr" => Typography::new(GUILLEMETS, DASH_BULLET),
+            "es" | "it" | "pt" | "ca" | "el" | "nb" | "nn" | "no" => {
+                Typography::new(GUILLEMETS, BULLET)
+            }
+            "ru" | "uk" | "be" => Typography::new(GUILLEMETS_LOW_HIGH, DASH_BULLET),
+            "pl" | "hu" | "ro" => Typography::new(LOW_RIGHT_QUOTES, BULLET),
+            "sv" | "fi" => Typography::new(RIGHT_QUOTES, BULLET),
+            "da" => Typography::new(REVERSED_GUILLEMETS, BULLET),
+            "ja" => Typography::new(CORNER_BRACKETS, BULLET),
+            _ => Typography::default(),
+        }
+    }
+}
+
+/// Whether a quote after `previous` opens a quotation.
+fn opens_quote(previous: Option<char>) -> bool {
+    match previous {
+        None => true,
+        Some(c) => {
+            c.is_whitespace()
+                || "([{\u{2013}\u{2014}/".contains(c)
+                || c == '\u{201C}'
+                || c == '\u{2018}'
+        }
+    }
+}
+
+/// Replace punctuation in `text` according to `typography`.
+///
+/// `previous` is the character before `text`, if any, to decide whether quotes
+/// at the beginning of `text` open or close a quotation; we update it to the
+/// last character of `text`.
+pub(crate) fn smarten(text: &str, typography: &Typography, previous: &mut Option<char>) -> String {
+    let text = text
+        .replace("---", "\u{2014}")
+        .replace("--", "\u{2013}")
+        .replace("...", "\u{2026}");
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        // Remember quotes as English quotes to decide about the next quote
+        let (replacement, remembered) = match c {
+            '"' if opens_quote(*previous) => (typography.quotes.0, '\u{201C}'),
+            '"' => (typography.quotes.1, '\u{201D}'),
+            '\'' if previous.is_some_and(char::is_alphanumeric)
+                && chars.peek().is_some_and(|c| c.is_alphanumeric()) =>
+            {
+                // An apostrophe inside a word
+                ('\u{2019}', '\u{2019}')
+            }
+            '\'' if opens_quote(*previous) => (typography.inner_quotes.0, '\u{2018}'),
+            '\'' => (typography.inner_quotes.1, '\u{2019}'),
+            c => (c, c),
+        };
+        *previous = Some(remembered);
+        result.push(replacement);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn smart(text: &str, language: &str) -> String {
+        smarten(text, &Typography::for_language(language), &mut None)
+    }
+
+    #[test]
+    fn english_quotes_dashes_and_ellipsis() {
+        assert_eq!(
+            smart("\"It's 'fine'\" -- she said... --- really", "en"),
+            "\u{201C}It\u{2019}s \u{2018}fine\u{2019}\u{201D} \u{2013} she said\u{2026} \u{2014} really"
+        );
+    }
+
+    #[test]
+    fn quotes_by_language() {
+        assert_eq!(smart("\"Hallo\"", "de-AT"), "\u{201E}Hallo\u{201C}");
+        assert_eq!(smart("\"Salut\"", "fr"), "\u{AB}Salut\u{BB}");
+        assert_eq!(smart("'Hej'", "sv_SE"), "\u{2019}Hej\u{2019}");
+        assert_eq!(smart("\"Hello\"", "tlh"), "\u{201C}Hello\u{201D}");
+    }
+
+    #[test]
+    fn previous_character_across_texts() {
+        let typography = Typography::default();
+        let mut previous = Some('a');
+        assert_eq!(smarten("\" b", &typography, &mut previous), "\u{201D} b");
+        assert_eq!(previous, Some('b'));
+        let mut previous = Some(' ');
+        assert_eq!(smarten("\"b", &typography, &mut previous), "\u{201C}b");
+    }
+
+    #[test]
+    fn bullets_by_language() {
+        assert_eq!(Typography::for_language("en").bullet, '\u{2022}');
+        assert_eq!(Typography::for_language("FR").bullet, '\u{2013}');
+    }
+}
@@ -0,0 +1,195 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Defaults for arguments from `MDCAT_*` environment variables.
+//!
+//! In containers or CI config files are awkward, so every flag has an
+//! environment variable, eg, `$MDCAT_COLUMNS` for `--columns`.  Variables
+//! take precedence over the theme file, and flags over variables.
+//!
+//! We take flags from the table of arguments in `crate::args`.
+
+use crate::args::ArgDef;
+use clap::ArgMatches;
+use std::ffi::OsString;
+
+/// Arguments without environment variables.
+///
+/// `$MDCAT_LANG` is the language of messages, not of `--lang`.
+const EXCLUDED: &[&str] = &["help", "help_json", "version", "lang"];
+
+/// The name of the environment variable for the flag `long`.
+///
+/// `--no-colour` becomes `$MDCAT_NO_COLOUR`.
+pub fn variable_name(long: &str) -> String {
+    format!("MDCAT_{}", long.replace('-', "_").to_uppercase())
+}
+
+/// The long flag of `arg`, if we take `arg` from the environment.
+///
+/// We take neither positional nor hidden arguments from the environment.
+fn variable_flag(arg: &ArgDef) -> Option<&'static str> {
+    arg.long
+        .filter(|_| !arg.hidden && !EXCLUDED.contains(&arg.name))
+}
+
+/// The environment variable for `arg`, if any.
+pub fn variable_for(arg: &ArgDef) -> Option<String> {
+    variable_flag(arg).map(variable_name)
+}
+
+/// Whether `value` of the variable `name` enables a flag.
+fn is_enabled(name: &str, value: &OsString) -> clap::Result<bool> {
+    match value.to_string_lossy().to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "" | "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err(clap::Error::value_validation_auto(format!(
+            "{:?} of ${}, expected 1, true, yes or on, or 0, false, no or off",
+            value, name
+        ))),
+    }
+}
+
+/// Get arguments from environment variables for the arguments `args`.
+///
+/// Look up variables with `var`, and skip arguments which `matches` already
+/// has from the command line, or which conflict with arguments we have.
+/// Return arguments to put before the arguments from the command line.
+pub fn arguments_from_env<F>(
+    args: &[ArgDef],
+    matches: &ArgMatches<'_>,
+    var: F,
+) -> clap::Result<Vec<OsString>>
+where
+    F: Fn(&str) -> Option<OsString>,
+{
+    let mut present = args
+        .iter()
+        .filter(|arg| matches.occurrences_of(arg.name) > 0)
+        .map(|arg| arg.name)
+        .collect::<Vec<_>>();
+    let mut arguments = Vec::new();
+    for arg in args {
+        let long = match variable_flag(arg) {
+            Some(long) => long,
+            None => continue,
+        };
+        let name = variable_name(long);
+        let value = match var(&name) {
+            Some(value) if !present.contains(&arg.name) => value,
+            _ => continue,
+        };
+        let conflicts = present.iter().any(|other| {
+            arg.conflicts.contains(other)
+                || args
+                    .iter()
+                    .any(|a| a.name == *other && a.conflicts.contains(&arg.name))
+        });
+        let requirements_met = arg
+            .requires
+            .iter()
+            .all(|required| present.contains(required));
+        if conflicts || !requirements_met {
+            continue;
+        }
+        if arg.takes_value {
+            let mut argument = OsString::from(format!("--{}=", long));
+            argument.push(value);
+            arguments.push(argument);
+        } else if is_enabled(&name, &value)? {
+            arguments.push(OsString::from(format!("--{}", long)));
+        } else {
+            continue;
+        }
+        present.push(arg.name);
+    }
+    Ok(arguments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::App;
+    use pretty_assertions::assert_eq;
+
+    const ARGS: &[ArgDef] = &[
+        ArgDef::option("columns", "columns", "Columns"),
+        ArgDef::flag("no_colour", "no-colour", "No colours").conflicts(&["paginate"]),
+        ArgDef::flag("paginate", "paginate", "Paginate"),
+        ArgDef::option("lang", "lang", "Language"),
+        ArgDef::flag("ansi_only", "ansi-only", "ANSI only")
+            .conflicts(&["no_colour"])
+            .hidden(),
+    ];
+
+    fn app() -> App<'static, 'static> {
+        App::new("mdcat").args(&ARGS.iter().map(|arg| arg.to_arg()).collect::<Vec<_>>())
+    }
+
+    fn arguments(variables: &[(&str, &str)], command_line: &[&str]) -> Vec<OsString> {
+        let matches = app().get_matches_from(command_line);
+        arguments_from_env(ARGS, &matches, |name| {
+            variables
+                .iter()
+                .find(|(variable, _)| *variable == name)
+                .map(|(_, value)| OsString::from(value))
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn variable_names() {
+        assert_eq!(variable_name("no-colour"), "MDCAT_NO_COLOUR");
+        assert_eq!(variable_name("columns"), "MDCAT_COLUMNS");
+    }
+
+    #[test]
+    fn arguments_from_variables() {
+        let variables = [
+            ("MDCAT_COLUMNS", "60"),
+            ("MDCAT_NO_COLOUR", "yes"),
+            ("MDCAT_LANG", "de"),
+        ];
+        assert_eq!(
+            arguments(&variables, &["mdcat"]),
+            vec!["--columns=60", "--no-colour"]
+        );
+        assert_eq!(
+            arguments(&[("MDCAT_NO_COLOUR", "0")], &["mdcat"]),
+            Vec::<OsString>::new()
+        );
+    }
+
+    #[test]
+    fn flags_take_precedence_over_variables() {
+        let variables = [("MDCAT_COLUMNS", "60"), ("MDCAT_NO_COLOUR", "1")];
+        assert_eq!(
+            arguments(&variables, &["mdcat", "--columns", "100", "--paginate"]),
+            Vec::<OsString>::new()
+        );
+        assert_eq!(
+            arguments(&variables, &["mdcat", "--ansi-only"]),
+            vec!["--columns=60"]
+        );
+    }
+
+    #[test]
+    fn reject_invalid_flag_values() {
+        let matches = app().get_matches_from(["mdcat"]);
+        let result = arguments_from_env(ARGS, &matches, |name| {
+            Some(OsString::from(name)).filter(|name| name == "MDCAT_PAGINATE")
+        });
+        assert!(result.is_err());
+    }
+}
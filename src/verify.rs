@@ -0,0 +1,147 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Check that rendering keeps all text of a document.
+//!
+//! Bugs in rendering, eg, for exotic nesting, may silently drop content.  We
+//! render the document as plain text, and check that every word of text and
+//! code in the document appears in the output, in order.
+
+use crate::environment::Environment;
+use crate::settings::SettingsBuilder;
+use crate::terminal::{TerminalCapabilities, TerminalSize};
+use failure::{Error, Fail};
+use pulldown_cmark::Event;
+use pulldown_cmark::Event::*;
+use std::fmt;
+use syntect::parsing::SyntaxSet;
+
+/// The number of columns to verify in.
+///
+/// Wide enough to not wrap text in table cells, which mixes up the order of
+/// words.
+const VERIFY_COLUMNS: usize = 1000;
+
+/// Text of the document which the output lacks.
+#[derive(Debug, PartialEq)]
+pub struct LostText {
+    /// The first word we lost.
+    pub word: String,
+    /// The text the word is part of.
+    pub text: String,
+}
+
+impl fmt::Display for LostText {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Output lost {:?} of text {:?}",
+            self.word,
+            self.text.trim()
+        )
+    }
+}
+
+impl Fail for LostText {}
+
+/// Find the first word of text in `events` which `output` lacks.
+///
+/// Look for words in order, and skip text events at the given `skipped`
+/// indexes.
+fn lost_text(events: &[Event], skipped: &[usize], output: &str) -> Option<LostText> {
+    let mut position = 0;
+    for (index, event) in events.iter().enumerate() {
+        let text = match event {
+            Text(text) if !skipped.contains(&index) => text,
+            _ => continue,
+        };
+        for word in text.split_whitespace() {
+            let word = word.replace('\u{AD}', "");
+            match output[position..].find(&word) {
+                Some(offset) => position += offset + word.len(),
+                None => {
+                    return Some(LostText {
+                        word,
+                        text: text.to_string(),
+                    })
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Render `events` as plain text and check that the output keeps all text.
+///
+/// Resolve references against `environment`.  Fail with `LostText` for
+/// the first word of text, including code, which the output lacks.
+pub fn verify<'a, I>(environment: &Environment, events: I) -> Result<(), Error>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    let events = events.collect::<Vec<_>>();
+    let settings = SettingsBuilder::new(TerminalCapabilities::none())
+        .terminal_size(TerminalSize::new(VERIFY_COLUMNS, 24))
+        .syntax_set(SyntaxSet::new())
+        .output_is_tty(false)
+        .deterministic(true)
+        .build()?;
+    let mut output = Vec::new();
+    crate::push_tty(&settings, &mut output, environment, events.iter().cloned())?;
+    let output = String::from_utf8_lossy(&output);
+    // We show attributes of figures as alignment, not as text
+    let mut skipped = crate::figures::figures(&events)
+        .attributes
+        .into_iter()
+        .collect::<Vec<_>>();
+    skipped.sort_unstable();
+    match lost_text(&events, &skipped, &output) {
+        Some(lost) => Err(lost.into()),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use pulldown_cmark::{Options, Parser};
+
+    fn events(markdown: &str) -> Vec<Event<'_>> {
+        Parser::new_ext(markdown, Options::ENABLE_TABLES | Options::ENABLE_FOOTNOTES).collect()
+    }
+
+    #[test]
+    fn find_lost_words() {
+        let events = events("Lorem *ipsum* `dolor`\n");
+        assert_eq!(lost_text(&events, &[], "Lorem ipsum dolor\n"), None);
+        assert_eq!(
+            lost_text(&events, &[], "Lorem dolor\n"),
+            Some(LostText {
+                word: "ipsum".to_string(),
+                text: "ipsum".to_string()
+            })
+        );
+        // Words must appear in order
+        assert!(lost_text(&events, &[], "dolor Lorem ipsum\n").is_some());
+    }
+
+    #[test]
+    fn verify_rendered_documents() {
+        let markdown = "# Lorem\n\n> * ipsum [dolor](https://example.com) sit\n>\n\
+                        >   | a | b |\n>   |---|---|\n>   | amet | elitr |\n\n\
+                        ![Logo](logo.png){.right}\n\nFoo[^1]\n\n[^1]: Bar\n";
+        verify(&Environment::isolated("/"), events(markdown).into_iter()).unwrap();
+    }
+}
@@ -0,0 +1,74 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Show images in an external viewer.
+//!
+//! If the terminal cannot show images inline, users may still look at images
+//! with a viewer, eg, `feh` or `wezterm imgcat`.
+
+use crate::shell::shell_command;
+use failure::{format_err, Error, ResultExt};
+use std::path::Path;
+use std::process::{Child, Stdio};
+
+/// Show the image at `path` with the viewer `command`.
+///
+/// Continue rendering while the viewer shows the image, and reap the viewer
+/// in the background once the user closes it.
+pub(crate) fn view_image(command: &str, path: &Path) -> Result<(), Error> {
+    let mut viewer = spawn_viewer(command, path)?;
+    std::thread::spawn(move || viewer.wait());
+    Ok(())
+}
+
+/// Start the viewer `command` for the image at `path`.
+///
+/// Run `command` with the shell and `path` as last argument.
+fn spawn_viewer(command: &str, path: &Path) -> Result<Child, Error> {
+    if command.trim().is_empty() {
+        return Err(format_err!("Empty image viewer command"));
+    }
+    let child = shell_command(command, &[path.as_os_str()])
+        .stdin(Stdio::null())
+        .spawn()
+        .with_context(|_| format!("Failed to start image viewer {}", command))?;
+    Ok(child)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn run_viewer_with_path() {
+        let mut viewer = spawn_viewer("test -f", Path::new("Cargo.toml")).unwrap();
+        assert!(viewer.wait().unwrap().success());
+        let mut viewer = spawn_viewer("test -d", Path::new("Cargo.toml")).unwrap();
+        assert!(!viewer.wait().unwrap().success());
+    }
+
+    #[test]
+    fn run_viewer_with_shell_syntax() {
+        let command = "test -n 'Lorem ipsum' && test -f";
+        let mut viewer = spawn_viewer(command, Path::new("Cargo.toml")).unwrap();
+        assert!(viewer.wait().unwrap().success());
+    }
+
+    #[test]
+    fn reject_empty_viewer() {
+        let error = view_image(" ", Path::new("Cargo.toml")).unwrap_err();
+        assert_eq!(error.to_string(), "Empty image viewer command");
+    }
+}
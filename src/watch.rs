@@ -0,0 +1,89 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Watch files for changes.
+
+use failure::Error;
+use notify::{DebouncedEvent, RecursiveMode, Watcher};
+use std::path::Path;
+use std::sync::mpsc::channel;
+use std::time::Duration;
+
+/// How long to wait for further writes before we consider a file changed.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Whether `event` changed the file at `path`.
+///
+/// Editors often save files atomically, by writing a new file and renaming
+/// it over the old one, so a file also changes if it is created or if some
+/// other file is renamed to it.
+fn changes(event: &DebouncedEvent, path: &Path) -> bool {
+    match event {
+        DebouncedEvent::Write(changed) | DebouncedEvent::Create(changed) => changed == path,
+        DebouncedEvent::Rename(_, to) => to == path,
+        _ => false,
+    }
+}
+
+/// Call `render` once, and again whenever the file at `path` changes.
+///
+/// Watch the directory of `path` rather than the file itself, to notice
+/// when editors replace the file.  Print errors of `render` and continue
+/// watching.
+pub fn watch<F>(path: &Path, mut render: F) -> Result<(), Error>
+where
+    F: FnMut() -> Result<(), Error>,
+{
+    let path = path.canonicalize()?;
+    let directory = path.parent().unwrap_or(&path);
+    let (sender, receiver) = channel();
+    let mut watcher = notify::watcher(sender, DEBOUNCE)?;
+    watcher.watch(directory, RecursiveMode::NonRecursive)?;
+    let mut render_or_print_error = || {
+        if let Err(error) = render() {
+            eprintln!("Error: {}", error);
+        }
+    };
+    render_or_print_error();
+    for event in receiver {
+        if changes(&event, &path) {
+            render_or_print_error();
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn changes_of_file() {
+        let path = PathBuf::from("/docs/README.md");
+        let other = PathBuf::from("/docs/.README.md.swp");
+        assert!(changes(&DebouncedEvent::Write(path.clone()), &path));
+        assert!(changes(&DebouncedEvent::Create(path.clone()), &path));
+        assert!(changes(
+            &DebouncedEvent::Rename(other.clone(), path.clone()),
+            &path
+        ));
+        assert!(!changes(&DebouncedEvent::Write(other.clone()), &path));
+        assert!(!changes(
+            &DebouncedEvent::Rename(path.clone(), other),
+            &path
+        ));
+        assert!(!changes(&DebouncedEvent::Remove(path.clone()), &path));
+    }
+}
@@ -0,0 +1,198 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Measure, truncate and wrap text for the terminal.
+//!
+//! We measure text by clusters of characters which terminals show as one,
+//! eg, a letter with combining accents, an emoji sequence joined with ZWJ, or
+//! a flag, and never split these clusters.
+
+use std::borrow::Cow;
+use unicode_width::UnicodeWidthChar;
+
+/// The zero width joiner, which joins emoji into sequences.
+const ZWJ: char = '\u{200D}';
+
+/// Whether `c` is a regional indicator, two of which make a flag.
+fn is_regional_indicator(c: char) -> bool {
+    ('\u{1F1E6}'..='\u{1F1FF}').contains(&c)
+}
+
+/// Whether `c` modifies the skin tone of the emoji before.
+fn is_emoji_modifier(c: char) -> bool {
+    ('\u{1F3FB}'..='\u{1F3FF}').contains(&c)
+}
+
+/// The length in bytes of the first cluster of `text`.
+///
+/// A cluster continues with characters without width, eg, combining marks
+/// or variation selectors, with skin tone modifiers, with any character
+/// after a ZWJ, and with the second regional indicator of a flag.  Escape sequences never continue a
+/// cluster.
+pub(crate) fn cluster_len(text: &str) -> usize {
+    let mut chars = text.char_indices();
+    let mut previous = match chars.next() {
+        Some((_, c)) => c,
+        None => return 0,
+    };
+    let mut indicators = usize::from(is_regional_indicator(previous));
+    for (index, c) in chars {
+        let continues = c != '\x1b'
+            && (previous == ZWJ
+                || c.width() == Some(0)
+                || is_emoji_modifier(c)
+                || (is_regional_indicator(c) && indicators == 1));
+        if !continues {
+            return index;
+        }
+        if is_regional_indicator(c) {
+            indicators += 1;
+        }
+        previous = c;
+    }
+    text.len()
+}
+
+/// The number of columns a `cluster` takes on the terminal.
+///
+/// Terminals show emoji sequences and flags, which have more than one
+/// visible character, as a single wide character.
+pub(crate) fn cluster_width(cluster: &str) -> usize {
+    let mut widths = cluster
+        .chars()
+        .map(|c| c.width().unwrap_or(0))
+        .filter(|&width| 0 < width);
+    match (widths.next(), widths.next()) {
+        (Some(_), Some(_)) => 2,
+        (Some(width), None) => width,
+        _ => 0,
+    }
+}
+
+/// Iterate over the clusters of `text`.
+fn clusters(text: &str) -> impl Iterator<Item = &str> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        let len = cluster_len(rest);
+        if len == 0 {
+            None
+        } else {
+            let (cluster, tail) = rest.split_at(len);
+            rest = tail;
+            Some(cluster)
+        }
+    })
+}
+
+/// The number of columns styled `text` takes on the terminal.
+///
+/// Ignore escape sequences, eg, styles and OSC 8 links, and count wide
+/// characters, eg, CJK characters and emoji, as two columns.
+pub fn display_width(text: &str) -> usize {
+    crate::table::display_width(text)
+}
+
+/// Truncate plain `text` to `width` columns, with an ellipsis if we cut it.
+///
+/// Cut between clusters only, and leave room for the ellipsis.  Return an
+/// empty string for zero columns, where not even the ellipsis fits.
+pub fn truncate_to_width(text: &str, width: usize) -> Cow<'_, str> {
+    if display_width(text) <= width {
+        return Cow::Borrowed(text);
+    }
+    if width == 0 {
+        return Cow::Borrowed("");
+    }
+    let mut truncated = String::new();
+    let mut columns = 0;
+    for cluster in clusters(text) {
+        let cluster_width = cluster_width(cluster);
+        if width < columns + cluster_width + 1 {
+            break;
+        }
+        truncated.push_str(cluster);
+        columns += cluster_width;
+    }
+    truncated.push('\u{2026}');
+    Cow::Owned(truncated)
+}
+
+/// Wrap styled `text` into lines of at most `width` columns.
+///
+/// Break lines at whitespace, and words longer than `width` between any two
+/// clusters.  End every line with a style reset if styles are active, and
+/// continue these styles on the next line.
+pub fn wrap_styled(text: &str, width: usize) -> Vec<String> {
+    crate::table::wrap(text, width)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn measure_clusters() {
+        assert_eq!(display_width("e\u{301}te\u{301}"), 3);
+        assert_eq!(display_width("日本"), 4);
+        // A family, joined with ZWJ
+        assert_eq!(
+            display_width("\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}"),
+            2
+        );
+        // The flag of Germany, and half a flag
+        assert_eq!(display_width("\u{1F1E9}\u{1F1EA}\u{1F1E9}"), 3);
+        // A waving hand with medium skin tone
+        assert_eq!(display_width("\u{1F44B}\u{1F3FD}"), 2);
+    }
+
+    #[test]
+    fn truncate_between_clusters() {
+        assert_eq!(truncate_to_width("Lorem", 5), "Lorem");
+        assert_eq!(truncate_to_width("Lorem ipsum", 6), "Lorem\u{2026}");
+        assert_eq!(
+            truncate_to_width("e\u{301}e\u{301}e\u{301}", 2),
+            "e\u{301}\u{2026}"
+        );
+        assert_eq!(truncate_to_width("日本語", 4), "日\u{2026}");
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        assert_eq!(
+            truncate_to_width(&format!("{}{}", family, family), 3),
+            format!("{}\u{2026}", family)
+        );
+    }
+
+    #[test]
+    fn truncate_to_no_columns() {
+        assert_eq!(truncate_to_width("Lorem", 0), "");
+        assert_eq!(truncate_to_width("", 0), "");
+        assert_eq!(truncate_to_width("Lorem", 1), "\u{2026}");
+    }
+
+    #[test]
+    fn wrap_without_splitting_clusters() {
+        assert_eq!(
+            wrap_styled("e\u{301}e\u{301}e\u{301}", 2),
+            vec!["e\u{301}e\u{301}", "e\u{301}"]
+        );
+        assert_eq!(
+            wrap_styled("\u{1F1E9}\u{1F1EA}\u{1F1EB}\u{1F1F7}", 3),
+            vec!["\u{1F1E9}\u{1F1EA}", "\u{1F1EB}\u{1F1F7}"]
+        );
+        assert_eq!(
+            wrap_styled("\x1b[1mfoo bar\x1b[0m", 3),
+            vec!["\x1b[1mfoo\x1b[0m", "\x1b[1mbar\x1b[0m"]
+        );
+    }
+}
@@ -13,13 +13,14 @@
 // limitations under the License.
 
 #![deny(warnings, missing_docs, clippy::all)]
+
+//! Test formatting output against expected HTML.
+
 // Currently we only run formatting tests on Unix, because we rely on a Python
 // tool here, and I failed to setup Python properly on Travis CI' Windows
 // workers.
 #![cfg(unix)]
 
-use mdcat;
-
 use pretty_assertions::assert_eq;
 use pulldown_cmark::Parser;
 use std::fs::File;
@@ -29,7 +30,7 @@ use std::process::{Command, Stdio};
 use syntect::parsing::SyntaxSet;
 
 fn format_ansi_to_html(markdown: &str) -> String {
-    let child = Command::new("ansi2html")
+    let mut child = Command::new("ansi2html")
         .arg("--input-encoding")
         .arg("utf8")
         .arg("--output-encoding")
@@ -40,29 +41,24 @@ fn format_ansi_to_html(markdown: &str) -> String {
         .spawn()
         .expect("Failed to start ansi2html");
     {
-        let size = mdcat::TerminalSize::default();
-        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let settings = mdcat::SettingsBuilder::new(mdcat::TerminalCapabilities::ansi())
+            .syntax_set(SyntaxSet::load_defaults_newlines())
+            .build()
+            .expect("Invalid settings");
         let wd = std::env::current_dir().expect("No working directory");
         let parser = Parser::new(markdown);
+        let environment = mdcat::Environment::isolated(&wd);
         mdcat::push_tty(
-            &mut child.stdin.unwrap(),
-            mdcat::TerminalCapabilities::ansi(),
-            size,
+            &settings,
+            &mut child.stdin.take().unwrap(),
+            &environment,
             parser,
-            &wd,
-            mdcat::ResourceAccess::LocalOnly,
-            syntax_set,
         )
         .expect("Formatting failed")
     }
-    let mut buffer = Vec::new();
-    child
-        .stdout
-        .unwrap()
-        .read_to_end(&mut buffer)
-        .expect("Failed to read");
-
-    String::from_utf8(buffer)
+    let output = child.wait_with_output().expect("Failed to read");
+
+    String::from_utf8(output.stdout)
         .expect("Failed to convert from bytes")
         // Normalize line endings
         .replace("\r\n", "\n")
@@ -0,0 +1,215 @@
+// Copyright 2018 Sebastian Wiesner <sebastian@swsnr.de>
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// 	http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![deny(warnings, clippy::all)]
+
+//! Test rendered output on an emulated terminal.
+
+use mdcat::{Environment, SettingsBuilder, Spacing, TerminalCapabilities, TerminalSize, Theme};
+use pretty_assertions::assert_eq;
+use pulldown_cmark::Parser;
+use syntect::parsing::SyntaxSet;
+use vt100::Color;
+
+/// The size of the emulated terminal.
+const COLUMNS: u16 = 40;
+const ROWS: u16 = 24;
+
+/// Render `markdown` with settings from `builder` onto an emulated terminal.
+fn render_screen(markdown: &str, builder: SettingsBuilder) -> vt100::Parser {
+    let settings = builder
+        .terminal_size(TerminalSize::new(usize::from(COLUMNS), usize::from(ROWS)))
+        .syntax_set(SyntaxSet::load_defaults_newlines())
+        .build()
+        .expect("Invalid settings");
+    let mut output = Vec::new();
+    mdcat::push_tty(
+        &settings,
+        &mut output,
+        &Environment::isolated("/"),
+        Parser::new(markdown),
+    )
+    .expect("Formatting failed");
+    // Translate newlines like a TTY does
+    let output = String::from_utf8(output)
+        .expect("Output not UTF-8")
+        .replace('\n', "\r\n");
+    let mut terminal = vt100::Parser::new(ROWS, COLUMNS, 0);
+    terminal.process(output.as_bytes());
+    terminal
+}
+
+/// Get the lines on the screen of `terminal`, without trailing empty lines.
+fn screen_lines(terminal: &vt100::Parser) -> Vec<String> {
+    let contents = terminal.screen().contents();
+    contents
+        .trim_end()
+        .lines()
+        .map(ToString::to_string)
+        .collect()
+}
+
+/// Find the position of `text` on the screen of `terminal`.
+fn find(terminal: &vt100::Parser, text: &str) -> (u16, u16) {
+    screen_lines(terminal)
+        .iter()
+        .enumerate()
+        .find_map(|(row, line)| {
+            line.find(text)
+                .map(|byte| (row as u16, line[..byte].chars().count() as u16))
+        })
+        .unwrap_or_else(|| panic!("{:?} not on screen", text))
+}
+
+const DOCUMENT: &str = "# Heading
+
+Some *emphasis* and `code`.
+
+> A quote
+
+* One
+* Two
+    * Nested
+
+---
+";
+
+#[test]
+fn layout_of_plain_terminal() {
+    let terminal = render_screen(DOCUMENT, SettingsBuilder::new(TerminalCapabilities::none()));
+    let rule = "\u{2550}".repeat(usize::from(COLUMNS));
+    assert_eq!(
+        screen_lines(&terminal),
+        vec![
+            "\u{2504}Heading",
+            "",
+            "Some emphasis and code.",
+            "",
+            "    A quote",
+            "",
+            "\u{2022} One",
+            "\u{2022} Two",
+            "  \u{2022} Nested",
+            "",
+            rule.as_str(),
+        ]
+    );
+}
+
+#[test]
+fn all_capabilities_show_the_same_text() {
+    let plain = screen_lines(&render_screen(
+        DOCUMENT,
+        SettingsBuilder::new(TerminalCapabilities::none()),
+    ));
+    let ansi = screen_lines(&render_screen(
+        DOCUMENT,
+        SettingsBuilder::new(TerminalCapabilities::ansi()),
+    ));
+    let monochrome = screen_lines(&render_screen(
+        DOCUMENT,
+        SettingsBuilder::new(TerminalCapabilities::ansi().monochrome()).theme(Theme::monochrome()),
+    ));
+    assert_eq!(ansi, plain);
+    assert_eq!(monochrome, plain);
+}
+
+#[test]
+fn ansi_terminal_shows_styles() {
+    let terminal = render_screen(DOCUMENT, SettingsBuilder::new(TerminalCapabilities::ansi()));
+    let screen = terminal.screen();
+
+    let (row, col) = find(&terminal, "Heading");
+    let heading = screen.cell(row, col).unwrap();
+    assert!(heading.bold());
+    assert_eq!(heading.fgcolor(), Color::Idx(4));
+
+    let (row, col) = find(&terminal, "emphasis");
+    assert!(screen.cell(row, col).unwrap().italic());
+
+    let (row, col) = find(&terminal, "code");
+    assert_eq!(screen.cell(row, col).unwrap().fgcolor(), Color::Idx(3));
+
+    let (row, col) = find(&terminal, "A quote");
+    let quote = screen.cell(row, col).unwrap();
+    assert!(quote.italic());
+    assert_eq!(quote.fgcolor(), Color::Idx(2));
+}
+
+#[test]
+fn monochrome_terminal_shows_no_colours() {
+    let terminal = render_screen(
+        DOCUMENT,
+        SettingsBuilder::new(TerminalCapabilities::ansi().monochrome()).theme(Theme::monochrome()),
+    );
+    let screen = terminal.screen();
+    for row in 0..ROWS {
+        for col in 0..COLUMNS {
+            let cell = screen.cell(row, col).unwrap();
+            assert_eq!(cell.fgcolor(), Color::Default, "at {}:{}", row, col);
+            assert_eq!(cell.bgcolor(), Color::Default, "at {}:{}", row, col);
+        }
+    }
+    let (row, col) = find(&terminal, "Heading");
+    let heading = screen.cell(row, col).unwrap();
+    assert!(heading.bold());
+    assert!(heading.underline());
+}
+
+#[test]
+fn code_blocks_have_borders() {
+    let terminal = render_screen(
+        "```\nfoo\n```\n\nbar\n",
+        SettingsBuilder::new(TerminalCapabilities::ansi()),
+    );
+    let border = "\u{2500}".repeat(20);
+    assert_eq!(
+        screen_lines(&terminal),
+        vec![border.as_str(), "foo", border.as_str(), "", "bar"]
+    );
+}
+
+#[test]
+fn compact_spacing() {
+    let theme = Theme {
+        spacing: Spacing::compact(),
+        ..Theme::default()
+    };
+    let terminal = render_screen(
+        "# Heading\n\nFoo\n\nBar\n* One\n* Two\n\n```\nbaz\n```\n```\nqux\n```\n# Next\n",
+        SettingsBuilder::new(TerminalCapabilities::none()).theme(theme),
+    );
+    let border = "\u{2500}".repeat(20);
+    assert_eq!(
+        screen_lines(&terminal),
+        vec![
+            "\u{2504}Heading",
+            "Foo",
+            "",
+            "Bar",
+            "",
+            "\u{2022} One",
+            "\u{2022} Two",
+            "",
+            border.as_str(),
+            "baz",
+            border.as_str(),
+            border.as_str(),
+            "qux",
+            border.as_str(),
+            "",
+            "\u{2504}Next",
+        ]
+    );
+}